@@ -1,8 +1,7 @@
 use rayon::prelude::*;
 
 use std::{
-    fs::File,
-    io::{Error, ErrorKind, Read},
+    io::{Error, ErrorKind},
     ops::Range,
 };
 
@@ -14,10 +13,11 @@ pub enum Endian {
     Big,
 }
 
+/// Reads values from a borrowed buffer, nothing read from it is copied unless asked for
 #[derive(Clone)]
-pub struct ByteReader {
+pub struct ByteReader<'a> {
     /// The buffer
-    data: Vec<u8>,
+    data: &'a [u8],
     /// The current offset (position) in the buffer
     offset: usize,
     /// The buffer length
@@ -28,50 +28,42 @@ pub struct ByteReader {
     push_offset: Option<usize>,
 }
 
-impl ByteReader {
-    pub fn from_vec(vec: &Vec<u8>) -> Self {
+impl<'a> ByteReader<'a> {
+    pub fn from_slice(data: &'a [u8]) -> Self {
         Self {
-            data: vec.to_vec(),
+            data,
             offset: 0,
-            length: vec.len(),
+            length: data.len(),
             endian: Endian::Little,
             push_offset: None,
         }
     }
 
-    pub fn from_file(file: &mut File) -> std::io::Result<Self> {
-        let mut data = vec![];
-        let length = file.read_to_end(&mut data).unwrap();
-        Ok(Self {
-            data: data,
-            length: length,
-            offset: 0,
-            endian: Endian::Little,
-            push_offset: None,
-        })
+    pub fn from_vec(vec: &'a Vec<u8>) -> Self {
+        Self::from_slice(vec)
     }
 }
 
-impl ByteReader {
-    pub fn set_endian<'a>(&'a mut self, endian: Endian) -> &'a mut Self {
+impl<'a> ByteReader<'a> {
+    pub fn set_endian(&mut self, endian: Endian) -> &mut Self {
         self.endian = endian;
         self
     }
-    pub fn move_to<'a>(&'a mut self, offset: usize) -> &'a mut Self {
+    pub fn move_to(&mut self, offset: usize) -> &mut Self {
         self.offset = offset;
         self
     }
 
-    pub fn jump<'a>(&'a mut self, jump_by: usize) -> &'a mut Self {
-        self.offset += jump_by;
+    pub fn jump(&mut self, jump_by: usize) -> &mut Self {
+        self.offset = self.offset.saturating_add(jump_by);
         self
     }
 
-    pub fn push_index<'a>(&'a mut self) -> &'a mut Self {
+    pub fn push_index(&mut self) -> &mut Self {
         self.push_offset = Some(self.offset);
         self
     }
-    pub fn pop_index<'a>(&'a mut self) -> &'a mut Self {
+    pub fn pop_index(&mut self) -> &mut Self {
         if let Some(offset) = self.push_offset {
             self.offset = offset;
             self.push_offset = None;
@@ -86,30 +78,28 @@ impl ByteReader {
     pub fn get_current_offset(&self) -> usize {
         self.offset
     }
-    pub fn read_bytes(&mut self, bytes: usize) -> std::io::Result<Vec<u8>> {
-        let data = self
-            .data
-            .get(self.offset..self.offset + bytes)
-            .ok_or_else(|| {
-                Error::new(
-                    ErrorKind::UnexpectedEof,
-                    format!("failed to read {} bytes from offset {}", bytes, self.offset),
-                )
-            })?;
+    /// Reads `bytes` bytes without copying them out of the buffer
+    pub fn read_slice(&mut self, bytes: usize) -> std::io::Result<&'a [u8]> {
+        let data = self.peak_slice(bytes)?;
         self.offset += bytes;
-        Ok(data.to_vec())
+        Ok(data)
+    }
+    pub fn peak_slice(&self, bytes: usize) -> std::io::Result<&'a [u8]> {
+        let data: &'a [u8] = self.data;
+        let end = self.offset.checked_add(bytes);
+        end.and_then(|end| data.get(self.offset..end)).ok_or_else(|| {
+            Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("failed to read {} bytes from offset {}", bytes, self.offset),
+            )
+        })
+    }
+
+    pub fn read_bytes(&mut self, bytes: usize) -> std::io::Result<Vec<u8>> {
+        Ok(self.read_slice(bytes)?.to_vec())
     }
     pub fn peak_bytes(&self, bytes: usize) -> std::io::Result<Vec<u8>> {
-        let data = self
-            .data
-            .get(self.offset..self.offset + bytes)
-            .ok_or_else(|| {
-                Error::new(
-                    ErrorKind::UnexpectedEof,
-                    format!("failed to read {} bytes from offset {}", bytes, self.offset),
-                )
-            })?;
-        Ok(data.to_vec())
+        Ok(self.peak_slice(bytes)?.to_vec())
     }
 
     pub fn read_rest(&mut self) -> std::io::Result<Vec<u8>> {
@@ -216,18 +206,16 @@ impl ByteReader {
     }
 
     pub fn read_string(&mut self, length: usize) -> std::io::Result<String> {
-        Ok(
-            String::from_utf8(self.read_bytes(length)?.to_vec()).map_err(|err| {
-                Error::new(
-                    ErrorKind::InvalidData,
-                    format!("Failed to parse Utf8 Bytes to Utf8String: {:?}", err),
-                )
-            })?,
-        )
+        Ok(String::from_utf8(self.read_bytes(length)?).map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Failed to parse Utf8 Bytes to Utf8String: {:?}", err),
+            )
+        })?)
     }
 
     pub fn read_string_lossy(&mut self, length: usize) -> std::io::Result<String> {
-        Ok(String::from_utf8_lossy(self.read_bytes(length)?.as_slice()).to_string())
+        Ok(String::from_utf8_lossy(self.read_slice(length)?).to_string())
     }
 
     pub fn read<T: FromBinaryReader>(&mut self) -> std::io::Result<T> {
@@ -240,10 +228,10 @@ impl ByteReader {
 }
 
 pub trait FromBinaryReader {
-    fn read_from_byte_reader(reader: &mut ByteReader) -> std::io::Result<Self>
+    fn read_from_byte_reader(reader: &mut ByteReader<'_>) -> std::io::Result<Self>
     where
         Self: Sized;
-    fn peak_from_byte_reader(reader: &mut ByteReader) -> std::io::Result<Self>
+    fn peak_from_byte_reader(reader: &mut ByteReader<'_>) -> std::io::Result<Self>
     where
         Self: Sized;
 }
@@ -251,17 +239,17 @@ pub trait FromBinaryReader {
 macro_rules! impl_from_binary_reader {
     ($ty:ident, $bytes:expr) => {
         impl FromBinaryReader for $ty {
-            fn read_from_byte_reader(reader: &mut crate::ByteReader) -> std::io::Result<$ty> {
+            fn read_from_byte_reader(reader: &mut crate::ByteReader<'_>) -> std::io::Result<$ty> {
                 let endian = reader.endian.clone();
-                let data = reader.read_bytes($bytes)?;
+                let data = reader.read_slice($bytes)?;
                 match endian {
                     Endian::Little => Ok($ty::from_le_bytes(data[..$bytes].try_into().unwrap())),
                     Endian::Big => Ok($ty::from_be_bytes(data[..$bytes].try_into().unwrap())),
                 }
             }
-            fn peak_from_byte_reader(reader: &mut crate::ByteReader) -> std::io::Result<$ty> {
+            fn peak_from_byte_reader(reader: &mut crate::ByteReader<'_>) -> std::io::Result<$ty> {
                 let endian = reader.endian.clone();
-                let data = reader.peak_bytes($bytes)?;
+                let data = reader.peak_slice($bytes)?;
                 match endian {
                     Endian::Little => Ok($ty::from_le_bytes(data[..$bytes].try_into().unwrap())),
                     Endian::Big => Ok($ty::from_be_bytes(data[..$bytes].try_into().unwrap())),
@@ -1,16 +1,26 @@
+use std::borrow::Cow;
+
 #[derive(Debug, Default, Clone)]
-pub struct AttributeInfo {
+pub struct AttributeInfo<'a> {
     pub attribute_name_index: u16,
-    pub attribute: AttributeInfoData,
+    pub attribute: AttributeInfoData<'a>,
+}
+
+impl AttributeInfo<'_> {
+    pub fn into_owned(self) -> AttributeInfo<'static> {
+        AttributeInfo {
+            attribute_name_index: self.attribute_name_index,
+            attribute: self.attribute.into_owned(),
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone)]
-pub enum AttributeInfoData {
+pub enum AttributeInfoData<'a> {
     #[default]
     None,
-    NoneAnnotated(String),
 
-    Code(CodeAttribute),
+    Code(CodeAttribute<'a>),
     LineNumberTable(LineNumberTableAttribute),
     SourceFile(SourceFileAttribute),
     BootstrapMethods(BootstrapMethodsAttribute),
@@ -22,15 +32,137 @@ pub enum AttributeInfoData {
     StackMapTable(StackMapTableAttribute),
     ConstantValue(ConstantValueAttribute),
     Synthetic,
+    Deprecated,
+    SourceDebugExtension(SourceDebugExtensionAttribute),
+    MethodParameters(MethodParametersAttribute),
+    RuntimeVisibleAnnotations(AnnotationsAttribute),
+    RuntimeInvisibleAnnotations(AnnotationsAttribute),
+    RuntimeVisibleParameterAnnotations(ParameterAnnotationsAttribute),
+    RuntimeInvisibleParameterAnnotations(ParameterAnnotationsAttribute),
+    RuntimeVisibleTypeAnnotations(TypeAnnotationsAttribute),
+    RuntimeInvisibleTypeAnnotations(TypeAnnotationsAttribute),
+    AnnotationDefault(AnnotationDefaultAttribute),
+    InnerClasses(InnerClassesAttribute),
+    NestHost(NestHostAttribute),
+    NestMembers(NestMembersAttribute),
+    Record(RecordAttribute<'a>),
+    PermittedSubclasses(PermittedSubclassesAttribute),
+    Module(ModuleAttribute),
+    ModulePackages(ModulePackagesAttribute),
+    ModuleMainClass(ModuleMainClassAttribute),
+    Unknown(UnknownAttribute),
+}
+
+impl AttributeInfoData<'_> {
+    /// Copies the code arrays that borrow from the class file
+    pub fn into_owned(self) -> AttributeInfoData<'static> {
+        use AttributeInfoData as Data;
+
+        match self {
+            Data::None => Data::None,
+            Data::Code(code) => Data::Code(code.into_owned()),
+            Data::LineNumberTable(table) => Data::LineNumberTable(table),
+            Data::SourceFile(source_file) => Data::SourceFile(source_file),
+            Data::BootstrapMethods(methods) => Data::BootstrapMethods(methods),
+            Data::LocalVariableTable(table) => Data::LocalVariableTable(table),
+            Data::LocalVariableTypeTable(table) => Data::LocalVariableTypeTable(table),
+            Data::Signature(signature) => Data::Signature(signature),
+            Data::EnclosingMethod(method) => Data::EnclosingMethod(method),
+            Data::Exceptions(exceptions) => Data::Exceptions(exceptions),
+            Data::StackMapTable(table) => Data::StackMapTable(table),
+            Data::ConstantValue(value) => Data::ConstantValue(value),
+            Data::Synthetic => Data::Synthetic,
+            Data::Deprecated => Data::Deprecated,
+            Data::SourceDebugExtension(extension) => Data::SourceDebugExtension(extension),
+            Data::MethodParameters(parameters) => Data::MethodParameters(parameters),
+            Data::RuntimeVisibleAnnotations(annotations) => {
+                Data::RuntimeVisibleAnnotations(annotations)
+            }
+            Data::RuntimeInvisibleAnnotations(annotations) => {
+                Data::RuntimeInvisibleAnnotations(annotations)
+            }
+            Data::RuntimeVisibleParameterAnnotations(annotations) => {
+                Data::RuntimeVisibleParameterAnnotations(annotations)
+            }
+            Data::RuntimeInvisibleParameterAnnotations(annotations) => {
+                Data::RuntimeInvisibleParameterAnnotations(annotations)
+            }
+            Data::RuntimeVisibleTypeAnnotations(annotations) => {
+                Data::RuntimeVisibleTypeAnnotations(annotations)
+            }
+            Data::RuntimeInvisibleTypeAnnotations(annotations) => {
+                Data::RuntimeInvisibleTypeAnnotations(annotations)
+            }
+            Data::AnnotationDefault(default) => Data::AnnotationDefault(default),
+            Data::InnerClasses(classes) => Data::InnerClasses(classes),
+            Data::NestHost(host) => Data::NestHost(host),
+            Data::NestMembers(members) => Data::NestMembers(members),
+            Data::Record(record) => Data::Record(record.into_owned()),
+            Data::PermittedSubclasses(classes) => Data::PermittedSubclasses(classes),
+            Data::Module(module) => Data::Module(module),
+            Data::ModulePackages(packages) => Data::ModulePackages(packages),
+            Data::ModuleMainClass(main_class) => Data::ModuleMainClass(main_class),
+            Data::Unknown(unknown) => Data::Unknown(unknown),
+        }
+    }
+}
+
+/// Extended debugging information, e.g. an SMAP for languages compiled to java byte code
+/// (JVMS 4.7.11)
+#[derive(Debug, Default, Clone)]
+pub struct SourceDebugExtensionAttribute {
+    pub debug_extension: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct MethodParametersAttribute {
+    pub parameters: Vec<MethodParameter>,
+}
+
+/// `name_index` is 0 for a parameter without a name (JVMS 4.7.24)
+#[derive(Debug, Default, Clone)]
+pub struct MethodParameter {
+    pub name_index: u16,
+    pub access_flags: u16,
 }
 
+/// An attribute this parser doesn't know, with its contents left undecoded
 #[derive(Debug, Default, Clone)]
-pub struct CodeAttribute {
+pub struct UnknownAttribute {
+    pub name: String,
+    pub info: Vec<u8>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CodeAttribute<'a> {
     pub max_stack: u16,
     pub max_locals: u16,
-    pub code: Vec<u8>,
+    /// Borrowed from the class file when it was parsed from a slice
+    pub code: Cow<'a, [u8]>,
     pub exception_table: Vec<ExceptionTable>,
-    pub attribute_info: Vec<AttributeInfo>,
+    pub attribute_info: Vec<AttributeInfo<'a>>,
+}
+
+impl CodeAttribute<'_> {
+    pub fn into_owned(self) -> CodeAttribute<'static> {
+        CodeAttribute {
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            code: Cow::Owned(self.code.into_owned()),
+            exception_table: self.exception_table,
+            attribute_info: into_owned_attributes(self.attribute_info),
+        }
+    }
+}
+
+/// Copies the attributes that borrow from the class file
+pub(crate) fn into_owned_attributes(
+    attributes: Vec<AttributeInfo<'_>>,
+) -> Vec<AttributeInfo<'static>> {
+    attributes
+        .into_iter()
+        .map(AttributeInfo::into_owned)
+        .collect()
 }
 
 #[derive(Debug, Default, Clone)]
@@ -107,21 +239,323 @@ pub struct StackMapTableAttribute {
     pub entries: Vec<StackMapFrame>,
 }
 
+/// A frame of the `StackMapTable` attribute, `offset_delta` is relative to the previous frame
+/// (JVMS 4.7.4)
 #[derive(Debug, Default, Clone)]
 pub enum StackMapFrame {
     #[default]
     None,
 
-    SameFrame,
-    SameFrameExtended,
-    SameLocalsStackItemFrame,
-    SameLocalsStackItemFrameExtended,
-    ChopFrame,
-    AppendFrame,
-    FullFrame,
+    /// The same locals as the previous frame and an empty stack
+    SameFrame {
+        offset_delta: u16,
+    },
+    SameFrameExtended {
+        offset_delta: u16,
+    },
+    /// The same locals as the previous frame and one item on the stack
+    SameLocalsStackItemFrame {
+        offset_delta: u16,
+        stack: VerificationTypeInfo,
+    },
+    SameLocalsStackItemFrameExtended {
+        offset_delta: u16,
+        stack: VerificationTypeInfo,
+    },
+    /// The previous frame without its last `chopped` locals and an empty stack
+    ChopFrame {
+        offset_delta: u16,
+        chopped: u8,
+    },
+    /// The previous frame with additional locals and an empty stack
+    AppendFrame {
+        offset_delta: u16,
+        locals: Vec<VerificationTypeInfo>,
+    },
+    FullFrame {
+        offset_delta: u16,
+        locals: Vec<VerificationTypeInfo>,
+        stack: Vec<VerificationTypeInfo>,
+    },
+}
+
+impl StackMapFrame {
+    pub fn offset_delta(&self) -> u16 {
+        match self {
+            StackMapFrame::None => 0,
+            StackMapFrame::SameFrame { offset_delta }
+            | StackMapFrame::SameFrameExtended { offset_delta }
+            | StackMapFrame::SameLocalsStackItemFrame { offset_delta, .. }
+            | StackMapFrame::SameLocalsStackItemFrameExtended { offset_delta, .. }
+            | StackMapFrame::ChopFrame { offset_delta, .. }
+            | StackMapFrame::AppendFrame { offset_delta, .. }
+            | StackMapFrame::FullFrame { offset_delta, .. } => *offset_delta,
+        }
+    }
+}
+
+/// The type of a local variable or stack item in a stack map frame (JVMS 4.7.4).
+/// `Long` and `Double` stand for two locals or stack items
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerificationTypeInfo {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    UninitializedThis,
+    /// An instance of the class at `cpool_index`
+    Object {
+        cpool_index: u16,
+    },
+    /// An object created by the `new` instruction at `offset`, that hasn't been initialized yet
+    Uninitialized {
+        offset: u16,
+    },
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct ConstantValueAttribute {
     pub constantvalue_index: u16,
 }
+
+#[derive(Debug, Default, Clone)]
+pub struct InnerClassesAttribute {
+    pub classes: Vec<InnerClass>,
+}
+
+/// A class or interface that is a member of a nest, `outer_class_info_index` and
+/// `inner_name_index` are 0 for local and anonymous classes (JVMS 4.7.6)
+#[derive(Debug, Default, Clone)]
+pub struct InnerClass {
+    pub inner_class_info_index: u16,
+    pub outer_class_info_index: u16,
+    pub inner_name_index: u16,
+    pub inner_class_access_flags: u16,
+}
+
+/// The class at `host_class_index` is the nest host of this class (JVMS 4.7.28)
+#[derive(Debug, Default, Clone)]
+pub struct NestHostAttribute {
+    pub host_class_index: u16,
+}
+
+/// The classes that claim membership in the nest hosted by this class (JVMS 4.7.29)
+#[derive(Debug, Default, Clone)]
+pub struct NestMembersAttribute {
+    pub classes: Vec<u16>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RecordAttribute<'a> {
+    pub components: Vec<RecordComponentInfo<'a>>,
+}
+
+impl RecordAttribute<'_> {
+    pub fn into_owned(self) -> RecordAttribute<'static> {
+        RecordAttribute {
+            components: self
+                .components
+                .into_iter()
+                .map(|component| RecordComponentInfo {
+                    name_index: component.name_index,
+                    descriptor_index: component.descriptor_index,
+                    attributes: into_owned_attributes(component.attributes),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A component of a record class, `attributes` can hold `Signature` and annotations
+/// (JVMS 4.7.30)
+#[derive(Debug, Default, Clone)]
+pub struct RecordComponentInfo<'a> {
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes: Vec<AttributeInfo<'a>>,
+}
+
+/// The classes and interfaces allowed to directly extend or implement this sealed class
+/// (JVMS 4.7.31)
+#[derive(Debug, Default, Clone)]
+pub struct PermittedSubclassesAttribute {
+    pub classes: Vec<u16>,
+}
+
+/// The module declared by a `module-info.class`, the indices point to `CONSTANT_Module`,
+/// `CONSTANT_Package` and `CONSTANT_Class` entries (JVMS 4.7.25)
+#[derive(Debug, Default, Clone)]
+pub struct ModuleAttribute {
+    pub module_name_index: u16,
+    pub module_flags: u16,
+    /// 0 if the module has no version
+    pub module_version_index: u16,
+    pub requires: Vec<ModuleRequires>,
+    pub exports: Vec<ModuleExports>,
+    pub opens: Vec<ModuleOpens>,
+    pub uses_index: Vec<u16>,
+    pub provides: Vec<ModuleProvides>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ModuleRequires {
+    pub requires_index: u16,
+    pub requires_flags: u16,
+    pub requires_version_index: u16,
+}
+
+/// A package exported to every module, or only to the modules in `exports_to_index`
+#[derive(Debug, Default, Clone)]
+pub struct ModuleExports {
+    pub exports_index: u16,
+    pub exports_flags: u16,
+    pub exports_to_index: Vec<u16>,
+}
+
+/// A package opened for reflection to every module, or only to the modules in `opens_to_index`
+#[derive(Debug, Default, Clone)]
+pub struct ModuleOpens {
+    pub opens_index: u16,
+    pub opens_flags: u16,
+    pub opens_to_index: Vec<u16>,
+}
+
+/// The implementations of the service interface at `provides_index`
+#[derive(Debug, Default, Clone)]
+pub struct ModuleProvides {
+    pub provides_index: u16,
+    pub provides_with_index: Vec<u16>,
+}
+
+/// Every package of the module, including the ones that aren't exported or opened
+/// (JVMS 4.7.26)
+#[derive(Debug, Default, Clone)]
+pub struct ModulePackagesAttribute {
+    pub package_index: Vec<u16>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ModuleMainClassAttribute {
+    pub main_class_index: u16,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AnnotationsAttribute {
+    pub annotations: Vec<Annotation>,
+}
+
+/// One list of annotations per formal parameter of the method (JVMS 4.7.18)
+#[derive(Debug, Default, Clone)]
+pub struct ParameterAnnotationsAttribute {
+    pub parameter_annotations: Vec<Vec<Annotation>>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TypeAnnotationsAttribute {
+    pub annotations: Vec<TypeAnnotation>,
+}
+
+/// The default value of the annotation interface element represented by the method (JVMS 4.7.22)
+#[derive(Debug, Clone)]
+pub struct AnnotationDefaultAttribute {
+    pub default_value: ElementValue,
+}
+
+/// `type_index` points to the utf8 field descriptor of the annotation interface,
+/// e.g. `Ljava/lang/Deprecated;` (JVMS 4.7.16)
+#[derive(Debug, Default, Clone)]
+pub struct Annotation {
+    pub type_index: u16,
+    pub element_value_pairs: Vec<ElementValuePair>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ElementValuePair {
+    pub element_name_index: u16,
+    pub value: ElementValue,
+}
+
+/// The value of an annotation element (JVMS 4.7.16.1)
+#[derive(Debug, Clone)]
+pub enum ElementValue {
+    /// A primitive or `String` constant, `tag` is one of `B C D F I J S Z s`
+    Const {
+        tag: u8,
+        const_value_index: u16,
+    },
+    /// `type_name_index` is the utf8 field descriptor of the enum class,
+    /// `const_name_index` the simple name of the enum constant
+    Enum {
+        type_name_index: u16,
+        const_name_index: u16,
+    },
+    /// A class literal, `class_info_index` is the utf8 return descriptor, e.g. `Ljava/lang/Object;` or `V`
+    Class {
+        class_info_index: u16,
+    },
+    Annotation(Annotation),
+    Array(Vec<ElementValue>),
+}
+
+/// An annotation on a use of a type (JVMS 4.7.20)
+#[derive(Debug, Clone)]
+pub struct TypeAnnotation {
+    pub target_type: u8,
+    pub target_info: TypeAnnotationTarget,
+    pub target_path: Vec<TypePathEntry>,
+    pub annotation: Annotation,
+}
+
+/// Which type in a declaration or expression is annotated, decided by `target_type` (JVMS 4.7.20.1)
+#[derive(Debug, Clone)]
+pub enum TypeAnnotationTarget {
+    TypeParameter {
+        type_parameter_index: u8,
+    },
+    /// `supertype_index` is 65535 for the super class, otherwise an index into `interfaces`
+    Supertype {
+        supertype_index: u16,
+    },
+    TypeParameterBound {
+        type_parameter_index: u8,
+        bound_index: u8,
+    },
+    /// The type in a field declaration, the return type of a method or the receiver type
+    Empty,
+    FormalParameter {
+        formal_parameter_index: u8,
+    },
+    Throws {
+        throws_type_index: u16,
+    },
+    LocalVar {
+        table: Vec<LocalVarTargetEntry>,
+    },
+    Catch {
+        exception_table_index: u16,
+    },
+    /// `instanceof`, `new` or a method reference expression at bytecode `offset`
+    Offset {
+        offset: u16,
+    },
+    /// A cast or type argument of a generic call at bytecode `offset`
+    TypeArgument {
+        offset: u16,
+        type_argument_index: u8,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalVarTargetEntry {
+    pub start_pc: u16,
+    pub length: u16,
+    pub index: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct TypePathEntry {
+    pub type_path_kind: u8,
+    pub type_argument_index: u8,
+}
@@ -3,14 +3,22 @@ use crate::classfile::attributes::{
     ExceptionTable, LineNumber, LineNumberTableAttribute, SourceFileAttribute,
 };
 use crate::classfile::constant_pool::ConstantPool;
-use byte_reader::{ByteReader, Endian};
-use std::{error::Error, path::PathBuf};
+use std::{borrow::Cow, error::Error, path::PathBuf};
 
 use super::attributes::{
-    ConstantValueAttribute, EnclosingMethodAttribute, ExceptionsAttribute,
+    into_owned_attributes, Annotation, AnnotationDefaultAttribute, AnnotationsAttribute,
+    ConstantValueAttribute, ElementValue, ElementValuePair, EnclosingMethodAttribute,
+    ExceptionsAttribute, InnerClass, InnerClassesAttribute, LocalVarTargetEntry,
     LocalVariableTableAttribute, LocalVariableTableEntry, LocalVariableTypeTableAttribute,
-    SignatureAttribute, StackMapTableAttribute,
+    MethodParameter, MethodParametersAttribute, ModuleAttribute, ModuleExports,
+    ModuleMainClassAttribute, ModuleOpens, ModulePackagesAttribute, ModuleProvides, ModuleRequires,
+    NestHostAttribute, NestMembersAttribute, ParameterAnnotationsAttribute,
+    PermittedSubclassesAttribute, RecordAttribute, RecordComponentInfo, SignatureAttribute,
+    SourceDebugExtensionAttribute, StackMapFrame, StackMapTableAttribute, TypeAnnotation,
+    TypeAnnotationTarget, TypeAnnotationsAttribute, TypePathEntry, UnknownAttribute,
+    VerificationTypeInfo,
 };
+use super::{error::ClassFormatError, reader::{ClassReader, Structure}};
 
 type AccessFlags = u16;
 
@@ -20,10 +28,11 @@ pub mod ClassAccessFlags {
     pub const ACC_FINAL: u16 = 0x0010;
     pub const ACC_SUPER: u16 = 0x0020;
     pub const ACC_INTERFACE: u16 = 0x0200;
-    pub const ACC_ABSTRACT: u16 = 0x0040;
+    pub const ACC_ABSTRACT: u16 = 0x0400;
     pub const ACC_SYNTHETIC: u16 = 0x1000;
     pub const ACC_ANNOTATION: u16 = 0x2000;
     pub const ACC_ENUM: u16 = 0x4000;
+    pub const ACC_MODULE: u16 = 0x8000;
 }
 
 #[allow(non_snake_case)]
@@ -54,58 +63,114 @@ pub mod FieldAccessFlags {
     pub const ACC_SYNTHETIC: u16 = 0x1000;
     pub const ACC_ENUM: u16 = 0x4000;
 }
+
+/// Flags of the `Module` attribute and its `requires`, `exports` and `opens` entries
+#[allow(non_snake_case)]
+pub mod ModuleFlags {
+    /// On the module, every package is open
+    pub const ACC_OPEN: u16 = 0x0020;
+    /// On a `requires`, modules reading this module also read the required one
+    pub const ACC_TRANSITIVE: u16 = 0x0020;
+    /// On a `requires`, the dependency is only mandatory at compile time
+    pub const ACC_STATIC_PHASE: u16 = 0x0040;
+    pub const ACC_SYNTHETIC: u16 = 0x1000;
+    pub const ACC_MANDATED: u16 = 0x8000;
+}
+
+/// Parses or skips the attributes of a class, field or method
+type AttributesParser<'a> =
+    fn(&mut ClassReader<'a>, &ConstantPool<'a>) -> Result<Vec<AttributeInfo<'a>>, ClassFormatError>;
+
+/// A parsed class file. The utf8 constants and code arrays borrow from the bytes it was parsed
+/// from, [`JavaClass::into_owned`] copies them to keep the class around longer
 #[derive(Debug)]
-pub struct JavaClass {
+pub struct JavaClass<'a> {
     pub magic: u32,
     pub minor_version: u16,
     pub major_version: u16,
-    pub constant_pool: ConstantPool,
+    pub constant_pool: ConstantPool<'a>,
     pub access_flags: AccessFlags,
     pub this_class: u16,
     pub super_class: u16,
     pub interfaces: Vec<u16>,
-    pub fields: Vec<FieldInfo>,
-    pub methods: Vec<MethodInfo>,
-    pub attributes: Vec<AttributeInfo>,
+    pub fields: Vec<FieldInfo<'a>>,
+    pub methods: Vec<MethodInfo<'a>>,
+    pub attributes: Vec<AttributeInfo<'a>>,
 }
 
-impl JavaClass {
-    pub fn from_file(path: &PathBuf) -> Result<JavaClass, Box<dyn Error>> {
-        JavaClass::from_bytes(&std::fs::read(path)?)
+impl<'a> JavaClass<'a> {
+    pub fn from_file(path: &PathBuf) -> Result<JavaClass<'static>, Box<dyn Error>> {
+        Ok(JavaClass::from_bytes(&std::fs::read(path)?)?.into_owned())
+    }
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<JavaClass<'a>, ClassFormatError> {
+        JavaClass::parse(bytes, JavaClass::parse_attributes)
     }
-    pub fn from_bytes(bytes: &Vec<u8>) -> Result<JavaClass, Box<dyn Error>> {
-        let mut reader = ByteReader::from_vec(bytes);
-        reader.set_endian(Endian::Big);
+
+    /// Parses a class without the attributes of the class, its fields and its methods, so the
+    /// code isn't parsed either. Enough to look at the declarations of a lot of classes quickly
+    pub fn skim(bytes: &'a [u8]) -> Result<JavaClass<'a>, ClassFormatError> {
+        JavaClass::parse(bytes, JavaClass::skip_attributes)
+    }
+
+    fn parse(
+        bytes: &'a [u8],
+        attributes: AttributesParser<'a>,
+    ) -> Result<JavaClass<'a>, ClassFormatError> {
+        let mut reader = ClassReader::new(bytes);
+
+        let magic = reader.read()?;
+        if magic != 0xCAFEBABE {
+            return Err(ClassFormatError::BadMagic { magic });
+        }
 
         let mut class = JavaClass {
-            magic: reader.read()?,
+            magic,
             minor_version: reader.read()?,
             major_version: reader.read()?,
             constant_pool: ConstantPool::from_reader(&mut reader)?,
             access_flags: reader.read()?,
             this_class: reader.read()?,
             super_class: reader.read()?,
-            interfaces: (0..reader.read::<u16>()?)
-                .map(|_| reader.read::<u16>().unwrap())
-                .collect::<Vec<u16>>(),
+            interfaces: reader.read_u16_table()?,
             fields: vec![],
             methods: vec![],
             attributes: vec![],
         };
 
-        class.fields = JavaClass::parse_fields(&mut reader, &class.constant_pool)?;
+        class.fields = JavaClass::parse_fields(&mut reader, &class.constant_pool, attributes)?;
 
-        class.methods = JavaClass::parse_methods(&mut reader, &class.constant_pool)?;
+        class.methods = JavaClass::parse_methods(&mut reader, &class.constant_pool, attributes)?;
 
-        class.attributes = JavaClass::parse_attributes(&mut reader, &class.constant_pool)?;
+        class.attributes = attributes(&mut reader, &class.constant_pool)?;
 
         Ok(class)
     }
 
+    pub fn into_owned(self) -> JavaClass<'static> {
+        JavaClass {
+            magic: self.magic,
+            minor_version: self.minor_version,
+            major_version: self.major_version,
+            constant_pool: self.constant_pool.into_owned(),
+            access_flags: self.access_flags,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces,
+            fields: self.fields.into_iter().map(FieldInfo::into_owned).collect(),
+            methods: self
+                .methods
+                .into_iter()
+                .map(MethodInfo::into_owned)
+                .collect(),
+            attributes: into_owned_attributes(self.attributes),
+        }
+    }
+
     fn parse_methods(
-        reader: &mut ByteReader,
-        constant_pool: &ConstantPool,
-    ) -> Result<Vec<MethodInfo>, Box<dyn Error>> {
+        reader: &mut ClassReader<'a>,
+        constant_pool: &ConstantPool<'a>,
+        attributes: AttributesParser<'a>,
+    ) -> Result<Vec<MethodInfo<'a>>, ClassFormatError> {
         let method_count: u16 = reader.read()?;
         let mut methods = vec![];
 
@@ -116,219 +181,302 @@ impl JavaClass {
                 descriptor_index: reader.read()?,
                 attributes: vec![],
             };
-            method.attributes = JavaClass::parse_attributes(reader, constant_pool)?;
+            method.attributes = attributes(reader, constant_pool)?;
             methods.push(method);
         }
 
         Ok(methods)
     }
 
+    fn skip_attributes(
+        reader: &mut ClassReader<'a>,
+        _constant_pool: &ConstantPool<'a>,
+    ) -> Result<Vec<AttributeInfo<'a>>, ClassFormatError> {
+        let attribute_count: u16 = reader.read()?;
+        for _ in 0..attribute_count {
+            let _attribute_name_index: u16 = reader.read()?;
+            let attribute_length: u32 = reader.read()?;
+            reader.read_slice(attribute_length as usize)?;
+        }
+
+        Ok(vec![])
+    }
+
     fn parse_attributes(
-        reader: &mut ByteReader,
-        constant_pool: &ConstantPool,
-    ) -> Result<Vec<AttributeInfo>, Box<dyn Error>> {
+        reader: &mut ClassReader<'a>,
+        constant_pool: &ConstantPool<'a>,
+    ) -> Result<Vec<AttributeInfo<'a>>, ClassFormatError> {
         let attribute_count: u16 = reader.read()?;
         let mut attributes = vec![];
 
-        #[allow(unused_variables)]
-        for i in 0..attribute_count.to_owned() as usize {
+        for _ in 0..attribute_count {
             let attribute_name_index = reader.read()?;
+            let Some(attribute_tag) = constant_pool.get_utf8_at(attribute_name_index) else {
+                return Err(reader.invalid_index(attribute_name_index));
+            };
             let attribute_length = reader.read::<u32>()?;
+            let start = reader.offset();
 
-            let attribute_tag = constant_pool.get_utf8_at(attribute_name_index).unwrap();
+            let structure = Structure::Attribute(attribute_tag.data.clone());
+            let attribute = reader.within(structure, |reader| {
+                let attribute = match attribute_tag.data.as_ref() {
+                    "Code" => {
+                        let max_stack = reader.read()?;
+                        let max_locals = reader.read()?;
+                        let code_length: u32 = reader.read()?;
+                        let code = Cow::Borrowed(reader.read_slice(code_length as usize)?);
+                        let exception_table_length: u16 = reader.read()?;
 
-            let attribute = match attribute_tag.data.as_str() {
-                "Code" => {
-                    let max_stack = reader.read()?;
-                    let max_locals = reader.read()?;
-                    let code_length: u32 = reader.read()?;
-                    let code = reader.read_bytes(code_length as usize)?;
-                    let exception_table_length: u16 = reader.read()?;
+                        let mut exception_table = vec![];
 
-                    let mut exception_table = vec![];
+                        for _ in 0..exception_table_length as usize {
+                            exception_table.push(ExceptionTable {
+                                start_pc: reader.read()?,
+                                end_pc: reader.read()?,
+                                handler_pc: reader.read()?,
+                                catch_type: reader.read()?,
+                            });
+                        }
 
-                    for _ in 0..exception_table_length as usize {
-                        exception_table.push(ExceptionTable {
-                            start_pc: reader.read()?,
-                            end_pc: reader.read()?,
-                            handler_pc: reader.read()?,
-                            catch_type: reader.read()?,
-                        });
+                        let attribute_info = JavaClass::parse_attributes(reader, constant_pool)?;
+
+                        AttributeInfoData::Code(CodeAttribute {
+                            max_stack,
+                            max_locals,
+                            code,
+                            exception_table,
+                            attribute_info,
+                        })
                     }
 
-                    let attribute_info = JavaClass::parse_attributes(reader, constant_pool)?;
+                    "LineNumberTable" => {
+                        let line_number_table_length: u16 = reader.read()?;
+                        let mut line_number_table = vec![];
 
-                    AttributeInfoData::Code(CodeAttribute {
-                        max_stack,
-                        max_locals,
-                        code,
-                        exception_table,
-                        attribute_info,
-                    })
-                }
+                        for _ in 0..line_number_table_length as usize {
+                            line_number_table.push(LineNumber {
+                                start_pc: reader.read()?,
+                                line_number: reader.read()?,
+                            })
+                        }
 
-                "LineNumberTable" => {
-                    let line_number_table_length: u16 = reader.read()?;
-                    let mut line_number_table = vec![];
+                        AttributeInfoData::LineNumberTable(LineNumberTableAttribute {
+                            line_number_table,
+                        })
+                    }
 
-                    for _ in 0..line_number_table_length as usize {
-                        line_number_table.push(LineNumber {
-                            start_pc: reader.read()?,
-                            line_number: reader.read()?,
+                    "SourceFile" => AttributeInfoData::SourceFile(SourceFileAttribute {
+                        sourcefile_index: reader.read()?,
+                    }),
+
+                    "BootstrapMethods" => {
+                        let num_bootstrap_methods: u16 = reader.read()?;
+                        let mut bootstrap_methods = vec![];
+
+                        for _ in 0..num_bootstrap_methods as usize {
+                            let bootstrap_method_ref = reader.read()?;
+                            let num_bootstrap_arguments: u16 = reader.read()?;
+                            let mut bootstrap_arguments = vec![];
+
+                            for _ in 0..num_bootstrap_arguments as usize {
+                                let arg_index = reader.read()?;
+                                bootstrap_arguments.push(arg_index);
+                            }
+
+                            bootstrap_methods.push(BootstrapMethod {
+                                bootstrap_method_ref,
+                                bootstrap_arguments,
+                            });
+                        }
+
+                        AttributeInfoData::BootstrapMethods(BootstrapMethodsAttribute {
+                            attribute_name_index,
+                            bootstrap_methods,
                         })
                     }
 
-                    AttributeInfoData::LineNumberTable(LineNumberTableAttribute {
-                        line_number_table,
-                    })
-                }
+                    "Signature" => AttributeInfoData::Signature(SignatureAttribute {
+                        signature_index: reader.read()?,
+                    }),
+
+                    "EnclosingMethod" => {
+                        AttributeInfoData::EnclosingMethod(EnclosingMethodAttribute {
+                            class_index: reader.read()?,
+                            method_index: reader.read()?,
+                        })
+                    }
+
+                    "InnerClasses" => {
+                        let number_of_classes: u16 = reader.read()?;
+                        let mut classes = vec![];
+
+                        for _ in 0..number_of_classes {
+                            classes.push(InnerClass {
+                                inner_class_info_index: reader.read()?,
+                                outer_class_info_index: reader.read()?,
+                                inner_name_index: reader.read()?,
+                                inner_class_access_flags: reader.read()?,
+                            });
+                        }
+
+                        AttributeInfoData::InnerClasses(InnerClassesAttribute { classes })
+                    }
+
+                    "NestHost" => AttributeInfoData::NestHost(NestHostAttribute {
+                        host_class_index: reader.read()?,
+                    }),
 
-                "SourceFile" => AttributeInfoData::SourceFile(SourceFileAttribute {
-                    sourcefile_index: reader.read()?,
-                }),
+                    "NestMembers" => AttributeInfoData::NestMembers(NestMembersAttribute {
+                        classes: reader.read_u16_table()?,
+                    }),
 
-                "BootstrapMethods" => {
-                    let num_bootstrap_methods: u16 = reader.read()?;
-                    let mut bootstrap_methods = vec![];
+                    "PermittedSubclasses" => {
+                        AttributeInfoData::PermittedSubclasses(PermittedSubclassesAttribute {
+                            classes: reader.read_u16_table()?,
+                        })
+                    }
 
-                    for _ in 0..num_bootstrap_methods as usize {
-                        let bootstrap_method_ref = reader.read()?;
-                        let num_bootstrap_arguments: u16 = reader.read()?;
-                        let mut bootstrap_arguments = vec![];
+                    "Record" => {
+                        let components_count: u16 = reader.read()?;
+                        let mut components = vec![];
 
-                        for _ in 0..num_bootstrap_arguments as usize {
-                            let arg_index = reader.read()?;
-                            bootstrap_arguments.push(arg_index);
+                        for _ in 0..components_count {
+                            components.push(RecordComponentInfo {
+                                name_index: reader.read()?,
+                                descriptor_index: reader.read()?,
+                                attributes: JavaClass::parse_attributes(reader, constant_pool)?,
+                            });
                         }
 
-                        bootstrap_methods.push(BootstrapMethod {
-                            bootstrap_method_ref,
-                            bootstrap_arguments,
-                        });
+                        AttributeInfoData::Record(RecordAttribute { components })
                     }
 
-                    AttributeInfoData::BootstrapMethods(BootstrapMethodsAttribute {
-                        attribute_name_index,
-                        bootstrap_methods,
-                    })
-                }
+                    "Exceptions" => AttributeInfoData::Exceptions(ExceptionsAttribute {
+                        exception_index_table: reader.read_u16_table()?,
+                    }),
 
-                "Signature" => AttributeInfoData::Signature(SignatureAttribute {
-                    signature_index: reader.read().unwrap(),
-                }),
+                    "StackMapTable" => {
+                        let number_of_entries: u16 = reader.read()?;
+                        let mut entries = vec![];
 
-                "EnclosingMethod" => AttributeInfoData::EnclosingMethod(EnclosingMethodAttribute {
-                    class_index: reader.read().unwrap(),
-                    method_index: reader.read().unwrap(),
-                }),
+                        for _ in 0..number_of_entries {
+                            entries.push(JavaClass::parse_stack_map_frame(reader)?);
+                        }
 
-                "InnerClasses" => {
-                    reader.jump(attribute_length as usize);
-                    AttributeInfoData::None
-                }
+                        AttributeInfoData::StackMapTable(StackMapTableAttribute { entries })
+                    }
+                    "ConstantValue" => AttributeInfoData::ConstantValue(ConstantValueAttribute {
+                        constantvalue_index: reader.read()?,
+                    }),
 
-                "Exceptions" => AttributeInfoData::Exceptions(ExceptionsAttribute {
-                    exception_index_table: (0..reader.read::<u16>().unwrap())
-                        .map(|_| reader.read().unwrap())
-                        .collect(),
-                }),
-
-                "StackMapTable" => {
-                    reader.jump(attribute_length as usize);
-                    // println!("StackMapTable not implemented");
-
-                    AttributeInfoData::StackMapTable(StackMapTableAttribute { entries: vec![] })
-                    /*
-                    AttributeInfoData::StackMapTable(StackMapTableAttribute {
-                        entries: (0..*reader.read::<u16>().unwrap())
-                            .map(|_| {
-                                let frame_type: u8 = *reader.read().unwrap();
-
-                                match frame_type {
-                                    0..=63 => StackMapFrame::SameFrame,
-                                    64..=127 => StackMapFrame::SameLocalsStackItemFrame,
-                                    247 => StackMapFrame::SameLocalsStackItemFrameExtended,
-                                    248..=250 => StackMapFrame::ChopFrame,
-                                    251 => StackMapFrame::SameFrameExtended,
-                                    252..=254 => StackMapFrame::AppendFrame,
-                                    255 => StackMapFrame::FullFrame,
-
-                                    _ => unreachable!("This should be unreacable if everything"),
-                                }
-                            })
-                            .collect(),
-                    })
-                    */
-                }
-                "ConstantValue" => AttributeInfoData::ConstantValue(ConstantValueAttribute {
-                    constantvalue_index: reader.read().unwrap(),
-                }),
-
-                "LocalVariableTable" => {
-                    AttributeInfoData::LocalVariableTable(LocalVariableTableAttribute {
-                        local_variable_table: (0..reader.read::<u16>().unwrap())
-                            .map(|_| LocalVariableTableEntry {
-                                start_pc: reader.read().unwrap(),
-                                length: reader.read().unwrap(),
-                                name_index: reader.read().unwrap(),
-                                signature_descriptor_index: reader.read().unwrap(),
-                                index: reader.read().unwrap(),
-                            })
-                            .collect(),
-                    })
-                }
+                    "LocalVariableTable" => {
+                        AttributeInfoData::LocalVariableTable(LocalVariableTableAttribute {
+                            local_variable_table: JavaClass::parse_local_variable_table(reader)?,
+                        })
+                    }
 
-                "LocalVariableTypeTable" => {
-                    AttributeInfoData::LocalVariableTypeTable(LocalVariableTypeTableAttribute {
-                        local_variable_type_table: (0..reader.read::<u16>().unwrap())
-                            .map(|_| LocalVariableTableEntry {
-                                start_pc: reader.read().unwrap(),
-                                length: reader.read().unwrap(),
-                                name_index: reader.read().unwrap(),
-                                signature_descriptor_index: reader.read().unwrap(),
-                                index: reader.read().unwrap(),
-                            })
-                            .collect(),
-                    })
-                }
+                    "LocalVariableTypeTable" => {
+                        AttributeInfoData::LocalVariableTypeTable(LocalVariableTypeTableAttribute {
+                            local_variable_type_table: JavaClass::parse_local_variable_table(
+                                reader,
+                            )?,
+                        })
+                    }
 
-                "Synthetic" => AttributeInfoData::Synthetic,
-
-                // Optional to implement
-                "SourceDebugExtension"
-                | "Deprecated"
-                | "RuntimeVisibleAnnotations"
-                | "RuntimeInvisibleAnnotations"
-                | "RuntimeVisibleParameterAnnotations"
-                | "RuntimeInvisibleParameterAnnotations"
-                | "RuntimeVisibleTypeAnnotations"
-                | "RuntimeInvisibleTypeAnnotations"
-                | "AnnotationDefault"
-                | "MethodParameters"
-                | "Module"
-                | "ModulePackages"
-                | "ModuleMainClass" => {
-                    // Just skip over them
-                    reader.jump(attribute_length as usize);
-                    // Add it with the attribute tag (so we can see it is skipped)
-                    AttributeInfoData::NoneAnnotated(attribute_tag.data.clone())
-                }
+                    "Synthetic" => AttributeInfoData::Synthetic,
+
+                    "RuntimeVisibleAnnotations" => {
+                        AttributeInfoData::RuntimeVisibleAnnotations(AnnotationsAttribute {
+                            annotations: JavaClass::parse_annotations(reader)?,
+                        })
+                    }
+                    "RuntimeInvisibleAnnotations" => {
+                        AttributeInfoData::RuntimeInvisibleAnnotations(AnnotationsAttribute {
+                            annotations: JavaClass::parse_annotations(reader)?,
+                        })
+                    }
+
+                    "RuntimeVisibleParameterAnnotations" => {
+                        AttributeInfoData::RuntimeVisibleParameterAnnotations(
+                            JavaClass::parse_parameter_annotations(reader)?,
+                        )
+                    }
+                    "RuntimeInvisibleParameterAnnotations" => {
+                        AttributeInfoData::RuntimeInvisibleParameterAnnotations(
+                            JavaClass::parse_parameter_annotations(reader)?,
+                        )
+                    }
+
+                    "RuntimeVisibleTypeAnnotations" => {
+                        AttributeInfoData::RuntimeVisibleTypeAnnotations(TypeAnnotationsAttribute {
+                            annotations: JavaClass::parse_type_annotations(reader)?,
+                        })
+                    }
+                    "RuntimeInvisibleTypeAnnotations" => {
+                        AttributeInfoData::RuntimeInvisibleTypeAnnotations(
+                            TypeAnnotationsAttribute {
+                                annotations: JavaClass::parse_type_annotations(reader)?,
+                            },
+                        )
+                    }
 
-                not_implemented_type => {
-                    // reader.jump(attribute_length as usize);
+                    "AnnotationDefault" => {
+                        AttributeInfoData::AnnotationDefault(AnnotationDefaultAttribute {
+                            default_value: JavaClass::parse_element_value(reader)?,
+                        })
+                    }
+
+                    "Module" => AttributeInfoData::Module(JavaClass::parse_module(reader)?),
 
-                    // println!(
-                    //     "#{i} = Skipping parsing for attribute: {}",
-                    //     not_implemented_type
-                    // );
+                    "ModulePackages" => {
+                        AttributeInfoData::ModulePackages(ModulePackagesAttribute {
+                            package_index: reader.read_u16_table()?,
+                        })
+                    }
 
-                    // AttributeInfoData::None
-                    todo!(
-                        "Implement attribute parsing for attribute: {}",
-                        not_implemented_type
-                    )
+                    "ModuleMainClass" => {
+                        AttributeInfoData::ModuleMainClass(ModuleMainClassAttribute {
+                            main_class_index: reader.read()?,
+                        })
+                    }
+
+                    "Deprecated" => AttributeInfoData::Deprecated,
+
+                    "SourceDebugExtension" => {
+                        AttributeInfoData::SourceDebugExtension(SourceDebugExtensionAttribute {
+                            debug_extension: reader.read_bytes(attribute_length as usize)?,
+                        })
+                    }
+
+                    "MethodParameters" => {
+                        let parameters_count: u8 = reader.read()?;
+                        let mut parameters = vec![];
+
+                        for _ in 0..parameters_count {
+                            parameters.push(MethodParameter {
+                                name_index: reader.read()?,
+                                access_flags: reader.read()?,
+                            });
+                        }
+
+                        AttributeInfoData::MethodParameters(MethodParametersAttribute {
+                            parameters,
+                        })
+                    }
+
+                    // Attributes we don't know are kept as they are (JVMS 4.7.1)
+                    name => AttributeInfoData::Unknown(UnknownAttribute {
+                        name: name.to_string(),
+                        info: reader.read_bytes(attribute_length as usize)?,
+                    }),
+                };
+
+                if reader.offset() - start != attribute_length as usize {
+                    return Err(reader.attribute_length(start, attribute_length));
                 }
-            };
+                Ok(attribute)
+            })?;
+
             attributes.push(AttributeInfo {
                 attribute_name_index,
                 attribute,
@@ -338,10 +486,313 @@ impl JavaClass {
         Ok(attributes)
     }
 
+    fn parse_local_variable_table(
+        reader: &mut ClassReader<'a>,
+    ) -> Result<Vec<LocalVariableTableEntry>, ClassFormatError> {
+        let table_length: u16 = reader.read()?;
+        let mut table = vec![];
+
+        for _ in 0..table_length {
+            table.push(LocalVariableTableEntry {
+                start_pc: reader.read()?,
+                length: reader.read()?,
+                name_index: reader.read()?,
+                signature_descriptor_index: reader.read()?,
+                index: reader.read()?,
+            });
+        }
+
+        Ok(table)
+    }
+
+    fn parse_stack_map_frame(
+        reader: &mut ClassReader<'a>,
+    ) -> Result<StackMapFrame, ClassFormatError> {
+        let frame_type: u8 = reader.read()?;
+
+        let frame = match frame_type {
+            0..=63 => StackMapFrame::SameFrame {
+                offset_delta: frame_type as u16,
+            },
+            64..=127 => StackMapFrame::SameLocalsStackItemFrame {
+                offset_delta: frame_type as u16 - 64,
+                stack: JavaClass::parse_verification_type(reader)?,
+            },
+            247 => StackMapFrame::SameLocalsStackItemFrameExtended {
+                offset_delta: reader.read()?,
+                stack: JavaClass::parse_verification_type(reader)?,
+            },
+            248..=250 => StackMapFrame::ChopFrame {
+                offset_delta: reader.read()?,
+                chopped: 251 - frame_type,
+            },
+            251 => StackMapFrame::SameFrameExtended {
+                offset_delta: reader.read()?,
+            },
+            252..=254 => {
+                let offset_delta = reader.read()?;
+                let locals = (0..frame_type - 251)
+                    .map(|_| JavaClass::parse_verification_type(reader))
+                    .collect::<Result<_, _>>()?;
+
+                StackMapFrame::AppendFrame {
+                    offset_delta,
+                    locals,
+                }
+            }
+            255 => {
+                let offset_delta = reader.read()?;
+                let number_of_locals: u16 = reader.read()?;
+                let locals = (0..number_of_locals)
+                    .map(|_| JavaClass::parse_verification_type(reader))
+                    .collect::<Result<_, _>>()?;
+                let number_of_stack_items: u16 = reader.read()?;
+                let stack = (0..number_of_stack_items)
+                    .map(|_| JavaClass::parse_verification_type(reader))
+                    .collect::<Result<_, _>>()?;
+
+                StackMapFrame::FullFrame {
+                    offset_delta,
+                    locals,
+                    stack,
+                }
+            }
+            reserved => return Err(reader.unknown_tag(reserved)),
+        };
+
+        Ok(frame)
+    }
+
+    fn parse_verification_type(
+        reader: &mut ClassReader<'a>,
+    ) -> Result<VerificationTypeInfo, ClassFormatError> {
+        let tag: u8 = reader.read()?;
+
+        let verification_type = match tag {
+            0 => VerificationTypeInfo::Top,
+            1 => VerificationTypeInfo::Integer,
+            2 => VerificationTypeInfo::Float,
+            3 => VerificationTypeInfo::Double,
+            4 => VerificationTypeInfo::Long,
+            5 => VerificationTypeInfo::Null,
+            6 => VerificationTypeInfo::UninitializedThis,
+            7 => VerificationTypeInfo::Object {
+                cpool_index: reader.read()?,
+            },
+            8 => VerificationTypeInfo::Uninitialized {
+                offset: reader.read()?,
+            },
+            unknown => return Err(reader.unknown_tag(unknown)),
+        };
+
+        Ok(verification_type)
+    }
+
+    fn parse_module(reader: &mut ClassReader<'a>) -> Result<ModuleAttribute, ClassFormatError> {
+        let module_name_index = reader.read()?;
+        let module_flags = reader.read()?;
+        let module_version_index = reader.read()?;
+
+        let requires_count: u16 = reader.read()?;
+        let mut requires = vec![];
+        for _ in 0..requires_count {
+            requires.push(ModuleRequires {
+                requires_index: reader.read()?,
+                requires_flags: reader.read()?,
+                requires_version_index: reader.read()?,
+            });
+        }
+
+        let exports_count: u16 = reader.read()?;
+        let mut exports = vec![];
+        for _ in 0..exports_count {
+            exports.push(ModuleExports {
+                exports_index: reader.read()?,
+                exports_flags: reader.read()?,
+                exports_to_index: reader.read_u16_table()?,
+            });
+        }
+
+        let opens_count: u16 = reader.read()?;
+        let mut opens = vec![];
+        for _ in 0..opens_count {
+            opens.push(ModuleOpens {
+                opens_index: reader.read()?,
+                opens_flags: reader.read()?,
+                opens_to_index: reader.read_u16_table()?,
+            });
+        }
+
+        let uses_index = reader.read_u16_table()?;
+
+        let provides_count: u16 = reader.read()?;
+        let mut provides = vec![];
+        for _ in 0..provides_count {
+            provides.push(ModuleProvides {
+                provides_index: reader.read()?,
+                provides_with_index: reader.read_u16_table()?,
+            });
+        }
+
+        Ok(ModuleAttribute {
+            module_name_index,
+            module_flags,
+            module_version_index,
+            requires,
+            exports,
+            opens,
+            uses_index,
+            provides,
+        })
+    }
+
+    fn parse_annotations(
+        reader: &mut ClassReader<'a>,
+    ) -> Result<Vec<Annotation>, ClassFormatError> {
+        let num_annotations: u16 = reader.read()?;
+        (0..num_annotations)
+            .map(|_| JavaClass::parse_annotation(reader))
+            .collect()
+    }
+
+    fn parse_parameter_annotations(
+        reader: &mut ClassReader<'a>,
+    ) -> Result<ParameterAnnotationsAttribute, ClassFormatError> {
+        let num_parameters: u8 = reader.read()?;
+        let parameter_annotations = (0..num_parameters)
+            .map(|_| JavaClass::parse_annotations(reader))
+            .collect::<Result<_, _>>()?;
+
+        Ok(ParameterAnnotationsAttribute {
+            parameter_annotations,
+        })
+    }
+
+    fn parse_annotation(reader: &mut ClassReader<'a>) -> Result<Annotation, ClassFormatError> {
+        let type_index = reader.read()?;
+        let num_element_value_pairs: u16 = reader.read()?;
+        let mut element_value_pairs = vec![];
+
+        for _ in 0..num_element_value_pairs {
+            element_value_pairs.push(ElementValuePair {
+                element_name_index: reader.read()?,
+                value: JavaClass::parse_element_value(reader)?,
+            });
+        }
+
+        Ok(Annotation {
+            type_index,
+            element_value_pairs,
+        })
+    }
+
+    fn parse_element_value(reader: &mut ClassReader<'a>) -> Result<ElementValue, ClassFormatError> {
+        let tag: u8 = reader.read()?;
+
+        let value = match tag {
+            b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => ElementValue::Const {
+                tag,
+                const_value_index: reader.read()?,
+            },
+            b'e' => ElementValue::Enum {
+                type_name_index: reader.read()?,
+                const_name_index: reader.read()?,
+            },
+            b'c' => ElementValue::Class {
+                class_info_index: reader.read()?,
+            },
+            b'@' => ElementValue::Annotation(JavaClass::parse_annotation(reader)?),
+            b'[' => {
+                let num_values: u16 = reader.read()?;
+                ElementValue::Array(
+                    (0..num_values)
+                        .map(|_| JavaClass::parse_element_value(reader))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            unknown => return Err(reader.unknown_tag(unknown)),
+        };
+
+        Ok(value)
+    }
+
+    fn parse_type_annotations(
+        reader: &mut ClassReader<'a>,
+    ) -> Result<Vec<TypeAnnotation>, ClassFormatError> {
+        let num_annotations: u16 = reader.read()?;
+        let mut annotations = vec![];
+
+        for _ in 0..num_annotations {
+            let target_type: u8 = reader.read()?;
+
+            let target_info = match target_type {
+                0x00 | 0x01 => TypeAnnotationTarget::TypeParameter {
+                    type_parameter_index: reader.read()?,
+                },
+                0x10 => TypeAnnotationTarget::Supertype {
+                    supertype_index: reader.read()?,
+                },
+                0x11 | 0x12 => TypeAnnotationTarget::TypeParameterBound {
+                    type_parameter_index: reader.read()?,
+                    bound_index: reader.read()?,
+                },
+                0x13..=0x15 => TypeAnnotationTarget::Empty,
+                0x16 => TypeAnnotationTarget::FormalParameter {
+                    formal_parameter_index: reader.read()?,
+                },
+                0x17 => TypeAnnotationTarget::Throws {
+                    throws_type_index: reader.read()?,
+                },
+                0x40 | 0x41 => {
+                    let table_length: u16 = reader.read()?;
+                    let mut table = vec![];
+                    for _ in 0..table_length {
+                        table.push(LocalVarTargetEntry {
+                            start_pc: reader.read()?,
+                            length: reader.read()?,
+                            index: reader.read()?,
+                        });
+                    }
+                    TypeAnnotationTarget::LocalVar { table }
+                }
+                0x42 => TypeAnnotationTarget::Catch {
+                    exception_table_index: reader.read()?,
+                },
+                0x43..=0x46 => TypeAnnotationTarget::Offset {
+                    offset: reader.read()?,
+                },
+                0x47..=0x4B => TypeAnnotationTarget::TypeArgument {
+                    offset: reader.read()?,
+                    type_argument_index: reader.read()?,
+                },
+                unknown => return Err(reader.unknown_tag(unknown)),
+            };
+
+            let path_length: u8 = reader.read()?;
+            let mut target_path = vec![];
+            for _ in 0..path_length {
+                target_path.push(TypePathEntry {
+                    type_path_kind: reader.read()?,
+                    type_argument_index: reader.read()?,
+                });
+            }
+
+            annotations.push(TypeAnnotation {
+                target_type,
+                target_info,
+                target_path,
+                annotation: JavaClass::parse_annotation(reader)?,
+            });
+        }
+
+        Ok(annotations)
+    }
+
     fn parse_fields(
-        reader: &mut ByteReader,
-        constant_pool: &ConstantPool,
-    ) -> Result<Vec<FieldInfo>, Box<dyn Error>> {
+        reader: &mut ClassReader<'a>,
+        constant_pool: &ConstantPool<'a>,
+        attributes: AttributesParser<'a>,
+    ) -> Result<Vec<FieldInfo<'a>>, ClassFormatError> {
         let field_count: u16 = reader.read()?;
 
         let mut fields = vec![];
@@ -350,7 +801,7 @@ impl JavaClass {
                 access_flags: reader.read()?,
                 name_index: reader.read()?,
                 descriptor_index: reader.read()?,
-                attributes: JavaClass::parse_attributes(reader, constant_pool)?,
+                attributes: attributes(reader, constant_pool)?,
             })
         }
 
@@ -358,31 +809,626 @@ impl JavaClass {
     }
 }
 
-impl JavaClass {
-    pub fn get_method_by_name(&self, name: &String) -> Option<&MethodInfo> {
+impl JavaClass<'_> {
+    pub fn get_method_by_name(&self, name: &str) -> Option<&MethodInfo<'_>> {
         self.methods.iter().find(|method| {
-            &self
-                .constant_pool
+            self.constant_pool
                 .get_utf8_at(method.name_index)
-                .unwrap()
-                .data
-                == name
+                .map(|v| v.data.as_ref())
+                == Some(name)
         })
     }
+
+    /// Finds a method by both name and descriptor, which is needed to tell overloads apart
+    pub fn get_method(&self, name: &str, descriptor: &str) -> Option<&MethodInfo<'_>> {
+        self.methods.iter().find(|method| {
+            self.constant_pool
+                .get_utf8_at(method.name_index)
+                .map(|v| v.data.as_ref())
+                == Some(name)
+                && self
+                    .constant_pool
+                    .get_utf8_at(method.descriptor_index)
+                    .map(|v| v.data.as_ref())
+                    == Some(descriptor)
+        })
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<&FieldInfo<'_>> {
+        self.fields.iter().find(|field| {
+            self.constant_pool
+                .get_utf8_at(field.name_index)
+                .map(|v| v.data.as_ref())
+                == Some(name)
+        })
+    }
+
+    /// The binary name of this class, e.g. `java/lang/Object`
+    pub fn get_name(&self) -> Option<&str> {
+        self.constant_pool.get_class_name_at(self.this_class)
+    }
+
+    /// The binary name of the super class, `None` for `java/lang/Object`
+    pub fn get_super_name(&self) -> Option<&str> {
+        if self.super_class == 0 {
+            return None;
+        }
+        self.constant_pool.get_class_name_at(self.super_class)
+    }
+
+    pub fn get_interface_names(&self) -> Vec<&str> {
+        self.interfaces
+            .iter()
+            .filter_map(|index| self.constant_pool.get_class_name_at(*index))
+            .collect()
+    }
+
+    /// The binary name of the nest host named by the `NestHost` attribute, `None` if the class
+    /// doesn't claim to be a nest member
+    pub fn get_nest_host_name(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::NestHost(nest_host) => self
+                    .constant_pool
+                    .get_class_name_at(nest_host.host_class_index),
+                _ => None,
+            })
+    }
+
+    /// The binary names listed in the `NestMembers` attribute
+    pub fn get_nest_member_names(&self) -> Vec<&str> {
+        self.attributes
+            .iter()
+            .filter_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::NestMembers(members) => Some(&members.classes),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|index| self.constant_pool.get_class_name_at(*index))
+            .collect()
+    }
+
+    /// The binary names listed in the `PermittedSubclasses` attribute, empty unless the class is
+    /// sealed
+    pub fn get_permitted_subclass_names(&self) -> Vec<&str> {
+        self.attributes
+            .iter()
+            .filter_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::PermittedSubclasses(permitted) => Some(&permitted.classes),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|index| self.constant_pool.get_class_name_at(*index))
+            .collect()
+    }
+
+    /// The `Module` attribute, only present in a `module-info.class`
+    pub fn get_module(&self) -> Option<&ModuleAttribute> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::Module(module) => Some(module),
+                _ => None,
+            })
+    }
+
+    /// The internal names of the packages listed in the `ModulePackages` attribute
+    pub fn get_module_package_names(&self) -> Vec<&str> {
+        self.attributes
+            .iter()
+            .filter_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::ModulePackages(packages) => Some(&packages.package_index),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|index| self.constant_pool.get_package_name_at(*index))
+            .collect()
+    }
+
+    /// The binary name of the main class of a module, from the `ModuleMainClass` attribute
+    pub fn get_module_main_class_name(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::ModuleMainClass(main_class) => self
+                    .constant_pool
+                    .get_class_name_at(main_class.main_class_index),
+                _ => None,
+            })
+    }
+
+    pub fn get_inner_classes(&self) -> Option<&InnerClassesAttribute> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::InnerClasses(inner_classes) => Some(inner_classes),
+                _ => None,
+            })
+    }
+
+    /// The components of a record class, `None` if the class isn't a record
+    pub fn get_record(&self) -> Option<&RecordAttribute<'_>> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::Record(record) => Some(record),
+                _ => None,
+            })
+    }
+
+    /// The generic signature of the class, parse it with
+    /// [`parse_class_signature`](super::signature::parse_class_signature)
+    pub fn get_signature(&self) -> Option<&str> {
+        signature_of(&self.attributes, &self.constant_pool)
+    }
+
+    pub fn get_bootstrap_methods(&self) -> Option<&BootstrapMethodsAttribute> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::BootstrapMethods(bootstrap_methods) => Some(bootstrap_methods),
+                _ => None,
+            })
+    }
+}
+
+/// The `Signature` attribute among `attributes`, e.g. `Ljava/util/List<TT;>;`
+fn signature_of<'a>(
+    attributes: &[AttributeInfo<'_>],
+    constant_pool: &'a ConstantPool<'_>,
+) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find_map(|attribute| match &attribute.attribute {
+            AttributeInfoData::Signature(signature) => constant_pool
+                .get_utf8_at(signature.signature_index)
+                .map(|v| v.data.as_ref()),
+            _ => None,
+        })
+}
+
+/// Runtime visible and invisible annotations found among `attributes`
+fn annotations_of<'a>(attributes: &'a [AttributeInfo<'_>]) -> impl Iterator<Item = &'a Annotation> {
+    attributes
+        .iter()
+        .filter_map(|attribute| match &attribute.attribute {
+            AttributeInfoData::RuntimeVisibleAnnotations(annotations)
+            | AttributeInfoData::RuntimeInvisibleAnnotations(annotations) => {
+                Some(annotations.annotations.iter())
+            }
+            _ => None,
+        })
+        .flatten()
+}
+
+fn find_annotation<'a>(
+    attributes: &'a [AttributeInfo<'_>],
+    constant_pool: &ConstantPool<'_>,
+    descriptor: &str,
+) -> Option<&'a Annotation> {
+    annotations_of(attributes).find(|annotation| {
+        constant_pool
+            .get_utf8_at(annotation.type_index)
+            .map(|v| v.data.as_ref())
+            == Some(descriptor)
+    })
+}
+
+impl JavaClass<'_> {
+    pub fn annotations(&self) -> impl Iterator<Item = &Annotation> {
+        annotations_of(&self.attributes)
+    }
+
+    /// Finds an annotation on the class by its type descriptor, e.g. `Ljava/lang/FunctionalInterface;`
+    pub fn get_annotation(&self, descriptor: &str) -> Option<&Annotation> {
+        find_annotation(&self.attributes, &self.constant_pool, descriptor)
+    }
+}
+
+impl Annotation {
+    /// The field descriptor of the annotation interface
+    pub fn get_type<'a>(&self, constant_pool: &'a ConstantPool) -> Option<&'a str> {
+        constant_pool
+            .get_utf8_at(self.type_index)
+            .map(|v| v.data.as_ref())
+    }
+
+    pub fn get_element(&self, constant_pool: &ConstantPool, name: &str) -> Option<&ElementValue> {
+        self.element_value_pairs
+            .iter()
+            .find(|pair| {
+                constant_pool
+                    .get_utf8_at(pair.element_name_index)
+                    .map(|v| v.data.as_ref())
+                    == Some(name)
+            })
+            .map(|pair| &pair.value)
+    }
+}
+
+impl MethodInfo<'_> {
+    pub fn annotations(&self) -> impl Iterator<Item = &Annotation> {
+        annotations_of(&self.attributes)
+    }
+
+    /// Finds an annotation on the method by its type descriptor, e.g. `Ljava/lang/Override;`
+    pub fn get_annotation(
+        &self,
+        constant_pool: &ConstantPool,
+        descriptor: &str,
+    ) -> Option<&Annotation> {
+        find_annotation(&self.attributes, constant_pool, descriptor)
+    }
+
+    /// The annotations of every formal parameter, visible ones first
+    pub fn parameter_annotations(&self) -> Vec<Vec<&Annotation>> {
+        let mut parameters: Vec<Vec<&Annotation>> = vec![];
+
+        for attribute in &self.attributes {
+            let (AttributeInfoData::RuntimeVisibleParameterAnnotations(attribute)
+            | AttributeInfoData::RuntimeInvisibleParameterAnnotations(attribute)) =
+                &attribute.attribute
+            else {
+                continue;
+            };
+
+            for (i, annotations) in attribute.parameter_annotations.iter().enumerate() {
+                if parameters.len() <= i {
+                    parameters.resize_with(i + 1, Vec::new);
+                }
+                parameters[i].extend(annotations);
+            }
+        }
+
+        parameters
+    }
+
+    /// The default value of an annotation interface element
+    pub fn get_annotation_default(&self) -> Option<&ElementValue> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::AnnotationDefault(default) => Some(&default.default_value),
+                _ => None,
+            })
+    }
+
+    /// The generic signature of the method, parse it with
+    /// [`parse_method_signature`](super::signature::parse_method_signature)
+    pub fn get_signature<'a>(&self, constant_pool: &'a ConstantPool) -> Option<&'a str> {
+        signature_of(&self.attributes, constant_pool)
+    }
+
+    pub fn get_code(&self) -> Option<&CodeAttribute<'_>> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::Code(code) => Some(code),
+                _ => None,
+            })
+    }
+}
+
+impl CodeAttribute<'_> {
+    pub fn get_stack_map_table(&self) -> Option<&StackMapTableAttribute> {
+        self.attribute_info
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::StackMapTable(stack_map) => Some(stack_map),
+                _ => None,
+            })
+    }
+}
+
+impl FieldInfo<'_> {
+    pub fn annotations(&self) -> impl Iterator<Item = &Annotation> {
+        annotations_of(&self.attributes)
+    }
+
+    pub fn get_annotation(
+        &self,
+        constant_pool: &ConstantPool,
+        descriptor: &str,
+    ) -> Option<&Annotation> {
+        find_annotation(&self.attributes, constant_pool, descriptor)
+    }
+
+    /// The generic signature of the field, parse it with
+    /// [`parse_field_signature`](super::signature::parse_field_signature)
+    pub fn get_signature<'a>(&self, constant_pool: &'a ConstantPool) -> Option<&'a str> {
+        signature_of(&self.attributes, constant_pool)
+    }
+
+    pub fn get_constant_value(&self) -> Option<&ConstantValueAttribute> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::ConstantValue(value) => Some(value),
+                _ => None,
+            })
+    }
 }
 
 #[derive(Debug)]
-pub struct MethodInfo {
+pub struct MethodInfo<'a> {
     pub access_flags: AccessFlags,
     pub name_index: u16,
     pub descriptor_index: u16,
-    pub attributes: Vec<AttributeInfo>,
+    pub attributes: Vec<AttributeInfo<'a>>,
+}
+
+impl MethodInfo<'_> {
+    pub fn into_owned(self) -> MethodInfo<'static> {
+        MethodInfo {
+            access_flags: self.access_flags,
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: into_owned_attributes(self.attributes),
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct FieldInfo {
+pub struct FieldInfo<'a> {
     pub access_flags: AccessFlags,
     pub name_index: u16,
     pub descriptor_index: u16,
-    pub attributes: Vec<AttributeInfo>,
+    pub attributes: Vec<AttributeInfo<'a>>,
+}
+
+impl FieldInfo<'_> {
+    pub fn into_owned(self) -> FieldInfo<'static> {
+        FieldInfo {
+            access_flags: self.access_flags,
+            name_index: self.name_index,
+            descriptor_index: self.descriptor_index,
+            attributes: into_owned_attributes(self.attributes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{annotations_of, JavaClass};
+    use crate::{
+        classfile::{
+            attributes::{Annotation, ElementValue},
+            constant_pool::{ConstantPool, CpInfo},
+            error::ClassFormatError,
+        },
+    };
+
+    fn test_class_bytes(name: &str) -> Vec<u8> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("test-data/classes")
+            .join(format!("{name}.class"));
+        std::fs::read(path).unwrap()
+    }
+
+    /// A class of `test-data/classes`, compiled from `test-data/src` with `javac --release 8`,
+    /// `--release 17` for `Shapes.java` and the `module` directory
+    fn test_class(name: &str) -> JavaClass<'static> {
+        JavaClass::from_bytes(&test_class_bytes(name)).unwrap().into_owned()
+    }
+
+    fn utf8<'a>(constant_pool: &'a ConstantPool, index: u16) -> &'a str {
+        &constant_pool.get_utf8_at(index).unwrap().data
+    }
+
+    /// The elements of an annotation written like the source, e.g. `value="shapes"`
+    fn describe(value: &ElementValue, constant_pool: &ConstantPool) -> String {
+        match value {
+            ElementValue::Const {
+                tag: b's',
+                const_value_index,
+            } => format!("{:?}", utf8(constant_pool, *const_value_index)),
+            ElementValue::Const {
+                const_value_index, ..
+            } => match constant_pool.get_at(*const_value_index) {
+                Some(CpInfo::Integer(int)) => int.bytes.to_string(),
+                entry => panic!("unexpected constant {entry:?}"),
+            },
+            ElementValue::Enum {
+                type_name_index,
+                const_name_index,
+            } => format!(
+                "{}.{}",
+                utf8(constant_pool, *type_name_index),
+                utf8(constant_pool, *const_name_index)
+            ),
+            ElementValue::Class { class_info_index } => {
+                format!("{}.class", utf8(constant_pool, *class_info_index))
+            }
+            ElementValue::Annotation(annotation) => describe_annotation(annotation, constant_pool),
+            ElementValue::Array(values) => {
+                let values = values
+                    .iter()
+                    .map(|value| describe(value, constant_pool))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", values.join(", "))
+            }
+        }
+    }
+
+    fn describe_annotation(annotation: &Annotation, constant_pool: &ConstantPool) -> String {
+        let elements = annotation
+            .element_value_pairs
+            .iter()
+            .map(|pair| {
+                format!(
+                    "{}={}",
+                    utf8(constant_pool, pair.element_name_index),
+                    describe(&pair.value, constant_pool)
+                )
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "@{}({})",
+            annotation.get_type(constant_pool).unwrap(),
+            elements.join(", ")
+        )
+    }
+
+    #[test]
+    fn annotations_are_decoded() {
+        let annotations = test_class("Annotations");
+        let constant_pool = &annotations.constant_pool;
+
+        let info = annotations.get_annotation("LInfo;").unwrap();
+        assert_eq!(
+            describe_annotation(info, constant_pool),
+            "@LInfo;(value=\"shapes\", numbers={3, 4, 5}, type=Ljava/lang/String;.class, \
+             kind=Ljava/lang/annotation/ElementType;.FIELD)"
+        );
+
+        let sorted = annotations.get_method_by_name("sorted").unwrap();
+        assert!(sorted
+            .get_annotation(constant_pool, "Ljava/lang/Deprecated;")
+            .is_some());
+        let parameters = sorted.parameter_annotations();
+        assert_eq!(parameters.len(), 2);
+        assert!(parameters[0].is_empty());
+        assert_eq!(
+            describe_annotation(parameters[1][0], constant_pool),
+            "@LInfo;(value=\"flag\")"
+        );
+
+        let info = test_class("Info");
+        let default = |name: &str| {
+            let method = info.get_method_by_name(name).unwrap();
+            describe(
+                method.get_annotation_default().unwrap(),
+                &info.constant_pool,
+            )
+        };
+        assert_eq!(default("value"), "\"none\"");
+        assert_eq!(default("numbers"), "{1, 2}");
+        assert_eq!(default("type"), "Ljava/lang/Object;.class");
+        assert_eq!(default("nested"), "@Ljava/lang/Deprecated;()");
+    }
+
+    #[test]
+    fn nests_records_and_sealed_types_are_decoded() {
+        let shapes = test_class("Shapes");
+        let members = shapes.get_nest_member_names();
+        for member in ["Shapes$Circle", "Shapes$Square", "Shapes$1"] {
+            assert!(members.contains(&member), "{member} isn't in {members:?}");
+        }
+        let inner_names = shapes
+            .get_inner_classes()
+            .unwrap()
+            .classes
+            .iter()
+            .filter(|inner| inner.inner_name_index != 0)
+            .map(|inner| utf8(&shapes.constant_pool, inner.inner_name_index))
+            .collect::<Vec<_>>();
+        assert!(inner_names.contains(&"Circle") && inner_names.contains(&"Square"));
+
+        let circle = test_class("Shapes$Circle");
+        assert_eq!(circle.get_nest_host_name(), Some("Shapes"));
+        let components = &circle.get_record().unwrap().components;
+        assert_eq!(components.len(), 1);
+        let radius = &components[0];
+        assert_eq!(utf8(&circle.constant_pool, radius.name_index), "radius");
+        assert_eq!(utf8(&circle.constant_pool, radius.descriptor_index), "D");
+        let annotations = annotations_of(&radius.attributes).collect::<Vec<_>>();
+        assert_eq!(
+            describe_annotation(annotations[0], &circle.constant_pool),
+            "@LInfo;(value=\"radius\")"
+        );
+
+        let shape = test_class("Shape");
+        assert_eq!(
+            shape.get_permitted_subclass_names(),
+            ["Shapes$Circle", "Shapes$Square"]
+        );
+    }
+
+    #[test]
+    fn module_attributes_are_decoded() {
+        let module_info = test_class("module/module-info");
+        let constant_pool = &module_info.constant_pool;
+        let module = module_info.get_module().unwrap();
+        let module_names = |indices: &[u16]| {
+            indices
+                .iter()
+                .map(|index| constant_pool.get_module_name_at(*index).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let package_name = |index| constant_pool.get_package_name_at(index).unwrap();
+        let class_names = |indices: &[u16]| {
+            indices
+                .iter()
+                .map(|index| constant_pool.get_class_name_at(*index).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(module_names(&[module.module_name_index]), ["sample"]);
+        let requires = module
+            .requires
+            .iter()
+            .map(|requires| requires.requires_index)
+            .collect::<Vec<_>>();
+        assert_eq!(module_names(&requires), ["java.base", "java.logging"]);
+
+        let [exports] = &module.exports[..] else {
+            panic!("expected one exported package, found {:?}", module.exports);
+        };
+        assert_eq!(package_name(exports.exports_index), "sample");
+        assert!(exports.exports_to_index.is_empty());
+        let [opens] = &module.opens[..] else {
+            panic!("expected one opened package, found {:?}", module.opens);
+        };
+        assert_eq!(package_name(opens.opens_index), "sample");
+        assert_eq!(module_names(&opens.opens_to_index), ["java.logging"]);
+
+        assert_eq!(class_names(&module.uses_index), ["java/lang/Runnable"]);
+        let [provides] = &module.provides[..] else {
+            panic!("expected one provided service, found {:?}", module.provides);
+        };
+        assert_eq!(
+            class_names(&[provides.provides_index]),
+            ["java/lang/Runnable"]
+        );
+        assert_eq!(class_names(&provides.provides_with_index), ["sample/Task"]);
+    }
+
+    #[test]
+    fn malformed_classes_are_errors() {
+        let bytes = test_class_bytes("module/sample/Task");
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = 0;
+        assert_eq!(
+            JavaClass::from_bytes(&bad_magic).unwrap_err(),
+            ClassFormatError::BadMagic { magic: 0x00FEBABE }
+        );
+
+        // The first constant pool entry starts after the magic, the version and the pool count
+        let mut unknown_tag = bytes.clone();
+        unknown_tag[10] = 2;
+        let error = JavaClass::from_bytes(&unknown_tag).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Unknown tag 2 in constant_pool at offset 0xA"
+        );
+
+        // The last attribute of the class is its SourceFile
+        let error = JavaClass::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Truncated class file in SourceFile attribute at offset 0x102"
+        );
+        // Every prefix of the class ends in the middle of something
+        for length in 0..bytes.len() {
+            let error = JavaClass::from_bytes(&bytes[..length]).unwrap_err();
+            assert!(
+                error.offset() <= length,
+                "{error} is past the end of {length} bytes"
+            );
+        }
+    }
 }
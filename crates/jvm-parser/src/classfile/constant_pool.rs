@@ -1,69 +1,90 @@
-use byte_reader::ByteReader;
-use std::io::Result;
+use std::{borrow::Cow, collections::HashMap};
 
-#[derive(Debug, Clone)]
-pub struct ConstantPool {
-    pub pool_entries: Vec<CpInfo>,
+use super::{error::ClassFormatError, reader::ClassReader, writer::ClassWriter};
+
+#[derive(Debug, Default, Clone)]
+pub struct ConstantPool<'a> {
+    pub pool_entries: Vec<CpInfo<'a>>,
 }
 
-impl ConstantPool {
-    pub fn from_reader(reader: &mut ByteReader) -> Result<Self> {
-        let pool_count = reader.read::<u16>()? - 1;
+impl<'a> ConstantPool<'a> {
+    pub(crate) fn from_reader(reader: &mut ClassReader<'a>) -> Result<Self, ClassFormatError> {
+        let pool_count = reader.read::<u16>()?.saturating_sub(1) as usize;
 
-        let mut last_was_8byte = false;
-        let entries: Vec<CpInfo> = (0..pool_count)
-            .map(|i| {
-                if last_was_8byte {
-                    last_was_8byte = false;
-                    return CpInfo::EmptyCpEntry;
-                }
-                let cp_tag: u8 = reader.read().unwrap();
+        reader.within("constant_pool", |reader| {
+            let mut entries = Vec::with_capacity(pool_count);
+            while entries.len() < pool_count {
+                let cp_tag: u8 = reader.read()?;
 
-                if cp_tag == 5 || cp_tag == 6 { // CONSTANT_Long CONSTANT_Double
-                    last_was_8byte = true;
-                }
+                let entry = match cp_tag {
+                    1 => {
+                        let (data, raw) = reader.read_utf8()?;
+                        CpInfo::Utf8(CpInfoUtf8 { tag: "CONSTANT_Utf8", data, raw })
+                    }
+                    3 => CpInfo::Integer(CpInfoInteger { tag: "CONSTANT_Integer", bytes: reader.read()? }),
+                    4 => CpInfo::Float(CpInfoFloat { tag: "CONSTANT_Float", bytes: reader.read()? }),
+                    5 => CpInfo::Long(CpInfoLong { tag: "CONSTANT_Long", bytes: reader.read()? }),
+                    6 => CpInfo::Double(CpInfoDouble { tag: "CONSTANT_Double", bytes: reader.read()? }),
+                    7 => CpInfo::Class(CpInfoClass { tag: "CONSTANT_Class", name_index: reader.read()? }),
+                    8 => CpInfo::String(CpInfoString { tag: "CONSTANT_String", string_index: reader.read()? }),
+                    9 => CpInfo::Refs(CpInfoRefs { tag: "CONSTANT_Fieldref", class_index: reader.read()?, name_and_type_index: reader.read()? }),
+                    10 => CpInfo::Refs(CpInfoRefs { tag: "CONSTANT_Methodref", class_index: reader.read()?, name_and_type_index: reader.read()? }),
+                    11 => CpInfo::Refs(CpInfoRefs { tag: "CONSTANT_InterfaceMethodref", class_index: reader.read()?, name_and_type_index: reader.read()? }),
+                    12 => CpInfo::NameAndType(CpInfoNameAndType { tag: "CONSTANT_NameAndType", name_index: reader.read()?, descriptor_index: reader.read()? }),
+                    15 => CpInfo::MethodHandle(CpInfoMethodHandle { tag: "CONSTANT_MethodHandle", reference_kind: reader.read()?, reference_index: reader.read()? }),
+                    16 => CpInfo::MethodType(CpInfoMethodType { tag: "CONSTANT_MethodType", descriptor_index: reader.read()? }),
+                    17 => CpInfo::InvokeDynamic(CpInfoInvokeDynamic { tag: "CONSTANT_Dynamic", bootstrap_method_attr_index: reader.read()?, name_and_type_index: reader.read()? }),
+                    18 => CpInfo::InvokeDynamic(CpInfoInvokeDynamic { tag: "CONSTANT_InvokeDynamic", bootstrap_method_attr_index: reader.read()?, name_and_type_index: reader.read()? }),
+                    19 => CpInfo::Module(CpInfoModule { tag: "CONSTANT_Module", name_index: reader.read()? }),
+                    20 => CpInfo::Package(CpInfoPackage { tag: "CONSTANT_Package", name_index: reader.read()? }),
 
-                return match cp_tag  {
-                    1 => CpInfo::Utf8(CpInfoUtf8 { tag: "CONSTANT_Utf8", data: reader.read_string_lossy_length::<u16>().unwrap() }),
-                    3 => CpInfo::Integer(CpInfoInteger { tag: "CONSTANT_Integer", bytes: reader.read().unwrap() }),
-                    4 => CpInfo::Float(CpInfoFloat { tag: "CONSTANT_Float", bytes: reader.read().unwrap() }),
-                    5 => CpInfo::Long(CpInfoLong { tag: "CONSTANT_Long", bytes: reader.read().unwrap() }),
-                    6 => CpInfo::Double(CpInfoDouble { tag: "CONSTANT_Double", bytes: reader.read().unwrap() }),
-                    7 => CpInfo::Class(CpInfoClass { tag: "CONSTANT_Class", name_index: reader.read().unwrap() }),
-                    8 => CpInfo::String(CpInfoString { tag: "CONSTANT_String", string_index: reader.read().unwrap() }),
-                    9 => CpInfo::Refs(CpInfoRefs { tag: "CONSTANT_Fieldref", class_index: reader.read().unwrap(), name_and_type_index: reader.read().unwrap() }),
-                    10 => CpInfo::Refs(CpInfoRefs { tag: "CONSTANT_Methodref", class_index: reader.read().unwrap(), name_and_type_index: reader.read().unwrap() }),
-                    11 => CpInfo::Refs(CpInfoRefs { tag: "CONSTANT_InterfaceMethodref", class_index: reader.read().unwrap(), name_and_type_index: reader.read().unwrap() }),
-                    12 => CpInfo::NameAndType(CpInfoNameAndType { tag: "CONSTANT_NameAndType", name_index: reader.read().unwrap(), descriptor_index: reader.read().unwrap() }),
-                    15 => CpInfo::MethodHandle(CpInfoMethodHandle { tag: "CONSTANT_MethodHandle", reference_kind: reader.read().unwrap(), reference_index: reader.read().unwrap() }),
-                    16 => CpInfo::MethodType(CpInfoMethodType { tag: "CONSTANT_MethodType", descriptor_index: reader.read().unwrap() }),
-                    17 => CpInfo::InvokeDynamic(CpInfoInvokeDynamic { tag: "CONSTANT_Dynamic", bootstrap_method_attr_index: reader.read().unwrap(), name_and_type_index: reader.read().unwrap() }),
-                    18 => CpInfo::InvokeDynamic(CpInfoInvokeDynamic { tag: "CONSTANT_InvokeDynamic", bootstrap_method_attr_index: reader.read().unwrap(), name_and_type_index: reader.read().unwrap() }),
-                    19 => todo!("Implement CONSTANT_TYPE: 'CONSTANT_Module'"),
-                    20 => todo!("Implement CONSTANT_TYPE: 'CONSTANT_Package'"),
-
-                    unknown_tag => unreachable!("Unknown CONSTANT_TYPE: {}\nConsult the oracle documentation for missing tag: https://docs.oracle.com/javase/specs/jvms/se19/html/jvms-4.html#jvms-4.4\n\nSome Extra Information:\nindex: {i}\npool_count: {pool_count}\nOffset: {:#X}\n", unknown_tag, reader.get_current_offset() - 1),
+                    // Consult the documentation for missing tags: https://docs.oracle.com/javase/specs/jvms/se19/html/jvms-4.html#jvms-4.4
+                    unknown_tag => return Err(reader.unknown_tag(unknown_tag)),
                 };
-            }).collect();
+                entries.push(entry);
+
+                // CONSTANT_Long and CONSTANT_Double take up two entries
+                if (cp_tag == 5 || cp_tag == 6) && entries.len() < pool_count {
+                    entries.push(CpInfo::EmptyCpEntry);
+                }
+            }
 
-        Ok(Self {
-            pool_entries: entries,
+            Ok(Self {
+                pool_entries: entries,
+            })
         })
     }
 
-    pub fn get_at(&self, index: u16) -> Option<&CpInfo> {
-        self.pool_entries.get(index as usize - 1)
+    /// Copies the entries that borrow from the class file
+    pub fn into_owned(self) -> ConstantPool<'static> {
+        ConstantPool {
+            pool_entries: self
+                .pool_entries
+                .into_iter()
+                .map(CpInfo::into_owned)
+                .collect(),
+        }
+    }
+
+    pub fn get_at(&self, index: u16) -> Option<&CpInfo<'a>> {
+        self.pool_entries.get((index as usize).checked_sub(1)?)
     }
 
     pub fn get_class_at(&self, index: u16) -> Option<&CpInfoClass> {
-        if let CpInfo::Class(class) = self.get_at(index).unwrap() {
+        if let CpInfo::Class(class) = self.get_at(index)? {
             return Some(class);
         }
         None
     }
 
+    pub fn get_class_name_at(&self, index: u16) -> Option<&str> {
+        let class = self.get_class_at(index)?;
+        self.get_utf8_at(class.name_index)
+            .map(|utf8| utf8.data.as_ref())
+    }
+
     pub fn get_refs_at(&self, index: u16) -> Option<&CpInfoRefs> {
-        if let CpInfo::Refs(refs) = self.get_at(index).unwrap() {
+        if let CpInfo::Refs(refs) = self.get_at(index)? {
             return Some(refs);
         }
         None
@@ -87,30 +108,219 @@ impl ConstantPool {
     }
 
     pub fn get_name_type_at(&self, index: u16) -> Option<&CpInfoNameAndType> {
-        if let CpInfo::NameAndType(name_type) = self.get_at(index).unwrap() {
+        if let CpInfo::NameAndType(name_type) = self.get_at(index)? {
             return Some(name_type);
         }
         None
     }
 
-    pub fn get_utf8_at(&self, index: u16) -> Option<&CpInfoUtf8> {
-        if let CpInfo::Utf8(utf8) = self.get_at(index).unwrap() {
+    pub fn get_utf8_at(&self, index: u16) -> Option<&CpInfoUtf8<'a>> {
+        if let CpInfo::Utf8(utf8) = self.get_at(index)? {
             return Some(utf8);
         }
         None
     }
 
+    /// The name of the module at a `CONSTANT_Module` entry, e.g. `java.base`
+    pub fn get_module_name_at(&self, index: u16) -> Option<&str> {
+        let CpInfo::Module(module) = self.get_at(index)? else {
+            return None;
+        };
+        self.get_utf8_at(module.name_index)
+            .map(|utf8| utf8.data.as_ref())
+    }
+
+    /// The internal name of the package at a `CONSTANT_Package` entry, e.g. `java/lang`
+    pub fn get_package_name_at(&self, index: u16) -> Option<&str> {
+        let CpInfo::Package(package) = self.get_at(index)? else {
+            return None;
+        };
+        self.get_utf8_at(package.name_index)
+            .map(|utf8| utf8.data.as_ref())
+    }
+
     pub fn get_string_at(&self, index: u16) -> Option<&CpInfoString> {
-        if let CpInfo::String(str) = self.get_at(index).unwrap() {
+        if let CpInfo::String(str) = self.get_at(index)? {
             return Some(str);
         }
         None
     }
 }
 
+/// Builds a constant pool, adding an entry that is already in the pool returns the index of
+/// the existing one
+#[derive(Debug, Default)]
+pub struct ConstantPoolBuilder<'a> {
+    pool: ConstantPool<'a>,
+    /// The index of every entry, keyed by how the entry is written in a class file
+    indices: HashMap<Vec<u8>, u16>,
+}
+
+impl<'a> ConstantPoolBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Continues building on the entries of an existing pool, their indices stay the same
+    pub fn from_pool(pool: ConstantPool<'a>) -> Self {
+        let mut indices = HashMap::new();
+        for (i, entry) in pool.pool_entries.iter().enumerate() {
+            if !matches!(entry, CpInfo::EmptyCpEntry) {
+                indices.entry(entry_key(entry)).or_insert(i as u16 + 1);
+            }
+        }
+
+        Self { pool, indices }
+    }
+
+    pub fn build(self) -> ConstantPool<'a> {
+        self.pool
+    }
+
+    /// The entries added so far
+    pub fn constant_pool(&self) -> &ConstantPool<'a> {
+        &self.pool
+    }
+
+    /// Adds an entry unless an equal one exists, and returns its index
+    pub fn add(&mut self, entry: CpInfo<'a>) -> u16 {
+        let key = entry_key(&entry);
+        if let Some(index) = self.indices.get(&key) {
+            return *index;
+        }
+
+        let two_slots = matches!(entry, CpInfo::Long(_) | CpInfo::Double(_));
+        let index = u16::try_from(self.pool.pool_entries.len() + 1)
+            .ok()
+            .filter(|index| *index < u16::MAX - two_slots as u16)
+            .expect("A constant pool can't hold more than 65534 entries");
+
+        self.pool.pool_entries.push(entry);
+        if two_slots {
+            self.pool.pool_entries.push(CpInfo::EmptyCpEntry);
+        }
+        self.indices.insert(key, index);
+        index
+    }
+
+    pub fn utf8(&mut self, data: &str) -> u16 {
+        self.add(CpInfo::Utf8(CpInfoUtf8 {
+            tag: "CONSTANT_Utf8",
+            data: Cow::Owned(data.to_string()),
+            raw: None,
+        }))
+    }
+
+    pub fn integer(&mut self, value: i32) -> u16 {
+        self.add(CpInfo::Integer(CpInfoInteger {
+            tag: "CONSTANT_Integer",
+            bytes: value,
+        }))
+    }
+
+    pub fn float(&mut self, value: f32) -> u16 {
+        self.add(CpInfo::Float(CpInfoFloat {
+            tag: "CONSTANT_Float",
+            bytes: value,
+        }))
+    }
+
+    pub fn long(&mut self, value: i64) -> u16 {
+        self.add(CpInfo::Long(CpInfoLong {
+            tag: "CONSTANT_Long",
+            bytes: value as u64,
+        }))
+    }
+
+    pub fn double(&mut self, value: f64) -> u16 {
+        self.add(CpInfo::Double(CpInfoDouble {
+            tag: "CONSTANT_Double",
+            bytes: value,
+        }))
+    }
+
+    /// A class by its binary name, e.g. `java/lang/Object`, or an array class by its descriptor
+    pub fn class(&mut self, name: &str) -> u16 {
+        let name_index = self.utf8(name);
+        self.add(CpInfo::Class(CpInfoClass {
+            tag: "CONSTANT_Class",
+            name_index,
+        }))
+    }
+
+    pub fn string(&mut self, value: &str) -> u16 {
+        let string_index = self.utf8(value);
+        self.add(CpInfo::String(CpInfoString {
+            tag: "CONSTANT_String",
+            string_index,
+        }))
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> u16 {
+        let name_index = self.utf8(name);
+        let descriptor_index = self.utf8(descriptor);
+        self.add(CpInfo::NameAndType(CpInfoNameAndType {
+            tag: "CONSTANT_NameAndType",
+            name_index,
+            descriptor_index,
+        }))
+    }
+
+    pub fn field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        self.member_ref("CONSTANT_Fieldref", class, name, descriptor)
+    }
+
+    pub fn method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        self.member_ref("CONSTANT_Methodref", class, name, descriptor)
+    }
+
+    pub fn interface_method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> u16 {
+        self.member_ref("CONSTANT_InterfaceMethodref", class, name, descriptor)
+    }
+
+    fn member_ref(
+        &mut self,
+        tag: &'static str,
+        class: &str,
+        name: &str,
+        descriptor: &str,
+    ) -> u16 {
+        let class_index = self.class(class);
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        self.add(CpInfo::Refs(CpInfoRefs {
+            tag,
+            class_index,
+            name_and_type_index,
+        }))
+    }
+
+    pub fn method_type(&mut self, descriptor: &str) -> u16 {
+        let descriptor_index = self.utf8(descriptor);
+        self.add(CpInfo::MethodType(CpInfoMethodType {
+            tag: "CONSTANT_MethodType",
+            descriptor_index,
+        }))
+    }
+
+    /// `reference_index` is the field or method ref the handle refers to
+    pub fn method_handle(&mut self, reference_kind: u8, reference_index: u16) -> u16 {
+        self.add(CpInfo::MethodHandle(CpInfoMethodHandle {
+            tag: "CONSTANT_MethodHandle",
+            reference_kind,
+            reference_index,
+        }))
+    }
+}
+
+fn entry_key(entry: &CpInfo<'_>) -> Vec<u8> {
+    let mut writer = ClassWriter::default();
+    entry.write(&mut writer);
+    writer.into_bytes()
+}
+
 // From: https://docs.oracle.com/javase/specs/jvms/se7/html/jvms-4.html#jvms-4.4
 #[derive(Debug, Clone)]
-pub enum CpInfo {
+pub enum CpInfo<'a> {
     /// This dumb mf was needed because Oracle thought it was funny that 8byte Constant Pool entries shuld take up two spots
     /// Thankfully they admitted that this was a dumb mistake on their side (too late to fix apparently)
     /// Source: Check the italic text: https://docs.oracle.com/javase/specs/jvms/se19/html/jvms-4.html#jvms-4.4.5
@@ -119,7 +329,7 @@ pub enum CpInfo {
     Class(CpInfoClass),
     Refs(CpInfoRefs),
     NameAndType(CpInfoNameAndType),
-    Utf8(CpInfoUtf8),
+    Utf8(CpInfoUtf8<'a>),
     String(CpInfoString),
     Integer(CpInfoInteger),
     Float(CpInfoFloat),
@@ -128,6 +338,34 @@ pub enum CpInfo {
     InvokeDynamic(CpInfoInvokeDynamic),
     MethodHandle(CpInfoMethodHandle),
     MethodType(CpInfoMethodType),
+    Module(CpInfoModule),
+    Package(CpInfoPackage),
+}
+
+impl CpInfo<'_> {
+    pub fn into_owned(self) -> CpInfo<'static> {
+        match self {
+            CpInfo::EmptyCpEntry => CpInfo::EmptyCpEntry,
+            CpInfo::Class(class) => CpInfo::Class(class),
+            CpInfo::Refs(refs) => CpInfo::Refs(refs),
+            CpInfo::NameAndType(name_and_type) => CpInfo::NameAndType(name_and_type),
+            CpInfo::Utf8(utf8) => CpInfo::Utf8(CpInfoUtf8 {
+                tag: utf8.tag,
+                data: Cow::Owned(utf8.data.into_owned()),
+                raw: utf8.raw,
+            }),
+            CpInfo::String(string) => CpInfo::String(string),
+            CpInfo::Integer(integer) => CpInfo::Integer(integer),
+            CpInfo::Float(float) => CpInfo::Float(float),
+            CpInfo::Long(long) => CpInfo::Long(long),
+            CpInfo::Double(double) => CpInfo::Double(double),
+            CpInfo::InvokeDynamic(invoke_dynamic) => CpInfo::InvokeDynamic(invoke_dynamic),
+            CpInfo::MethodHandle(method_handle) => CpInfo::MethodHandle(method_handle),
+            CpInfo::MethodType(method_type) => CpInfo::MethodType(method_type),
+            CpInfo::Module(module) => CpInfo::Module(module),
+            CpInfo::Package(package) => CpInfo::Package(package),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -173,9 +411,12 @@ pub struct CpInfoNameAndType {
 }
 
 #[derive(Debug, Clone)]
-pub struct CpInfoUtf8 {
+pub struct CpInfoUtf8<'a> {
     pub tag: &'static str,
-    pub data: String,
+    /// Borrowed from the class file unless it had to be decoded
+    pub data: Cow<'a, str>,
+    /// The original bytes, kept only when they aren't valid modified utf8 and `data` is lossy
+    pub raw: Option<Vec<u8>>,
 }
 #[derive(Debug, Clone)]
 pub struct CpInfoString {
@@ -202,3 +443,15 @@ pub struct CpInfoMethodType {
     pub tag: &'static str,
     pub descriptor_index: u16,
 }
+
+#[derive(Debug, Clone)]
+pub struct CpInfoModule {
+    pub tag: &'static str,
+    pub name_index: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct CpInfoPackage {
+    pub tag: &'static str,
+    pub name_index: u16,
+}
@@ -0,0 +1,397 @@
+//! Field and method descriptors (JVMS 4.3), the erased types of fields and methods, e.g.
+//! `[Ljava/lang/String;` or `(IJLjava/lang/Object;)V`.
+//!
+//! The descriptors display in the form they have in a class file, so parsing and displaying
+//! gives back the same string.
+
+use std::fmt;
+
+/// Why a descriptor couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorError {
+    pub descriptor: String,
+    /// The byte offset in `descriptor` where parsing stopped
+    pub position: usize,
+    /// What was expected at `position`, e.g. `a field type`
+    pub expected: &'static str,
+}
+
+impl fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid descriptor {}, expected {} at position {}",
+            self.descriptor, self.expected, self.position
+        )
+    }
+}
+
+impl std::error::Error for DescriptorError {}
+
+/// The primitive types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+}
+
+impl BaseType {
+    pub(crate) fn from_tag(tag: u8) -> Option<BaseType> {
+        match tag {
+            b'B' => Some(BaseType::Byte),
+            b'C' => Some(BaseType::Char),
+            b'D' => Some(BaseType::Double),
+            b'F' => Some(BaseType::Float),
+            b'I' => Some(BaseType::Int),
+            b'J' => Some(BaseType::Long),
+            b'S' => Some(BaseType::Short),
+            b'Z' => Some(BaseType::Boolean),
+            _ => None,
+        }
+    }
+
+    /// The descriptor of the type, e.g. `I` for `int`
+    pub fn descriptor(&self) -> char {
+        match self {
+            BaseType::Byte => 'B',
+            BaseType::Char => 'C',
+            BaseType::Double => 'D',
+            BaseType::Float => 'F',
+            BaseType::Int => 'I',
+            BaseType::Long => 'J',
+            BaseType::Short => 'S',
+            BaseType::Boolean => 'Z',
+        }
+    }
+}
+
+/// The name of the type in java source, e.g. `int`
+impl fmt::Display for BaseType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BaseType::Byte => "byte",
+            BaseType::Char => "char",
+            BaseType::Double => "double",
+            BaseType::Float => "float",
+            BaseType::Int => "int",
+            BaseType::Long => "long",
+            BaseType::Short => "short",
+            BaseType::Boolean => "boolean",
+        };
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldDescriptor {
+    Base(BaseType),
+    /// A class or interface by its binary name, e.g. `java/lang/String`
+    Object(String),
+    /// An array and the type of its components
+    Array(Box<FieldDescriptor>),
+}
+
+impl FieldDescriptor {
+    /// The local variable slots a value of the type takes, 2 for `long` and `double`
+    pub fn slots(&self) -> usize {
+        match self {
+            FieldDescriptor::Base(BaseType::Long | BaseType::Double) => 2,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldDescriptor>,
+    /// `None` for `void`
+    pub return_type: Option<FieldDescriptor>,
+}
+
+impl MethodDescriptor {
+    /// The local variable slots the arguments take, without `this`
+    pub fn parameter_slots(&self) -> usize {
+        self.parameters.iter().map(FieldDescriptor::slots).sum()
+    }
+}
+
+pub fn parse_field_descriptor(descriptor: &str) -> Result<FieldDescriptor, DescriptorError> {
+    let mut parser = DescriptorParser {
+        descriptor,
+        position: 0,
+    };
+    let field = parser.field_type()?;
+    parser.finish()?;
+    Ok(field)
+}
+
+pub fn parse_method_descriptor(descriptor: &str) -> Result<MethodDescriptor, DescriptorError> {
+    let mut parser = DescriptorParser {
+        descriptor,
+        position: 0,
+    };
+
+    parser.expect(b'(', "(")?;
+    let mut parameters = vec![];
+    while !parser.eat(b')') {
+        parameters.push(parser.field_type()?);
+    }
+    let return_type = match parser.eat(b'V') {
+        true => None,
+        false => Some(parser.field_type()?),
+    };
+    parser.finish()?;
+
+    Ok(MethodDescriptor {
+        parameters,
+        return_type,
+    })
+}
+
+/// An array type can have at most 255 dimensions (JVMS 4.3.2)
+const MAX_DIMENSIONS: usize = 255;
+
+struct DescriptorParser<'a> {
+    descriptor: &'a str,
+    position: usize,
+}
+
+impl DescriptorParser<'_> {
+    fn error(&self, expected: &'static str) -> DescriptorError {
+        DescriptorError {
+            descriptor: self.descriptor.to_string(),
+            position: self.position,
+            expected,
+        }
+    }
+
+    /// Skips `byte` if it is next
+    fn eat(&mut self, byte: u8) -> bool {
+        let found = self.descriptor.as_bytes().get(self.position) == Some(&byte);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, byte: u8, expected: &'static str) -> Result<(), DescriptorError> {
+        match self.eat(byte) {
+            true => Ok(()),
+            false => Err(self.error(expected)),
+        }
+    }
+
+    fn finish(&self) -> Result<(), DescriptorError> {
+        match self.position == self.descriptor.len() {
+            true => Ok(()),
+            false => Err(self.error("the end of the descriptor")),
+        }
+    }
+
+    fn field_type(&mut self) -> Result<FieldDescriptor, DescriptorError> {
+        let mut dimensions = 0;
+        while self.eat(b'[') {
+            dimensions += 1;
+            if dimensions > MAX_DIMENSIONS {
+                self.position -= 1;
+                return Err(self.error("at most 255 array dimensions"));
+            }
+        }
+
+        let tag = self.descriptor.as_bytes().get(self.position).copied();
+        let mut field = match tag.and_then(BaseType::from_tag) {
+            Some(base_type) => {
+                self.position += 1;
+                FieldDescriptor::Base(base_type)
+            }
+            None if tag == Some(b'L') => {
+                self.position += 1;
+                FieldDescriptor::Object(self.class_name()?)
+            }
+            None => return Err(self.error("a field type")),
+        };
+
+        for _ in 0..dimensions {
+            field = FieldDescriptor::Array(Box::new(field));
+        }
+        Ok(field)
+    }
+
+    /// A binary name in internal form up to the `;` that ends it, every package and the class
+    /// are non-empty and can't contain `.`, `;` or `[` (JVMS 4.2.1)
+    fn class_name(&mut self) -> Result<String, DescriptorError> {
+        let start = self.position;
+        loop {
+            let rest = &self.descriptor[self.position..];
+            let length = rest.find(['.', ';', '[', '/']).unwrap_or(rest.len());
+            if length == 0 {
+                return Err(self.error("a class name"));
+            }
+            self.position += length;
+
+            if !self.eat(b'/') {
+                let name = self.descriptor[start..self.position].to_string();
+                self.expect(b';', ";")?;
+                return Ok(name);
+            }
+        }
+    }
+}
+
+impl fmt::Display for FieldDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldDescriptor::Base(base_type) => write!(f, "{}", base_type.descriptor()),
+            FieldDescriptor::Object(class_name) => write!(f, "L{class_name};"),
+            FieldDescriptor::Array(component) => write!(f, "[{component}"),
+        }
+    }
+}
+
+impl fmt::Display for MethodDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for parameter in &self.parameters {
+            write!(f, "{parameter}")?;
+        }
+        match &self.return_type {
+            Some(return_type) => write!(f, "){return_type}"),
+            None => write!(f, ")V"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{
+        parse_field_descriptor, parse_method_descriptor, BaseType, FieldDescriptor,
+        MethodDescriptor,
+    };
+
+    fn base_type() -> impl Strategy<Value = BaseType> {
+        prop_oneof![
+            Just(BaseType::Byte),
+            Just(BaseType::Char),
+            Just(BaseType::Double),
+            Just(BaseType::Float),
+            Just(BaseType::Int),
+            Just(BaseType::Long),
+            Just(BaseType::Short),
+            Just(BaseType::Boolean),
+        ]
+    }
+
+    fn field_descriptor() -> impl Strategy<Value = FieldDescriptor> {
+        let leaf = prop_oneof![
+            base_type().prop_map(FieldDescriptor::Base),
+            "[a-z][a-z0-9_]{0,8}(/[A-Za-z$][A-Za-z0-9_$\u{e9}]{0,8}){0,3}"
+                .prop_map(FieldDescriptor::Object),
+        ];
+        leaf.prop_recursive(4, 16, 1, |component| {
+            component.prop_map(|component| FieldDescriptor::Array(Box::new(component)))
+        })
+    }
+
+    fn method_descriptor() -> impl Strategy<Value = MethodDescriptor> {
+        (
+            prop::collection::vec(field_descriptor(), 0..6),
+            prop::option::of(field_descriptor()),
+        )
+            .prop_map(|(parameters, return_type)| MethodDescriptor {
+                parameters,
+                return_type,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn field_descriptors_round_trip(field in field_descriptor()) {
+            prop_assert_eq!(parse_field_descriptor(&field.to_string()), Ok(field));
+        }
+
+        #[test]
+        fn method_descriptors_round_trip(method in method_descriptor()) {
+            let parsed = parse_method_descriptor(&method.to_string());
+            prop_assert_eq!(parsed, Ok(method));
+        }
+
+        #[test]
+        fn garbage_is_rejected_without_panicking(descriptor in "[(\\[)BCDFIJSZVL;/a.]{0,12}") {
+            if let Ok(method) = parse_method_descriptor(&descriptor) {
+                prop_assert_eq!(&method.to_string(), &descriptor);
+            }
+            if let Ok(field) = parse_field_descriptor(&descriptor) {
+                prop_assert_eq!(&field.to_string(), &descriptor);
+            }
+        }
+    }
+
+    #[test]
+    fn parses_descriptors() {
+        let byte_void = parse_method_descriptor("(B)V").unwrap();
+        assert_eq!(
+            byte_void,
+            MethodDescriptor {
+                parameters: vec![FieldDescriptor::Base(BaseType::Byte)],
+                return_type: None,
+            }
+        );
+
+        let class_bool_array = parse_method_descriptor("(Ljava/io/PrintStream;)[Z").unwrap();
+        assert_eq!(
+            class_bool_array,
+            MethodDescriptor {
+                parameters: vec![FieldDescriptor::Object("java/io/PrintStream".to_string())],
+                return_type: Some(FieldDescriptor::Array(Box::new(FieldDescriptor::Base(
+                    BaseType::Boolean
+                )))),
+            }
+        );
+
+        let mut multidim_array = parse_field_descriptor("[[[[D").unwrap();
+        for _ in 0..4 {
+            let FieldDescriptor::Array(component) = multidim_array else {
+                panic!("Expected an array, found {multidim_array}");
+            };
+            multidim_array = *component;
+        }
+        assert_eq!(multidim_array, FieldDescriptor::Base(BaseType::Double));
+    }
+
+    #[test]
+    fn validates_descriptors() {
+        let method = parse_method_descriptor("(DJ[D[[Ljava/lang/String;I)V").unwrap();
+        assert_eq!(
+            method.parameters[0],
+            FieldDescriptor::Base(BaseType::Double)
+        );
+        assert_eq!(method.parameter_slots(), 7);
+        assert_eq!(method.return_type, None);
+
+        for invalid in [
+            "(Q)V",
+            "(I)",
+            "(I)VV",
+            "(Ljava/lang/String)V",
+            "(L;)V",
+            "(Ljava//String;)V",
+            "(Ljava.lang.String;)V",
+            "([)V",
+            "(V)V",
+        ] {
+            assert!(parse_method_descriptor(invalid).is_err(), "{invalid}");
+        }
+
+        assert!(parse_field_descriptor(&format!("{}I", "[".repeat(255))).is_ok());
+        let error = parse_field_descriptor(&format!("{}I", "[".repeat(256))).unwrap_err();
+        assert_eq!(error.position, 255);
+    }
+}
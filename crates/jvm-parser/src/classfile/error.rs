@@ -0,0 +1,93 @@
+use std::fmt;
+
+/// Why a class file couldn't be parsed. `offset` is where in the class file the problem was
+/// found and `structure` what was being parsed there, e.g. `method_info` or `Code attribute`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClassFormatError {
+    /// The class file doesn't start with `0xCAFEBABE`
+    BadMagic { magic: u32 },
+    /// The class file ended in the middle of a structure
+    Truncated { offset: usize, structure: String },
+    /// A tag byte that isn't defined, e.g. of a constant pool entry or an element value
+    UnknownTag {
+        offset: usize,
+        structure: String,
+        tag: u8,
+    },
+    /// A constant pool index that is out of range or refers to the wrong kind of entry
+    InvalidIndex {
+        offset: usize,
+        structure: String,
+        index: u16,
+    },
+    /// The contents of an attribute didn't take up exactly `attribute_length` bytes
+    AttributeLength {
+        offset: usize,
+        structure: String,
+        expected: u32,
+        actual: usize,
+    },
+}
+
+impl ClassFormatError {
+    pub fn offset(&self) -> usize {
+        match self {
+            ClassFormatError::BadMagic { .. } => 0,
+            ClassFormatError::Truncated { offset, .. }
+            | ClassFormatError::UnknownTag { offset, .. }
+            | ClassFormatError::InvalidIndex { offset, .. }
+            | ClassFormatError::AttributeLength { offset, .. } => *offset,
+        }
+    }
+
+    pub fn structure(&self) -> &str {
+        match self {
+            ClassFormatError::BadMagic { .. } => "magic",
+            ClassFormatError::Truncated { structure, .. }
+            | ClassFormatError::UnknownTag { structure, .. }
+            | ClassFormatError::InvalidIndex { structure, .. }
+            | ClassFormatError::AttributeLength { structure, .. } => structure,
+        }
+    }
+}
+
+impl fmt::Display for ClassFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClassFormatError::BadMagic { magic } => {
+                write!(f, "Incompatible magic value {magic:#010X}")
+            }
+            ClassFormatError::Truncated { offset, structure } => {
+                write!(
+                    f,
+                    "Truncated class file in {structure} at offset {offset:#X}"
+                )
+            }
+            ClassFormatError::UnknownTag {
+                offset,
+                structure,
+                tag,
+            } => write!(f, "Unknown tag {tag} in {structure} at offset {offset:#X}"),
+            ClassFormatError::InvalidIndex {
+                offset,
+                structure,
+                index,
+            } => write!(
+                f,
+                "Invalid constant pool index {index} in {structure} at offset {offset:#X}"
+            ),
+            ClassFormatError::AttributeLength {
+                offset,
+                structure,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "The {structure} at offset {offset:#X} has a length of {expected} bytes, but \
+                 {actual} bytes were read"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ClassFormatError {}
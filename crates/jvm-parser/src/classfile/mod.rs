@@ -1,5 +1,12 @@
 pub mod attributes;
 pub mod classfile;
 pub mod constant_pool;
+pub mod descriptor;
+pub mod error;
+mod mutf8;
+mod reader;
+pub mod signature;
+mod writer;
 
 pub use classfile::JavaClass;
+pub use error::ClassFormatError;
@@ -0,0 +1,62 @@
+//! The modified UTF-8 of `CONSTANT_Utf8` entries (JVMS 4.4.7). It differs from UTF-8 in that
+//! `\0` takes two bytes and characters outside the BMP are written as two 3-byte surrogates.
+
+use std::borrow::Cow;
+
+/// Decodes modified UTF-8, returns `None` if the bytes aren't valid or contain a lone surrogate.
+/// Almost every entry is also plain UTF-8, those are borrowed instead of decoded
+pub fn decode(bytes: &[u8]) -> Option<Cow<'_, str>> {
+    // Modified UTF-8 never has a `\0` byte or the 4-byte sequences of UTF-8, without them both
+    // encodings are the same
+    if !bytes.iter().any(|byte| *byte == 0 || *byte >= 0xF0) {
+        if let Ok(data) = std::str::from_utf8(bytes) {
+            return Some(Cow::Borrowed(data));
+        }
+    }
+
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let continuation = |offset: usize| {
+            bytes
+                .get(i + offset)
+                .filter(|byte| *byte & 0xC0 == 0x80)
+                .map(|byte| (byte & 0x3F) as u16)
+        };
+
+        let (unit, length) = match bytes[i] {
+            0x01..=0x7F => (bytes[i] as u16, 1),
+            byte if byte & 0xE0 == 0xC0 => (((byte & 0x1F) as u16) << 6 | continuation(1)?, 2),
+            byte if byte & 0xF0 == 0xE0 => (
+                ((byte & 0x0F) as u16) << 12 | continuation(1)? << 6 | continuation(2)?,
+                3,
+            ),
+            _ => return None,
+        };
+        units.push(unit);
+        i += length;
+    }
+
+    String::from_utf16(&units).ok().map(Cow::Owned)
+}
+
+pub fn encode(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(value.len());
+
+    for unit in value.encode_utf16() {
+        match unit {
+            0x01..=0x7F => bytes.push(unit as u8),
+            0x00 | 0x80..=0x7FF => {
+                bytes.extend([0xC0 | (unit >> 6) as u8, 0x80 | (unit & 0x3F) as u8]);
+            }
+            _ => bytes.extend([
+                0xE0 | (unit >> 12) as u8,
+                0x80 | ((unit >> 6) & 0x3F) as u8,
+                0x80 | (unit & 0x3F) as u8,
+            ]),
+        }
+    }
+
+    bytes
+}
@@ -0,0 +1,131 @@
+use std::{borrow::Cow, fmt};
+
+use byte_reader::{ByteReader, Endian, FromBinaryReader};
+
+use super::{error::ClassFormatError, mutf8};
+
+/// Reads the big endian items of a class file and turns failed reads into [`ClassFormatError`]s
+/// for the structure that is being parsed
+pub(crate) struct ClassReader<'a> {
+    reader: ByteReader<'a>,
+    structure: Structure<'a>,
+}
+
+/// The structure a [`ClassReader`] is in, it is only turned into a string for an error
+#[derive(Debug)]
+pub(crate) enum Structure<'a> {
+    /// e.g. `ClassFile` or `method_info`
+    Named(&'static str),
+    /// An attribute by its name, shown as e.g. `Code attribute`
+    Attribute(Cow<'a, str>),
+}
+
+impl From<&'static str> for Structure<'_> {
+    fn from(name: &'static str) -> Self {
+        Structure::Named(name)
+    }
+}
+
+impl fmt::Display for Structure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Structure::Named(name) => f.write_str(name),
+            Structure::Attribute(name) => write!(f, "{name} attribute"),
+        }
+    }
+}
+
+impl<'a> ClassReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        let mut reader = ByteReader::from_slice(bytes);
+        reader.set_endian(Endian::Big);
+
+        Self {
+            reader,
+            structure: Structure::Named("ClassFile"),
+        }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.reader.get_current_offset()
+    }
+
+    /// Parses a structure, errors while doing so are attributed to `structure`
+    pub fn within<T>(
+        &mut self,
+        structure: impl Into<Structure<'a>>,
+        parse: impl FnOnce(&mut Self) -> Result<T, ClassFormatError>,
+    ) -> Result<T, ClassFormatError> {
+        let outer = std::mem::replace(&mut self.structure, structure.into());
+        let result = parse(self);
+        self.structure = outer;
+        result
+    }
+
+    pub fn read<T: FromBinaryReader>(&mut self) -> Result<T, ClassFormatError> {
+        self.reader.read().map_err(|_| self.truncated())
+    }
+
+    /// Reads `length` bytes that borrow from the class file
+    pub fn read_slice(&mut self, length: usize) -> Result<&'a [u8], ClassFormatError> {
+        self.reader.read_slice(length).map_err(|_| self.truncated())
+    }
+
+    pub fn read_bytes(&mut self, length: usize) -> Result<Vec<u8>, ClassFormatError> {
+        Ok(self.read_slice(length)?.to_vec())
+    }
+
+    /// Reads a `u2` length followed by that many bytes of (modified) utf8. Bytes that don't decode
+    /// to a valid string (e.g. a lone surrogate) are returned as well so they can be written back
+    pub fn read_utf8(&mut self) -> Result<(Cow<'a, str>, Option<Vec<u8>>), ClassFormatError> {
+        let length: u16 = self.read()?;
+        let bytes = self.read_slice(length as usize)?;
+        Ok(match mutf8::decode(bytes) {
+            Some(data) => (data, None),
+            None => (
+                String::from_utf8_lossy(bytes).into_owned().into(),
+                Some(bytes.to_vec()),
+            ),
+        })
+    }
+
+    /// Reads a `u2` count followed by that many `u2` items
+    pub fn read_u16_table(&mut self) -> Result<Vec<u16>, ClassFormatError> {
+        let count: u16 = self.read()?;
+        (0..count).map(|_| self.read()).collect()
+    }
+
+    fn truncated(&self) -> ClassFormatError {
+        ClassFormatError::Truncated {
+            offset: self.offset(),
+            structure: self.structure.to_string(),
+        }
+    }
+
+    /// An error for the `u1` tag that was just read
+    pub fn unknown_tag(&self, tag: u8) -> ClassFormatError {
+        ClassFormatError::UnknownTag {
+            offset: self.offset() - 1,
+            structure: self.structure.to_string(),
+            tag,
+        }
+    }
+
+    /// An error for the `u2` constant pool index that was just read
+    pub fn invalid_index(&self, index: u16) -> ClassFormatError {
+        ClassFormatError::InvalidIndex {
+            offset: self.offset() - 2,
+            structure: self.structure.to_string(),
+            index,
+        }
+    }
+
+    pub fn attribute_length(&self, start: usize, expected: u32) -> ClassFormatError {
+        ClassFormatError::AttributeLength {
+            offset: start,
+            structure: self.structure.to_string(),
+            expected,
+            actual: self.offset() - start,
+        }
+    }
+}
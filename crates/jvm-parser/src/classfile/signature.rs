@@ -0,0 +1,516 @@
+//! Generic signatures from `Signature` attributes (JVMS 4.7.9.1). Unlike descriptors they keep
+//! type parameters, type arguments and type variables, e.g. `Ljava/util/List<TT;>;`.
+//!
+//! The types display as they would be written in java source, with binary names, e.g.
+//! `java.util.Map$Entry<K, ? extends V>`.
+
+use std::fmt;
+
+use super::descriptor::BaseType;
+
+/// Why a signature couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureError {
+    pub signature: String,
+    /// The byte offset in `signature` where parsing stopped
+    pub position: usize,
+    /// What was expected at `position`, e.g. `a type argument`
+    pub expected: &'static str,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid signature {}, expected {} at position {}",
+            self.signature, self.expected, self.position
+        )
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JavaTypeSignature {
+    Base(BaseType),
+    Reference(ReferenceTypeSignature),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReferenceTypeSignature {
+    Class(ClassTypeSignature),
+    /// The name of a type variable, e.g. `T` for `TT;`
+    TypeVariable(String),
+    /// An array and the signature of its components
+    Array(Box<JavaTypeSignature>),
+}
+
+/// A class type like `Ljava/util/Map$Entry<TK;TV;>;`. An inner class of a generic class is
+/// written with one `.` separated segment per class, e.g. `LOuter<TT;>.Inner;`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassTypeSignature {
+    /// The internal name of the package, e.g. `java/util`, empty for the unnamed package
+    pub package: String,
+    pub classes: Vec<SimpleClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleClassTypeSignature {
+    /// The binary name of the class without its package, or for the segments after the first the
+    /// simple name of the inner class
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeArgument {
+    /// `*`, written `?` in java
+    Wildcard,
+    /// `+`, `? extends` the bound
+    Extends(ReferenceTypeSignature),
+    /// `-`, `? super` the bound
+    Super(ReferenceTypeSignature),
+    Exact(ReferenceTypeSignature),
+}
+
+/// A type parameter like `T:Ljava/lang/Object;` or `E::Ljava/lang/Comparable<TE;>;`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeParameter {
+    pub name: String,
+    /// `None` when the only bounds are interfaces
+    pub class_bound: Option<ReferenceTypeSignature>,
+    pub interface_bounds: Vec<ReferenceTypeSignature>,
+}
+
+/// The signature of a generic class or of a class that extends or implements generic types
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub super_class: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<JavaTypeSignature>,
+    /// `None` for `void`
+    pub return_type: Option<JavaTypeSignature>,
+    /// Only present if the method throws a type variable, the `Exceptions` attribute lists the
+    /// erased exceptions in any case
+    pub throws: Vec<ReferenceTypeSignature>,
+}
+
+/// The type of a field, a record component or a local variable
+pub type FieldSignature = ReferenceTypeSignature;
+
+impl ClassTypeSignature {
+    /// The binary name of the erased class, e.g. `java/util/Map$Entry`
+    pub fn binary_name(&self) -> String {
+        let classes: Vec<&str> = self
+            .classes
+            .iter()
+            .map(|class| class.name.as_str())
+            .collect();
+        match self.package.is_empty() {
+            true => classes.join("$"),
+            false => format!("{}/{}", self.package, classes.join("$")),
+        }
+    }
+}
+
+impl JavaTypeSignature {
+    /// The descriptor of the erased type, `None` if it depends on a type variable, whose erasure
+    /// is its leftmost bound
+    pub fn erased_descriptor(&self) -> Option<String> {
+        match self {
+            JavaTypeSignature::Base(base_type) => Some(base_type.descriptor().to_string()),
+            JavaTypeSignature::Reference(reference) => reference.erased_descriptor(),
+        }
+    }
+}
+
+impl ReferenceTypeSignature {
+    /// The descriptor of the erased type, `None` if it depends on a type variable, whose erasure
+    /// is its leftmost bound
+    pub fn erased_descriptor(&self) -> Option<String> {
+        match self {
+            ReferenceTypeSignature::Class(class) => Some(format!("L{};", class.binary_name())),
+            ReferenceTypeSignature::TypeVariable(_) => None,
+            ReferenceTypeSignature::Array(component) => {
+                Some(format!("[{}", component.erased_descriptor()?))
+            }
+        }
+    }
+}
+
+pub fn parse_class_signature(signature: &str) -> Result<ClassSignature, SignatureError> {
+    let mut parser = SignatureParser::new(signature);
+    let type_parameters = parser.type_parameters()?;
+    let super_class = parser.class_type()?;
+    let mut interfaces = vec![];
+    while !parser.at_end() {
+        interfaces.push(parser.class_type()?);
+    }
+
+    Ok(ClassSignature {
+        type_parameters,
+        super_class,
+        interfaces,
+    })
+}
+
+pub fn parse_method_signature(signature: &str) -> Result<MethodSignature, SignatureError> {
+    let mut parser = SignatureParser::new(signature);
+    let type_parameters = parser.type_parameters()?;
+
+    parser.expect(b'(', "(")?;
+    let mut parameters = vec![];
+    while !parser.eat(b')') {
+        parameters.push(parser.java_type()?);
+    }
+
+    let return_type = match parser.eat(b'V') {
+        true => None,
+        false => Some(parser.java_type()?),
+    };
+
+    let mut throws = vec![];
+    while parser.eat(b'^') {
+        let thrown = match parser.peek() {
+            Some(b'T') => parser.reference_type()?,
+            _ => ReferenceTypeSignature::Class(parser.class_type()?),
+        };
+        throws.push(thrown);
+    }
+    parser.finish()?;
+
+    Ok(MethodSignature {
+        type_parameters,
+        parameters,
+        return_type,
+        throws,
+    })
+}
+
+pub fn parse_field_signature(signature: &str) -> Result<FieldSignature, SignatureError> {
+    let mut parser = SignatureParser::new(signature);
+    let field = parser.reference_type()?;
+    parser.finish()?;
+    Ok(field)
+}
+
+struct SignatureParser<'a> {
+    signature: &'a str,
+    position: usize,
+}
+
+impl<'a> SignatureParser<'a> {
+    fn new(signature: &'a str) -> Self {
+        SignatureParser {
+            signature,
+            position: 0,
+        }
+    }
+
+    fn error(&self, expected: &'static str) -> SignatureError {
+        SignatureError {
+            signature: self.signature.to_string(),
+            position: self.position,
+            expected,
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.position == self.signature.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.signature.as_bytes().get(self.position).copied()
+    }
+
+    /// Skips `byte` if it is next
+    fn eat(&mut self, byte: u8) -> bool {
+        let found = self.peek() == Some(byte);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, byte: u8, expected: &'static str) -> Result<(), SignatureError> {
+        match self.eat(byte) {
+            true => Ok(()),
+            false => Err(self.error(expected)),
+        }
+    }
+
+    fn finish(&self) -> Result<(), SignatureError> {
+        match self.at_end() {
+            true => Ok(()),
+            false => Err(self.error("the end of the signature")),
+        }
+    }
+
+    /// An unqualified name, which can't contain any of `.;[/<>:` (JVMS 4.2.2)
+    fn identifier(&mut self) -> Result<String, SignatureError> {
+        let rest = &self.signature[self.position..];
+        let length = rest
+            .find(['.', ';', '[', '/', '<', '>', ':'])
+            .unwrap_or(rest.len());
+        if length == 0 {
+            return Err(self.error("an identifier"));
+        }
+        self.position += length;
+        Ok(rest[..length].to_string())
+    }
+
+    fn type_parameters(&mut self) -> Result<Vec<TypeParameter>, SignatureError> {
+        let mut type_parameters = vec![];
+        if !self.eat(b'<') {
+            return Ok(type_parameters);
+        }
+
+        loop {
+            let name = self.identifier()?;
+            self.expect(b':', "a class bound")?;
+            let class_bound = match self.peek() {
+                Some(b':') => None,
+                _ => Some(self.reference_type()?),
+            };
+            let mut interface_bounds = vec![];
+            while self.eat(b':') {
+                interface_bounds.push(self.reference_type()?);
+            }
+            type_parameters.push(TypeParameter {
+                name,
+                class_bound,
+                interface_bounds,
+            });
+
+            if self.eat(b'>') {
+                return Ok(type_parameters);
+            }
+        }
+    }
+
+    fn java_type(&mut self) -> Result<JavaTypeSignature, SignatureError> {
+        match self.peek().and_then(BaseType::from_tag) {
+            Some(base_type) => {
+                self.position += 1;
+                Ok(JavaTypeSignature::Base(base_type))
+            }
+            None => Ok(JavaTypeSignature::Reference(self.reference_type()?)),
+        }
+    }
+
+    fn reference_type(&mut self) -> Result<ReferenceTypeSignature, SignatureError> {
+        match self.peek() {
+            Some(b'L') => Ok(ReferenceTypeSignature::Class(self.class_type()?)),
+            Some(b'T') => {
+                self.position += 1;
+                let name = self.identifier()?;
+                self.expect(b';', ";")?;
+                Ok(ReferenceTypeSignature::TypeVariable(name))
+            }
+            Some(b'[') => {
+                self.position += 1;
+                Ok(ReferenceTypeSignature::Array(Box::new(self.java_type()?)))
+            }
+            _ => Err(self.error("a reference type")),
+        }
+    }
+
+    fn class_type(&mut self) -> Result<ClassTypeSignature, SignatureError> {
+        self.expect(b'L', "a class type")?;
+
+        let mut package = vec![];
+        let mut name = self.identifier()?;
+        while self.eat(b'/') {
+            package.push(name);
+            name = self.identifier()?;
+        }
+
+        let mut classes = vec![SimpleClassTypeSignature {
+            type_arguments: self.type_arguments()?,
+            name,
+        }];
+        while self.eat(b'.') {
+            classes.push(SimpleClassTypeSignature {
+                name: self.identifier()?,
+                type_arguments: self.type_arguments()?,
+            });
+        }
+        self.expect(b';', ";")?;
+
+        Ok(ClassTypeSignature {
+            package: package.join("/"),
+            classes,
+        })
+    }
+
+    fn type_arguments(&mut self) -> Result<Vec<TypeArgument>, SignatureError> {
+        let mut type_arguments = vec![];
+        if !self.eat(b'<') {
+            return Ok(type_arguments);
+        }
+
+        loop {
+            let type_argument = match self.peek() {
+                Some(b'*') => {
+                    self.position += 1;
+                    TypeArgument::Wildcard
+                }
+                Some(b'+') => {
+                    self.position += 1;
+                    TypeArgument::Extends(self.reference_type()?)
+                }
+                Some(b'-') => {
+                    self.position += 1;
+                    TypeArgument::Super(self.reference_type()?)
+                }
+                Some(b'L' | b'T' | b'[') => TypeArgument::Exact(self.reference_type()?),
+                _ => return Err(self.error("a type argument")),
+            };
+            type_arguments.push(type_argument);
+
+            if self.eat(b'>') {
+                return Ok(type_arguments);
+            }
+        }
+    }
+}
+
+/// Writes `items` separated by `, `
+fn comma_separated<T: fmt::Display>(f: &mut fmt::Formatter<'_>, items: &[T]) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{item}")?;
+    }
+    Ok(())
+}
+
+/// `<T, U extends java.lang.Number>`, nothing if there are no type parameters
+pub struct TypeParameters<'a>(pub &'a [TypeParameter]);
+
+impl fmt::Display for TypeParameters<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        write!(f, "<")?;
+        comma_separated(f, self.0)?;
+        write!(f, ">")
+    }
+}
+
+impl fmt::Display for JavaTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JavaTypeSignature::Base(base_type) => write!(f, "{base_type}"),
+            JavaTypeSignature::Reference(reference) => write!(f, "{reference}"),
+        }
+    }
+}
+
+impl fmt::Display for ReferenceTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReferenceTypeSignature::Class(class) => write!(f, "{class}"),
+            ReferenceTypeSignature::TypeVariable(name) => write!(f, "{name}"),
+            ReferenceTypeSignature::Array(component) => write!(f, "{component}[]"),
+        }
+    }
+}
+
+impl fmt::Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.package.is_empty() {
+            write!(f, "{}.", self.package.replace('/', "."))?;
+        }
+        for (i, class) in self.classes.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", class.name)?;
+            if !class.type_arguments.is_empty() {
+                write!(f, "<")?;
+                comma_separated(f, &class.type_arguments)?;
+                write!(f, ">")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeArgument::Wildcard => write!(f, "?"),
+            TypeArgument::Extends(bound) => write!(f, "? extends {bound}"),
+            TypeArgument::Super(bound) => write!(f, "? super {bound}"),
+            TypeArgument::Exact(argument) => write!(f, "{argument}"),
+        }
+    }
+}
+
+impl fmt::Display for TypeParameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        let bounds = self.class_bound.iter().chain(&self.interface_bounds);
+        for (i, bound) in bounds.enumerate() {
+            let separator = if i == 0 { " extends " } else { " & " };
+            write!(f, "{separator}{bound}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        parse_class_signature, parse_field_signature, parse_method_signature, TypeParameters,
+    };
+
+    #[test]
+    fn parses_generic_signatures() {
+        let class = parse_class_signature(
+            "<T:Ljava/lang/Object;U:Ljava/lang/Number;:Ljava/lang/Comparable<TU;>;>\
+             Ljava/util/AbstractList<TT;>;Ljava/util/RandomAccess;",
+        )
+        .unwrap();
+        assert_eq!(
+            TypeParameters(&class.type_parameters).to_string(),
+            "<T extends java.lang.Object, U extends java.lang.Number & java.lang.Comparable<U>>"
+        );
+        assert_eq!(class.super_class.binary_name(), "java/util/AbstractList");
+        assert_eq!(class.interfaces[0].to_string(), "java.util.RandomAccess");
+
+        let method = parse_method_signature(
+            "<K::Ljava/lang/Comparable<-TK;>;>(Ljava/util/Collection<+TK;>;[I)TK;^TE;",
+        )
+        .unwrap();
+        assert_eq!(
+            TypeParameters(&method.type_parameters).to_string(),
+            "<K extends java.lang.Comparable<? super K>>"
+        );
+        assert_eq!(
+            method.parameters[0].to_string(),
+            "java.util.Collection<? extends K>"
+        );
+        assert_eq!(method.parameters[1].erased_descriptor().unwrap(), "[I");
+        assert_eq!(method.return_type.unwrap().erased_descriptor(), None);
+        assert_eq!(method.throws[0].to_string(), "E");
+
+        let field = parse_field_signature("LOuter<TT;>.Inner<*>.Deep;").unwrap();
+        assert_eq!(field.to_string(), "Outer<T>.Inner<?>.Deep");
+        assert_eq!(field.erased_descriptor().unwrap(), "LOuter$Inner$Deep;");
+
+        let error = parse_field_signature("Ljava/util/List<>;").unwrap_err();
+        assert_eq!((error.position, error.expected), (16, "a type argument"));
+        assert!(parse_field_signature("I").is_err());
+        assert!(parse_method_signature("()V;").is_err());
+    }
+}
@@ -0,0 +1,624 @@
+use super::{
+    attributes::{
+        Annotation, AttributeInfo, AttributeInfoData, ElementValue, LocalVariableTableEntry,
+        StackMapFrame, TypeAnnotation, TypeAnnotationTarget, VerificationTypeInfo,
+    },
+    classfile::{FieldInfo, JavaClass, MethodInfo},
+    constant_pool::{ConstantPool, CpInfo},
+    mutf8,
+};
+
+/// Writes the big endian items of a class file, the counterpart of `ClassReader`
+#[derive(Default)]
+pub(crate) struct ClassWriter {
+    bytes: Vec<u8>,
+}
+
+impl ClassWriter {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn u1(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn u2(&mut self, value: u16) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    pub fn u4(&mut self, value: u32) {
+        self.bytes.extend(value.to_be_bytes());
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Writes a `u2` count followed by the items
+    pub fn u2_table(&mut self, table: &[u16]) {
+        self.u2(table.len() as u16);
+        table.iter().for_each(|item| self.u2(*item));
+    }
+}
+
+impl JavaClass<'_> {
+    /// Serializes the class to the class file format. A class parsed with
+    /// [`JavaClass::from_bytes`] is written back byte for byte
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = ClassWriter::default();
+
+        writer.u4(self.magic);
+        writer.u2(self.minor_version);
+        writer.u2(self.major_version);
+        self.constant_pool.write(&mut writer);
+        writer.u2(self.access_flags);
+        writer.u2(self.this_class);
+        writer.u2(self.super_class);
+        writer.u2_table(&self.interfaces);
+
+        writer.u2(self.fields.len() as u16);
+        for field in &self.fields {
+            field.write(&mut writer);
+        }
+
+        writer.u2(self.methods.len() as u16);
+        for method in &self.methods {
+            method.write(&mut writer);
+        }
+
+        write_attributes(&mut writer, &self.attributes);
+
+        writer.into_bytes()
+    }
+}
+
+impl FieldInfo<'_> {
+    fn write(&self, writer: &mut ClassWriter) {
+        writer.u2(self.access_flags);
+        writer.u2(self.name_index);
+        writer.u2(self.descriptor_index);
+        write_attributes(writer, &self.attributes);
+    }
+}
+
+impl MethodInfo<'_> {
+    fn write(&self, writer: &mut ClassWriter) {
+        writer.u2(self.access_flags);
+        writer.u2(self.name_index);
+        writer.u2(self.descriptor_index);
+        write_attributes(writer, &self.attributes);
+    }
+}
+
+impl ConstantPool<'_> {
+    fn write(&self, writer: &mut ClassWriter) {
+        writer.u2((self.pool_entries.len() + 1) as u16);
+        for entry in &self.pool_entries {
+            entry.write(writer);
+        }
+    }
+}
+
+impl CpInfo<'_> {
+    /// The `tag` byte of the entry in a class file (JVMS 4.4)
+    pub fn tag_byte(&self) -> u8 {
+        match self {
+            CpInfo::EmptyCpEntry => 0,
+            CpInfo::Utf8(_) => 1,
+            CpInfo::Integer(_) => 3,
+            CpInfo::Float(_) => 4,
+            CpInfo::Long(_) => 5,
+            CpInfo::Double(_) => 6,
+            CpInfo::Class(_) => 7,
+            CpInfo::String(_) => 8,
+            CpInfo::Refs(refs) => match refs.tag {
+                "CONSTANT_Fieldref" => 9,
+                "CONSTANT_Methodref" => 10,
+                _ => 11,
+            },
+            CpInfo::NameAndType(_) => 12,
+            CpInfo::MethodHandle(_) => 15,
+            CpInfo::MethodType(_) => 16,
+            CpInfo::InvokeDynamic(dynamic) if dynamic.tag == "CONSTANT_Dynamic" => 17,
+            CpInfo::InvokeDynamic(_) => 18,
+            CpInfo::Module(_) => 19,
+            CpInfo::Package(_) => 20,
+        }
+    }
+
+    pub(crate) fn write(&self, writer: &mut ClassWriter) {
+        // The second slot of a long or double isn't in the class file
+        if let CpInfo::EmptyCpEntry = self {
+            return;
+        }
+        writer.u1(self.tag_byte());
+
+        match self {
+            CpInfo::EmptyCpEntry => {}
+            CpInfo::Utf8(utf8) => {
+                let bytes = utf8
+                    .raw
+                    .clone()
+                    .unwrap_or_else(|| mutf8::encode(&utf8.data));
+                writer.u2(bytes.len() as u16);
+                writer.write_bytes(&bytes);
+            }
+            CpInfo::Integer(int) => writer.u4(int.bytes as u32),
+            CpInfo::Float(float) => writer.u4(float.bytes.to_bits()),
+            CpInfo::Long(long) => writer.write_bytes(&long.bytes.to_be_bytes()),
+            CpInfo::Double(double) => writer.write_bytes(&double.bytes.to_bits().to_be_bytes()),
+            CpInfo::Class(class) => writer.u2(class.name_index),
+            CpInfo::String(string) => writer.u2(string.string_index),
+            CpInfo::Refs(refs) => {
+                writer.u2(refs.class_index);
+                writer.u2(refs.name_and_type_index);
+            }
+            CpInfo::NameAndType(name_and_type) => {
+                writer.u2(name_and_type.name_index);
+                writer.u2(name_and_type.descriptor_index);
+            }
+            CpInfo::MethodHandle(handle) => {
+                writer.u1(handle.reference_kind);
+                writer.u2(handle.reference_index);
+            }
+            CpInfo::MethodType(method_type) => writer.u2(method_type.descriptor_index),
+            CpInfo::InvokeDynamic(dynamic) => {
+                writer.u2(dynamic.bootstrap_method_attr_index);
+                writer.u2(dynamic.name_and_type_index);
+            }
+            CpInfo::Module(module) => writer.u2(module.name_index),
+            CpInfo::Package(package) => writer.u2(package.name_index),
+        }
+    }
+}
+
+fn write_attributes(writer: &mut ClassWriter, attributes: &[AttributeInfo]) {
+    writer.u2(attributes.len() as u16);
+
+    for attribute in attributes {
+        // The length comes before the contents, so they're written on their own first
+        let mut contents = ClassWriter::default();
+        write_attribute_contents(&mut contents, &attribute.attribute);
+        let contents = contents.into_bytes();
+
+        writer.u2(attribute.attribute_name_index);
+        writer.u4(contents.len() as u32);
+        writer.write_bytes(&contents);
+    }
+}
+
+fn write_attribute_contents(writer: &mut ClassWriter, attribute: &AttributeInfoData) {
+    match attribute {
+        AttributeInfoData::None | AttributeInfoData::Synthetic | AttributeInfoData::Deprecated => {}
+
+        AttributeInfoData::Code(code) => {
+            writer.u2(code.max_stack);
+            writer.u2(code.max_locals);
+            writer.u4(code.code.len() as u32);
+            writer.write_bytes(&code.code);
+
+            writer.u2(code.exception_table.len() as u16);
+            for exception in &code.exception_table {
+                writer.u2(exception.start_pc);
+                writer.u2(exception.end_pc);
+                writer.u2(exception.handler_pc);
+                writer.u2(exception.catch_type);
+            }
+
+            write_attributes(writer, &code.attribute_info);
+        }
+        AttributeInfoData::LineNumberTable(table) => {
+            writer.u2(table.line_number_table.len() as u16);
+            for line in &table.line_number_table {
+                writer.u2(line.start_pc);
+                writer.u2(line.line_number);
+            }
+        }
+        AttributeInfoData::SourceFile(source_file) => writer.u2(source_file.sourcefile_index),
+        AttributeInfoData::BootstrapMethods(bootstrap_methods) => {
+            writer.u2(bootstrap_methods.bootstrap_methods.len() as u16);
+            for method in &bootstrap_methods.bootstrap_methods {
+                writer.u2(method.bootstrap_method_ref);
+                writer.u2_table(&method.bootstrap_arguments);
+            }
+        }
+        AttributeInfoData::LocalVariableTable(table) => {
+            write_local_variable_table(writer, &table.local_variable_table)
+        }
+        AttributeInfoData::LocalVariableTypeTable(table) => {
+            write_local_variable_table(writer, &table.local_variable_type_table)
+        }
+        AttributeInfoData::Signature(signature) => writer.u2(signature.signature_index),
+        AttributeInfoData::EnclosingMethod(enclosing) => {
+            writer.u2(enclosing.class_index);
+            writer.u2(enclosing.method_index);
+        }
+        AttributeInfoData::Exceptions(exceptions) => {
+            writer.u2_table(&exceptions.exception_index_table)
+        }
+        AttributeInfoData::StackMapTable(stack_map) => {
+            writer.u2(stack_map.entries.len() as u16);
+            for frame in &stack_map.entries {
+                write_stack_map_frame(writer, frame);
+            }
+        }
+        AttributeInfoData::ConstantValue(value) => writer.u2(value.constantvalue_index),
+        AttributeInfoData::SourceDebugExtension(extension) => {
+            writer.write_bytes(&extension.debug_extension)
+        }
+        AttributeInfoData::MethodParameters(method_parameters) => {
+            writer.u1(method_parameters.parameters.len() as u8);
+            for parameter in &method_parameters.parameters {
+                writer.u2(parameter.name_index);
+                writer.u2(parameter.access_flags);
+            }
+        }
+
+        AttributeInfoData::RuntimeVisibleAnnotations(annotations)
+        | AttributeInfoData::RuntimeInvisibleAnnotations(annotations) => {
+            write_annotations(writer, &annotations.annotations)
+        }
+        AttributeInfoData::RuntimeVisibleParameterAnnotations(parameters)
+        | AttributeInfoData::RuntimeInvisibleParameterAnnotations(parameters) => {
+            writer.u1(parameters.parameter_annotations.len() as u8);
+            for annotations in &parameters.parameter_annotations {
+                write_annotations(writer, annotations);
+            }
+        }
+        AttributeInfoData::RuntimeVisibleTypeAnnotations(annotations)
+        | AttributeInfoData::RuntimeInvisibleTypeAnnotations(annotations) => {
+            writer.u2(annotations.annotations.len() as u16);
+            for annotation in &annotations.annotations {
+                write_type_annotation(writer, annotation);
+            }
+        }
+        AttributeInfoData::AnnotationDefault(default) => {
+            write_element_value(writer, &default.default_value)
+        }
+
+        AttributeInfoData::InnerClasses(inner_classes) => {
+            writer.u2(inner_classes.classes.len() as u16);
+            for class in &inner_classes.classes {
+                writer.u2(class.inner_class_info_index);
+                writer.u2(class.outer_class_info_index);
+                writer.u2(class.inner_name_index);
+                writer.u2(class.inner_class_access_flags);
+            }
+        }
+        AttributeInfoData::NestHost(nest_host) => writer.u2(nest_host.host_class_index),
+        AttributeInfoData::NestMembers(members) => writer.u2_table(&members.classes),
+        AttributeInfoData::Record(record) => {
+            writer.u2(record.components.len() as u16);
+            for component in &record.components {
+                writer.u2(component.name_index);
+                writer.u2(component.descriptor_index);
+                write_attributes(writer, &component.attributes);
+            }
+        }
+        AttributeInfoData::PermittedSubclasses(permitted) => writer.u2_table(&permitted.classes),
+
+        AttributeInfoData::Module(module) => {
+            writer.u2(module.module_name_index);
+            writer.u2(module.module_flags);
+            writer.u2(module.module_version_index);
+
+            writer.u2(module.requires.len() as u16);
+            for requires in &module.requires {
+                writer.u2(requires.requires_index);
+                writer.u2(requires.requires_flags);
+                writer.u2(requires.requires_version_index);
+            }
+
+            writer.u2(module.exports.len() as u16);
+            for exports in &module.exports {
+                writer.u2(exports.exports_index);
+                writer.u2(exports.exports_flags);
+                writer.u2_table(&exports.exports_to_index);
+            }
+
+            writer.u2(module.opens.len() as u16);
+            for opens in &module.opens {
+                writer.u2(opens.opens_index);
+                writer.u2(opens.opens_flags);
+                writer.u2_table(&opens.opens_to_index);
+            }
+
+            writer.u2_table(&module.uses_index);
+
+            writer.u2(module.provides.len() as u16);
+            for provides in &module.provides {
+                writer.u2(provides.provides_index);
+                writer.u2_table(&provides.provides_with_index);
+            }
+        }
+        AttributeInfoData::ModulePackages(packages) => writer.u2_table(&packages.package_index),
+        AttributeInfoData::ModuleMainClass(main_class) => writer.u2(main_class.main_class_index),
+
+        AttributeInfoData::Unknown(unknown) => writer.write_bytes(&unknown.info),
+    }
+}
+
+fn write_local_variable_table(writer: &mut ClassWriter, table: &[LocalVariableTableEntry]) {
+    writer.u2(table.len() as u16);
+    for entry in table {
+        writer.u2(entry.start_pc);
+        writer.u2(entry.length);
+        writer.u2(entry.name_index);
+        writer.u2(entry.signature_descriptor_index);
+        writer.u2(entry.index);
+    }
+}
+
+fn write_stack_map_frame(writer: &mut ClassWriter, frame: &StackMapFrame) {
+    match frame {
+        StackMapFrame::None => {}
+        StackMapFrame::SameFrame { offset_delta } => writer.u1(*offset_delta as u8),
+        StackMapFrame::SameFrameExtended { offset_delta } => {
+            writer.u1(251);
+            writer.u2(*offset_delta);
+        }
+        StackMapFrame::SameLocalsStackItemFrame {
+            offset_delta,
+            stack,
+        } => {
+            writer.u1(64 + *offset_delta as u8);
+            write_verification_type(writer, stack);
+        }
+        StackMapFrame::SameLocalsStackItemFrameExtended {
+            offset_delta,
+            stack,
+        } => {
+            writer.u1(247);
+            writer.u2(*offset_delta);
+            write_verification_type(writer, stack);
+        }
+        StackMapFrame::ChopFrame {
+            offset_delta,
+            chopped,
+        } => {
+            writer.u1(251 - chopped);
+            writer.u2(*offset_delta);
+        }
+        StackMapFrame::AppendFrame {
+            offset_delta,
+            locals,
+        } => {
+            writer.u1(251 + locals.len() as u8);
+            writer.u2(*offset_delta);
+            for local in locals {
+                write_verification_type(writer, local);
+            }
+        }
+        StackMapFrame::FullFrame {
+            offset_delta,
+            locals,
+            stack,
+        } => {
+            writer.u1(255);
+            writer.u2(*offset_delta);
+            writer.u2(locals.len() as u16);
+            for local in locals {
+                write_verification_type(writer, local);
+            }
+            writer.u2(stack.len() as u16);
+            for item in stack {
+                write_verification_type(writer, item);
+            }
+        }
+    }
+}
+
+fn write_verification_type(writer: &mut ClassWriter, verification_type: &VerificationTypeInfo) {
+    match verification_type {
+        VerificationTypeInfo::Top => writer.u1(0),
+        VerificationTypeInfo::Integer => writer.u1(1),
+        VerificationTypeInfo::Float => writer.u1(2),
+        VerificationTypeInfo::Double => writer.u1(3),
+        VerificationTypeInfo::Long => writer.u1(4),
+        VerificationTypeInfo::Null => writer.u1(5),
+        VerificationTypeInfo::UninitializedThis => writer.u1(6),
+        VerificationTypeInfo::Object { cpool_index } => {
+            writer.u1(7);
+            writer.u2(*cpool_index);
+        }
+        VerificationTypeInfo::Uninitialized { offset } => {
+            writer.u1(8);
+            writer.u2(*offset);
+        }
+    }
+}
+
+fn write_annotations(writer: &mut ClassWriter, annotations: &[Annotation]) {
+    writer.u2(annotations.len() as u16);
+    for annotation in annotations {
+        write_annotation(writer, annotation);
+    }
+}
+
+fn write_annotation(writer: &mut ClassWriter, annotation: &Annotation) {
+    writer.u2(annotation.type_index);
+    writer.u2(annotation.element_value_pairs.len() as u16);
+    for pair in &annotation.element_value_pairs {
+        writer.u2(pair.element_name_index);
+        write_element_value(writer, &pair.value);
+    }
+}
+
+fn write_element_value(writer: &mut ClassWriter, value: &ElementValue) {
+    match value {
+        ElementValue::Const {
+            tag,
+            const_value_index,
+        } => {
+            writer.u1(*tag);
+            writer.u2(*const_value_index);
+        }
+        ElementValue::Enum {
+            type_name_index,
+            const_name_index,
+        } => {
+            writer.u1(b'e');
+            writer.u2(*type_name_index);
+            writer.u2(*const_name_index);
+        }
+        ElementValue::Class { class_info_index } => {
+            writer.u1(b'c');
+            writer.u2(*class_info_index);
+        }
+        ElementValue::Annotation(annotation) => {
+            writer.u1(b'@');
+            write_annotation(writer, annotation);
+        }
+        ElementValue::Array(values) => {
+            writer.u1(b'[');
+            writer.u2(values.len() as u16);
+            for value in values {
+                write_element_value(writer, value);
+            }
+        }
+    }
+}
+
+fn write_type_annotation(writer: &mut ClassWriter, annotation: &TypeAnnotation) {
+    writer.u1(annotation.target_type);
+
+    match &annotation.target_info {
+        TypeAnnotationTarget::TypeParameter {
+            type_parameter_index,
+        } => writer.u1(*type_parameter_index),
+        TypeAnnotationTarget::Supertype { supertype_index } => writer.u2(*supertype_index),
+        TypeAnnotationTarget::TypeParameterBound {
+            type_parameter_index,
+            bound_index,
+        } => {
+            writer.u1(*type_parameter_index);
+            writer.u1(*bound_index);
+        }
+        TypeAnnotationTarget::Empty => {}
+        TypeAnnotationTarget::FormalParameter {
+            formal_parameter_index,
+        } => writer.u1(*formal_parameter_index),
+        TypeAnnotationTarget::Throws { throws_type_index } => writer.u2(*throws_type_index),
+        TypeAnnotationTarget::LocalVar { table } => {
+            writer.u2(table.len() as u16);
+            for entry in table {
+                writer.u2(entry.start_pc);
+                writer.u2(entry.length);
+                writer.u2(entry.index);
+            }
+        }
+        TypeAnnotationTarget::Catch {
+            exception_table_index,
+        } => writer.u2(*exception_table_index),
+        TypeAnnotationTarget::Offset { offset } => writer.u2(*offset),
+        TypeAnnotationTarget::TypeArgument {
+            offset,
+            type_argument_index,
+        } => {
+            writer.u2(*offset);
+            writer.u1(*type_argument_index);
+        }
+    }
+
+    writer.u1(annotation.target_path.len() as u8);
+    for entry in &annotation.target_path {
+        writer.u1(entry.type_path_kind);
+        writer.u1(entry.type_argument_index);
+    }
+
+    write_annotation(writer, &annotation.annotation);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, path::PathBuf};
+
+    use crate::{
+        classfile::{
+            constant_pool::{ConstantPoolBuilder, CpInfo},
+            JavaClass,
+        },
+        zip,
+    };
+
+    /// `test-data/sample.jar` is built from `test-data/sample/src` with
+    /// `javac --release 17 -g -parameters`
+    fn sample_classes() -> Vec<(String, Vec<u8>)> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-data/sample.jar");
+        let jar = std::fs::read(path).unwrap();
+
+        zip::read_central_directory(&jar)
+            .unwrap()
+            .into_iter()
+            .filter(|entry| entry.name.ends_with(".class"))
+            .map(|entry| {
+                let bytes = entry.read(&jar).unwrap().into_owned();
+                (entry.name, bytes)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn classes_round_trip() {
+        let classes = sample_classes();
+        assert_eq!(classes.len(), 10);
+
+        for (file_name, bytes) in classes {
+            let java_class = JavaClass::from_bytes(&bytes).unwrap();
+            assert!(
+                java_class.to_bytes() == bytes,
+                "{file_name} wasn't written back byte for byte"
+            );
+        }
+    }
+
+    #[test]
+    fn parsed_classes_borrow_from_their_bytes() {
+        for (file_name, bytes) in sample_classes() {
+            let java_class = JavaClass::from_bytes(&bytes).unwrap();
+            for entry in &java_class.constant_pool.pool_entries {
+                if let CpInfo::Utf8(utf8) = entry {
+                    assert!(
+                        matches!(utf8.data, Cow::Borrowed(_)) || utf8.data.contains('\0'),
+                        "{file_name} copied the utf8 entry {:?}",
+                        utf8.data
+                    );
+                }
+            }
+            for method in &java_class.methods {
+                if let Some(code) = method.get_code() {
+                    assert!(matches!(code.code, Cow::Borrowed(_)));
+                }
+            }
+
+            let owned = java_class.into_owned();
+            assert!(owned.to_bytes() == bytes, "{file_name} changed when owned");
+        }
+    }
+
+    #[test]
+    fn constant_pool_builder_deduplicates() {
+        let mut builder = ConstantPoolBuilder::new();
+        let method = builder.method_ref("java/lang/Object", "<init>", "()V");
+        let class = builder.class("java/lang/Object");
+        let long = builder.long(1);
+
+        assert_eq!(
+            builder.method_ref("java/lang/Object", "<init>", "()V"),
+            method
+        );
+        assert_eq!(builder.class("java/lang/Object"), class);
+        assert_eq!(builder.utf8("()V"), 4);
+        // A long takes up two entries
+        assert_eq!(builder.integer(1), long + 2);
+
+        let pool = builder.build();
+        assert_eq!(pool.get_class_name_at(class), Some("java/lang/Object"));
+        let (_, _, name_and_type) = pool.get_refs_ext_at(method).unwrap();
+        assert_eq!(
+            pool.get_utf8_at(name_and_type.name_index).unwrap().data,
+            "<init>"
+        );
+    }
+}
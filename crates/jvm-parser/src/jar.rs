@@ -1,24 +1,82 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io::Read;
-use std::{error::Error, fs::File, path::PathBuf};
+use std::io::{ErrorKind, Read};
+use std::ops::Deref;
+use std::sync::OnceLock;
+use std::{error::Error, fmt, fs::File, path::PathBuf};
 
-use byte_reader::ByteReader;
 use flate2::read::DeflateDecoder;
-use rayon::prelude::*;
+use memmap2::Mmap;
+
+use crate::{
+    classfile::{error::ClassFormatError, JavaClass},
+    zip::{self, ZipEntry},
+};
+
+/// Why a class in a jar couldn't be read, `name` is the name of its entry
+#[derive(Debug)]
+pub enum ReadClassError {
+    /// The entry couldn't be read out of the archive
+    Io { name: String, error: std::io::Error },
+    /// The entry isn't a valid class file
+    ClassFormat {
+        name: String,
+        error: ClassFormatError,
+    },
+}
+
+impl fmt::Display for ReadClassError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadClassError::Io { name, error } => write!(f, "Failed to read '{name}': {error}"),
+            ReadClassError::ClassFormat { name, error } => {
+                write!(f, "Failed to parse the class file '{name}': {error}")
+            }
+        }
+    }
+}
 
-use crate::classfile::JavaClass;
+impl Error for ReadClassError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReadClassError::Io { error, .. } => Some(error),
+            ReadClassError::ClassFormat { error, .. } => Some(error),
+        }
+    }
+}
 
+/// A jar whose entries are only read when they are asked for, opening it reads nothing but the
+/// central directory and the manifest
 #[derive(Debug, Default)]
 pub struct JarFile {
     pub manifest: JarManifest,
-    pub classes: HashMap<String, JavaClass>,
+    source: JarSource,
+    /// In the order of the central directory
+    entries: Vec<ZipEntry>,
+    /// The index of every entry in `entries` by its name
+    entry_indices: HashMap<String, usize>,
+    /// The contents of the compressed class files by their index in `entries`. They are inflated
+    /// when the class is first read and kept, so the classes can borrow from them
+    inflated: Vec<OnceLock<Vec<u8>>>,
+    /// The feature version the entries of a multi-release jar are selected for, see
+    /// [`JarFile::set_version`]
+    version: u16,
+    /// The index of the entry under `META-INF/versions` that replaces a name for `version`
+    versioned_indices: HashMap<String, usize>,
 }
 
+/// Classes and resources for a feature version `N` are in `META-INF/versions/N/` of a
+/// multi-release jar, the ones at the root of the jar are for this version
+pub const BASE_VERSION: u16 = 8;
+const VERSIONS_DIRECTORY: &str = "META-INF/versions/";
+
 #[derive(Debug, Default)]
 pub struct JarManifest {
     pub version: Option<String>,
     pub created_by: Option<String>,
     pub main_class: Option<String>,
+    /// `Multi-Release: true`, the jar has entries for later versions in `META-INF/versions`
+    pub multi_release: bool,
 }
 
 impl JarManifest {
@@ -30,8 +88,8 @@ impl JarManifest {
         JarManifest::from_string(&data)
     }
 
-    pub fn from_bytes(bytes: &Vec<u8>) -> JarManifest {
-        JarManifest::from_string(&String::from_utf8(bytes.clone()).unwrap())
+    pub fn from_bytes(bytes: &[u8]) -> JarManifest {
+        JarManifest::from_string(&String::from_utf8_lossy(bytes).to_string())
     }
 
     pub fn from_string(manifest_content: &String) -> JarManifest {
@@ -52,6 +110,7 @@ impl JarManifest {
                     "Manifest-Version" => manifest.version = Some(value.to_string()),
                     "Created-By" => manifest.created_by = Some(value.to_string()),
                     "Main-Class" => manifest.main_class = Some(value.to_string()),
+                    "Multi-Release" => manifest.multi_release = value.eq_ignore_ascii_case("true"),
 
                     _ => {} //println!("Implement manifest parsing for key: '{}'", attribute),
                 };
@@ -64,113 +123,389 @@ impl JarManifest {
     }
 }
 
+/// The bytes of a jar on disk. The file is mapped into memory, so opening it doesn't read it and
+/// only the pages of the entries that are read get loaded
+#[derive(Debug)]
+pub enum JarSource {
+    Mapped(Mmap),
+    /// Files that can't be mapped, e.g. pipes, are read into memory instead
+    Read(Vec<u8>),
+}
+
+impl JarSource {
+    pub fn open(path: &PathBuf) -> std::io::Result<JarSource> {
+        let file = File::open(path)?;
+        // Safety: the mapping is only read, the jar changing on disk while it is open is not
+        // supported, like it isn't for the JDK
+        match unsafe { Mmap::map(&file) } {
+            Ok(mmap) => Ok(JarSource::Mapped(mmap)),
+            Err(_) => Ok(JarSource::Read(std::fs::read(path)?)),
+        }
+    }
+}
+
+impl Default for JarSource {
+    fn default() -> Self {
+        JarSource::Read(vec![])
+    }
+}
+
+impl Deref for JarSource {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            JarSource::Mapped(mmap) => mmap,
+            JarSource::Read(bytes) => bytes,
+        }
+    }
+}
+
 impl JarFile {
     pub fn from_file(path: &PathBuf) -> Result<JarFile, Box<dyn Error>> {
-        let mut jar_reader = ByteReader::from_file(&mut File::open(path)?)?;
+        JarFile::from_source(JarSource::open(path)?)
+    }
 
-        let central_dir_file_header = vec![0x50, 0x4B, 0x01, 0x02];
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<JarFile, Box<dyn Error>> {
+        JarFile::from_source(JarSource::Read(bytes))
+    }
+
+    pub fn from_source(source: JarSource) -> Result<JarFile, Box<dyn Error>> {
+        let entries = zip::read_central_directory(&source)?;
+        let entry_indices = entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (entry.name.clone(), index))
+            .collect();
+        let inflated = entries.iter().map(|_| OnceLock::new()).collect();
 
         let mut jar_file = JarFile {
             manifest: JarManifest::default(),
-            classes: HashMap::new(),
+            source,
+            entries,
+            entry_indices,
+            inflated,
+            version: BASE_VERSION,
+            versioned_indices: HashMap::new(),
         };
 
-        let cdr_offsets: Vec<usize> =
-            jar_reader.find_all_offsets_parallel(&central_dir_file_header);
+        if jar_file.entry("META-INF/MANIFEST.MF").is_some() {
+            jar_file.manifest = JarManifest::from_bytes(&jar_file.read("META-INF/MANIFEST.MF")?);
+        }
+
+        Ok(jar_file)
+    }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Selects the entries of a multi-release jar for the feature version `version`. A name
+    /// resolves to the entry in the highest `META-INF/versions/N/` with `N <= version`, or else
+    /// to the one at the root. Names in `META-INF` are never versioned, and jars without
+    /// `Multi-Release: true` in their manifest always resolve names to the root
+    pub fn set_version(&mut self, version: u16) {
+        self.version = version;
+        self.versioned_indices.clear();
+        if !self.manifest.multi_release {
+            return;
+        }
+
+        let mut selected_versions = HashMap::new();
+        for (index, entry) in self.entries.iter().enumerate() {
+            let Some((entry_version, name)) = entry
+                .name
+                .strip_prefix(VERSIONS_DIRECTORY)
+                .and_then(|versioned| versioned.split_once('/'))
+            else {
+                continue;
+            };
+            let Ok(entry_version) = entry_version.parse::<u16>() else {
+                continue;
+            };
+            if entry_version <= BASE_VERSION
+                || entry_version > version
+                || name.is_empty()
+                || name.starts_with("META-INF/")
+            {
+                continue;
+            }
+
+            let selected_version = selected_versions.entry(name).or_insert(entry_version);
+            if entry_version >= *selected_version {
+                *selected_version = entry_version;
+                self.versioned_indices.insert(name.to_string(), index);
+            }
+        }
+    }
+
+    /// Every file and directory in the jar, including the ones of every version
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// The entry `name` resolves to for the version of the jar
+    pub fn entry(&self, name: &str) -> Option<&ZipEntry> {
+        self.entry_index(name).map(|index| &self.entries[index])
+    }
+
+    fn entry_index(&self, name: &str) -> Option<usize> {
+        self.versioned_indices
+            .get(name)
+            .or_else(|| self.entry_indices.get(name))
+            .copied()
+    }
+
+    /// The uncompressed contents of the entry `name`, e.g. `META-INF/services/java.sql.Driver`
+    pub fn read(&self, name: &str) -> std::io::Result<Cow<'_, [u8]>> {
+        match self.entry(name) {
+            Some(entry) => entry.read(&self.source),
+            None => Err(std::io::Error::new(
+                ErrorKind::NotFound,
+                format!("The jar has no entry '{name}'"),
+            )),
+        }
+    }
 
-        let java_class_bytes: Vec<Vec<u8>> = cdr_offsets
+    /// The binary names of the classes in the jar, e.g. `java/lang/Object`. The classes of a
+    /// multi-release jar are the ones for its version, the ones only in `META-INF/versions` last
+    pub fn class_names(&self) -> impl Iterator<Item = &str> {
+        let multi_release = self.manifest.multi_release;
+        let root_names = self
+            .entries
             .iter()
-            .filter_map(|file_offset| {
-                jar_reader.move_to(*file_offset);
-
-                let (file_name, bytes) = read_cdr_file_bytes(&mut jar_reader).unwrap();
-
-                if file_name == "META-INF/MANIFEST.MF" {
-                    jar_file.manifest = JarManifest::from_bytes(&bytes);
-                    return None;
-                } else if file_name.ends_with(".class") {
-                    return Some(bytes);
-                } else {
-                    return None;
-                }
-            })
-            .collect();
+            .map(|entry| entry.name.as_str())
+            .filter(move |name| !(multi_release && name.starts_with(VERSIONS_DIRECTORY)));
+        let versioned_names = self
+            .versioned_indices
+            .keys()
+            .map(String::as_str)
+            .filter(|name| !self.entry_indices.contains_key(*name));
+
+        root_names
+            .chain(versioned_names)
+            .filter_map(|name| name.strip_suffix(".class"))
+    }
 
-        jar_file.classes = java_class_bytes
-            .into_par_iter()
-            .map(|bytes| {
-                let java_class = JavaClass::from_bytes(&bytes).unwrap();
-
-                let class = java_class
-                    .constant_pool
-                    .get_class_at(java_class.this_class)
-                    .unwrap();
-
-                let name = java_class
-                    .constant_pool
-                    .get_utf8_at(class.name_index)
-                    .unwrap()
-                    .data
-                    .clone();
-
-                (name, java_class)
-            })
-            // .inspect(|(class, _)| println!("The class '{class}' was parsed"))
-            .collect();
+    /// Parses the class with the binary name `name`, `None` if the jar doesn't contain it. The
+    /// class borrows its code arrays and utf8 entries from the jar instead of copying them
+    pub fn read_class(&self, name: &str) -> Option<Result<JavaClass<'_>, ReadClassError>> {
+        let index = self.entry_index(&format!("{name}.class"))?;
+        Some(self.parse_class(index))
+    }
 
-        Ok(jar_file)
+    fn parse_class(&self, index: usize) -> Result<JavaClass<'_>, ReadClassError> {
+        let name = &self.entries[index].name;
+        let bytes = self
+            .class_bytes(index)
+            .map_err(|error| ReadClassError::Io {
+                name: name.clone(),
+                error,
+            })?;
+        JavaClass::from_bytes(bytes).map_err(|error| ReadClassError::ClassFormat {
+            name: name.clone(),
+            error,
+        })
+    }
+
+    /// The contents of a class file, stored ones are read in place
+    fn class_bytes(&self, index: usize) -> std::io::Result<&[u8]> {
+        if let Some(bytes) = self.inflated[index].get() {
+            return Ok(bytes);
+        }
+
+        Ok(match self.entries[index].read(&self.source)? {
+            Cow::Borrowed(bytes) => bytes,
+            Cow::Owned(bytes) => self.inflated[index].get_or_init(|| bytes),
+        })
+    }
+
+    /// The class named by the `Main-Class` attribute of the manifest
+    pub fn get_main_class(&self) -> Option<Result<JavaClass<'_>, ReadClassError>> {
+        let main_class = self.manifest.main_class.as_ref()?;
+        self.read_class(&main_class.replace('.', "/"))
     }
 }
 
-fn read_cdr_file_bytes(reader: &mut ByteReader) -> std::io::Result<(String, Vec<u8>)> {
-    let compressed_size = reader.jump(20).read::<u32>()? as usize;
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, path::PathBuf};
 
-    let file_name_length = reader.jump(4).read::<u16>()? as usize;
+    use super::{JarFile, JarSource, ReadClassError, BASE_VERSION};
+    use crate::classfile::{attributes::AttributeInfoData, error::ClassFormatError, JavaClass};
 
-    // The file offset + the fields we don't care about
-    let file_data_offset = reader.jump(12).read::<u32>()? as usize;
+    fn sample_jar() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-data/sample.jar")
+    }
 
-    // Read the file name
-    let file_name = reader.read_string(file_name_length)?;
+    /// `sample/Color.class` at the root, `sample/Info.class` in version 11 and `sample/Shape.class`
+    /// in version 17 under its name, `sample/resource.txt` contains the version it is for
+    fn multi_release_jar() -> JarFile {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-data/multi-release.jar");
+        JarFile::from_file(&path).unwrap()
+    }
 
-    // Move to the file entry
-    reader.move_to(file_data_offset);
+    #[test]
+    fn classes_borrow_from_the_jar() {
+        fn first_code<'a>(java_class: &JavaClass<'a>) -> Cow<'a, [u8]> {
+            java_class
+                .methods
+                .iter()
+                .flat_map(|method| &method.attributes)
+                .find_map(|attribute| match &attribute.attribute {
+                    AttributeInfoData::Code(code) => Some(code.code.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        }
 
-    let compression_method = reader.jump(8).read::<u16>()? as usize;
+        let jar_file = JarFile::from_file(&sample_jar()).unwrap();
+        let circle = jar_file
+            .read_class("sample/Shapes$Circle")
+            .unwrap()
+            .unwrap();
+        let code = first_code(&circle);
+        assert!(matches!(code, Cow::Borrowed(_)));
+
+        // The class is inflated once, reading it again borrows the same bytes
+        let read_again = jar_file
+            .read_class("sample/Shapes$Circle")
+            .unwrap()
+            .unwrap();
+        assert_eq!(code.as_ptr(), first_code(&read_again).as_ptr());
+    }
 
-    // Read the amount of extra fields
-    let extra_field_length = reader.jump(18).read::<u16>()? as usize;
+    #[test]
+    fn jars_are_mapped() {
+        let source = JarSource::open(&sample_jar()).unwrap();
+        assert!(matches!(source, JarSource::Mapped(_)));
+        assert_eq!(
+            source.len(),
+            std::fs::metadata(sample_jar()).unwrap().len() as usize
+        );
+
+        let jar_file = JarFile::from_source(source).unwrap();
+        assert_eq!(jar_file.class_names().count(), 10);
+    }
 
-    // Read the deflated bytes
-    let data = reader
-        .jump(file_name_length + extra_field_length)
-        .read_bytes(compressed_size)?;
+    #[test]
+    fn entries_are_read_when_asked_for() {
+        let jar_file = JarFile::from_bytes(std::fs::read(sample_jar()).unwrap()).unwrap();
+        assert_eq!(jar_file.entries().len(), 13);
+        assert!(jar_file.entry("sample/").unwrap().is_directory());
+        assert_eq!(
+            jar_file.manifest.created_by.as_deref(),
+            Some("17.0.15 (Debian)")
+        );
+
+        let manifest = jar_file.read("META-INF/MANIFEST.MF").unwrap();
+        assert!(manifest.starts_with(b"Manifest-Version: 1.0"));
+        let error = jar_file.read("sample/Missing.class").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+
+        let circle = jar_file
+            .read_class("sample/Shapes$Circle")
+            .unwrap()
+            .unwrap();
+        assert_eq!(circle.get_name(), Some("sample/Shapes$Circle"));
+        assert!(jar_file.read_class("sample/Missing").is_none());
+        assert!(jar_file.get_main_class().is_none());
+    }
 
-    let data = match compression_method {
-        0 => data,
+    #[test]
+    fn multi_release_jars_resolve_names_for_their_version() {
+        let mut jar_file = multi_release_jar();
+        assert!(jar_file.manifest.multi_release);
+        assert_eq!(jar_file.version(), BASE_VERSION);
 
-        8 => {
-            let mut decoder = DeflateDecoder::new(data.as_slice());
-            let mut buffer = vec![];
-            decoder.read_to_end(&mut buffer).unwrap();
-            buffer
-        }
-        not_impl_comp_method => {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("The the compression method {not_impl_comp_method} is not implemented yet, consider adding it"),
-            ));
+        let class_name = |jar_file: &JarFile, name: &str| {
+            let java_class = jar_file.read_class(name).unwrap().unwrap();
+            java_class.get_name().unwrap().to_string()
+        };
+        for (version, resource, color, class_names) in [
+            (BASE_VERSION, "8", "sample/Color", vec!["sample/Color"]),
+            (10, "9", "sample/Color", vec!["sample/Color"]),
+            (
+                11,
+                "11",
+                "sample/Info",
+                vec!["sample/Color", "sample/NonNull"],
+            ),
+            (
+                21,
+                "11",
+                "sample/Shape",
+                vec!["sample/Color", "sample/NonNull"],
+            ),
+        ] {
+            jar_file.set_version(version);
+            assert_eq!(
+                &*jar_file.read("sample/resource.txt").unwrap(),
+                resource.as_bytes()
+            );
+            assert_eq!(class_name(&jar_file, "sample/Color"), color);
+            assert_eq!(jar_file.class_names().collect::<Vec<_>>(), class_names);
+            assert_eq!(
+                &*jar_file.read("META-INF/services/sample.Service").unwrap(),
+                b"sample.Color"
+            );
         }
-    };
+        assert_eq!(
+            jar_file.entry("sample/NonNull.class").unwrap().name,
+            "META-INF/versions/11/sample/NonNull.class"
+        );
+
+        // Without the manifest attribute the versions are ordinary entries
+        jar_file.manifest.multi_release = false;
+        jar_file.set_version(21);
+        assert_eq!(&*jar_file.read("sample/resource.txt").unwrap(), b"8");
+        assert_eq!(class_name(&jar_file, "sample/Color"), "sample/Color");
+        assert_eq!(jar_file.class_names().count(), 4);
+        assert!(jar_file.read_class("sample/NonNull").is_none());
+    }
 
-    Ok((file_name, data))
-}
+    #[test]
+    fn broken_classes_are_told_apart() {
+        // `a.class` is full of zip header signatures instead of a class file
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-data/zip/prefixed.zip");
+        let archive = JarFile::from_file(&path).unwrap();
+        let error = archive.read_class("a").unwrap().unwrap_err();
+        assert!(matches!(
+            error,
+            ReadClassError::ClassFormat {
+                error: ClassFormatError::BadMagic { .. },
+                ..
+            }
+        ));
+
+        // The compressed data of the entry doesn't match its checksum anymore
+        let mut bytes = std::fs::read(sample_jar()).unwrap();
+        let jar_file = JarFile::from_bytes(bytes.clone()).unwrap();
+        let color = jar_file.entry("sample/Color.class").unwrap();
+        let data_offset = color.local_header_offset as usize + 30 + color.name.len();
+        bytes[data_offset + color.compressed_size as usize / 2] ^= 0xFF;
+        let broken_jar_file = JarFile::from_bytes(bytes).unwrap();
+        let error = broken_jar_file
+            .read_class("sample/Color")
+            .unwrap()
+            .unwrap_err();
+        assert!(matches!(error, ReadClassError::Io { ref name, .. } if name == &color.name));
+    }
 
-impl JarFile {
-    pub fn get_main_class(&self) -> Option<&JavaClass> {
-        let Some(main_class) = &self.manifest.main_class else {
-        	return None;
-        };
-        self.classes.get(main_class)
+    #[test]
+    fn skimmed_classes_have_no_attributes() {
+        let jar_file = JarFile::from_file(&sample_jar()).unwrap();
+        let bytes = jar_file.read("sample/Shapes.class").unwrap();
+        let skimmed = JavaClass::skim(&bytes).unwrap();
+        let parsed = JavaClass::from_bytes(&bytes).unwrap();
+
+        assert_eq!(skimmed.get_name(), parsed.get_name());
+        assert!(skimmed.attributes.is_empty());
+        assert_eq!(skimmed.methods.len(), parsed.methods.len());
+        for (skimmed, parsed) in skimmed.methods.iter().zip(&parsed.methods) {
+            assert_eq!(skimmed.name_index, parsed.name_index);
+            assert_eq!(skimmed.descriptor_index, parsed.descriptor_index);
+            assert!(skimmed.attributes.is_empty() && !parsed.attributes.is_empty());
+        }
     }
 }
@@ -1,2 +1,3 @@
 pub mod classfile;
 pub mod jar;
+pub mod zip;
@@ -0,0 +1,356 @@
+//! The ZIP archive format jars are stored in, see the `APPNOTE.TXT` of the ZIP specification.
+//! Entries are found through the central directory at the end of the archive, so bytes that
+//! happen to look like headers inside of entries are never mistaken for one.
+
+use std::borrow::Cow;
+use std::io::{Error, ErrorKind, Read};
+
+use byte_reader::ByteReader;
+use flate2::{read::DeflateDecoder, Crc};
+
+const LOCAL_FILE_HEADER: u32 = 0x04034B50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x02014B50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054B50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY: u32 = 0x06064B50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR: u32 = 0x07064B50;
+
+/// The fixed size part of the end of central directory record, the comment follows it
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize = 22;
+/// The fixed size part of the ZIP64 end of central directory record, the extensible data follows
+const ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE: usize = 56;
+const ZIP64_LOCATOR_SIZE: usize = 20;
+/// Deflate can't compress data by more than about this much, so sizes in a corrupt central
+/// directory can't make an entry allocate more than its compressed size allows
+const MAX_DEFLATE_RATIO: u64 = 1032;
+/// The id of the extra field with the 64-bit sizes and offset of an entry
+const ZIP64_EXTRA_FIELD: u16 = 0x0001;
+
+/// The entry is encrypted
+const FLAG_ENCRYPTED: u16 = 0x0001;
+
+pub const METHOD_STORED: u16 = 0;
+pub const METHOD_DEFLATED: u16 = 8;
+
+/// A file or directory in the central directory of an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntry {
+    /// The path in the archive, directories end with `/`
+    pub name: String,
+    pub flags: u16,
+    pub compression_method: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    /// Where the local file header of the entry starts in the archive
+    pub local_header_offset: u64,
+}
+
+impl ZipEntry {
+    pub fn is_directory(&self) -> bool {
+        self.name.ends_with('/')
+    }
+
+    /// The uncompressed contents of the entry, stored entries borrow from `archive`. The sizes
+    /// come from the central directory, so entries followed by a data descriptor work too
+    pub fn read<'a>(&self, archive: &'a [u8]) -> std::io::Result<Cow<'a, [u8]>> {
+        if self.flags & FLAG_ENCRYPTED != 0 {
+            return Err(invalid(format!("The entry '{}' is encrypted", self.name)));
+        }
+
+        let mut reader = ByteReader::from_slice(archive);
+        reader.move_to(to_usize(self.local_header_offset)?);
+        if reader.read::<u32>()? != LOCAL_FILE_HEADER {
+            return Err(invalid(format!(
+                "No local file header for the entry '{}'",
+                self.name
+            )));
+        }
+
+        // The name and extra field of the local header can differ from the central directory
+        let name_length = reader.jump(22).read::<u16>()? as usize;
+        let extra_length = reader.read::<u16>()? as usize;
+        let compressed = reader
+            .jump(name_length + extra_length)
+            .read_slice(to_usize(self.compressed_size)?)?;
+
+        let data = match self.compression_method {
+            METHOD_STORED => Cow::Borrowed(compressed),
+            METHOD_DEFLATED => {
+                let capacity = self
+                    .uncompressed_size
+                    .min(self.compressed_size.saturating_mul(MAX_DEFLATE_RATIO));
+                let mut data = Vec::with_capacity(to_usize(capacity)?);
+                // A byte more than expected is enough to tell the size is wrong
+                DeflateDecoder::new(compressed)
+                    .take(self.uncompressed_size.saturating_add(1))
+                    .read_to_end(&mut data)?;
+                Cow::Owned(data)
+            }
+            method => {
+                return Err(invalid(format!(
+                    "The compression method {method} of the entry '{}' isn't supported",
+                    self.name
+                )))
+            }
+        };
+
+        let mut crc = Crc::new();
+        crc.update(&data);
+        if data.len() as u64 != self.uncompressed_size || crc.sum() != self.crc32 {
+            return Err(invalid(format!("The entry '{}' is corrupt", self.name)));
+        }
+
+        Ok(data)
+    }
+}
+
+/// Reads the entries listed in the central directory of an archive
+pub fn read_central_directory(archive: &[u8]) -> std::io::Result<Vec<ZipEntry>> {
+    let end_offset = find_end_of_central_directory(archive)?;
+
+    let mut reader = ByteReader::from_slice(archive);
+    reader.move_to(end_offset + 4);
+    let disk: u16 = reader.read()?;
+    let directory_disk: u16 = reader.read()?;
+    let mut entry_count = reader.jump(2).read::<u16>()? as u64;
+    let mut directory_size = reader.read::<u32>()? as u64;
+    let mut directory_offset = reader.read::<u32>()? as u64;
+    if disk != directory_disk {
+        return Err(invalid("Archives that span several disks aren't supported"));
+    }
+
+    // Values that don't fit are saturated and the real ones are in the ZIP64 record
+    let zip64 = entry_count == u16::MAX as u64
+        || directory_size == u32::MAX as u64
+        || directory_offset == u32::MAX as u64;
+    let mut directory_end = end_offset;
+    if let Some(locator_offset) = end_offset.checked_sub(ZIP64_LOCATOR_SIZE) {
+        reader.move_to(locator_offset);
+        if reader.read::<u32>()? == ZIP64_END_OF_CENTRAL_DIRECTORY_LOCATOR {
+            // The recorded offset doesn't count data before the archive, but the record is
+            // right before the locator unless it has extensible data
+            let recorded_offset = to_usize(reader.jump(4).read::<u64>()?)?;
+            let mut is_record = |offset: &usize| {
+                reader.move_to(*offset).read::<u32>().ok() == Some(ZIP64_END_OF_CENTRAL_DIRECTORY)
+            };
+            let record_offset = locator_offset
+                .checked_sub(ZIP64_END_OF_CENTRAL_DIRECTORY_SIZE)
+                .filter(&mut is_record)
+                .or_else(|| Some(recorded_offset).filter(&mut is_record))
+                .ok_or_else(|| invalid("No ZIP64 end of central directory record"))?;
+
+            reader.move_to(record_offset + 4);
+            entry_count = reader.jump(28).read()?;
+            directory_size = reader.read()?;
+            directory_offset = reader.read()?;
+            directory_end = record_offset;
+        } else if zip64 {
+            return Err(invalid("No ZIP64 end of central directory locator"));
+        }
+    }
+
+    // Data before the archive, e.g. a launcher script, shifts every offset in it
+    let directory_offset = to_usize(directory_offset)?;
+    let prefix = directory_end
+        .checked_sub(directory_offset)
+        .and_then(|end| end.checked_sub(to_usize(directory_size).ok()?))
+        .ok_or_else(|| invalid("The central directory is out of bounds"))?;
+
+    reader.move_to(prefix + directory_offset);
+    let mut entries = Vec::with_capacity(entry_count.min(u16::MAX as u64) as usize);
+    for _ in 0..entry_count {
+        let mut entry = read_central_directory_header(&mut reader)?;
+        entry.local_header_offset += prefix as u64;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// The record is at the very end, followed only by a comment of up to 65535 bytes
+fn find_end_of_central_directory(archive: &[u8]) -> std::io::Result<usize> {
+    let last = archive
+        .len()
+        .checked_sub(END_OF_CENTRAL_DIRECTORY_SIZE)
+        .ok_or_else(|| invalid("Too short to be a zip archive"))?;
+    let first = last.saturating_sub(u16::MAX as usize);
+
+    let signature = END_OF_CENTRAL_DIRECTORY.to_le_bytes();
+    (first..=last)
+        .rev()
+        .find(|offset| {
+            let comment_length = u16::from_le_bytes([archive[offset + 20], archive[offset + 21]]);
+            archive[*offset..offset + 4] == signature
+                && offset + END_OF_CENTRAL_DIRECTORY_SIZE + comment_length as usize == archive.len()
+        })
+        .ok_or_else(|| invalid("No end of central directory record, this isn't a zip archive"))
+}
+
+fn read_central_directory_header(reader: &mut ByteReader<'_>) -> std::io::Result<ZipEntry> {
+    if reader.read::<u32>()? != CENTRAL_DIRECTORY_HEADER {
+        return Err(invalid(format!(
+            "No central directory file header at offset {}",
+            reader.get_current_offset() - 4
+        )));
+    }
+
+    let flags = reader.jump(4).read()?;
+    let compression_method = reader.read()?;
+    let crc32 = reader.jump(4).read()?;
+    let mut compressed_size = reader.read::<u32>()? as u64;
+    let mut uncompressed_size = reader.read::<u32>()? as u64;
+    let name_length = reader.read::<u16>()? as usize;
+    let extra_length = reader.read::<u16>()? as usize;
+    let comment_length = reader.read::<u16>()? as usize;
+    let mut local_header_offset = reader.jump(8).read::<u32>()? as u64;
+    // Jars always use UTF-8 names, whether or not the language encoding flag is set
+    let name = reader.read_string_lossy(name_length)?;
+
+    let mut extra = ByteReader::from_slice(reader.read_slice(extra_length)?);
+    while let (Ok(id), Ok(length)) = (extra.read::<u16>(), extra.read::<u16>()) {
+        let end = extra.get_current_offset() + length as usize;
+        if id == ZIP64_EXTRA_FIELD {
+            // Only the values that are saturated in the header are in the field, in this order
+            for value in [
+                &mut uncompressed_size,
+                &mut compressed_size,
+                &mut local_header_offset,
+            ] {
+                if *value == u32::MAX as u64 {
+                    *value = extra.read()?;
+                }
+            }
+        }
+        extra.move_to(end);
+    }
+    reader.jump(comment_length);
+
+    Ok(ZipEntry {
+        name,
+        flags,
+        compression_method,
+        crc32,
+        compressed_size,
+        uncompressed_size,
+        local_header_offset,
+    })
+}
+
+fn to_usize(value: u64) -> std::io::Result<usize> {
+    usize::try_from(value).map_err(|_| invalid(format!("The offset {value} is out of bounds")))
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{read_central_directory, ZipEntry, METHOD_DEFLATED, METHOD_STORED};
+
+    /// The archives in `test-data/zip` are written by python's `zipfile`, `zip64.zip` with the
+    /// ZIP64 limits lowered so it has ZIP64 records and extra fields
+    fn archive(name: &str) -> Vec<u8> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-data/zip");
+        std::fs::read(path.join(name)).unwrap()
+    }
+
+    fn entry<'a>(entries: &'a [ZipEntry], name: &str) -> &'a ZipEntry {
+        entries.iter().find(|entry| entry.name == name).unwrap()
+    }
+
+    #[test]
+    fn reads_zip64_archives() {
+        let archive = archive("zip64.zip");
+        let entries = read_central_directory(&archive).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let manifest = entry(&entries, "META-INF/MANIFEST.MF");
+        assert_eq!(
+            &*manifest.read(&archive).unwrap(),
+            b"Manifest-Version: 1.0\r\n\r\n"
+        );
+        let stored = entry(&entries, "stored.txt");
+        assert_eq!(stored.compression_method, METHOD_STORED);
+        assert_eq!(stored.local_header_offset, 97);
+        assert_eq!(&*stored.read(&archive).unwrap(), b"stored");
+    }
+
+    #[test]
+    fn reads_prefixed_zip64_archives() {
+        let mut archive = b"#!/bin/sh\nexec java -jar \"$0\"\n".to_vec();
+        let prefix = archive.len() as u64;
+        archive.extend(self::archive("zip64.zip"));
+
+        let entries = read_central_directory(&archive).unwrap();
+        let stored = entry(&entries, "stored.txt");
+        assert_eq!(stored.local_header_offset, 97 + prefix);
+        assert_eq!(&*stored.read(&archive).unwrap(), b"stored");
+    }
+
+    #[test]
+    fn sizes_are_checked_while_inflating() {
+        let archive = archive("descriptor.zip");
+        let entries = read_central_directory(&archive).unwrap();
+        let entry = entry(&entries, "x.txt");
+        assert_eq!(entry.compression_method, METHOD_DEFLATED);
+
+        // Neither allocates the claimed size nor inflates past it
+        for uncompressed_size in [entry.uncompressed_size - 1, u64::MAX / 2] {
+            let entry = ZipEntry {
+                uncompressed_size,
+                ..entry.clone()
+            };
+            assert!(entry.read(&archive).is_err());
+        }
+    }
+
+    #[test]
+    fn reads_entries_followed_by_data_descriptors() {
+        let archive = archive("descriptor.zip");
+        let entries = read_central_directory(&archive).unwrap();
+
+        let data = entry(&entries, "x.txt").read(&archive).unwrap();
+        assert_eq!(&*data, "data descriptor".repeat(30).as_bytes());
+        assert_eq!(
+            &*entry(&entries, "y.txt").read(&archive).unwrap(),
+            b"yyyyyyyyyy"
+        );
+    }
+
+    #[test]
+    fn headers_inside_entries_are_not_entries() {
+        // A shell script before the archive, a stored entry full of header signatures and a
+        // comment that contains the end of central directory signature
+        let archive = archive("prefixed.zip");
+        let entries = read_central_directory(&archive).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let class = entry(&entries, "a.class").read(&archive).unwrap();
+        assert!(class.starts_with(b"PK\x01\x02PK\x01\x02"));
+        assert_eq!(
+            &*entry(&entries, "b.txt").read(&archive).unwrap(),
+            "hello".repeat(100).as_bytes()
+        );
+    }
+
+    #[test]
+    fn rejects_broken_archives() {
+        let mut archive = archive("prefixed.zip");
+        let entries = read_central_directory(&archive).unwrap();
+        let class = entry(&entries, "a.class");
+
+        let data_offset = archive
+            .windows(9)
+            .position(|window| window == b"a.classPK")
+            .unwrap();
+        archive[data_offset + 7] = b'Q';
+        assert!(class.read(&archive).is_err());
+
+        for length in [0, 21, archive.len() / 2, archive.len() - 1] {
+            assert!(read_central_directory(&archive[..length]).is_err());
+        }
+    }
+}
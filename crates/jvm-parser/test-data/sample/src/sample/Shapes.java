@@ -0,0 +1,118 @@
+package sample;
+
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.ArrayList;
+import java.util.List;
+import java.util.function.Function;
+
+@Retention(RetentionPolicy.RUNTIME)
+@interface Info {
+    String value() default "none";
+    int[] numbers() default {1, 2};
+    Class<?> type() default Object.class;
+    ElementType kind() default ElementType.TYPE;
+    Deprecated nested() default @Deprecated;
+}
+
+@Target(ElementType.TYPE_USE)
+@Retention(RetentionPolicy.RUNTIME)
+@interface NonNull {}
+
+sealed interface Shape permits Shapes.Circle, Shapes.Square {
+    double area();
+
+    default String describe() {
+        return getClass().getSimpleName() + " with area " + area();
+    }
+}
+
+enum Color { RED, GREEN, BLUE }
+
+@Info(value = "shapes", numbers = {3, 4, 5}, type = String.class, kind = ElementType.FIELD)
+public class Shapes implements Runnable {
+    static final long BIG = 0x1234_5678_9ABC_DEF0L;
+    static final double PI_ISH = 3.14159;
+    static final float NOT_A_NUMBER = Float.NaN;
+    static final String WEIRD = "nul \0 char, emoji \uD83D\uDE00 and \u00E9";
+
+    record Circle(@Info("radius") double radius) implements Shape {
+        public double area() {
+            return Math.PI * radius * radius;
+        }
+    }
+
+    static final class Square implements Shape {
+        private final double side;
+
+        Square(double side) {
+            this.side = side;
+        }
+
+        public double area() {
+            return side * side;
+        }
+    }
+
+    private int counter;
+
+    @Deprecated
+    public <T extends Comparable<T>> List<@NonNull T> sorted(List<T> input, @Info("flag") boolean reverse)
+            throws IllegalStateException {
+        List<T> copy = new ArrayList<>(input);
+        copy.sort(reverse ? (a, b) -> b.compareTo(a) : Comparable::compareTo);
+        return copy;
+    }
+
+    int classify(Object value) {
+        if (value instanceof Circle circle && circle.radius() > 1) {
+            return 1;
+        }
+        switch (String.valueOf(value).length()) {
+            case 0: return 10;
+            case 1: return 11;
+            case 2: return 12;
+            case 3: return 13;
+            default: break;
+        }
+        return switch (Color.values()[counter % 3]) {
+            case RED -> 2;
+            case GREEN -> 3;
+            case BLUE -> 4;
+        };
+    }
+
+    long sum(long[] values, double scale) {
+        long total = 0;
+        try {
+            for (long value : values) {
+                total += value;
+            }
+        } catch (ArrayIndexOutOfBoundsException | NullPointerException e) {
+            total = -1;
+        } finally {
+            counter++;
+        }
+        return (long) (total * scale);
+    }
+
+    synchronized void increment() {
+        Runnable task = new Runnable() {
+            public void run() {
+                counter++;
+            }
+        };
+        task.run();
+        Function<Integer, String> format = i -> "#" + i + WEIRD;
+        format.apply(counter);
+    }
+
+    @Override
+    public void run() {
+        int[][] grid = new int[3][4];
+        grid[1][2] = (int) BIG;
+        increment();
+    }
+}
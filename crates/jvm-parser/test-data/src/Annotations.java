@@ -0,0 +1,21 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.util.List;
+
+@Retention(RetentionPolicy.RUNTIME)
+@interface Info {
+    String value() default "none";
+    int[] numbers() default {1, 2};
+    Class<?> type() default Object.class;
+    ElementType kind() default ElementType.TYPE;
+    Deprecated nested() default @Deprecated;
+}
+
+@Info(value = "shapes", numbers = {3, 4, 5}, type = String.class, kind = ElementType.FIELD)
+public class Annotations {
+    @Deprecated
+    public List<String> sorted(List<String> input, @Info("flag") boolean reverse) {
+        return input;
+    }
+}
@@ -0,0 +1,30 @@
+sealed interface Shape permits Shapes.Circle, Shapes.Square {
+    double area();
+}
+
+public class Shapes {
+    record Circle(@Info("radius") double radius) implements Shape {
+        public double area() {
+            return Math.PI * radius * radius;
+        }
+    }
+
+    static final class Square implements Shape {
+        private final double side;
+
+        Square(double side) {
+            this.side = side;
+        }
+
+        public double area() {
+            return side * side;
+        }
+    }
+
+    static Runnable task() {
+        return new Runnable() {
+            public void run() {
+            }
+        };
+    }
+}
@@ -0,0 +1,7 @@
+module sample {
+    requires java.logging;
+    exports sample;
+    opens sample to java.logging;
+    uses java.lang.Runnable;
+    provides java.lang.Runnable with sample.Task;
+}
@@ -0,0 +1,1278 @@
+use std::{
+    fmt::{Display, LowerExp, Result, Write},
+    path::PathBuf,
+};
+
+use jvm_parser::{
+    classfile::{
+        attributes::{AttributeInfo, AttributeInfoData, CodeAttribute, LocalVariableTableEntry},
+        classfile::{
+            ClassAccessFlags, FieldAccessFlags, FieldInfo, MethodAccessFlags, MethodInfo,
+            ModuleFlags,
+        },
+        constant_pool::{ConstantPool, CpInfo},
+        descriptor::parse_method_descriptor,
+        signature::{
+            parse_class_signature, parse_field_signature, parse_method_signature,
+            ClassTypeSignature, JavaTypeSignature, ReferenceTypeSignature, TypeParameter,
+        },
+        JavaClass,
+    },
+    jar::JarFile,
+};
+
+use crate::jvm::opcodes::{parse_opcodes, CmpConditions, OpCodes};
+
+/// What is printed besides the declarations, like the flags of the JDK's `javap`
+#[derive(Debug, Clone, Copy)]
+pub struct JavapOptions {
+    /// `-c`, the byte code of every method
+    pub code: bool,
+    /// `-v`, the constant pool, flags and attributes as well as the byte code
+    pub verbose: bool,
+    /// `-p`, private members too
+    pub private: bool,
+    /// `--multi-release`, the version the classes of a multi-release jar are read for
+    pub multi_release: Option<u16>,
+}
+
+const CLASS_FLAGS: &[(u16, &str)] = &[
+    (ClassAccessFlags::ACC_PUBLIC, "ACC_PUBLIC"),
+    (ClassAccessFlags::ACC_FINAL, "ACC_FINAL"),
+    (ClassAccessFlags::ACC_SUPER, "ACC_SUPER"),
+    (ClassAccessFlags::ACC_INTERFACE, "ACC_INTERFACE"),
+    (ClassAccessFlags::ACC_ABSTRACT, "ACC_ABSTRACT"),
+    (ClassAccessFlags::ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+    (ClassAccessFlags::ACC_ANNOTATION, "ACC_ANNOTATION"),
+    (ClassAccessFlags::ACC_ENUM, "ACC_ENUM"),
+    (ClassAccessFlags::ACC_MODULE, "ACC_MODULE"),
+];
+
+const METHOD_FLAGS: &[(u16, &str)] = &[
+    (MethodAccessFlags::ACC_PUBLIC, "ACC_PUBLIC"),
+    (MethodAccessFlags::ACC_PRIVATE, "ACC_PRIVATE"),
+    (MethodAccessFlags::ACC_PROTECTED, "ACC_PROTECTED"),
+    (MethodAccessFlags::ACC_STATIC, "ACC_STATIC"),
+    (MethodAccessFlags::ACC_FINAL, "ACC_FINAL"),
+    (MethodAccessFlags::ACC_SYNCHRONIZED, "ACC_SYNCHRONIZED"),
+    (MethodAccessFlags::ACC_BRIDGE, "ACC_BRIDGE"),
+    (MethodAccessFlags::ACC_VARARGS, "ACC_VARARGS"),
+    (MethodAccessFlags::ACC_NATIVE, "ACC_NATIVE"),
+    (MethodAccessFlags::ACC_ABSTRACT, "ACC_ABSTRACT"),
+    (MethodAccessFlags::ACC_STRICT, "ACC_STRICT"),
+    (MethodAccessFlags::ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+];
+
+const FIELD_FLAGS: &[(u16, &str)] = &[
+    (FieldAccessFlags::ACC_PUBLIC, "ACC_PUBLIC"),
+    (FieldAccessFlags::ACC_PRIVATE, "ACC_PRIVATE"),
+    (FieldAccessFlags::ACC_PROTECTED, "ACC_PROTECTED"),
+    (FieldAccessFlags::ACC_STATIC, "ACC_STATIC"),
+    (FieldAccessFlags::ACC_FINAL, "ACC_FINAL"),
+    (FieldAccessFlags::ACC_VOLATILE, "ACC_VOLATILE"),
+    (FieldAccessFlags::ACC_TRANSIENT, "ACC_TRANSIENT"),
+    (FieldAccessFlags::ACC_SYNTHETIC, "ACC_SYNTHETIC"),
+    (FieldAccessFlags::ACC_ENUM, "ACC_ENUM"),
+];
+
+/// Prints the classes of a `.class` file or a jar, the latter sorted by their file name
+pub fn javap(path: &PathBuf, options: JavapOptions) -> std::result::Result<(), String> {
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "jar" || extension == "zip")
+    {
+        let mut jar_file = JarFile::from_file(path).map_err(|err| err.to_string())?;
+        if let Some(version) = options.multi_release {
+            jar_file.set_version(version);
+        }
+        let mut class_names: Vec<_> = jar_file.class_names().collect();
+        class_names.sort_by_cached_key(|class_name| format!("{class_name}.class"));
+
+        for class_name in class_names {
+            let java_class = jar_file
+                .read_class(class_name)
+                .unwrap()
+                .map_err(|err| err.to_string())?;
+            let file_name = &jar_file.entry(&format!("{class_name}.class")).unwrap().name;
+            let origin = format!("jar:file:{}!/{file_name}", path.display());
+            print!("{}", disassemble(&java_class, &origin, options));
+        }
+    } else {
+        let java_class = JavaClass::from_file(path).map_err(|err| err.to_string())?;
+        let origin = std::fs::canonicalize(path).unwrap_or(path.clone());
+        print!(
+            "{}",
+            disassemble(&java_class, &origin.display().to_string(), options)
+        );
+    }
+
+    Ok(())
+}
+
+/// The `javap` listing of a class, `origin` is where it was read from
+pub fn disassemble(java_class: &JavaClass, origin: &str, options: JavapOptions) -> String {
+    let mut out = String::new();
+    Disassembler {
+        java_class,
+        constant_pool: &java_class.constant_pool,
+        options,
+    }
+    .class(&mut out, origin)
+    .expect("Writing to a String can't fail");
+    out
+}
+
+struct Disassembler<'a> {
+    java_class: &'a JavaClass<'a>,
+    constant_pool: &'a ConstantPool<'a>,
+    options: JavapOptions,
+}
+
+impl Disassembler<'_> {
+    fn utf8(&self, index: u16) -> &str {
+        self.constant_pool
+            .get_utf8_at(index)
+            .map(|utf8| utf8.data.as_ref())
+            .unwrap_or("<invalid>")
+    }
+
+    fn this_name(&self) -> &str {
+        self.java_class.get_name().unwrap_or("<invalid>")
+    }
+
+    fn class(&self, out: &mut String, origin: &str) -> Result {
+        let java_class = self.java_class;
+        let source_file =
+            java_class
+                .attributes
+                .iter()
+                .find_map(|attribute| match &attribute.attribute {
+                    AttributeInfoData::SourceFile(source_file) => {
+                        Some(self.utf8(source_file.sourcefile_index))
+                    }
+                    _ => None,
+                });
+
+        if self.options.verbose {
+            writeln!(out, "Classfile {origin}")?;
+            if let Some(source_file) = source_file {
+                writeln!(out, "  Compiled from \"{source_file}\"")?;
+            }
+        } else if let Some(source_file) = source_file {
+            writeln!(out, "Compiled from \"{source_file}\"")?;
+        }
+
+        let module = java_class.get_module();
+        let declaration = match module {
+            Some(module) => format!(
+                "module {}",
+                self.constant_pool
+                    .get_module_name_at(module.module_name_index)
+                    .unwrap_or("<invalid>")
+            ),
+            None => self.class_declaration(),
+        };
+
+        if self.options.verbose {
+            writeln!(out, "{declaration}")?;
+            writeln!(out, "  minor version: {}", java_class.minor_version)?;
+            writeln!(out, "  major version: {}", java_class.major_version)?;
+            writeln!(
+                out,
+                "  flags: {}",
+                flags(java_class.access_flags, CLASS_FLAGS)
+            )?;
+            writeln!(
+                out,
+                "  {:<40}// {}",
+                format!("this_class: #{}", java_class.this_class),
+                self.constant(java_class.this_class)
+            )?;
+            writeln!(
+                out,
+                "  {:<40}// {}",
+                format!("super_class: #{}", java_class.super_class),
+                self.constant(java_class.super_class)
+            )?;
+            writeln!(
+                out,
+                "  interfaces: {}, fields: {}, methods: {}, attributes: {}",
+                java_class.interfaces.len(),
+                java_class.fields.len(),
+                java_class.methods.len(),
+                java_class.attributes.len()
+            )?;
+            self.constant_pool_listing(out)?;
+            writeln!(out, "{{")?;
+        } else {
+            writeln!(out, "{declaration} {{")?;
+        }
+
+        if let Some(module) = module {
+            self.module_directives(out, module)?;
+        }
+
+        let separate = self.options.code || self.options.verbose;
+        let mut first = true;
+        let mut separator = |out: &mut String| {
+            if separate && !std::mem::take(&mut first) {
+                writeln!(out)?;
+            }
+            Ok(())
+        };
+
+        for field in &java_class.fields {
+            if field.access_flags & FieldAccessFlags::ACC_PRIVATE == 0 || self.options.private {
+                separator(out)?;
+                self.field(out, field)?;
+            }
+        }
+        for method in &java_class.methods {
+            if method.access_flags & MethodAccessFlags::ACC_PRIVATE == 0 || self.options.private {
+                separator(out)?;
+                self.method(out, method)?;
+            }
+        }
+        writeln!(out, "}}")?;
+
+        if self.options.verbose {
+            self.class_attributes(out)?;
+        }
+        Ok(())
+    }
+
+    fn class_declaration(&self) -> String {
+        let java_class = self.java_class;
+        let access_flags = java_class.access_flags;
+        let is_interface = access_flags & ClassAccessFlags::ACC_INTERFACE != 0;
+
+        let mut words = vec![];
+        if access_flags & ClassAccessFlags::ACC_PUBLIC != 0 {
+            words.push("public");
+        }
+        if !is_interface && access_flags & ClassAccessFlags::ACC_ABSTRACT != 0 {
+            words.push("abstract");
+        }
+        if access_flags & ClassAccessFlags::ACC_FINAL != 0 {
+            words.push("final");
+        }
+        words.push(if is_interface { "interface" } else { "class" });
+
+        let mut declaration = format!("{} {}", words.join(" "), java_name(self.this_name()));
+        let signature = java_class
+            .get_signature()
+            .and_then(|signature| parse_class_signature(signature).ok());
+        let (super_name, interfaces) = match signature {
+            Some(signature) => {
+                declaration.push_str(&self.type_parameters(&signature.type_parameters));
+                let is_object = signature.super_class.binary_name() == "java/lang/Object";
+                (
+                    Some(signature.super_class.to_string())
+                        .filter(|_| self.options.verbose || !is_object),
+                    signature
+                        .interfaces
+                        .iter()
+                        .map(ClassTypeSignature::to_string)
+                        .collect(),
+                )
+            }
+            None => (
+                java_class
+                    .get_super_name()
+                    .filter(|super_name| *super_name != "java/lang/Object")
+                    .map(java_name),
+                java_class
+                    .get_interface_names()
+                    .into_iter()
+                    .map(java_name)
+                    .collect::<Vec<_>>(),
+            ),
+        };
+        if let Some(super_name) = super_name.filter(|_| !is_interface) {
+            declaration.push_str(&format!(" extends {super_name}"));
+        }
+        if !interfaces.is_empty() {
+            let keyword = if is_interface {
+                "extends"
+            } else {
+                "implements"
+            };
+            declaration.push_str(&format!(" {keyword} {}", interfaces.join(", ")));
+        }
+
+        declaration
+    }
+
+    /// `<T, U extends java.lang.Number>`, the `java.lang.Object` class bound is only shown when
+    /// verbose
+    fn type_parameters(&self, type_parameters: &[TypeParameter]) -> String {
+        if type_parameters.is_empty() {
+            return String::new();
+        }
+
+        let type_parameters: Vec<String> = type_parameters
+            .iter()
+            .map(|type_parameter| {
+                let class_bound = type_parameter.class_bound.as_ref().filter(|bound| {
+                    self.options.verbose
+                        || bound.erased_descriptor().as_deref() != Some("Ljava/lang/Object;")
+                });
+                let bounds: Vec<String> = class_bound
+                    .into_iter()
+                    .chain(&type_parameter.interface_bounds)
+                    .map(ReferenceTypeSignature::to_string)
+                    .collect();
+                match bounds.is_empty() {
+                    true => type_parameter.name.clone(),
+                    false => format!("{} extends {}", type_parameter.name, bounds.join(" & ")),
+                }
+            })
+            .collect();
+        format!("<{}>", type_parameters.join(", "))
+    }
+
+    fn module_directives(
+        &self,
+        out: &mut String,
+        module: &jvm_parser::classfile::attributes::ModuleAttribute,
+    ) -> Result {
+        let constant_pool = self.constant_pool;
+        let module_name = |index| java_name(constant_pool.get_module_name_at(index).unwrap_or(""));
+        let package_name =
+            |index| java_name(constant_pool.get_package_name_at(index).unwrap_or(""));
+        let class_name = |index| java_name(constant_pool.get_class_name_at(index).unwrap_or(""));
+        let targets = |out: &mut String, indices: &[u16]| {
+            if indices.is_empty() {
+                return writeln!(out, ";");
+            }
+            writeln!(out, " to")?;
+            let names: Vec<String> = indices.iter().map(|index| module_name(*index)).collect();
+            writeln!(out, "    {};", names.join(",\n    "))
+        };
+
+        for requires in &module.requires {
+            let mut modifiers = String::new();
+            if requires.requires_flags & ModuleFlags::ACC_TRANSITIVE != 0 {
+                modifiers.push_str("transitive ");
+            }
+            if requires.requires_flags & ModuleFlags::ACC_STATIC_PHASE != 0 {
+                modifiers.push_str("static ");
+            }
+            writeln!(
+                out,
+                "  requires {modifiers}{};",
+                module_name(requires.requires_index)
+            )?;
+        }
+        for exports in &module.exports {
+            write!(out, "  exports {}", package_name(exports.exports_index))?;
+            targets(out, &exports.exports_to_index)?;
+        }
+        for opens in &module.opens {
+            write!(out, "  opens {}", package_name(opens.opens_index))?;
+            targets(out, &opens.opens_to_index)?;
+        }
+        for uses in &module.uses_index {
+            writeln!(out, "  uses {};", class_name(*uses))?;
+        }
+        for provides in &module.provides {
+            let implementations: Vec<String> = provides
+                .provides_with_index
+                .iter()
+                .map(|index| class_name(*index))
+                .collect();
+            writeln!(
+                out,
+                "  provides {} with\n    {};",
+                class_name(provides.provides_index),
+                implementations.join(",\n    ")
+            )?;
+        }
+        Ok(())
+    }
+
+    fn constant_pool_listing(&self, out: &mut String) -> Result {
+        writeln!(out, "Constant pool:")?;
+        let width = format!("#{}", self.constant_pool.pool_entries.len()).len() + 2;
+
+        for (position, entry) in self.constant_pool.pool_entries.iter().enumerate() {
+            let index = position as u16 + 1;
+            let references = match entry {
+                CpInfo::EmptyCpEntry => continue,
+                CpInfo::Utf8(_)
+                | CpInfo::Integer(_)
+                | CpInfo::Float(_)
+                | CpInfo::Long(_)
+                | CpInfo::Double(_) => None,
+                CpInfo::Class(class) => Some(format!("#{}", class.name_index)),
+                CpInfo::String(string) => Some(format!("#{}", string.string_index)),
+                CpInfo::Refs(refs) => Some(format!(
+                    "#{}.#{}",
+                    refs.class_index, refs.name_and_type_index
+                )),
+                CpInfo::NameAndType(name_and_type) => Some(format!(
+                    "#{}:#{}",
+                    name_and_type.name_index, name_and_type.descriptor_index
+                )),
+                CpInfo::InvokeDynamic(dynamic) => Some(format!(
+                    "#{}:#{}",
+                    dynamic.bootstrap_method_attr_index, dynamic.name_and_type_index
+                )),
+                CpInfo::MethodHandle(handle) => Some(format!(
+                    "{}:#{}",
+                    handle.reference_kind, handle.reference_index
+                )),
+                CpInfo::MethodType(method_type) => {
+                    Some(format!("#{}", method_type.descriptor_index))
+                }
+                CpInfo::Module(module) => Some(format!("#{}", module.name_index)),
+                CpInfo::Package(package) => Some(format!("#{}", package.name_index)),
+            };
+
+            let kind = format!("{:<18}", tag_name(entry));
+            let number = format!("#{index}");
+            match references {
+                // The comments line up no matter how wide the indices are
+                Some(references) => writeln!(
+                    out,
+                    "{:<42}// {}",
+                    format!("{number:>width$} = {kind} {references}"),
+                    self.constant(index)
+                )?,
+                None => writeln!(out, "{number:>width$} = {kind} {}", self.constant(index))?,
+            }
+        }
+        Ok(())
+    }
+
+    /// A constant pool entry as it appears in comments, member references include their class
+    fn constant(&self, index: u16) -> String {
+        let constant_pool = self.constant_pool;
+        match constant_pool.get_at(index) {
+            Some(CpInfo::Utf8(utf8)) => escape(&utf8.data),
+            Some(CpInfo::Integer(int)) => int.bytes.to_string(),
+            Some(CpInfo::Float(float)) => format!("{}f", java_decimal(float.bytes)),
+            Some(CpInfo::Long(long)) => format!("{}l", long.bytes as i64),
+            Some(CpInfo::Double(double)) => format!("{}d", java_decimal(double.bytes)),
+            Some(CpInfo::Class(class)) => {
+                let name = self.utf8(class.name_index);
+                match name.starts_with('[') {
+                    true => format!("\"{name}\""),
+                    false => name.to_string(),
+                }
+            }
+            Some(CpInfo::String(string)) => escape(self.utf8(string.string_index)),
+            Some(CpInfo::Refs(refs)) => format!(
+                "{}.{}",
+                self.constant(refs.class_index),
+                self.constant(refs.name_and_type_index)
+            ),
+            Some(CpInfo::NameAndType(name_and_type)) => {
+                let name = self.utf8(name_and_type.name_index);
+                let descriptor = self.utf8(name_and_type.descriptor_index);
+                match name.starts_with('<') {
+                    true => format!("\"{name}\":{descriptor}"),
+                    false => format!("{name}:{descriptor}"),
+                }
+            }
+            Some(CpInfo::InvokeDynamic(dynamic)) => format!(
+                "#{}:{}",
+                dynamic.bootstrap_method_attr_index,
+                self.constant(dynamic.name_and_type_index)
+            ),
+            Some(CpInfo::MethodHandle(handle)) => format!(
+                "{} {}",
+                reference_kind(handle.reference_kind),
+                self.constant(handle.reference_index)
+            ),
+            Some(CpInfo::MethodType(method_type)) => {
+                format!(" {}", self.utf8(method_type.descriptor_index))
+            }
+            Some(CpInfo::Module(module)) => self.utf8(module.name_index).to_string(),
+            Some(CpInfo::Package(package)) => self.utf8(package.name_index).to_string(),
+            Some(CpInfo::EmptyCpEntry) | None => "<invalid>".to_string(),
+        }
+    }
+
+    /// The comment of an opcode that refers to the constant pool, members of this class are
+    /// shown without it
+    fn operand_comment(&self, index: u16) -> String {
+        let constant_pool = self.constant_pool;
+        match constant_pool.get_at(index) {
+            Some(CpInfo::Refs(refs)) => {
+                let kind = match refs.tag {
+                    "CONSTANT_Fieldref" => "Field",
+                    "CONSTANT_Methodref" => "Method",
+                    _ => "InterfaceMethod",
+                };
+                let name_and_type = self.constant(refs.name_and_type_index);
+                match constant_pool.get_class_name_at(refs.class_index) {
+                    Some(class_name) if class_name == self.this_name() => {
+                        format!("{kind} {name_and_type}")
+                    }
+                    _ => format!("{kind} {}", self.constant(index)),
+                }
+            }
+            Some(CpInfo::Class(_)) => format!("class {}", self.constant(index)),
+            Some(CpInfo::String(_)) => format!("String {}", self.constant(index)),
+            Some(CpInfo::Integer(_)) => format!("int {}", self.constant(index)),
+            Some(CpInfo::Float(_)) => format!("float {}", self.constant(index)),
+            Some(CpInfo::Long(_)) => format!("long {}", self.constant(index)),
+            Some(CpInfo::Double(_)) => format!("double {}", self.constant(index)),
+            Some(entry) => format!("{} {}", tag_name(entry), self.constant(index)),
+            None => "<invalid>".to_string(),
+        }
+    }
+
+    fn field(&self, out: &mut String, field: &FieldInfo) -> Result {
+        let access_flags = field.access_flags;
+        let mut modifiers = access_modifiers(access_flags);
+        for (flag, modifier) in [
+            (FieldAccessFlags::ACC_STATIC, "static "),
+            (FieldAccessFlags::ACC_FINAL, "final "),
+            (FieldAccessFlags::ACC_TRANSIENT, "transient "),
+            (FieldAccessFlags::ACC_VOLATILE, "volatile "),
+        ] {
+            if access_flags & flag != 0 {
+                modifiers.push_str(modifier);
+            }
+        }
+
+        let descriptor = self.utf8(field.descriptor_index);
+        let field_type = match field
+            .get_signature(self.constant_pool)
+            .and_then(|signature| parse_field_signature(signature).ok())
+        {
+            Some(signature) => signature.to_string(),
+            None => java_type(descriptor),
+        };
+        writeln!(
+            out,
+            "  {modifiers}{field_type} {};",
+            self.utf8(field.name_index)
+        )?;
+
+        if self.options.verbose {
+            writeln!(out, "    descriptor: {descriptor}")?;
+            writeln!(out, "    flags: {}", flags(access_flags, FIELD_FLAGS))?;
+            if let Some(constant_value) = field.get_constant_value() {
+                writeln!(
+                    out,
+                    "    ConstantValue: {}",
+                    self.operand_comment(constant_value.constantvalue_index)
+                )?;
+            }
+            self.member_attributes(out, &field.attributes)?;
+        }
+        Ok(())
+    }
+
+    fn method(&self, out: &mut String, method: &MethodInfo) -> Result {
+        let access_flags = method.access_flags;
+        let name = self.utf8(method.name_index);
+        let descriptor = self.utf8(method.descriptor_index);
+        let (parameters, return_type) = parse_method_descriptor(descriptor)
+            .map(|descriptor| (descriptor.parameters, descriptor.return_type))
+            .unwrap_or_default();
+
+        let mut modifiers = access_modifiers(access_flags);
+        let is_interface = self.java_class.access_flags & ClassAccessFlags::ACC_INTERFACE != 0;
+        if is_interface
+            && access_flags
+                & (MethodAccessFlags::ACC_ABSTRACT
+                    | MethodAccessFlags::ACC_STATIC
+                    | MethodAccessFlags::ACC_PRIVATE)
+                == 0
+        {
+            modifiers.push_str("default ");
+        }
+        for (flag, modifier) in [
+            (MethodAccessFlags::ACC_ABSTRACT, "abstract "),
+            (MethodAccessFlags::ACC_STATIC, "static "),
+            (MethodAccessFlags::ACC_FINAL, "final "),
+            (MethodAccessFlags::ACC_SYNCHRONIZED, "synchronized "),
+            (MethodAccessFlags::ACC_NATIVE, "native "),
+        ] {
+            if access_flags & flag != 0 {
+                modifiers.push_str(modifier);
+            }
+        }
+
+        // The generic signature leaves out synthetic parameters, like the name and ordinal of
+        // an enum constructor
+        let signature = method
+            .get_signature(self.constant_pool)
+            .and_then(|signature| parse_method_signature(signature).ok());
+        let (type_parameters, mut parameter_types, return_type) = match &signature {
+            Some(signature) => (
+                match signature.type_parameters.is_empty() {
+                    true => String::new(),
+                    false => format!("{} ", self.type_parameters(&signature.type_parameters)),
+                },
+                signature
+                    .parameters
+                    .iter()
+                    .map(JavaTypeSignature::to_string)
+                    .collect(),
+                signature
+                    .return_type
+                    .as_ref()
+                    .map_or("void".to_string(), JavaTypeSignature::to_string),
+            ),
+            None => (
+                String::new(),
+                parameters
+                    .iter()
+                    .map(|parameter| java_type(&parameter.to_string()))
+                    .collect::<Vec<_>>(),
+                return_type
+                    .as_ref()
+                    .map_or("void".to_string(), |return_type| {
+                        java_type(&return_type.to_string())
+                    }),
+            ),
+        };
+        if access_flags & MethodAccessFlags::ACC_VARARGS != 0 {
+            if let Some(last) = parameter_types.last_mut() {
+                if let Some(element) = last.strip_suffix("[]") {
+                    *last = format!("{element}...");
+                }
+            }
+        }
+        let parameter_types = parameter_types.join(", ");
+
+        let exceptions: Vec<String> = method
+            .attributes
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::Exceptions(exceptions) => {
+                    Some(&exceptions.exception_index_table)
+                }
+                _ => None,
+            })
+            .into_iter()
+            .flatten()
+            .filter_map(|index| self.constant_pool.get_class_name_at(*index))
+            .map(java_name)
+            .collect();
+        let throws = match signature.as_ref().map(|signature| &signature.throws) {
+            Some(thrown) if !thrown.is_empty() && !exceptions.is_empty() => {
+                let thrown: Vec<String> = thrown
+                    .iter()
+                    .map(ReferenceTypeSignature::to_string)
+                    .collect();
+                format!(" throws {}", thrown.join(", "))
+            }
+            _ if !exceptions.is_empty() => format!(" throws {}", exceptions.join(", ")),
+            _ => String::new(),
+        };
+
+        match name {
+            "<clinit>" => writeln!(out, "  static {{}};")?,
+            "<init>" => writeln!(
+                out,
+                "  {modifiers}{type_parameters}{}({parameter_types}){throws};",
+                java_name(self.this_name())
+            )?,
+            _ => writeln!(
+                out,
+                "  {modifiers}{type_parameters}{return_type} {name}({parameter_types}){throws};"
+            )?,
+        }
+
+        if self.options.verbose {
+            writeln!(out, "    descriptor: {descriptor}")?;
+            writeln!(out, "    flags: {}", flags(access_flags, METHOD_FLAGS))?;
+        }
+        if let Some(code) = method.get_code() {
+            if self.options.code || self.options.verbose {
+                let is_static = access_flags & MethodAccessFlags::ACC_STATIC != 0;
+                let args_size = parameters.len() + if is_static { 0 } else { 1 };
+                self.code(out, code, args_size)?;
+            }
+        }
+        if self.options.verbose {
+            if !exceptions.is_empty() {
+                writeln!(
+                    out,
+                    "    Exceptions:\n      throws {}",
+                    exceptions.join(", ")
+                )?;
+            }
+            self.member_attributes(out, &method.attributes)?;
+        }
+        Ok(())
+    }
+
+    fn member_attributes(&self, out: &mut String, attributes: &[AttributeInfo]) -> Result {
+        for attribute in attributes {
+            match &attribute.attribute {
+                AttributeInfoData::Deprecated => writeln!(out, "    Deprecated: true")?,
+                AttributeInfoData::Synthetic => writeln!(out, "    Synthetic: true")?,
+                AttributeInfoData::Signature(signature) => writeln!(
+                    out,
+                    "    {:<40}// {}",
+                    format!("Signature: #{}", signature.signature_index),
+                    self.constant(signature.signature_index)
+                )?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn code(&self, out: &mut String, code: &CodeAttribute, args_size: usize) -> Result {
+        // The verbose listing is indented further, under the stack and locals sizes
+        let (indent, pc_width) = match self.options.verbose {
+            true => ("      ", 10),
+            false => ("    ", 8),
+        };
+
+        writeln!(out, "    Code:")?;
+        if self.options.verbose {
+            writeln!(
+                out,
+                "      stack={}, locals={}, args_size={args_size}",
+                code.max_stack, code.max_locals
+            )?;
+        }
+
+        let opcodes = match parse_opcodes(&code.code) {
+            Ok(opcodes) => opcodes,
+            Err(err) => return writeln!(out, "{indent}  Invalid byte code: {err}"),
+        };
+        for (pc, opcode) in &opcodes {
+            write!(out, "{pc:>pc_width$}: ")?;
+            self.instruction(out, *pc, opcode, pc_width)?;
+        }
+
+        if !code.exception_table.is_empty() {
+            writeln!(out, "{indent}Exception table:")?;
+            writeln!(out, "{indent}   from    to  target type")?;
+            for entry in &code.exception_table {
+                let catch_type = match entry.catch_type {
+                    0 => "any".to_string(),
+                    catch_type => format!("Class {}", self.constant(catch_type)),
+                };
+                writeln!(
+                    out,
+                    "{indent}   {:>5} {:>5} {:>5}   {catch_type}",
+                    entry.start_pc, entry.end_pc, entry.handler_pc
+                )?;
+            }
+        }
+
+        if self.options.verbose {
+            for attribute in &code.attribute_info {
+                match &attribute.attribute {
+                    AttributeInfoData::LineNumberTable(line_numbers) => {
+                        writeln!(out, "      LineNumberTable:")?;
+                        for line in &line_numbers.line_number_table {
+                            writeln!(out, "        line {}: {}", line.line_number, line.start_pc)?;
+                        }
+                    }
+                    AttributeInfoData::LocalVariableTable(locals) => self.local_variables(
+                        out,
+                        "LocalVariableTable",
+                        &locals.local_variable_table,
+                    )?,
+                    AttributeInfoData::LocalVariableTypeTable(locals) => self.local_variables(
+                        out,
+                        "LocalVariableTypeTable",
+                        &locals.local_variable_type_table,
+                    )?,
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn local_variables(
+        &self,
+        out: &mut String,
+        name: &str,
+        locals: &[LocalVariableTableEntry],
+    ) -> Result {
+        writeln!(out, "      {name}:")?;
+        writeln!(out, "        Start  Length  Slot  Name   Signature")?;
+        for local in locals {
+            writeln!(
+                out,
+                "{:>13}{:>8}{:>6} {:>5}   {}",
+                local.start_pc,
+                local.length,
+                local.index,
+                self.utf8(local.name_index),
+                self.utf8(local.signature_descriptor_index)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes an opcode with its operands, after its pc
+    fn instruction(
+        &self,
+        out: &mut String,
+        pc: usize,
+        opcode: &OpCodes,
+        pc_width: usize,
+    ) -> Result {
+        let target = |offset: i64| (pc as i64 + offset).to_string();
+        let local = |name: &str, index: &u8| match index {
+            0..=3 => (format!("{name}_{index}"), String::new(), None),
+            _ => (name.to_string(), index.to_string(), None),
+        };
+
+        let (mnemonic, operands, comment_index) = match opcode {
+            OpCodes::getstatic(index)
+            | OpCodes::putstatic(index)
+            | OpCodes::getfield(index)
+            | OpCodes::putfield(index)
+            | OpCodes::invokevirtual(index)
+            | OpCodes::invokespecial(index)
+            | OpCodes::invokestatic(index)
+            | OpCodes::new(index)
+            | OpCodes::anewarray(index)
+            | OpCodes::checkcast(index)
+            | OpCodes::instanceof(index)
+            | OpCodes::ldc_w(index)
+            | OpCodes::ldc2_w(index) => {
+                let mnemonic = format!("{opcode:?}");
+                let mnemonic = mnemonic.split('(').next().unwrap_or_default();
+                (mnemonic.to_string(), format!("#{index}"), Some(*index))
+            }
+            OpCodes::ldc(index) => ("ldc".to_string(), format!("#{index}"), Some(*index as u16)),
+            OpCodes::invokeinterface(index, count) => (
+                "invokeinterface".to_string(),
+                format!("#{index},  {count}"),
+                Some(*index),
+            ),
+            OpCodes::invokedynamic(index) => (
+                "invokedynamic".to_string(),
+                format!("#{index},  0"),
+                Some(*index),
+            ),
+            OpCodes::multianewarray(index, dimensions) => (
+                "multianewarray".to_string(),
+                format!("#{index},  {dimensions}"),
+                Some(*index),
+            ),
+
+            OpCodes::bipush(value) => ("bipush".to_string(), (*value as i8).to_string(), None),
+            OpCodes::sipush(value) => ("sipush".to_string(), (*value as i16).to_string(), None),
+            OpCodes::newarray(atype) => {
+                let element = match atype {
+                    4 => "boolean",
+                    5 => "char",
+                    6 => "float",
+                    7 => "double",
+                    8 => "byte",
+                    9 => "short",
+                    10 => "int",
+                    11 => "long",
+                    _ => "<invalid>",
+                };
+                ("newarray".to_string(), format!(" {element}"), None)
+            }
+
+            OpCodes::iconst_(-1) => ("iconst_m1".to_string(), String::new(), None),
+            OpCodes::iconst_(value) => (format!("iconst_{value}"), String::new(), None),
+            OpCodes::lconst_(value) => (format!("lconst_{value}"), String::new(), None),
+            OpCodes::fconst_(value) => (format!("fconst_{value}"), String::new(), None),
+            OpCodes::dconst_(value) => (format!("dconst_{value}"), String::new(), None),
+
+            OpCodes::iload_(index) => local("iload", index),
+            OpCodes::lload_(index) => local("lload", index),
+            OpCodes::fload_(index) => local("fload", index),
+            OpCodes::dload_(index) => local("dload", index),
+            OpCodes::aload_(index) => local("aload", index),
+            OpCodes::istore_(index) => local("istore", index),
+            OpCodes::lstore_(index) => local("lstore", index),
+            OpCodes::fstore_(index) => local("fstore", index),
+            OpCodes::dstore_(index) => local("dstore", index),
+            OpCodes::astore_(index) => local("astore", index),
+            OpCodes::ret(index) => ("ret".to_string(), index.to_string(), None),
+            OpCodes::iinc(index, constant) => {
+                ("iinc".to_string(), format!("{index}, {constant}"), None)
+            }
+            OpCodes::wide {
+                opcode,
+                index,
+                constbyte,
+            } => {
+                let mnemonic = format!("{opcode:?}");
+                let mnemonic = mnemonic.split(['_', '(']).next().unwrap_or_default();
+                let operands = match constbyte {
+                    Some(constant) => format!("{index}, {constant}"),
+                    None => index.to_string(),
+                };
+                (format!("{mnemonic}_w"), operands, None)
+            }
+
+            OpCodes::if_icmp(condition, offset) => (
+                format!("if_icmp{}", condition_suffix(condition)),
+                target(*offset as i64),
+                None,
+            ),
+            OpCodes::if_acmp(condition, offset) => (
+                format!("if_acmp{}", condition_suffix(condition)),
+                target(*offset as i64),
+                None,
+            ),
+            OpCodes::if_cond(condition, offset) => (
+                format!("if{}", condition_suffix(condition)),
+                target(*offset as i64),
+                None,
+            ),
+            OpCodes::if_null(offset) => ("ifnull".to_string(), target(*offset as i64), None),
+            OpCodes::if_notnull(offset) => ("ifnonnull".to_string(), target(*offset as i64), None),
+            OpCodes::goto(offset) => ("goto".to_string(), target(*offset as i64), None),
+            OpCodes::jsr(offset) => ("jsr".to_string(), target(*offset as i64), None),
+            OpCodes::goto_w(offset) => ("goto_w".to_string(), target(*offset as i64), None),
+            OpCodes::jsr_w(offset) => ("jsr_w".to_string(), target(*offset as i64), None),
+
+            OpCodes::fcmp(1) => ("fcmpg".to_string(), String::new(), None),
+            OpCodes::fcmp(_) => ("fcmpl".to_string(), String::new(), None),
+            OpCodes::dcmp(1) => ("dcmpg".to_string(), String::new(), None),
+            OpCodes::dcmp(_) => ("dcmpl".to_string(), String::new(), None),
+            OpCodes::Return => ("return".to_string(), String::new(), None),
+
+            OpCodes::tableswitch(default, low, high, offsets) => {
+                writeln!(out, "{:<13} {{ // {low} to {high}", "tableswitch")?;
+                let cases = (*low..=*high).zip(offsets);
+                return self.switch_cases(out, pc, cases, *default, pc_width);
+            }
+            OpCodes::lookupswitch(default, pairs) => {
+                writeln!(out, "{:<13} {{ // {}", "lookupswitch", pairs.len())?;
+                let cases = pairs.iter().map(|(value, offset)| (*value, offset));
+                return self.switch_cases(out, pc, cases, *default, pc_width);
+            }
+
+            // Every other opcode is named after its variant and has no operands
+            _ => (format!("{opcode:?}"), String::new(), None),
+        };
+
+        let text = match operands.is_empty() {
+            true => mnemonic,
+            false => format!("{mnemonic:<13} {operands}"),
+        };
+        match comment_index {
+            Some(index) => writeln!(out, "{text:<34}// {}", self.operand_comment(index)),
+            None => writeln!(out, "{text}"),
+        }
+    }
+
+    fn switch_cases<'a>(
+        &self,
+        out: &mut String,
+        pc: usize,
+        cases: impl Iterator<Item = (i32, &'a i32)>,
+        default: i32,
+        pc_width: usize,
+    ) -> Result {
+        let width = pc_width + 14;
+        for (value, offset) in cases {
+            writeln!(out, "{value:>width$}: {}", pc as i64 + *offset as i64)?;
+        }
+        writeln!(out, "{:>width$}: {}", "default", pc as i64 + default as i64)?;
+        writeln!(out, "{:>1$}", "}", pc_width + 3)
+    }
+
+    fn class_attributes(&self, out: &mut String) -> Result {
+        let constant_pool = self.constant_pool;
+        for attribute in &self.java_class.attributes {
+            match &attribute.attribute {
+                AttributeInfoData::SourceFile(source_file) => writeln!(
+                    out,
+                    "SourceFile: \"{}\"",
+                    self.utf8(source_file.sourcefile_index)
+                )?,
+                AttributeInfoData::Signature(signature) => writeln!(
+                    out,
+                    "{:<40}// {}",
+                    format!("Signature: #{}", signature.signature_index),
+                    self.constant(signature.signature_index)
+                )?,
+                AttributeInfoData::NestHost(nest_host) => writeln!(
+                    out,
+                    "NestHost: class {}",
+                    self.constant(nest_host.host_class_index)
+                )?,
+                AttributeInfoData::NestMembers(nest_members) => {
+                    writeln!(out, "NestMembers:")?;
+                    for class in &nest_members.classes {
+                        writeln!(out, "  {}", self.constant(*class))?;
+                    }
+                }
+                AttributeInfoData::PermittedSubclasses(permitted_subclasses) => {
+                    writeln!(out, "PermittedSubclasses:")?;
+                    for class in &permitted_subclasses.classes {
+                        writeln!(out, "  {}", self.constant(*class))?;
+                    }
+                }
+                AttributeInfoData::BootstrapMethods(bootstrap_methods) => {
+                    writeln!(out, "BootstrapMethods:")?;
+                    for (index, method) in bootstrap_methods.bootstrap_methods.iter().enumerate() {
+                        writeln!(
+                            out,
+                            "  {index}: #{} {}",
+                            method.bootstrap_method_ref,
+                            self.constant(method.bootstrap_method_ref)
+                        )?;
+                        writeln!(out, "    Method arguments:")?;
+                        for argument in &method.bootstrap_arguments {
+                            let argument_text = match constant_pool.get_at(*argument) {
+                                Some(CpInfo::MethodType(method_type)) => {
+                                    self.utf8(method_type.descriptor_index).to_string()
+                                }
+                                _ => self.constant(*argument),
+                            };
+                            writeln!(out, "      #{argument} {argument_text}")?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The `(0x0021) ACC_PUBLIC, ACC_SUPER` of a `flags:` line
+fn flags(access_flags: u16, names: &[(u16, &str)]) -> String {
+    let names: Vec<&str> = names
+        .iter()
+        .filter(|(flag, _)| access_flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect();
+
+    match names.is_empty() {
+        true => format!("(0x{access_flags:04x})"),
+        false => format!("(0x{access_flags:04x}) {}", names.join(", ")),
+    }
+}
+
+/// `public `, `protected `, `private ` or nothing, the access flags are the same for fields
+/// and methods
+fn access_modifiers(access_flags: u16) -> String {
+    if access_flags & MethodAccessFlags::ACC_PUBLIC != 0 {
+        "public ".to_string()
+    } else if access_flags & MethodAccessFlags::ACC_PROTECTED != 0 {
+        "protected ".to_string()
+    } else if access_flags & MethodAccessFlags::ACC_PRIVATE != 0 {
+        "private ".to_string()
+    } else {
+        String::new()
+    }
+}
+
+fn condition_suffix(condition: &CmpConditions) -> &'static str {
+    match condition {
+        CmpConditions::Equal => "eq",
+        CmpConditions::NotEqual => "ne",
+        CmpConditions::LessThan => "lt",
+        CmpConditions::LessOrEqual => "le",
+        CmpConditions::GreaterOrEqual => "ge",
+        CmpConditions::GreaterThan => "gt",
+    }
+}
+
+fn tag_name(entry: &CpInfo) -> &'static str {
+    let tag = match entry {
+        CpInfo::EmptyCpEntry => "",
+        CpInfo::Class(class) => class.tag,
+        CpInfo::Refs(refs) => refs.tag,
+        CpInfo::NameAndType(name_and_type) => name_and_type.tag,
+        CpInfo::Utf8(utf8) => utf8.tag,
+        CpInfo::String(string) => string.tag,
+        CpInfo::Integer(int) => int.tag,
+        CpInfo::Float(float) => float.tag,
+        CpInfo::Long(long) => long.tag,
+        CpInfo::Double(double) => double.tag,
+        CpInfo::InvokeDynamic(dynamic) => dynamic.tag,
+        CpInfo::MethodHandle(handle) => handle.tag,
+        CpInfo::MethodType(method_type) => method_type.tag,
+        CpInfo::Module(module) => module.tag,
+        CpInfo::Package(package) => package.tag,
+    };
+    tag.strip_prefix("CONSTANT_").unwrap_or(tag)
+}
+
+fn reference_kind(kind: u8) -> &'static str {
+    match kind {
+        1 => "REF_getField",
+        2 => "REF_getStatic",
+        3 => "REF_putField",
+        4 => "REF_putStatic",
+        5 => "REF_invokeVirtual",
+        6 => "REF_invokeStatic",
+        7 => "REF_invokeSpecial",
+        8 => "REF_newInvokeSpecial",
+        9 => "REF_invokeInterface",
+        _ => "REF_???",
+    }
+}
+
+/// `java/lang/String` as `java.lang.String`
+fn java_name(internal_name: &str) -> String {
+    internal_name.replace('/', ".")
+}
+
+/// A field descriptor like `[Ljava/lang/String;` as the java type `java.lang.String[]`
+fn java_type(descriptor: &str) -> String {
+    let element = descriptor.trim_start_matches('[');
+    let dimensions = descriptor.len() - element.len();
+
+    let element = match element {
+        "B" => "byte".to_string(),
+        "C" => "char".to_string(),
+        "D" => "double".to_string(),
+        "F" => "float".to_string(),
+        "I" => "int".to_string(),
+        "J" => "long".to_string(),
+        "S" => "short".to_string(),
+        "Z" => "boolean".to_string(),
+        "V" => "void".to_string(),
+        class => java_name(
+            class
+                .strip_prefix('L')
+                .and_then(|class| class.strip_suffix(';'))
+                .unwrap_or(class),
+        ),
+    };
+    element + &"[]".repeat(dimensions)
+}
+
+/// Formats a float or double like java's `toString`, which switches to scientific notation
+/// outside of 10^-3 to 10^7
+fn java_decimal<T: Display + LowerExp + Copy + Into<f64>>(value: T) -> String {
+    let number: f64 = value.into();
+    if number.is_nan() {
+        return "NaN".to_string();
+    }
+    if number.is_infinite() {
+        return if number > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        }
+        .to_string();
+    }
+
+    if number == 0.0 || (1e-3..1e7).contains(&number.abs()) {
+        let plain = value.to_string();
+        match plain.contains('.') {
+            true => plain,
+            false => plain + ".0",
+        }
+    } else {
+        let scientific = format!("{value:e}");
+        let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+        match mantissa.contains('.') {
+            true => format!("{mantissa}E{exponent}"),
+            false => format!("{mantissa}.0E{exponent}"),
+        }
+    }
+}
+
+/// Escapes the control characters of a string constant, like `javap` does
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for character in value.chars() {
+        match character {
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\u{8}' => escaped.push_str("\\b"),
+            '\u{c}' => escaped.push_str("\\f"),
+            character if character.is_control() => {
+                escaped.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{disassemble, flags, java_decimal, java_type, JavapOptions, CLASS_FLAGS};
+    use crate::jvm::test_class;
+
+    #[test]
+    fn formats_like_javap() {
+        assert_eq!(java_type("[[Ljava/lang/String;"), "java.lang.String[][]");
+        assert_eq!(java_type("Z"), "boolean");
+        assert_eq!(java_decimal(1.0f64), "1.0");
+        assert_eq!(java_decimal(2e10f64), "2.0E10");
+        assert_eq!(java_decimal(1.5e-5f32), "1.5E-5");
+        assert_eq!(java_decimal(f32::NAN), "NaN");
+        assert_eq!(
+            flags(0x0421, CLASS_FLAGS),
+            "(0x0421) ACC_PUBLIC, ACC_SUPER, ACC_ABSTRACT"
+        );
+        assert_eq!(flags(0, CLASS_FLAGS), "(0x0000)");
+    }
+
+    #[test]
+    fn disassembles_like_javap() {
+        let options = JavapOptions {
+            code: true,
+            verbose: false,
+            private: true,
+            multi_release: None,
+        };
+        // What the JDK's `javap -c -p` prints for the class
+        assert_eq!(
+            disassemble(&test_class("Verification$NoFrame"), "", options),
+            r#"Compiled from "Verification.java"
+class Verification$NoFrame {
+  Verification$NoFrame();
+    Code:
+       0: aload_0
+       1: invokespecial #1                  // Method java/lang/Object."<init>":()V
+       4: return
+
+  static int run();
+    Code:
+       0: iconst_0
+       1: istore_0
+       2: iload_0
+       3: ifne          8
+       6: iconst_2
+       7: ireturn
+       8: iconst_1
+       9: ireturn
+}
+"#
+        );
+
+        // Members with a generic signature are declared with their type arguments
+        let constants = disassemble(&test_class("Constants"), "", options);
+        assert!(constants.lines().any(|line| line
+            == "  static int answer(java.lang.invoke.MethodHandles$Lookup, java.lang.String, \
+                java.lang.Class<?>, int);"));
+
+        let verbose = disassemble(
+            &test_class("Verification$Good"),
+            "Verification$Good.class",
+            JavapOptions {
+                verbose: true,
+                ..options
+            },
+        );
+        for line in [
+            "Classfile Verification$Good.class",
+            "  major version: 52",
+            "  flags: (0x0020) ACC_SUPER",
+            "   #1 = Methodref          #2.#3          // java/lang/Object.\"<init>\":()V",
+            "    flags: (0x0008) ACC_STATIC",
+            "      stack=1, locals=0, args_size=0",
+            "        line 4: 0",
+            "SourceFile: \"Verification.java\"",
+        ] {
+            assert!(verbose.lines().any(|printed| printed == line), "{line}");
+        }
+    }
+}
@@ -0,0 +1,1737 @@
+//! Assembles classes from opcodes, for the tests of the vm.
+//!
+//! Branches jump to labels that are resolved once the code is laid out. The frames of the
+//! `StackMapTable` are inferred by following every path through the code, so assembled classes
+//! pass verification like the ones javac compiles.
+
+use std::collections::{BTreeSet, HashMap};
+
+use jvm_parser::classfile::{
+    attributes::{
+        AttributeInfo, AttributeInfoData, CodeAttribute, ExceptionTable, StackMapFrame,
+        StackMapTableAttribute, VerificationTypeInfo,
+    },
+    classfile::{ClassAccessFlags, FieldInfo, MethodInfo},
+    constant_pool::{ConstantPool, ConstantPoolBuilder, CpInfo},
+    descriptor::{parse_field_descriptor, parse_method_descriptor},
+    JavaClass,
+};
+
+use super::{
+    opcodes::{CmpConditions, OpCodes},
+    verifier::{compact_locals, is_local_store, Frame, VerificationType as VT},
+};
+
+/// A position in the code that branches and exception table entries refer to before it is known
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+struct Instruction {
+    opcode: OpCodes,
+    /// The labels the opcode's offset operands jump to, in the order the operands appear
+    targets: Vec<Label>,
+}
+
+struct TryCatch {
+    start: Label,
+    end: Label,
+    handler: Label,
+    catch_type: u16,
+}
+
+/// Builds the [`CodeAttribute`] of a method from opcodes. Branches jump to labels, their offsets
+/// are filled in once the code is laid out, and `max_stack`, `max_locals` and the
+/// `StackMapTable` are computed
+pub struct CodeBuilder {
+    descriptor: String,
+    is_static: bool,
+    instructions: Vec<Instruction>,
+    /// The index in `instructions` of the opcode each label is placed before
+    labels: Vec<Option<usize>>,
+    try_catches: Vec<TryCatch>,
+}
+
+impl CodeBuilder {
+    /// A builder for the code of a method with the given descriptor, which decides together with
+    /// `is_static` how many locals the arguments take up
+    pub fn new(descriptor: &str, is_static: bool) -> Self {
+        Self {
+            descriptor: descriptor.to_string(),
+            is_static,
+            instructions: vec![],
+            labels: vec![],
+            try_catches: vec![],
+        }
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Places the label before the next opcode, or at the end of the code if none follows
+    pub fn bind(&mut self, label: Label) -> &mut Self {
+        assert!(
+            self.labels[label.0]
+                .replace(self.instructions.len())
+                .is_none(),
+            "A label can only be bound once"
+        );
+        self
+    }
+
+    /// Adds an opcode as is, branches added this way keep their offset
+    pub fn op(&mut self, opcode: OpCodes) -> &mut Self {
+        self.instructions.push(Instruction {
+            opcode,
+            targets: vec![],
+        });
+        self
+    }
+
+    /// Adds a branch like `goto` or `if_icmp`, its offset is replaced by the one to `target`
+    pub fn branch(&mut self, opcode: OpCodes, target: Label) -> &mut Self {
+        self.instructions.push(Instruction {
+            opcode,
+            targets: vec![target],
+        });
+        self
+    }
+
+    /// Jumps to `targets[value - low]`, or to `default` if the value is out of that range
+    pub fn tableswitch(&mut self, low: i32, default: Label, targets: &[Label]) -> &mut Self {
+        let high = low + targets.len() as i32 - 1;
+        self.instructions.push(Instruction {
+            opcode: OpCodes::tableswitch(0, low, high, vec![0; targets.len()]),
+            targets: [&[default][..], targets].concat(),
+        });
+        self
+    }
+
+    /// Jumps to the label paired with the value, or to `default` if there is none
+    pub fn lookupswitch(&mut self, default: Label, pairs: &[(i32, Label)]) -> &mut Self {
+        let mut pairs = pairs.to_vec();
+        pairs.sort_by_key(|(value, _)| *value);
+
+        self.instructions.push(Instruction {
+            opcode: OpCodes::lookupswitch(0, pairs.iter().map(|(value, _)| (*value, 0)).collect()),
+            targets: [default]
+                .into_iter()
+                .chain(pairs.iter().map(|(_, label)| *label))
+                .collect(),
+        });
+        self
+    }
+
+    /// Adds an exception table entry, exceptions of the `catch_type` class thrown between `start`
+    /// and `end` jump to `handler`. A `catch_type` of 0 catches all of them, like `finally`
+    pub fn try_catch(
+        &mut self,
+        start: Label,
+        end: Label,
+        handler: Label,
+        catch_type: u16,
+    ) -> &mut Self {
+        self.try_catches.push(TryCatch {
+            start,
+            end,
+            handler,
+            catch_type,
+        });
+        self
+    }
+
+    /// Lays out the code of the method `method_name` of `class_name`, which give the type of
+    /// `this`. The constant pool is read for the types of member accesses and gets the classes
+    /// the stack map refers to
+    pub fn build(
+        mut self,
+        class_name: &str,
+        method_name: &str,
+        constant_pool: &mut ConstantPoolBuilder<'static>,
+    ) -> Result<CodeAttribute<'static>, String> {
+        let positions = self
+            .labels
+            .iter()
+            .enumerate()
+            .map(|(label, position)| position.ok_or(format!("Label {label} is never bound")))
+            .collect::<Result<Vec<usize>, String>>()?;
+
+        // The offsets don't change the size of an opcode, so the first pass finds every pc
+        let mut pcs = vec![];
+        let mut code = vec![];
+        for instruction in &self.instructions {
+            pcs.push(code.len());
+            instruction.opcode.write(&mut code)?;
+        }
+        pcs.push(code.len());
+
+        let pc_of = |label: &Label| pcs[positions[label.0]];
+        for (index, instruction) in self.instructions.iter_mut().enumerate() {
+            if instruction.targets.is_empty() {
+                continue;
+            }
+
+            let offsets: Vec<i32> = instruction
+                .targets
+                .iter()
+                .map(|target| pc_of(target) as i32 - pcs[index] as i32)
+                .collect();
+            set_offsets(&mut instruction.opcode, &offsets)?;
+        }
+
+        code.clear();
+        for instruction in &self.instructions {
+            instruction.opcode.write(&mut code)?;
+        }
+        if code.len() > u16::MAX as usize {
+            return Err(format!(
+                "The code is {} bytes long, 65535 at most",
+                code.len()
+            ));
+        }
+
+        let exception_table = self
+            .try_catches
+            .iter()
+            .map(|try_catch| {
+                let (start_pc, end_pc) = (pc_of(&try_catch.start), pc_of(&try_catch.end));
+                if start_pc >= end_pc {
+                    return Err(format!(
+                        "The try block from pc {start_pc} to {end_pc} is empty"
+                    ));
+                }
+
+                Ok(ExceptionTable {
+                    start_pc: start_pc as u16,
+                    end_pc: end_pc as u16,
+                    handler_pc: pc_of(&try_catch.handler) as u16,
+                    catch_type: try_catch.catch_type,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let opcodes: Vec<(usize, &OpCodes)> = pcs
+            .iter()
+            .zip(&self.instructions)
+            .map(|(pc, instruction)| (*pc, &instruction.opcode))
+            .collect();
+        let max_locals = max_locals(&opcodes, &self.descriptor, self.is_static)?;
+        let initial = self.initial_frame(class_name, method_name, max_locals as usize)?;
+
+        let inference = FrameInference {
+            opcodes: &opcodes,
+            pc_index: opcodes
+                .iter()
+                .enumerate()
+                .map(|(index, (pc, _))| (*pc, index))
+                .collect(),
+            exception_table: &exception_table,
+            constant_pool: constant_pool.constant_pool(),
+            class_name,
+        };
+        let frames = inference.frames(initial.clone())?;
+        let max_stack = frames
+            .iter()
+            .map(Frame::stack_size)
+            .max()
+            .unwrap_or_default();
+
+        // Frames are needed where control flow merges, at branch targets and handlers
+        let frame_pcs: BTreeSet<usize> = opcodes
+            .iter()
+            .flat_map(|(pc, opcode)| jump_targets(*pc, opcode))
+            .map(|pc| pc as usize)
+            .chain(
+                exception_table
+                    .iter()
+                    .map(|entry| entry.handler_pc as usize),
+            )
+            .collect();
+        let frames: Vec<(usize, &Frame)> = frame_pcs
+            .into_iter()
+            .map(|pc| (pc, &frames[inference.pc_index[&pc]]))
+            .collect();
+
+        let mut attribute_info = vec![];
+        if !frames.is_empty() {
+            let entries = stack_map(&initial, &frames, constant_pool);
+            attribute_info.push(AttributeInfo {
+                attribute_name_index: constant_pool.utf8("StackMapTable"),
+                attribute: AttributeInfoData::StackMapTable(StackMapTableAttribute { entries }),
+            });
+        }
+
+        Ok(CodeAttribute {
+            max_stack: u16::try_from(max_stack)
+                .map_err(|_| format!("The stack gets {max_stack} slots deep"))?,
+            max_locals,
+            code: code.into(),
+            exception_table,
+            attribute_info,
+        })
+    }
+
+    /// The arguments, with `this` first unless the method is static. `this` is uninitialized in
+    /// a constructor until the constructor of the super class is called
+    fn initial_frame(
+        &self,
+        class_name: &str,
+        method_name: &str,
+        max_locals: usize,
+    ) -> Result<Frame, String> {
+        let mut locals = vec![];
+        if !self.is_static {
+            locals.push(
+                match method_name == "<init>" && class_name != "java/lang/Object" {
+                    true => VT::UninitializedThis,
+                    false => VT::Reference(class_name.to_string()),
+                },
+            );
+        }
+
+        let descriptor =
+            parse_method_descriptor(&self.descriptor).map_err(|err| err.to_string())?;
+        for parameter in &descriptor.parameters {
+            locals.push(VT::from_field_descriptor(parameter));
+            if parameter.slots() == 2 {
+                locals.push(VT::Top);
+            }
+        }
+
+        locals.resize(max_locals, VT::Top);
+        Ok(Frame {
+            locals,
+            stack: vec![],
+        })
+    }
+}
+
+struct Method {
+    info: MethodInfo<'static>,
+    /// `None` for abstract and native methods
+    code: Option<CodeBuilder>,
+}
+
+/// Builds a whole [`JavaClass`], its methods assembled by [`CodeBuilder`]s. Opcodes refer to the
+/// constant pool of the class, so their entries are added through [`ClassBuilder::pool`]
+pub struct ClassBuilder {
+    pool: ConstantPoolBuilder<'static>,
+    name: String,
+    access_flags: u16,
+    this_class: u16,
+    super_class: u16,
+    interfaces: Vec<u16>,
+    fields: Vec<FieldInfo<'static>>,
+    methods: Vec<Method>,
+}
+
+impl ClassBuilder {
+    /// A public class, `super_name` is `None` only for `java/lang/Object`
+    pub fn new(name: &str, super_name: Option<&str>) -> Self {
+        let mut pool = ConstantPoolBuilder::new();
+        let this_class = pool.class(name);
+        let super_class = super_name.map(|super_name| pool.class(super_name));
+
+        Self {
+            pool,
+            name: name.to_string(),
+            access_flags: ClassAccessFlags::ACC_PUBLIC | ClassAccessFlags::ACC_SUPER,
+            this_class,
+            super_class: super_class.unwrap_or_default(),
+            interfaces: vec![],
+            fields: vec![],
+            methods: vec![],
+        }
+    }
+
+    pub fn pool(&mut self) -> &mut ConstantPoolBuilder<'static> {
+        &mut self.pool
+    }
+
+    pub fn access_flags(&mut self, access_flags: u16) -> &mut Self {
+        self.access_flags = access_flags;
+        self
+    }
+
+    pub fn interface(&mut self, name: &str) -> &mut Self {
+        let interface = self.pool.class(name);
+        self.interfaces.push(interface);
+        self
+    }
+
+    pub fn field(&mut self, access_flags: u16, name: &str, descriptor: &str) -> &mut Self {
+        self.fields.push(FieldInfo {
+            access_flags,
+            name_index: self.pool.utf8(name),
+            descriptor_index: self.pool.utf8(descriptor),
+            attributes: vec![],
+        });
+        self
+    }
+
+    pub fn method(
+        &mut self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+        code: CodeBuilder,
+    ) -> &mut Self {
+        self.add_method(access_flags, name, descriptor, Some(code))
+    }
+
+    /// A method without code, `access_flags` should make it abstract or native
+    pub fn abstract_method(
+        &mut self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> &mut Self {
+        self.add_method(access_flags, name, descriptor, None)
+    }
+
+    fn add_method(
+        &mut self,
+        access_flags: u16,
+        name: &str,
+        descriptor: &str,
+        code: Option<CodeBuilder>,
+    ) -> &mut Self {
+        let info = MethodInfo {
+            access_flags,
+            name_index: self.pool.utf8(name),
+            descriptor_index: self.pool.utf8(descriptor),
+            attributes: vec![],
+        };
+        self.methods.push(Method { info, code });
+        self
+    }
+
+    /// A class file of version 52 (Java 8), the first one the type checker verifies without
+    /// falling back to type inference
+    pub fn build(mut self) -> Result<JavaClass<'static>, String> {
+        let code_name = self.pool.utf8("Code");
+
+        let mut methods = vec![];
+        for Method { mut info, code } in self.methods {
+            if let Some(code) = code {
+                let name = self
+                    .pool
+                    .constant_pool()
+                    .get_utf8_at(info.name_index)
+                    .map(|name| name.data.clone())
+                    .unwrap_or_default();
+                let code = AttributeInfo {
+                    attribute_name_index: code_name,
+                    attribute: AttributeInfoData::Code(code.build(
+                        &self.name,
+                        &name,
+                        &mut self.pool,
+                    )?),
+                };
+                info.attributes.insert(0, code);
+            }
+            methods.push(info);
+        }
+
+        Ok(JavaClass {
+            magic: 0xCAFEBABE,
+            minor_version: 0,
+            major_version: 52,
+            constant_pool: self.pool.build(),
+            access_flags: self.access_flags,
+            this_class: self.this_class,
+            super_class: self.super_class,
+            interfaces: self.interfaces,
+            fields: self.fields,
+            methods,
+            attributes: vec![],
+        })
+    }
+}
+
+/// Follows every path through the code to find the types of the locals and the stack before each
+/// opcode. Where paths meet their types are merged, which makes two different classes
+/// `java/lang/Object` since the classes aren't loaded to find a closer common super class
+struct FrameInference<'a> {
+    opcodes: &'a [(usize, &'a OpCodes)],
+    pc_index: HashMap<usize, usize>,
+    exception_table: &'a [ExceptionTable],
+    constant_pool: &'a ConstantPool<'static>,
+    class_name: &'a str,
+}
+
+impl FrameInference<'_> {
+    /// The frame before every opcode, starting from the arguments of the method
+    fn frames(&self, initial: Frame) -> Result<Vec<Frame>, String> {
+        let mut frames: Vec<Option<Frame>> = vec![None; self.opcodes.len()];
+        let mut pending = vec![];
+        self.merge(&mut frames, &mut pending, 0, initial)?;
+
+        while let Some(index) = pending.pop() {
+            let (pc, opcode) = self.opcodes[index];
+            let before = frames[index].clone().expect("pending opcodes have a frame");
+            let mut after = before.clone();
+            self.execute(pc, opcode, &mut after)
+                .map_err(|reason| format!("{opcode:?} at pc {pc} {reason}"))?;
+
+            // A handler is entered with the locals before the opcode, or after it for a store
+            for entry in self.exception_table {
+                if pc < entry.start_pc as usize || pc >= entry.end_pc as usize {
+                    continue;
+                }
+
+                let exception = match entry.catch_type {
+                    0 => VT::Reference("java/lang/Throwable".to_string()),
+                    index => VT::Reference(self.class_at(index)?),
+                };
+                let handler = self.index_of(entry.handler_pc as i64)?;
+                let mut locals = vec![&before.locals];
+                if is_local_store(opcode) {
+                    locals.push(&after.locals);
+                }
+                for locals in locals {
+                    let frame = Frame {
+                        locals: locals.clone(),
+                        stack: vec![exception.clone()],
+                    };
+                    self.merge(&mut frames, &mut pending, handler, frame)?;
+                }
+            }
+
+            for target in jump_targets(pc, opcode) {
+                let target = self.index_of(target)?;
+                self.merge(&mut frames, &mut pending, target, after.clone())?;
+            }
+            if falls_through(opcode) {
+                if index + 1 == self.opcodes.len() {
+                    return Err("The code doesn't end in a return, throw or jump".to_string());
+                }
+                self.merge(&mut frames, &mut pending, index + 1, after)?;
+            }
+        }
+
+        frames
+            .into_iter()
+            .zip(self.opcodes)
+            .map(|(frame, (pc, _))| frame.ok_or(format!("The opcode at pc {pc} is unreachable")))
+            .collect()
+    }
+
+    /// Merges a frame into the one known before an opcode, which is visited again if it changes
+    fn merge(
+        &self,
+        frames: &mut [Option<Frame>],
+        pending: &mut Vec<usize>,
+        index: usize,
+        frame: Frame,
+    ) -> Result<(), String> {
+        let Some(known) = &mut frames[index] else {
+            frames[index] = Some(frame);
+            pending.push(index);
+            return Ok(());
+        };
+
+        let pc = self.opcodes[index].0;
+        if known.stack.len() != frame.stack.len() {
+            return Err(format!(
+                "The stack holds {} or {} values at pc {pc} depending on the path",
+                known.stack_size(),
+                frame.stack_size()
+            ));
+        }
+
+        let mut changed = false;
+        for (known_local, local) in known.locals.iter_mut().zip(&frame.locals) {
+            let merged = merge_types(known_local, local);
+            changed |= merged != *known_local;
+            *known_local = merged;
+        }
+        for (known_value, value) in known.stack.iter_mut().zip(&frame.stack) {
+            let merged = merge_types(known_value, value);
+            if merged == VT::Top {
+                return Err(format!(
+                    "The stack holds {known_value} or {value} at pc {pc} depending on the path"
+                ));
+            }
+            changed |= merged != *known_value;
+            *known_value = merged;
+        }
+
+        if changed {
+            pending.push(index);
+        }
+        Ok(())
+    }
+
+    fn index_of(&self, pc: i64) -> Result<usize, String> {
+        usize::try_from(pc)
+            .ok()
+            .and_then(|pc| self.pc_index.get(&pc).copied())
+            .ok_or(format!("The jump to pc {pc} doesn't land on an opcode"))
+    }
+
+    /// Changes the frame the way the opcode at `pc` does
+    fn execute(&self, pc: usize, opcode: &OpCodes, frame: &mut Frame) -> Result<(), String> {
+        match opcode {
+            OpCodes::nop
+            | OpCodes::iinc(..)
+            | OpCodes::goto(_)
+            | OpCodes::goto_w(_)
+            | OpCodes::Return => {}
+            OpCodes::jsr(_) | OpCodes::jsr_w(_) | OpCodes::ret(_) => {
+                return Err("is a subroutine, which stack map frames can't describe".to_string())
+            }
+
+            OpCodes::aconst_null => frame.stack.push(VT::Null),
+            OpCodes::iconst_(_) | OpCodes::bipush(_) | OpCodes::sipush(_) => {
+                frame.stack.push(VT::Integer)
+            }
+            OpCodes::fconst_(_) => frame.stack.push(VT::Float),
+            OpCodes::lconst_(_) => frame.stack.push(VT::Long),
+            OpCodes::dconst_(_) => frame.stack.push(VT::Double),
+            OpCodes::ldc(index) => frame.stack.push(self.constant(*index as u16)?),
+            OpCodes::ldc_w(index) | OpCodes::ldc2_w(index) => {
+                frame.stack.push(self.constant(*index)?)
+            }
+
+            OpCodes::iload_(index)
+            | OpCodes::lload_(index)
+            | OpCodes::fload_(index)
+            | OpCodes::dload_(index)
+            | OpCodes::aload_(index) => load(frame, *index as usize)?,
+            OpCodes::istore_(index)
+            | OpCodes::lstore_(index)
+            | OpCodes::fstore_(index)
+            | OpCodes::dstore_(index)
+            | OpCodes::astore_(index) => store(frame, *index as usize)?,
+            OpCodes::wide { opcode, index, .. } => match **opcode {
+                OpCodes::iinc(..) => {}
+                OpCodes::iload_(_)
+                | OpCodes::lload_(_)
+                | OpCodes::fload_(_)
+                | OpCodes::dload_(_)
+                | OpCodes::aload_(_) => load(frame, *index as usize)?,
+                OpCodes::istore_(_)
+                | OpCodes::lstore_(_)
+                | OpCodes::fstore_(_)
+                | OpCodes::dstore_(_)
+                | OpCodes::astore_(_) => store(frame, *index as usize)?,
+                _ => return self.execute(pc, opcode, frame),
+            },
+
+            OpCodes::iaload | OpCodes::baload | OpCodes::caload | OpCodes::saload => {
+                operate(frame, 2, Some(VT::Integer))?
+            }
+            OpCodes::laload => operate(frame, 2, Some(VT::Long))?,
+            OpCodes::faload => operate(frame, 2, Some(VT::Float))?,
+            OpCodes::daload => operate(frame, 2, Some(VT::Double))?,
+            OpCodes::aaload => {
+                let component = match pop(frame, 2)?.remove(0) {
+                    VT::Null => VT::Null,
+                    array => array
+                        .component_type()
+                        .filter(|component| !component.is_category_2())
+                        .ok_or(format!("loads from {array}, which isn't an array"))?,
+                };
+                frame.stack.push(component);
+            }
+            OpCodes::iastore
+            | OpCodes::lastore
+            | OpCodes::fastore
+            | OpCodes::dastore
+            | OpCodes::aastore
+            | OpCodes::bastore
+            | OpCodes::castore
+            | OpCodes::sastore => operate(frame, 3, None)?,
+
+            OpCodes::pop => drop(pop_words(frame, 1)?),
+            OpCodes::pop2 => drop(pop_words(frame, 2)?),
+            OpCodes::dup => duplicate(frame, 1, 0)?,
+            OpCodes::dup_x1 => duplicate(frame, 1, 1)?,
+            OpCodes::dup_x2 => duplicate(frame, 1, 2)?,
+            OpCodes::dup2 => duplicate(frame, 2, 0)?,
+            OpCodes::dup2_x1 => duplicate(frame, 2, 1)?,
+            OpCodes::dup2_x2 => duplicate(frame, 2, 2)?,
+            OpCodes::swap => {
+                let top = pop_words(frame, 1)?;
+                let below = pop_words(frame, 1)?;
+                frame.stack.extend(top);
+                frame.stack.extend(below);
+            }
+
+            OpCodes::iadd
+            | OpCodes::isub
+            | OpCodes::imul
+            | OpCodes::idiv
+            | OpCodes::irem
+            | OpCodes::iand
+            | OpCodes::ior
+            | OpCodes::ixor
+            | OpCodes::ishl
+            | OpCodes::ishr
+            | OpCodes::iushr
+            | OpCodes::lcmp
+            | OpCodes::fcmp(_)
+            | OpCodes::dcmp(_) => operate(frame, 2, Some(VT::Integer))?,
+            OpCodes::ladd
+            | OpCodes::lsub
+            | OpCodes::lmul
+            | OpCodes::ldiv
+            | OpCodes::lrem
+            | OpCodes::land
+            | OpCodes::lor
+            | OpCodes::lxor
+            | OpCodes::lshl
+            | OpCodes::lshr
+            | OpCodes::lushr => operate(frame, 2, Some(VT::Long))?,
+            OpCodes::fadd | OpCodes::fsub | OpCodes::fmul | OpCodes::fdiv | OpCodes::frem => {
+                operate(frame, 2, Some(VT::Float))?
+            }
+            OpCodes::dadd | OpCodes::dsub | OpCodes::dmul | OpCodes::ddiv | OpCodes::drem => {
+                operate(frame, 2, Some(VT::Double))?
+            }
+
+            OpCodes::ineg
+            | OpCodes::i2b
+            | OpCodes::i2c
+            | OpCodes::i2s
+            | OpCodes::l2i
+            | OpCodes::f2i
+            | OpCodes::d2i
+            | OpCodes::arraylength
+            | OpCodes::instanceof(_) => operate(frame, 1, Some(VT::Integer))?,
+            OpCodes::lneg | OpCodes::i2l | OpCodes::f2l | OpCodes::d2l => {
+                operate(frame, 1, Some(VT::Long))?
+            }
+            OpCodes::fneg | OpCodes::i2f | OpCodes::l2f | OpCodes::d2f => {
+                operate(frame, 1, Some(VT::Float))?
+            }
+            OpCodes::dneg | OpCodes::i2d | OpCodes::l2d | OpCodes::f2d => {
+                operate(frame, 1, Some(VT::Double))?
+            }
+
+            OpCodes::if_icmp(..) | OpCodes::if_acmp(..) => operate(frame, 2, None)?,
+            OpCodes::if_cond(..)
+            | OpCodes::if_null(_)
+            | OpCodes::if_notnull(_)
+            | OpCodes::tableswitch(..)
+            | OpCodes::lookupswitch(..)
+            | OpCodes::monitorenter
+            | OpCodes::monitorexit
+            | OpCodes::athrow
+            | OpCodes::ireturn
+            | OpCodes::lreturn
+            | OpCodes::freturn
+            | OpCodes::dreturn
+            | OpCodes::areturn => operate(frame, 1, None)?,
+
+            OpCodes::getstatic(index) => {
+                let (_, descriptor) = self.member_at(*index)?;
+                operate(frame, 0, Some(field_type(descriptor)?))?
+            }
+            OpCodes::putstatic(_) => operate(frame, 1, None)?,
+            OpCodes::getfield(index) => {
+                let (_, descriptor) = self.member_at(*index)?;
+                operate(frame, 1, Some(field_type(descriptor)?))?
+            }
+            OpCodes::putfield(_) => operate(frame, 2, None)?,
+
+            OpCodes::invokevirtual(index) | OpCodes::invokeinterface(index, _) => {
+                self.invoke(frame, *index, 1)?
+            }
+            OpCodes::invokestatic(index) | OpCodes::invokedynamic(index) => {
+                self.invoke(frame, *index, 0)?
+            }
+            OpCodes::invokespecial(index) => {
+                let (name, descriptor) = self.member_at(*index)?;
+                let arguments = parse_method_descriptor(descriptor)
+                    .map_or(0, |descriptor| descriptor.parameters.len());
+                let receiver = frame
+                    .stack
+                    .len()
+                    .checked_sub(arguments + 1)
+                    .map(|receiver| frame.stack[receiver].clone());
+                self.invoke(frame, *index, 1)?;
+
+                // A constructor initializes every copy of the object it was called on
+                let initialized = match (name, receiver) {
+                    ("<init>", Some(VT::UninitializedThis)) => {
+                        Some((VT::UninitializedThis, self.class_name.to_string()))
+                    }
+                    ("<init>", Some(VT::Uninitialized(offset))) => {
+                        Some((VT::Uninitialized(offset), self.new_class_at(offset)?))
+                    }
+                    _ => None,
+                };
+                if let Some((uninitialized, class_name)) = initialized {
+                    for value in frame.locals.iter_mut().chain(&mut frame.stack) {
+                        if *value == uninitialized {
+                            *value = VT::Reference(class_name.clone());
+                        }
+                    }
+                }
+            }
+
+            OpCodes::new(_) => frame.stack.push(VT::Uninitialized(pc as u16)),
+            OpCodes::newarray(atype) => {
+                let component = match atype {
+                    4 => "Z",
+                    5 => "C",
+                    6 => "F",
+                    7 => "D",
+                    8 => "B",
+                    9 => "S",
+                    10 => "I",
+                    11 => "J",
+                    _ => return Err(format!("creates an array of the unknown type {atype}")),
+                };
+                operate(frame, 1, Some(VT::Reference(format!("[{component}"))))?
+            }
+            OpCodes::anewarray(index) => {
+                let component = self.class_at(*index)?;
+                let array = match component.starts_with('[') {
+                    true => format!("[{component}"),
+                    false => format!("[L{component};"),
+                };
+                operate(frame, 1, Some(VT::Reference(array)))?
+            }
+            OpCodes::multianewarray(index, dimensions) => {
+                let array = VT::Reference(self.class_at(*index)?);
+                operate(frame, *dimensions as usize, Some(array))?
+            }
+            OpCodes::checkcast(index) => {
+                operate(frame, 1, Some(VT::Reference(self.class_at(*index)?)))?
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Pops the arguments and the receiver if there is one, then pushes the returned value
+    fn invoke(&self, frame: &mut Frame, index: u16, receiver: usize) -> Result<(), String> {
+        let (_, descriptor) = self.member_at(index)?;
+        let descriptor = parse_method_descriptor(descriptor).map_err(|err| err.to_string())?;
+        let returned = descriptor
+            .return_type
+            .as_ref()
+            .map(VT::from_field_descriptor);
+        operate(frame, descriptor.parameters.len() + receiver, returned)
+    }
+
+    fn class_at(&self, index: u16) -> Result<String, String> {
+        self.constant_pool
+            .get_class_name_at(index)
+            .map(str::to_string)
+            .ok_or(format!("No class at constant pool index {index}"))
+    }
+
+    /// The class of the `new` opcode at `pc`
+    fn new_class_at(&self, pc: u16) -> Result<String, String> {
+        match self
+            .pc_index
+            .get(&(pc as usize))
+            .map(|index| self.opcodes[*index].1)
+        {
+            Some(OpCodes::new(index)) => self.class_at(*index),
+            _ => Err(format!("There is no new opcode at pc {pc}")),
+        }
+    }
+
+    /// The name and descriptor of the field, method or call site at a constant pool index
+    fn member_at(&self, index: u16) -> Result<(&str, &str), String> {
+        let name_and_type_index = match self.constant_pool.get_at(index) {
+            Some(CpInfo::Refs(refs)) => Some(refs.name_and_type_index),
+            Some(CpInfo::InvokeDynamic(dynamic)) => Some(dynamic.name_and_type_index),
+            _ => None,
+        };
+
+        let utf8 = |index| {
+            self.constant_pool
+                .get_utf8_at(index)
+                .map(|utf8| utf8.data.as_ref())
+        };
+        name_and_type_index
+            .and_then(|index| self.constant_pool.get_name_type_at(index))
+            .and_then(|name_and_type| {
+                Some((
+                    utf8(name_and_type.name_index)?,
+                    utf8(name_and_type.descriptor_index)?,
+                ))
+            })
+            .ok_or(format!(
+                "No member reference at constant pool index {index}"
+            ))
+    }
+
+    /// The type of the value `ldc` pushes for a constant
+    fn constant(&self, index: u16) -> Result<VT, String> {
+        let reference = |class_name: &str| Ok(VT::Reference(class_name.to_string()));
+        match self.constant_pool.get_at(index) {
+            Some(CpInfo::Integer(_)) => Ok(VT::Integer),
+            Some(CpInfo::Float(_)) => Ok(VT::Float),
+            Some(CpInfo::Long(_)) => Ok(VT::Long),
+            Some(CpInfo::Double(_)) => Ok(VT::Double),
+            Some(CpInfo::String(_)) => reference("java/lang/String"),
+            Some(CpInfo::Class(_)) => reference("java/lang/Class"),
+            Some(CpInfo::MethodType(_)) => reference("java/lang/invoke/MethodType"),
+            Some(CpInfo::MethodHandle(_)) => reference("java/lang/invoke/MethodHandle"),
+            Some(CpInfo::InvokeDynamic(dynamic)) if dynamic.tag == "CONSTANT_Dynamic" => {
+                field_type(self.member_at(index)?.1)
+            }
+            _ => Err(format!(
+                "No loadable constant at constant pool index {index}"
+            )),
+        }
+    }
+}
+
+/// The type two paths agree on, `Top` if they hold values that can't be used the same way
+fn merge_types(first: &VT, second: &VT) -> VT {
+    match (first, second) {
+        _ if first == second => first.clone(),
+        (VT::Null, VT::Reference(_)) => second.clone(),
+        (VT::Reference(_), VT::Null) => first.clone(),
+        (VT::Reference(_), VT::Reference(_)) => VT::Reference("java/lang/Object".to_string()),
+        _ => VT::Top,
+    }
+}
+
+fn field_type(descriptor: &str) -> Result<VT, String> {
+    parse_field_descriptor(descriptor)
+        .map(|descriptor| VT::from_field_descriptor(&descriptor))
+        .map_err(|err| err.to_string())
+}
+
+fn pop(frame: &mut Frame, count: usize) -> Result<Vec<VT>, String> {
+    let Some(remaining) = frame.stack.len().checked_sub(count) else {
+        return Err("pops more values than the stack holds".to_string());
+    };
+    Ok(frame.stack.split_off(remaining))
+}
+
+/// Pops the values that take up `words` stack slots, where longs and doubles take two
+fn pop_words(frame: &mut Frame, words: usize) -> Result<Vec<VT>, String> {
+    let mut values = vec![];
+    let mut popped = 0;
+    while popped < words {
+        let value = pop(frame, 1)?.remove(0);
+        popped += if value.is_category_2() { 2 } else { 1 };
+        values.insert(0, value);
+    }
+
+    match popped == words {
+        true => Ok(values),
+        false => Err("splits a long or double on the stack".to_string()),
+    }
+}
+
+/// Pops `operands` values and pushes the result, if any
+fn operate(frame: &mut Frame, operands: usize, result: Option<VT>) -> Result<(), String> {
+    pop(frame, operands)?;
+    frame.stack.extend(result);
+    Ok(())
+}
+
+/// Copies the top `words` stack slots below the `below` slots under them, like `dup_x1`
+fn duplicate(frame: &mut Frame, words: usize, below: usize) -> Result<(), String> {
+    let top = pop_words(frame, words)?;
+    let under = pop_words(frame, below)?;
+    frame.stack.extend(top.iter().cloned());
+    frame.stack.extend(under);
+    frame.stack.extend(top);
+    Ok(())
+}
+
+fn load(frame: &mut Frame, index: usize) -> Result<(), String> {
+    match frame.locals.get(index) {
+        Some(value) if *value != VT::Top => {
+            frame.stack.push(value.clone());
+            Ok(())
+        }
+        _ => Err(format!("loads local {index}, which holds no value")),
+    }
+}
+
+/// Stores the top of the stack in a local, a long or double also takes up the local after it
+fn store(frame: &mut Frame, index: usize) -> Result<(), String> {
+    let value = pop(frame, 1)?.remove(0);
+    if index > 0 && frame.locals[index - 1].is_category_2() {
+        frame.locals[index - 1] = VT::Top;
+    }
+    if value.is_category_2() {
+        frame.locals[index + 1] = VT::Top;
+    }
+    frame.locals[index] = value;
+    Ok(())
+}
+
+/// Writes the frames as the entries of a `StackMapTable`, each one as the difference to the
+/// frame before it where possible
+fn stack_map(
+    initial: &Frame,
+    frames: &[(usize, &Frame)],
+    constant_pool: &mut ConstantPoolBuilder<'static>,
+) -> Vec<StackMapFrame> {
+    let mut info = |value: &VT| match value {
+        VT::Top => VerificationTypeInfo::Top,
+        VT::Integer => VerificationTypeInfo::Integer,
+        VT::Float => VerificationTypeInfo::Float,
+        VT::Long => VerificationTypeInfo::Long,
+        VT::Double => VerificationTypeInfo::Double,
+        VT::Null => VerificationTypeInfo::Null,
+        VT::UninitializedThis => VerificationTypeInfo::UninitializedThis,
+        VT::Uninitialized(offset) => VerificationTypeInfo::Uninitialized { offset: *offset },
+        VT::Reference(name) => VerificationTypeInfo::Object {
+            cpool_index: constant_pool.class(name),
+        },
+    };
+
+    let mut entries = vec![];
+    let mut previous_locals = compact_locals(&initial.locals);
+    let mut previous_pc: Option<usize> = None;
+    for (pc, frame) in frames {
+        let offset_delta = previous_pc.map_or(*pc, |previous_pc| pc - previous_pc - 1) as u16;
+        previous_pc = Some(*pc);
+
+        let locals = compact_locals(&frame.locals);
+        let entry = match frame.stack.as_slice() {
+            [] if locals == previous_locals => match offset_delta {
+                0..=63 => StackMapFrame::SameFrame { offset_delta },
+                _ => StackMapFrame::SameFrameExtended { offset_delta },
+            },
+            [value] if locals == previous_locals => match offset_delta {
+                0..=63 => StackMapFrame::SameLocalsStackItemFrame {
+                    offset_delta,
+                    stack: info(value),
+                },
+                _ => StackMapFrame::SameLocalsStackItemFrameExtended {
+                    offset_delta,
+                    stack: info(value),
+                },
+            },
+            [] if locals.len() < previous_locals.len()
+                && previous_locals.len() - locals.len() <= 3
+                && previous_locals.starts_with(&locals) =>
+            {
+                StackMapFrame::ChopFrame {
+                    offset_delta,
+                    chopped: (previous_locals.len() - locals.len()) as u8,
+                }
+            }
+            [] if locals.len() > previous_locals.len()
+                && locals.len() - previous_locals.len() <= 3
+                && locals.starts_with(&previous_locals) =>
+            {
+                StackMapFrame::AppendFrame {
+                    offset_delta,
+                    locals: locals[previous_locals.len()..]
+                        .iter()
+                        .map(&mut info)
+                        .collect(),
+                }
+            }
+            stack => StackMapFrame::FullFrame {
+                offset_delta,
+                locals: locals.iter().map(&mut info).collect(),
+                stack: stack.iter().map(&mut info).collect(),
+            },
+        };
+
+        entries.push(entry);
+        previous_locals = locals;
+    }
+
+    entries
+}
+
+fn set_offsets(opcode: &mut OpCodes, offsets: &[i32]) -> Result<(), String> {
+    let short = |offset: i32| {
+        i16::try_from(offset)
+            .map_err(|_| format!("The branch offset {offset} doesn't fit in 16 bits, use goto_w"))
+    };
+
+    match (opcode, offsets) {
+        (
+            OpCodes::if_icmp(_, offset)
+            | OpCodes::if_cond(_, offset)
+            | OpCodes::if_acmp(_, offset)
+            | OpCodes::if_null(offset)
+            | OpCodes::if_notnull(offset)
+            | OpCodes::goto(offset)
+            | OpCodes::jsr(offset),
+            [target_offset],
+        ) => *offset = short(*target_offset)?,
+        (OpCodes::goto_w(offset) | OpCodes::jsr_w(offset), [target_offset]) => {
+            *offset = *target_offset
+        }
+        (OpCodes::tableswitch(default, _, _, offsets), [default_offset, target_offsets @ ..]) => {
+            *default = *default_offset;
+            *offsets = target_offsets.to_vec();
+        }
+        (OpCodes::lookupswitch(default, pairs), [default_offset, target_offsets @ ..]) => {
+            *default = *default_offset;
+            for ((_, offset), target_offset) in pairs.iter_mut().zip(target_offsets) {
+                *offset = *target_offset;
+            }
+        }
+        (opcode, _) => return Err(format!("{opcode:?} isn't a branch")),
+    }
+
+    Ok(())
+}
+
+/// The pcs an opcode jumps to, besides the next opcode
+fn jump_targets(pc: usize, opcode: &OpCodes) -> Vec<i64> {
+    let pc = pc as i64;
+    match opcode {
+        OpCodes::if_icmp(_, offset)
+        | OpCodes::if_cond(_, offset)
+        | OpCodes::if_acmp(_, offset)
+        | OpCodes::if_null(offset)
+        | OpCodes::if_notnull(offset)
+        | OpCodes::goto(offset)
+        | OpCodes::jsr(offset) => vec![pc + *offset as i64],
+        OpCodes::goto_w(offset) | OpCodes::jsr_w(offset) => vec![pc + *offset as i64],
+        OpCodes::tableswitch(default, _, _, offsets) => [default]
+            .into_iter()
+            .chain(offsets)
+            .map(|offset| pc + *offset as i64)
+            .collect(),
+        OpCodes::lookupswitch(default, pairs) => [default]
+            .into_iter()
+            .chain(pairs.iter().map(|(_, offset)| offset))
+            .map(|offset| pc + *offset as i64)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn falls_through(opcode: &OpCodes) -> bool {
+    !matches!(
+        opcode,
+        OpCodes::goto(_)
+            | OpCodes::goto_w(_)
+            | OpCodes::tableswitch(..)
+            | OpCodes::lookupswitch(..)
+            | OpCodes::athrow
+            | OpCodes::ret(_)
+            | OpCodes::ireturn
+            | OpCodes::lreturn
+            | OpCodes::freturn
+            | OpCodes::dreturn
+            | OpCodes::areturn
+            | OpCodes::Return
+    ) && !matches!(opcode, OpCodes::wide { opcode, .. } if matches!(**opcode, OpCodes::ret(_)))
+}
+
+/// The locals the arguments take up, or the highest one the code uses if that is more
+fn max_locals(
+    opcodes: &[(usize, &OpCodes)],
+    descriptor: &str,
+    is_static: bool,
+) -> Result<u16, String> {
+    let arguments = parse_method_descriptor(descriptor)
+        .map_err(|err| err.to_string())?
+        .parameter_slots()
+        + if is_static { 0 } else { 1 };
+
+    let used = opcodes.iter().filter_map(|(_, opcode)| match opcode {
+        OpCodes::iload_(index)
+        | OpCodes::fload_(index)
+        | OpCodes::aload_(index)
+        | OpCodes::istore_(index)
+        | OpCodes::fstore_(index)
+        | OpCodes::astore_(index)
+        | OpCodes::iinc(index, _)
+        | OpCodes::ret(index) => Some(*index as usize + 1),
+        OpCodes::lload_(index)
+        | OpCodes::dload_(index)
+        | OpCodes::lstore_(index)
+        | OpCodes::dstore_(index) => Some(*index as usize + 2),
+        OpCodes::wide { opcode, index, .. } => Some(
+            *index as usize
+                + match **opcode {
+                    OpCodes::lload_(_)
+                    | OpCodes::dload_(_)
+                    | OpCodes::lstore_(_)
+                    | OpCodes::dstore_(_) => 2,
+                    _ => 1,
+                },
+        ),
+        _ => None,
+    });
+
+    let max_locals = used.chain([arguments]).max().unwrap_or_default();
+    u16::try_from(max_locals).map_err(|_| format!("The code uses {max_locals} locals"))
+}
+
+/// The opcode byte for a local variable instruction, the short form like `iload_0` for the first
+/// four locals and the long form with an index operand for the others
+fn local_opcode(long_form: u8, short_form: u8, index: u8) -> Vec<u8> {
+    match index {
+        0..=3 => vec![short_form + index],
+        _ => vec![long_form, index],
+    }
+}
+
+fn cmp_opcode(base: u8, condition: &CmpConditions) -> u8 {
+    base + match condition {
+        CmpConditions::Equal => 0,
+        CmpConditions::NotEqual => 1,
+        CmpConditions::LessThan => 2,
+        CmpConditions::GreaterOrEqual => 3,
+        CmpConditions::GreaterThan => 4,
+        CmpConditions::LessOrEqual => 5,
+    }
+}
+
+impl OpCodes {
+    /// Encodes the opcode at the end of `code`, the reverse of `parse_opcodes` except that the
+    /// first four locals always get the short form. `code` has to hold the method's byte code up
+    /// to this opcode, switches are padded relative to its start
+    fn write(&self, code: &mut Vec<u8>) -> Result<(), String> {
+        let pc = code.len();
+        let bytes: Vec<u8> = match self {
+            OpCodes::nop => vec![0x00],
+            OpCodes::dup => vec![0x59],
+            OpCodes::dup2 => vec![0x5c],
+            OpCodes::dup_x1 => vec![0x5a],
+            OpCodes::dup_x2 => vec![0x5b],
+            OpCodes::dup2_x1 => vec![0x5d],
+            OpCodes::dup2_x2 => vec![0x5e],
+            OpCodes::pop => vec![0x57],
+            OpCodes::pop2 => vec![0x58],
+            OpCodes::getstatic(index) => [&[0xb2][..], &index.to_be_bytes()].concat(),
+            OpCodes::ldc(index) => vec![0x12, *index],
+            OpCodes::ldc_w(index) => [&[0x13][..], &index.to_be_bytes()].concat(),
+            OpCodes::ldc2_w(index) => [&[0x14][..], &index.to_be_bytes()].concat(),
+            OpCodes::invokevirtual(index) => [&[0xb6][..], &index.to_be_bytes()].concat(),
+            OpCodes::invokespecial(index) => [&[0xb7][..], &index.to_be_bytes()].concat(),
+            OpCodes::invokestatic(index) => [&[0xb8][..], &index.to_be_bytes()].concat(),
+            OpCodes::invokeinterface(index, count) => {
+                [&[0xb9][..], &index.to_be_bytes(), &[*count, 0]].concat()
+            }
+            OpCodes::invokedynamic(index) => [&[0xba][..], &index.to_be_bytes(), &[0, 0]].concat(),
+            OpCodes::bipush(value) => vec![0x10, *value],
+            OpCodes::sipush(value) => [&[0x11][..], &value.to_be_bytes()].concat(),
+
+            OpCodes::new(index) => [&[0xbb][..], &index.to_be_bytes()].concat(),
+            OpCodes::anewarray(index) => [&[0xbd][..], &index.to_be_bytes()].concat(),
+            OpCodes::newarray(atype) => vec![0xbc, *atype],
+            OpCodes::arraylength => vec![0xbe],
+            OpCodes::multianewarray(index, dimensions) => {
+                [&[0xc5][..], &index.to_be_bytes(), &[*dimensions]].concat()
+            }
+
+            OpCodes::istore_(index) => local_opcode(0x36, 0x3b, *index),
+            OpCodes::iconst_(value @ -1..=5) => vec![(0x3 + value) as u8],
+            OpCodes::iload_(index) => local_opcode(0x15, 0x1a, *index),
+
+            OpCodes::iaload => vec![0x2e],
+            OpCodes::baload => vec![0x33],
+            OpCodes::aaload => vec![0x32],
+            OpCodes::laload => vec![0x2f],
+            OpCodes::saload => vec![0x35],
+            OpCodes::iastore => vec![0x4f],
+            OpCodes::aastore => vec![0x53],
+            OpCodes::castore => vec![0x55],
+            OpCodes::fastore => vec![0x51],
+            OpCodes::dastore => vec![0x52],
+            OpCodes::sastore => vec![0x56],
+            OpCodes::faload => vec![0x30],
+            OpCodes::bastore => vec![0x54],
+            OpCodes::caload => vec![0x34],
+            OpCodes::lastore => vec![0x50],
+            OpCodes::daload => vec![0x31],
+
+            OpCodes::fstore_(index) => local_opcode(0x38, 0x43, *index),
+            OpCodes::fconst_(value) if *value == 0.0 => vec![0xb],
+            OpCodes::fconst_(value) if *value == 1.0 => vec![0xc],
+            OpCodes::fconst_(value) if *value == 2.0 => vec![0xd],
+            OpCodes::fload_(index) => local_opcode(0x17, 0x22, *index),
+
+            OpCodes::astore_(index) => local_opcode(0x3a, 0x4b, *index),
+            OpCodes::aconst_null => vec![0x1],
+            OpCodes::aload_(index) => local_opcode(0x19, 0x2a, *index),
+
+            OpCodes::lstore_(index) => local_opcode(0x37, 0x3f, *index),
+            OpCodes::lconst_(value @ 0..=1) => vec![0x9 + *value as u8],
+            OpCodes::lload_(index) => local_opcode(0x16, 0x1e, *index),
+
+            OpCodes::dstore_(index) => local_opcode(0x39, 0x47, *index),
+            OpCodes::dconst_(value) if *value == 0.0 => vec![0xe],
+            OpCodes::dconst_(value) if *value == 1.0 => vec![0xf],
+            OpCodes::dload_(index) => local_opcode(0x18, 0x26, *index),
+
+            OpCodes::checkcast(index) => [&[0xc0][..], &index.to_be_bytes()].concat(),
+            OpCodes::i2f => vec![0x86],
+            OpCodes::i2l => vec![0x85],
+            OpCodes::i2b => vec![0x91],
+            OpCodes::i2d => vec![0x87],
+            OpCodes::i2c => vec![0x92],
+            OpCodes::i2s => vec![0x93],
+
+            OpCodes::l2i => vec![0x88],
+            OpCodes::l2d => vec![0x8a],
+            OpCodes::l2f => vec![0x89],
+
+            OpCodes::f2d => vec![0x8d],
+            OpCodes::f2i => vec![0x8b],
+            OpCodes::f2l => vec![0x8c],
+
+            OpCodes::d2f => vec![0x90],
+            OpCodes::d2i => vec![0x8e],
+            OpCodes::d2l => vec![0x8f],
+
+            OpCodes::putfield(index) => [&[0xb5][..], &index.to_be_bytes()].concat(),
+            OpCodes::getfield(index) => [&[0xb4][..], &index.to_be_bytes()].concat(),
+
+            OpCodes::putstatic(index) => [&[0xb3][..], &index.to_be_bytes()].concat(),
+
+            OpCodes::if_icmp(condition, offset) => {
+                [&[cmp_opcode(0x9f, condition)][..], &offset.to_be_bytes()].concat()
+            }
+            OpCodes::if_cond(condition, offset) => {
+                [&[cmp_opcode(0x99, condition)][..], &offset.to_be_bytes()].concat()
+            }
+            OpCodes::if_acmp(
+                condition @ (CmpConditions::Equal | CmpConditions::NotEqual),
+                offset,
+            ) => [&[cmp_opcode(0xa5, condition)][..], &offset.to_be_bytes()].concat(),
+            OpCodes::if_null(offset) => [&[0xc6][..], &offset.to_be_bytes()].concat(),
+            OpCodes::if_notnull(offset) => [&[0xc7][..], &offset.to_be_bytes()].concat(),
+
+            OpCodes::lcmp => vec![0x94],
+
+            OpCodes::isub => vec![0x64],
+            OpCodes::iand => vec![0x7e],
+            OpCodes::iadd => vec![0x60],
+            OpCodes::imul => vec![0x68],
+            OpCodes::ixor => vec![0x82],
+            OpCodes::ior => vec![0x80],
+            OpCodes::iushr => vec![0x7c],
+            OpCodes::ishl => vec![0x78],
+            OpCodes::ishr => vec![0x7a],
+            OpCodes::idiv => vec![0x6c],
+            OpCodes::iinc(index, constant) => vec![0x84, *index, *constant as u8],
+            OpCodes::irem => vec![0x70],
+            OpCodes::ineg => vec![0x74],
+            OpCodes::swap => vec![0x5f],
+
+            OpCodes::lsub => vec![0x65],
+            OpCodes::ladd => vec![0x61],
+            OpCodes::land => vec![0x7f],
+            OpCodes::lmul => vec![0x69],
+            OpCodes::ldiv => vec![0x6d],
+            OpCodes::lrem => vec![0x71],
+            OpCodes::lshl => vec![0x79],
+            OpCodes::lshr => vec![0x7b],
+            OpCodes::lushr => vec![0x7d],
+            OpCodes::lneg => vec![0x75],
+            OpCodes::lor => vec![0x81],
+            OpCodes::lxor => vec![0x83],
+
+            OpCodes::fsub => vec![0x66],
+            OpCodes::fadd => vec![0x62],
+            OpCodes::fmul => vec![0x6a],
+            OpCodes::fdiv => vec![0x6e],
+            OpCodes::fneg => vec![0x76],
+            OpCodes::frem => vec![0x72],
+            OpCodes::fcmp(1) => vec![0x96],
+            OpCodes::fcmp(-1) => vec![0x95],
+
+            OpCodes::dsub => vec![0x67],
+            OpCodes::dadd => vec![0x63],
+            OpCodes::dmul => vec![0x6b],
+            OpCodes::ddiv => vec![0x6f],
+            OpCodes::dneg => vec![0x77],
+            OpCodes::drem => vec![0x73],
+            OpCodes::dcmp(1) => vec![0x98],
+            OpCodes::dcmp(-1) => vec![0x97],
+
+            OpCodes::goto(offset) => [&[0xa7][..], &offset.to_be_bytes()].concat(),
+            OpCodes::goto_w(offset) => [&[0xc8][..], &offset.to_be_bytes()].concat(),
+
+            OpCodes::monitorenter => vec![0xc2],
+            OpCodes::monitorexit => vec![0xc3],
+
+            OpCodes::instanceof(index) => [&[0xc1][..], &index.to_be_bytes()].concat(),
+
+            OpCodes::tableswitch(default, low, high, offsets) => {
+                let mut bytes = vec![0xaa];
+                bytes.resize(4 - pc % 4, 0);
+                for value in [default, low, high].into_iter().chain(offsets) {
+                    bytes.extend(value.to_be_bytes());
+                }
+                bytes
+            }
+            OpCodes::lookupswitch(default, pairs) => {
+                let mut bytes = vec![0xab];
+                bytes.resize(4 - pc % 4, 0);
+                bytes.extend(default.to_be_bytes());
+                bytes.extend((pairs.len() as i32).to_be_bytes());
+                for (value, offset) in pairs {
+                    bytes.extend(value.to_be_bytes());
+                    bytes.extend(offset.to_be_bytes());
+                }
+                bytes
+            }
+
+            OpCodes::wide {
+                opcode,
+                index,
+                constbyte,
+            } => {
+                let widened_opcode = match (opcode.as_ref(), constbyte) {
+                    (OpCodes::iload_(_), None) => 0x15,
+                    (OpCodes::fload_(_), None) => 0x17,
+                    (OpCodes::aload_(_), None) => 0x19,
+                    (OpCodes::lload_(_), None) => 0x16,
+                    (OpCodes::dload_(_), None) => 0x18,
+                    (OpCodes::istore_(_), None) => 0x36,
+                    (OpCodes::fstore_(_), None) => 0x38,
+                    (OpCodes::astore_(_), None) => 0x3a,
+                    (OpCodes::lstore_(_), None) => 0x37,
+                    (OpCodes::dstore_(_), None) => 0x39,
+                    (OpCodes::ret(_), None) => 0xa9,
+                    (OpCodes::iinc(_, _), Some(_)) => 0x84,
+                    _ => return Err(format!("Can't widen {opcode:?}")),
+                };
+
+                let mut bytes = [&[0xc4, widened_opcode][..], &index.to_be_bytes()].concat();
+                if let Some(constant) = constbyte {
+                    bytes.extend(constant.to_be_bytes());
+                }
+                bytes
+            }
+
+            OpCodes::jsr(offset) => [&[0xa8][..], &offset.to_be_bytes()].concat(),
+            OpCodes::jsr_w(offset) => [&[0xc9][..], &offset.to_be_bytes()].concat(),
+            OpCodes::ret(index) => vec![0xa9, *index],
+
+            OpCodes::athrow => vec![0xbf],
+
+            OpCodes::lreturn => vec![0xad],
+            OpCodes::ireturn => vec![0xac],
+            OpCodes::areturn => vec![0xb0],
+            OpCodes::dreturn => vec![0xaf],
+            OpCodes::freturn => vec![0xae],
+            OpCodes::Return => vec![0xb1],
+
+            unencodable => return Err(format!("{unencodable:?} has no encoding")),
+        };
+
+        code.extend(bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use jvm_parser::classfile::{
+        attributes::{AttributeInfoData, CodeAttribute, StackMapFrame, VerificationTypeInfo},
+        classfile::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+        constant_pool::ConstantPoolBuilder,
+    };
+
+    use super::{ClassBuilder, CodeBuilder, Label};
+    use crate::jvm::{
+        opcodes::{CmpConditions, OpCodes},
+        StackValue, JVM,
+    };
+
+    const PUBLIC_STATIC: u16 = MethodAccessFlags::ACC_PUBLIC | MethodAccessFlags::ACC_STATIC;
+
+    fn labels<const N: usize>(builder: &mut CodeBuilder) -> [Label; N] {
+        [(); N].map(|_| builder.new_label())
+    }
+
+    /// Loads the classes into a vm that verifies them, so their frames are checked before any
+    /// method runs
+    fn verifying_jvm(classes: impl IntoIterator<Item = ClassBuilder>) -> Arc<JVM> {
+        let mut jvm = JVM::with_classes(classes.into_iter().map(|class| class.build().unwrap()));
+        Arc::get_mut(&mut jvm).unwrap().set_verify(true);
+        jvm
+    }
+
+    fn stack_map<'a>(code: &'a CodeAttribute) -> &'a [StackMapFrame] {
+        code.attribute_info
+            .iter()
+            .find_map(|attribute| match &attribute.attribute {
+                AttributeInfoData::StackMapTable(stack_map) => Some(&stack_map.entries[..]),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    /// `static int sum(int n) { int total = 0; for (int i = 0; i < n; i++) total += i; return total; }`
+    fn sum() -> CodeBuilder {
+        let mut sum = CodeBuilder::new("(I)I", true);
+        let [check, end] = labels(&mut sum);
+        sum.op(OpCodes::iconst_(0))
+            .op(OpCodes::istore_(1))
+            .op(OpCodes::iconst_(0))
+            .op(OpCodes::istore_(2))
+            .bind(check)
+            .op(OpCodes::iload_(2))
+            .op(OpCodes::iload_(0))
+            .branch(OpCodes::if_icmp(CmpConditions::GreaterOrEqual, 0), end)
+            .op(OpCodes::iload_(1))
+            .op(OpCodes::iload_(2))
+            .op(OpCodes::iadd)
+            .op(OpCodes::istore_(1))
+            .op(OpCodes::iinc(2, 1))
+            .branch(OpCodes::goto(0), check)
+            .bind(end)
+            .op(OpCodes::iload_(1))
+            .op(OpCodes::ireturn);
+        sum
+    }
+
+    /// `static int divide(int a, int b) { try { return a / b; } catch (ArithmeticException e) { return -1; } }`
+    fn divide(arithmetic_exception: u16) -> CodeBuilder {
+        let mut divide = CodeBuilder::new("(II)I", true);
+        let [start, end, handler] = labels(&mut divide);
+        divide
+            .bind(start)
+            .op(OpCodes::iload_(0))
+            .op(OpCodes::iload_(1))
+            .op(OpCodes::idiv)
+            .bind(end)
+            .op(OpCodes::ireturn)
+            .bind(handler)
+            .op(OpCodes::astore_(2))
+            .op(OpCodes::iconst_(-1))
+            .op(OpCodes::ireturn)
+            .try_catch(start, end, handler, arithmetic_exception);
+        divide
+    }
+
+    #[test]
+    fn frames_are_inferred() {
+        let mut pool = ConstantPoolBuilder::new();
+        let sum = sum().build("Generated", "sum", &mut pool).unwrap();
+        assert_eq!((sum.max_stack, sum.max_locals), (2, 3));
+        // The loop check at pc 4 adds `total` and `i`, the return at pc 19 has the same locals
+        assert!(matches!(
+            stack_map(&sum),
+            [
+                StackMapFrame::AppendFrame {
+                    offset_delta: 4,
+                    locals,
+                },
+                StackMapFrame::SameFrame { offset_delta: 14 },
+            ] if matches!(locals[..], [VerificationTypeInfo::Integer, VerificationTypeInfo::Integer])
+        ));
+
+        let arithmetic_exception = pool.class("java/lang/ArithmeticException");
+        let divide = divide(arithmetic_exception)
+            .build("Generated", "divide", &mut pool)
+            .unwrap();
+        assert!(matches!(
+            stack_map(&divide),
+            [StackMapFrame::SameLocalsStackItemFrame {
+                offset_delta: 4,
+                stack: VerificationTypeInfo::Object { cpool_index },
+            }] if *cpool_index == arithmetic_exception
+        ));
+
+        // A long takes two stack slots, straight line code needs no frames
+        let mut widen = CodeBuilder::new("(I)I", true);
+        widen
+            .op(OpCodes::iload_(0))
+            .op(OpCodes::i2l)
+            .op(OpCodes::lconst_(1))
+            .op(OpCodes::ladd)
+            .op(OpCodes::l2i)
+            .op(OpCodes::ireturn);
+        let widen = widen.build("Generated", "widen", &mut pool).unwrap();
+        assert_eq!((widen.max_stack, widen.max_locals), (4, 1));
+        assert!(stack_map(&widen).is_empty());
+    }
+
+    #[test]
+    fn assembled_methods_pass_verification() {
+        let mut class = ClassBuilder::new("Generated", Some("java/lang/Object"));
+        let arithmetic_exception = class.pool().class("java/lang/ArithmeticException");
+        let object = class.pool().class("java/lang/Object");
+        let object_init = class.pool().method_ref("java/lang/Object", "<init>", "()V");
+        let text = class.pool().string("text");
+
+        // static long pick(int n) { switch (n) { case 1: return 10L; case 2: return 20L; default: return 0L; } }
+        let mut pick = CodeBuilder::new("(I)J", true);
+        let [one, two, default] = labels(&mut pick);
+        pick.op(OpCodes::iload_(0))
+            .tableswitch(1, default, &[one, two])
+            .bind(one)
+            .op(OpCodes::bipush(10))
+            .op(OpCodes::i2l)
+            .op(OpCodes::lreturn)
+            .bind(two)
+            .op(OpCodes::bipush(20))
+            .op(OpCodes::i2l)
+            .op(OpCodes::lreturn)
+            .bind(default)
+            .op(OpCodes::lconst_(0))
+            .op(OpCodes::lreturn);
+
+        // static int sparse(int n) { switch (n) { case 1000: return 1; case -5: return 2; default: return 0; } }
+        let mut sparse = CodeBuilder::new("(I)I", true);
+        let [thousand, minus_five, default] = labels(&mut sparse);
+        sparse
+            .op(OpCodes::iload_(0))
+            .lookupswitch(default, &[(1000, thousand), (-5, minus_five)])
+            .bind(thousand)
+            .op(OpCodes::iconst_(1))
+            .op(OpCodes::ireturn)
+            .bind(minus_five)
+            .op(OpCodes::iconst_(2))
+            .op(OpCodes::ireturn)
+            .bind(default)
+            .op(OpCodes::iconst_(0))
+            .op(OpCodes::ireturn);
+
+        // static Object choose(boolean text) { return text ? "text" : new Object(); }, the two
+        // branches meet with a String or an Object on the stack
+        let mut choose = CodeBuilder::new("(Z)Ljava/lang/Object;", true);
+        let [create, join] = labels(&mut choose);
+        choose
+            .op(OpCodes::iload_(0))
+            .branch(OpCodes::if_cond(CmpConditions::Equal, 0), create)
+            .op(OpCodes::ldc(text as u8))
+            .branch(OpCodes::goto(0), join)
+            .bind(create)
+            .op(OpCodes::new(object))
+            .op(OpCodes::dup)
+            .op(OpCodes::invokespecial(object_init))
+            .bind(join)
+            .op(OpCodes::areturn);
+
+        class
+            .method(PUBLIC_STATIC, "sum", "(I)I", sum())
+            .method(
+                PUBLIC_STATIC,
+                "divide",
+                "(II)I",
+                divide(arithmetic_exception),
+            )
+            .method(PUBLIC_STATIC, "pick", "(I)J", pick)
+            .method(PUBLIC_STATIC, "sparse", "(I)I", sparse)
+            .method(PUBLIC_STATIC, "choose", "(Z)Ljava/lang/Object;", choose);
+
+        let jvm = verifying_jvm([class]);
+        let run = |name, descriptor, args: &[i32]| {
+            let args = args.iter().map(|arg| StackValue::Integer(*arg)).collect();
+            jvm.run_static("Generated", name, descriptor, args).unwrap()
+        };
+
+        assert!(matches!(run("sum", "(I)I", &[5]), StackValue::Integer(10)));
+        assert!(matches!(
+            run("divide", "(II)I", &[9, 3]),
+            StackValue::Integer(3)
+        ));
+        assert!(matches!(
+            run("divide", "(II)I", &[1, 0]),
+            StackValue::Integer(-1)
+        ));
+        assert!(matches!(run("pick", "(I)J", &[2]), StackValue::Long(20)));
+        assert!(matches!(run("pick", "(I)J", &[7]), StackValue::Long(0)));
+        assert!(matches!(
+            run("sparse", "(I)I", &[-5]),
+            StackValue::Integer(2)
+        ));
+        assert!(matches!(
+            run("sparse", "(I)I", &[3]),
+            StackValue::Integer(0)
+        ));
+        assert!(matches!(
+            run("choose", "(Z)Ljava/lang/Object;", &[0]),
+            StackValue::JavaObjectRef(_)
+        ));
+    }
+
+    #[test]
+    fn constructors_fields_and_interfaces_pass_verification() {
+        // interface Task { void run(); }
+        let mut task = ClassBuilder::new("Task", Some("java/lang/Object"));
+        task.access_flags(ClassAccessFlags::ACC_PUBLIC | ClassAccessFlags::ACC_INTERFACE)
+            .abstract_method(
+                MethodAccessFlags::ACC_PUBLIC | MethodAccessFlags::ACC_ABSTRACT,
+                "run",
+                "()V",
+            );
+
+        // class Counter implements Task { static int count; public void run() { count++; } }
+        let mut counter = ClassBuilder::new("Counter", Some("java/lang/Object"));
+        let object_init = counter
+            .pool()
+            .method_ref("java/lang/Object", "<init>", "()V");
+        let counter_class = counter.pool().class("Counter");
+        let counter_init = counter.pool().method_ref("Counter", "<init>", "()V");
+        let count = counter.pool().field_ref("Counter", "count", "I");
+        let run = counter.pool().interface_method_ref("Task", "run", "()V");
+
+        // `this` is uninitialized until Object's constructor returns
+        let mut init = CodeBuilder::new("()V", false);
+        init.op(OpCodes::aload_(0))
+            .op(OpCodes::invokespecial(object_init))
+            .op(OpCodes::Return);
+
+        let mut increment = CodeBuilder::new("()V", false);
+        increment
+            .op(OpCodes::getstatic(count))
+            .op(OpCodes::iconst_(1))
+            .op(OpCodes::iadd)
+            .op(OpCodes::putstatic(count))
+            .op(OpCodes::Return);
+
+        // static int twice() { Task task = new Counter(); task.run(); task.run(); return count; }
+        let mut twice = CodeBuilder::new("()I", true);
+        twice
+            .op(OpCodes::new(counter_class))
+            .op(OpCodes::dup)
+            .op(OpCodes::invokespecial(counter_init))
+            .op(OpCodes::astore_(0))
+            .op(OpCodes::aload_(0))
+            .op(OpCodes::invokeinterface(run, 1))
+            .op(OpCodes::aload_(0))
+            .op(OpCodes::invokeinterface(run, 1))
+            .op(OpCodes::getstatic(count))
+            .op(OpCodes::ireturn);
+
+        counter
+            .interface("Task")
+            .field(FieldAccessFlags::ACC_STATIC, "count", "I")
+            .method(MethodAccessFlags::ACC_PUBLIC, "<init>", "()V", init)
+            .method(MethodAccessFlags::ACC_PUBLIC, "run", "()V", increment)
+            .method(PUBLIC_STATIC, "twice", "()I", twice);
+
+        let jvm = verifying_jvm([task, counter]);
+        assert!(matches!(
+            jvm.run_static("Counter", "twice", "()I", vec![]),
+            Ok(StackValue::Integer(2))
+        ));
+    }
+
+    #[test]
+    fn unbalanced_stack_is_rejected() {
+        let mut builder = CodeBuilder::new("()V", true);
+        let [skip] = labels(&mut builder);
+        builder
+            .op(OpCodes::iconst_(1))
+            .branch(OpCodes::if_cond(CmpConditions::Equal, 0), skip)
+            .op(OpCodes::iconst_(2))
+            .bind(skip)
+            .op(OpCodes::Return);
+
+        assert_eq!(
+            builder
+                .build("Generated", "unbalanced", &mut ConstantPoolBuilder::new())
+                .unwrap_err(),
+            "The stack holds 0 or 1 values at pc 5 depending on the path"
+        );
+    }
+}
@@ -0,0 +1,366 @@
+//! Minimal stand-ins for the core classes, so programs can run without an rt.jar.
+//!
+//! The shims have no methods, the behaviour comes from the native bindings in
+//! [`super::natives`]. Classes from a real rt.jar replace them when one is added.
+
+use jvm_parser::classfile::{
+    classfile::{ClassAccessFlags, FieldAccessFlags, FieldInfo},
+    constant_pool::{ConstantPool, CpInfo, CpInfoClass, CpInfoUtf8},
+    JavaClass,
+};
+
+/// `(name, super class)`, `java/lang/Object` is the only one without a super class
+const SHIM_CLASSES: &[(&str, &str)] = &[
+    ("java/lang/Object", ""),
+    ("java/lang/String", "java/lang/Object"),
+    ("java/lang/Class", "java/lang/Object"),
+    ("java/lang/ClassLoader", "java/lang/Object"),
+    ("java/lang/Thread", "java/lang/Object"),
+    ("java/lang/Throwable", "java/lang/Object"),
+    ("java/lang/Exception", "java/lang/Throwable"),
+    (
+        "java/lang/ReflectiveOperationException",
+        "java/lang/Exception",
+    ),
+    (
+        "java/lang/ClassNotFoundException",
+        "java/lang/ReflectiveOperationException",
+    ),
+    ("java/lang/RuntimeException", "java/lang/Exception"),
+    ("java/lang/SecurityException", "java/lang/RuntimeException"),
+    ("java/lang/InterruptedException", "java/lang/Exception"),
+    (
+        "java/lang/NullPointerException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/ArithmeticException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/NegativeArraySizeException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/IllegalArgumentException",
+        "java/lang/RuntimeException",
+    ),
+    ("java/lang/ClassCastException", "java/lang/RuntimeException"),
+    (
+        "java/lang/ArrayStoreException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/IllegalMonitorStateException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/IllegalThreadStateException",
+        "java/lang/IllegalArgumentException",
+    ),
+    (
+        "java/lang/IndexOutOfBoundsException",
+        "java/lang/RuntimeException",
+    ),
+    (
+        "java/lang/ArrayIndexOutOfBoundsException",
+        "java/lang/IndexOutOfBoundsException",
+    ),
+    ("java/lang/Error", "java/lang/Throwable"),
+    ("java/lang/VirtualMachineError", "java/lang/Error"),
+    (
+        "java/lang/OutOfMemoryError",
+        "java/lang/VirtualMachineError",
+    ),
+    ("java/lang/LinkageError", "java/lang/Error"),
+    ("java/lang/NoClassDefFoundError", "java/lang/LinkageError"),
+    (
+        "java/lang/ExceptionInInitializerError",
+        "java/lang/LinkageError",
+    ),
+    ("java/lang/ClassFormatError", "java/lang/LinkageError"),
+    ("java/lang/UnsatisfiedLinkError", "java/lang/LinkageError"),
+    ("java/lang/VerifyError", "java/lang/LinkageError"),
+    (
+        "java/lang/IncompatibleClassChangeError",
+        "java/lang/LinkageError",
+    ),
+    (
+        "java/lang/NoSuchMethodError",
+        "java/lang/IncompatibleClassChangeError",
+    ),
+    (
+        "java/lang/IllegalAccessError",
+        "java/lang/IncompatibleClassChangeError",
+    ),
+    (
+        "java/lang/NoSuchFieldError",
+        "java/lang/IncompatibleClassChangeError",
+    ),
+    (
+        "java/lang/AbstractMethodError",
+        "java/lang/IncompatibleClassChangeError",
+    ),
+    ("java/lang/BootstrapMethodError", "java/lang/LinkageError"),
+    (
+        "java/lang/IllegalAccessException",
+        "java/lang/ReflectiveOperationException",
+    ),
+    (
+        "java/lang/reflect/InvocationTargetException",
+        "java/lang/ReflectiveOperationException",
+    ),
+    ("java/lang/Number", "java/lang/Object"),
+    ("java/lang/Boolean", "java/lang/Object"),
+    ("java/lang/Character", "java/lang/Object"),
+    ("java/lang/Byte", "java/lang/Number"),
+    ("java/lang/Short", "java/lang/Number"),
+    ("java/lang/Integer", "java/lang/Number"),
+    ("java/lang/Long", "java/lang/Number"),
+    ("java/lang/Float", "java/lang/Number"),
+    ("java/lang/Double", "java/lang/Number"),
+    ("java/lang/reflect/AccessibleObject", "java/lang/Object"),
+    (
+        "java/lang/reflect/Executable",
+        "java/lang/reflect/AccessibleObject",
+    ),
+    (
+        "java/lang/reflect/Field",
+        "java/lang/reflect/AccessibleObject",
+    ),
+    ("java/lang/reflect/Method", "java/lang/reflect/Executable"),
+    (
+        "sun/reflect/generics/reflectiveObjects/ParameterizedTypeImpl",
+        "java/lang/Object",
+    ),
+    (
+        "sun/reflect/generics/reflectiveObjects/TypeVariableImpl",
+        "java/lang/Object",
+    ),
+    (
+        "sun/reflect/generics/reflectiveObjects/WildcardTypeImpl",
+        "java/lang/Object",
+    ),
+    (
+        "sun/reflect/generics/reflectiveObjects/GenericArrayTypeImpl",
+        "java/lang/Object",
+    ),
+    ("java/lang/invoke/MethodType", "java/lang/Object"),
+    ("java/lang/invoke/MethodHandle", "java/lang/Object"),
+    ("java/lang/invoke/MethodHandles", "java/lang/Object"),
+    ("java/lang/invoke/MethodHandles$Lookup", "java/lang/Object"),
+    (
+        "java/lang/invoke/WrongMethodTypeException",
+        "java/lang/RuntimeException",
+    ),
+];
+
+/// Interfaces, their super class is `java/lang/Object` like in any class file
+const SHIM_INTERFACES: &[&str] = &[
+    "java/lang/Runnable",
+    "java/lang/Cloneable",
+    "java/io/Serializable",
+    "java/lang/CharSequence",
+    "java/lang/Comparable",
+    "java/lang/reflect/Member",
+    "java/lang/reflect/Type",
+    "java/lang/reflect/ParameterizedType",
+    "java/lang/reflect/TypeVariable",
+    "java/lang/reflect/WildcardType",
+    "java/lang/reflect/GenericArrayType",
+];
+
+/// `(class, interface)` pairs of shims that implement an interface, or of interfaces and the
+/// interfaces they extend
+const SHIM_IMPLEMENTS: &[(&str, &str)] = &[
+    ("java/lang/Thread", "java/lang/Runnable"),
+    ("java/lang/String", "java/io/Serializable"),
+    ("java/lang/String", "java/lang/Comparable"),
+    ("java/lang/String", "java/lang/CharSequence"),
+    ("java/lang/Throwable", "java/io/Serializable"),
+    ("java/lang/Number", "java/io/Serializable"),
+    ("java/lang/Boolean", "java/io/Serializable"),
+    ("java/lang/Boolean", "java/lang/Comparable"),
+    ("java/lang/Character", "java/io/Serializable"),
+    ("java/lang/Character", "java/lang/Comparable"),
+    ("java/lang/Byte", "java/lang/Comparable"),
+    ("java/lang/Short", "java/lang/Comparable"),
+    ("java/lang/Integer", "java/lang/Comparable"),
+    ("java/lang/Long", "java/lang/Comparable"),
+    ("java/lang/Float", "java/lang/Comparable"),
+    ("java/lang/Double", "java/lang/Comparable"),
+    ("java/lang/reflect/Executable", "java/lang/reflect/Member"),
+    ("java/lang/reflect/Field", "java/lang/reflect/Member"),
+    ("java/lang/Class", "java/lang/reflect/Type"),
+    (
+        "java/lang/reflect/ParameterizedType",
+        "java/lang/reflect/Type",
+    ),
+    ("java/lang/reflect/TypeVariable", "java/lang/reflect/Type"),
+    ("java/lang/reflect/WildcardType", "java/lang/reflect/Type"),
+    (
+        "java/lang/reflect/GenericArrayType",
+        "java/lang/reflect/Type",
+    ),
+    (
+        "sun/reflect/generics/reflectiveObjects/ParameterizedTypeImpl",
+        "java/lang/reflect/ParameterizedType",
+    ),
+    (
+        "sun/reflect/generics/reflectiveObjects/TypeVariableImpl",
+        "java/lang/reflect/TypeVariable",
+    ),
+    (
+        "sun/reflect/generics/reflectiveObjects/WildcardTypeImpl",
+        "java/lang/reflect/WildcardType",
+    ),
+    (
+        "sun/reflect/generics/reflectiveObjects/GenericArrayTypeImpl",
+        "java/lang/reflect/GenericArrayType",
+    ),
+];
+
+/// `(class, name, descriptor)` of the `public static final` fields of shims, the natives bound to
+/// their `<clinit>` set them
+const SHIM_STATIC_FIELDS: &[(&str, &str, &str)] = &[
+    ("java/lang/Boolean", "TYPE", "Ljava/lang/Class;"),
+    ("java/lang/Byte", "TYPE", "Ljava/lang/Class;"),
+    ("java/lang/Character", "TYPE", "Ljava/lang/Class;"),
+    ("java/lang/Short", "TYPE", "Ljava/lang/Class;"),
+    ("java/lang/Integer", "TYPE", "Ljava/lang/Class;"),
+    ("java/lang/Long", "TYPE", "Ljava/lang/Class;"),
+    ("java/lang/Float", "TYPE", "Ljava/lang/Class;"),
+    ("java/lang/Double", "TYPE", "Ljava/lang/Class;"),
+];
+
+/// The interfaces every array class implements (JLS 10.8)
+const ARRAY_INTERFACES: &[&str] = &["java/lang/Cloneable", "java/io/Serializable"];
+
+pub fn shim_classes() -> Vec<(String, JavaClass<'static>)> {
+    let implemented = |name: &str| {
+        SHIM_IMPLEMENTS
+            .iter()
+            .filter(|(class, _)| *class == name)
+            .map(|(_, interface)| *interface)
+            .collect::<Vec<_>>()
+    };
+    let static_fields = |name: &str| {
+        SHIM_STATIC_FIELDS
+            .iter()
+            .filter(|(class, _, _)| *class == name)
+            .map(|(_, field, descriptor)| (*field, *descriptor))
+            .collect::<Vec<_>>()
+    };
+
+    let classes = SHIM_CLASSES.iter().map(|(name, super_name)| {
+        let super_name = Some(*super_name).filter(|super_name| !super_name.is_empty());
+        let access_flags = ClassAccessFlags::ACC_PUBLIC | ClassAccessFlags::ACC_SUPER;
+        (
+            name.to_string(),
+            shim_class(
+                name,
+                super_name,
+                access_flags,
+                &implemented(name),
+                &static_fields(name),
+            ),
+        )
+    });
+    let interfaces = SHIM_INTERFACES.iter().map(|name| {
+        let access_flags = ClassAccessFlags::ACC_PUBLIC
+            | ClassAccessFlags::ACC_INTERFACE
+            | ClassAccessFlags::ACC_ABSTRACT;
+        (
+            name.to_string(),
+            shim_class(
+                name,
+                Some("java/lang/Object"),
+                access_flags,
+                &implemented(name),
+                &[],
+            ),
+        )
+    });
+
+    classes.chain(interfaces).collect()
+}
+
+/// The class of an array type like `[I` or `[Ljava/lang/String;`, which no class file defines
+pub fn array_class(name: &str) -> JavaClass<'static> {
+    let access_flags =
+        ClassAccessFlags::ACC_PUBLIC | ClassAccessFlags::ACC_FINAL | ClassAccessFlags::ACC_ABSTRACT;
+    shim_class(
+        name,
+        Some("java/lang/Object"),
+        access_flags,
+        ARRAY_INTERFACES,
+        &[],
+    )
+}
+
+/// The class of a primitive type like `int`, or of `void`, which only exists for its mirror
+pub fn primitive_class(name: &str) -> JavaClass<'static> {
+    let access_flags =
+        ClassAccessFlags::ACC_PUBLIC | ClassAccessFlags::ACC_FINAL | ClassAccessFlags::ACC_ABSTRACT;
+    shim_class(name, None, access_flags, &[], &[])
+}
+
+fn shim_class(
+    name: &str,
+    super_name: Option<&str>,
+    access_flags: u16,
+    interface_names: &[&str],
+    static_fields: &[(&str, &str)],
+) -> JavaClass<'static> {
+    let utf8 = |data: &str| {
+        CpInfo::Utf8(CpInfoUtf8 {
+            tag: "CONSTANT_Utf8",
+            data: data.to_string().into(),
+            raw: None,
+        })
+    };
+    let class = |name_index: u16| {
+        CpInfo::Class(CpInfoClass {
+            tag: "CONSTANT_Class",
+            name_index,
+        })
+    };
+
+    let mut pool_entries = vec![utf8(name), class(1)];
+    if let Some(super_name) = super_name {
+        pool_entries.extend([utf8(super_name), class(3)]);
+    }
+
+    let mut interfaces = vec![];
+    for interface in interface_names {
+        pool_entries.extend([utf8(interface), class(pool_entries.len() as u16 + 1)]);
+        interfaces.push(pool_entries.len() as u16);
+    }
+
+    let mut fields = vec![];
+    for (field_name, descriptor) in static_fields {
+        pool_entries.extend([utf8(field_name), utf8(descriptor)]);
+        fields.push(FieldInfo {
+            access_flags: FieldAccessFlags::ACC_PUBLIC
+                | FieldAccessFlags::ACC_STATIC
+                | FieldAccessFlags::ACC_FINAL,
+            name_index: pool_entries.len() as u16 - 1,
+            descriptor_index: pool_entries.len() as u16,
+            attributes: vec![],
+        });
+    }
+
+    JavaClass {
+        magic: 0xCAFEBABE,
+        minor_version: 0,
+        major_version: 52,
+        constant_pool: ConstantPool { pool_entries },
+        access_flags,
+        this_class: 2,
+        super_class: if super_name.is_some() { 4 } else { 0 },
+        interfaces,
+        fields,
+        methods: vec![],
+        attributes: vec![],
+    }
+}
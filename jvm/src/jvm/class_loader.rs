@@ -0,0 +1,816 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Condvar, Mutex, OnceLock},
+};
+
+use jvm_parser::{
+    classfile::{classfile::FieldAccessFlags, constant_pool::CpInfo, JavaClass},
+    jar::{JarFile, ReadClassError},
+};
+
+use super::{
+    bootstrap,
+    heap::{HeapObject, NativeData},
+    interpreter::DecodedCode,
+    opcodes::parse_opcodes,
+    runtime_constant_pool::RuntimeConstantPool,
+    JavaObjectRef, JvmError, StackValue, JVM,
+};
+
+/// Identifies a class loader.
+///
+/// A class at runtime is identified by its binary name *and* its defining loader, so the same
+/// name can be defined by several loaders and still be different classes (JVMS 5.3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LoaderId(pub usize);
+
+impl LoaderId {
+    /// Loads the core classes, rt.jar and the shims in [`super::bootstrap`]
+    pub const BOOTSTRAP: LoaderId = LoaderId(0);
+    /// Loads the classes and jars given to the vm, its parent is the bootstrap loader
+    pub const APP: LoaderId = LoaderId(1);
+}
+
+pub struct ClassLoaderEntry {
+    /// The loader requests are delegated to first, `None` for the bootstrap loader
+    pub parent: Option<LoaderId>,
+    /// The `java/lang/ClassLoader` object that represents this loader in java code
+    pub object: Option<JavaObjectRef>,
+    /// If the loader is implemented in java, loading goes through its `loadClass` method
+    pub user_defined: bool,
+    /// Every class this loader is the defining or an initiating loader of
+    pub classes: HashMap<String, Arc<LoadedClass>>,
+    /// Parsed classes a builtin loader can find, but which hasn't been defined yet
+    pub class_path: HashMap<String, Arc<JavaClass<'static>>>,
+    /// Searched in order after `class_path`, their classes are parsed when they are loaded
+    pub jars: Vec<&'static JarFile>,
+}
+
+impl ClassLoaderEntry {
+    pub fn builtin(parent: Option<LoaderId>) -> Self {
+        Self {
+            parent,
+            object: None,
+            user_defined: false,
+            classes: HashMap::new(),
+            class_path: HashMap::new(),
+            jars: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitState {
+    Uninitialized,
+    /// Being initialized by the thread with this id
+    InProgress(u64),
+    Initialized,
+    Failed,
+}
+
+/// A class that has been defined by a loader
+pub struct LoadedClass {
+    pub name: String,
+    /// The defining loader
+    pub loader: LoaderId,
+    pub java_class: Arc<JavaClass<'static>>,
+    pub runtime_constant_pool: RuntimeConstantPool,
+    pub super_class: Option<Arc<LoadedClass>>,
+    pub interfaces: Vec<Arc<LoadedClass>>,
+    pub statics: Mutex<HashMap<String, StackValue>>,
+    pub init_state: Mutex<InitState>,
+    /// Notified when another thread finishes initializing the class
+    init_done: Condvar,
+    mirror: OnceLock<JavaObjectRef>,
+    /// The host of the nest the class belongs to, `None` if the class is its own host
+    nest_host: OnceLock<Option<Arc<LoadedClass>>>,
+    code: Vec<OnceLock<Arc<DecodedCode>>>,
+}
+
+impl fmt::Debug for LoadedClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadedClass")
+            .field("name", &self.name)
+            .field("loader", &self.loader)
+            .finish()
+    }
+}
+
+impl LoadedClass {
+    /// The `java/lang/Class` object of the class, if one was created
+    pub fn mirror(&self) -> Option<JavaObjectRef> {
+        self.mirror.get().copied()
+    }
+
+    /// Walks the super class chain, starting with the class itself
+    pub fn ancestors(self: &Arc<Self>) -> impl Iterator<Item = Arc<LoadedClass>> {
+        std::iter::successors(Some(self.clone()), |class| class.super_class.clone())
+    }
+
+    /// The binary name of the package of the class, empty for the unnamed package
+    pub fn package(&self) -> &str {
+        self.name
+            .rsplit_once('/')
+            .map(|(package, _)| package)
+            .unwrap_or_default()
+    }
+
+    pub fn is_subclass_of(self: &Arc<Self>, other: &Arc<LoadedClass>) -> bool {
+        self.ancestors().any(|class| Arc::ptr_eq(&class, other))
+    }
+
+    /// Whether instances of the class are instances of `other`, through a super class or an
+    /// implemented interface, directly or through a super interface (JVMS 6.5 `checkcast`)
+    pub fn is_assignable_to(self: &Arc<Self>, other: &Arc<LoadedClass>) -> bool {
+        self.ancestors().any(|class| {
+            Arc::ptr_eq(&class, other)
+                || class
+                    .interfaces
+                    .iter()
+                    .any(|interface| interface.is_assignable_to(other))
+        })
+    }
+
+    /// The decoded byte code of a method, decoded the first time the method runs
+    pub fn decoded_code(&self, method_index: usize) -> Result<Arc<DecodedCode>, JvmError> {
+        if let Some(code) = self.code[method_index].get() {
+            return Ok(code.clone());
+        }
+
+        let method = &self.java_class.methods[method_index];
+        let Some(code) = method.get_code() else {
+            return Err(JvmError::Internal(format!(
+                "The method #{method_index} in '{}' has no byte code",
+                self.name
+            )));
+        };
+
+        let opcodes = parse_opcodes(&code.code).map_err(|err| {
+            JvmError::Internal(format!(
+                "Failed to decode byte code in '{}': {err}",
+                self.name
+            ))
+        })?;
+
+        Ok(self.code[method_index]
+            .get_or_init(|| Arc::new(DecodedCode::new(opcodes)))
+            .clone())
+    }
+}
+
+impl JVM {
+    pub(super) fn find_loaded_class(
+        &self,
+        loader: LoaderId,
+        name: &str,
+    ) -> Option<Arc<LoadedClass>> {
+        self.class_loaders.lock().unwrap()[loader.0]
+            .classes
+            .get(name)
+            .cloned()
+    }
+
+    /// Loads a class through `loader` following the parent delegation model, returns `None` if
+    /// none of the loaders in the chain could find it
+    pub(super) fn load_class(
+        &self,
+        loader: LoaderId,
+        name: &str,
+    ) -> Result<Option<Arc<LoadedClass>>, JvmError> {
+        if let Some(class) = self.find_loaded_class(loader, name) {
+            return Ok(Some(class));
+        }
+        if name.starts_with('[') {
+            return self.load_array_class(loader, name);
+        }
+
+        let (parent, object, user_defined) = {
+            let loaders = self.class_loaders.lock().unwrap();
+            let entry = &loaders[loader.0];
+            (entry.parent, entry.object, entry.user_defined)
+        };
+
+        let class = match (user_defined, object) {
+            (true, Some(object)) => self.load_class_with_java_loader(object, name)?,
+            _ => {
+                let from_parent = match parent {
+                    Some(parent) => self.load_class(parent, name)?,
+                    None => None,
+                };
+
+                match from_parent {
+                    Some(class) => Some(class),
+                    None => {
+                        let java_class = self.class_loaders.lock().unwrap()[loader.0]
+                            .class_path
+                            .get(name)
+                            .cloned();
+
+                        match java_class {
+                            Some(java_class) => {
+                                Some(self.define_from_class_path(loader, name, java_class)?)
+                            }
+                            None => self.load_class_from_jars(loader, name)?,
+                        }
+                    }
+                }
+            }
+        };
+
+        if let Some(class) = &class {
+            if class.name != name {
+                return Err(self.new_exception(
+                    "java/lang/NoClassDefFoundError",
+                    &format!("{name} (wrong name: {})", class.name),
+                ));
+            }
+
+            // Record the loader as an initiating loader, so later lookups don't go through java
+            self.class_loaders.lock().unwrap()[loader.0]
+                .classes
+                .entry(name.to_string())
+                .or_insert_with(|| class.clone());
+        }
+
+        Ok(class)
+    }
+
+    /// Defines a class of the class path of a builtin loader. It stays on the class path until
+    /// it is defined, so a definition that fails can be retried
+    fn define_from_class_path(
+        &self,
+        loader: LoaderId,
+        name: &str,
+        java_class: Arc<JavaClass<'static>>,
+    ) -> Result<Arc<LoadedClass>, JvmError> {
+        // Another thread can define the class at the same time
+        let class = match self.define_class(loader, java_class, None) {
+            Ok(class) => class,
+            Err(err) => match self.find_loaded_class(loader, name) {
+                Some(class) if class.loader == loader => return Ok(class),
+                _ => return Err(err),
+            },
+        };
+
+        self.class_loaders.lock().unwrap()[loader.0]
+            .class_path
+            .remove(name);
+        Ok(class)
+    }
+
+    /// Parses and defines a class from the first jar of a builtin loader that contains it
+    fn load_class_from_jars(
+        &self,
+        loader: LoaderId,
+        name: &str,
+    ) -> Result<Option<Arc<LoadedClass>>, JvmError> {
+        let jars = self.class_loaders.lock().unwrap()[loader.0].jars.clone();
+        let Some(java_class) = jars.iter().find_map(|jar| jar.read_class(name)) else {
+            return Ok(None);
+        };
+        let java_class = java_class.map_err(|err| match err {
+            ReadClassError::ClassFormat { .. } => {
+                self.new_exception("java/lang/ClassFormatError", &err.to_string())
+            }
+            ReadClassError::Io { .. } => {
+                self.new_exception("java/lang/NoClassDefFoundError", &err.to_string())
+            }
+        })?;
+
+        // Another thread can define the class while this one parses it
+        match self.define_class(loader, java_class, Some(name)) {
+            Ok(class) => Ok(Some(class)),
+            Err(err) => match self.find_loaded_class(loader, name) {
+                Some(class) if class.loader == loader => Ok(Some(class)),
+                _ => Err(err),
+            },
+        }
+    }
+
+    /// Creates the class of an array type (JVMS 5.3.3). Its defining loader is the one of its
+    /// element class, arrays of primitives belong to the bootstrap loader
+    fn load_array_class(
+        &self,
+        loader: LoaderId,
+        name: &str,
+    ) -> Result<Option<Arc<LoadedClass>>, JvmError> {
+        let component_type = &name[1..];
+        let component_class = match component_type.strip_prefix('L') {
+            Some(class_name) => Some(class_name.trim_end_matches(';')),
+            None => Some(component_type).filter(|component| component.starts_with('[')),
+        };
+
+        let defining_loader = match component_class {
+            Some(component_class) => match self.load_class(loader, component_class)? {
+                Some(class) => class.loader,
+                None => return Ok(None),
+            },
+            None => LoaderId::BOOTSTRAP,
+        };
+
+        let class =
+            self.define_builtin_class(defining_loader, name, || bootstrap::array_class(name))?;
+        self.class_loaders.lock().unwrap()[loader.0]
+            .classes
+            .entry(name.to_string())
+            .or_insert_with(|| class.clone());
+        Ok(Some(class))
+    }
+
+    /// The class of `int`, `void` and the other primitive types, so they can have mirrors
+    pub(super) fn primitive_class(&self, name: &str) -> Result<Arc<LoadedClass>, JvmError> {
+        self.define_builtin_class(LoaderId::BOOTSTRAP, name, || {
+            bootstrap::primitive_class(name)
+        })
+    }
+
+    /// Defines a class without a class file, unless another thread defined it first
+    fn define_builtin_class(
+        &self,
+        loader: LoaderId,
+        name: &str,
+        java_class: impl FnOnce() -> JavaClass<'static>,
+    ) -> Result<Arc<LoadedClass>, JvmError> {
+        let defined = || {
+            self.find_loaded_class(loader, name)
+                .filter(|class| class.loader == loader)
+        };
+        if let Some(class) = defined() {
+            return Ok(class);
+        }
+
+        match self.define_class(loader, java_class(), None) {
+            Ok(class) => Ok(class),
+            Err(err) => defined().ok_or(err),
+        }
+    }
+
+    /// The class a field descriptor, or the `V` return type, stands for
+    pub(super) fn class_of_descriptor(
+        &self,
+        loader: LoaderId,
+        descriptor: &str,
+    ) -> Result<Arc<LoadedClass>, JvmError> {
+        let primitive = match descriptor {
+            "B" => "byte",
+            "C" => "char",
+            "D" => "double",
+            "F" => "float",
+            "I" => "int",
+            "J" => "long",
+            "S" => "short",
+            "Z" => "boolean",
+            "V" => "void",
+            _ => {
+                let class_name = descriptor
+                    .strip_prefix('L')
+                    .and_then(|class_name| class_name.strip_suffix(';'))
+                    .unwrap_or(descriptor);
+                return self.resolve_class(loader, class_name);
+            }
+        };
+        self.primitive_class(primitive)
+    }
+
+    fn load_class_with_java_loader(
+        &self,
+        loader_object: JavaObjectRef,
+        name: &str,
+    ) -> Result<Option<Arc<LoadedClass>>, JvmError> {
+        let result = self.invoke_virtual(
+            loader_object,
+            "loadClass",
+            "(Ljava/lang/String;)Ljava/lang/Class;",
+            vec![StackValue::String(name.replace('/', "."))],
+        );
+
+        match result {
+            Ok(StackValue::JavaObjectRef(mirror)) => Ok(self.class_from_mirror(mirror)),
+            Ok(_) => Ok(None),
+            Err(JvmError::Exception(exception))
+                if self.is_instance_of_name(exception, "java/lang/ClassNotFoundException") =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Loads a class and throws `NoClassDefFoundError` if it can't be found
+    pub(super) fn resolve_class(
+        &self,
+        loader: LoaderId,
+        name: &str,
+    ) -> Result<Arc<LoadedClass>, JvmError> {
+        match self.load_class(loader, name)? {
+            Some(class) => Ok(class),
+            None => Err(self.new_exception("java/lang/NoClassDefFoundError", name)),
+        }
+    }
+
+    /// Makes `loader` the defining loader of `java_class` (JVMS 5.3.5). `expected_name` is the
+    /// name the class was requested by, if any
+    pub(super) fn define_class(
+        &self,
+        loader: LoaderId,
+        java_class: impl Into<Arc<JavaClass<'static>>>,
+        expected_name: Option<&str>,
+    ) -> Result<Arc<LoadedClass>, JvmError> {
+        let java_class = java_class.into();
+        let Some(name) = java_class.get_name().map(|name| name.to_string()) else {
+            return Err(self.new_exception(
+                "java/lang/ClassFormatError",
+                "Invalid this_class constant pool index",
+            ));
+        };
+
+        let requested_name = expected_name.unwrap_or(&name);
+        if loader != LoaderId::BOOTSTRAP && requested_name.starts_with("java/") {
+            let package = requested_name
+                .rsplit_once('/')
+                .map(|(package, _)| package)
+                .unwrap_or_default();
+            return Err(self.new_exception(
+                "java/lang/SecurityException",
+                &format!("Prohibited package name: {}", package.replace('/', ".")),
+            ));
+        }
+
+        if requested_name != name {
+            return Err(self.new_exception(
+                "java/lang/NoClassDefFoundError",
+                &format!("{requested_name} (wrong name: {name})"),
+            ));
+        }
+
+        let super_class = match java_class.get_super_name() {
+            Some(super_name) => Some(self.resolve_class(loader, super_name)?),
+            None => None,
+        };
+
+        let interfaces = java_class
+            .get_interface_names()
+            .into_iter()
+            .map(|interface| self.resolve_class(loader, interface))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let class = Arc::new(LoadedClass {
+            name: name.clone(),
+            loader,
+            code: (0..java_class.methods.len())
+                .map(|_| OnceLock::new())
+                .collect(),
+            runtime_constant_pool: RuntimeConstantPool::new(
+                java_class.constant_pool.pool_entries.len(),
+            ),
+            java_class,
+            super_class,
+            interfaces,
+            statics: Mutex::new(HashMap::new()),
+            init_state: Mutex::new(InitState::Uninitialized),
+            init_done: Condvar::new(),
+            mirror: OnceLock::new(),
+            nest_host: OnceLock::new(),
+        });
+
+        // The check and the insert hold the lock together, two threads can't both define a class
+        let duplicate = {
+            let mut loaders = self.class_loaders.lock().unwrap();
+            let classes = &mut loaders[loader.0].classes;
+            match classes.get(&name) {
+                Some(existing) if existing.loader == loader => true,
+                _ => {
+                    classes.insert(name.clone(), class.clone());
+                    false
+                }
+            }
+        };
+        if duplicate {
+            return Err(self.new_exception(
+                "java/lang/LinkageError",
+                &format!(
+                    "loader {} attempted duplicate class definition for {}.",
+                    self.describe_loader(loader),
+                    name.replace('/', ".")
+                ),
+            ));
+        }
+
+        Ok(class)
+    }
+
+    /// Registers a loader implemented in java, `object` is the `java/lang/ClassLoader` instance
+    pub(super) fn create_class_loader(
+        &self,
+        parent: Option<LoaderId>,
+        object: JavaObjectRef,
+    ) -> LoaderId {
+        let mut loaders = self.class_loaders.lock().unwrap();
+        loaders.push(ClassLoaderEntry {
+            parent,
+            object: Some(object),
+            user_defined: true,
+            classes: HashMap::new(),
+            class_path: HashMap::new(),
+            jars: vec![],
+        });
+        LoaderId(loaders.len() - 1)
+    }
+
+    /// Names a loader the way HotSpot does in error messages, e.g. `com.example.MyLoader @2`
+    pub(super) fn describe_loader(&self, loader: LoaderId) -> String {
+        match loader {
+            LoaderId::BOOTSTRAP => "'bootstrap'".to_string(),
+            LoaderId::APP => "'app'".to_string(),
+            LoaderId(id) => {
+                let object = self.class_loaders.lock().unwrap()[id].object;
+                let class_name = object
+                    .and_then(|object| self.runtime_class(&StackValue::JavaObjectRef(object)).ok())
+                    .map(|class| class.name.replace('/', "."))
+                    .unwrap_or_default();
+                format!("{class_name} @{id}")
+            }
+        }
+    }
+
+    pub(super) fn parent_loader(&self, loader: LoaderId) -> Option<LoaderId> {
+        self.class_loaders.lock().unwrap()[loader.0].parent
+    }
+
+    /// The `java/lang/ClassLoader` object of a loader, `null` for the bootstrap loader
+    pub(super) fn class_loader_object(&self, loader: LoaderId) -> Result<StackValue, JvmError> {
+        if loader == LoaderId::BOOTSTRAP {
+            return Ok(StackValue::Null);
+        }
+
+        if let Some(object) = self.class_loaders.lock().unwrap()[loader.0].object {
+            return Ok(StackValue::JavaObjectRef(object));
+        }
+
+        let loader_class = self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/ClassLoader")?;
+        let object = self.new_object(&loader_class, NativeData::ClassLoader(loader))?;
+
+        let mut loaders = self.class_loaders.lock().unwrap();
+        Ok(StackValue::JavaObjectRef(
+            *loaders[loader.0].object.get_or_insert(object),
+        ))
+    }
+
+    /// The loader a `java/lang/ClassLoader` object represents, `None` if its constructor never ran
+    pub(super) fn loader_of_object(&self, object: JavaObjectRef) -> Option<LoaderId> {
+        match self.heap.lock().unwrap().get(object) {
+            HeapObject::Instance(instance) => match instance.native_data {
+                NativeData::ClassLoader(loader) => Some(loader),
+                _ => None,
+            },
+            HeapObject::Array(_) => None,
+        }
+    }
+
+    /// The `java/lang/Class` object of a class, created the first time it is asked for
+    pub(super) fn class_mirror(&self, class: &Arc<LoadedClass>) -> Result<JavaObjectRef, JvmError> {
+        if let Some(mirror) = class.mirror.get() {
+            return Ok(*mirror);
+        }
+
+        let class_class = self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/Class")?;
+        let mirror = self.new_object(&class_class, NativeData::Class(class.clone()))?;
+
+        Ok(*class.mirror.get_or_init(|| mirror))
+    }
+
+    pub(super) fn class_from_mirror(&self, mirror: JavaObjectRef) -> Option<Arc<LoadedClass>> {
+        match self.heap.lock().unwrap().get(mirror) {
+            HeapObject::Instance(instance) => match &instance.native_data {
+                NativeData::Class(class) => Some(class.clone()),
+                _ => None,
+            },
+            HeapObject::Array(_) => None,
+        }
+    }
+
+    /// The nest host of a class, the class itself when its `NestHost` isn't valid (JVMS 5.4.4)
+    pub(super) fn nest_host(&self, class: &Arc<LoadedClass>) -> Arc<LoadedClass> {
+        let host = class.nest_host.get_or_init(|| {
+            // A host that can't be loaded, is in another runtime package or doesn't list the
+            // class as a member doesn't count
+            let host_name = class.java_class.get_nest_host_name()?;
+            let host = self.resolve_class(class.loader, host_name).ok()?;
+
+            let package = |name: &str| {
+                name.rsplit_once('/')
+                    .map(|(package, _)| package.to_string())
+            };
+            let valid = host.loader == class.loader
+                && package(&host.name) == package(&class.name)
+                && host
+                    .java_class
+                    .get_nest_member_names()
+                    .contains(&class.name.as_str());
+            valid.then_some(host)
+        });
+
+        host.clone().unwrap_or_else(|| class.clone())
+    }
+
+    pub(super) fn are_nestmates(&self, a: &Arc<LoadedClass>, b: &Arc<LoadedClass>) -> bool {
+        Arc::ptr_eq(a, b) || Arc::ptr_eq(&self.nest_host(a), &self.nest_host(b))
+    }
+
+    /// Runs the static initializer of a class and its super classes (JVMS 5.5). The class is
+    /// verified first if verification is turned on
+    pub(super) fn initialize_class(&self, class: &Arc<LoadedClass>) -> Result<(), JvmError> {
+        if self.verify
+            && class.loader != LoaderId::BOOTSTRAP
+            && *class.init_state.lock().unwrap() == InitState::Uninitialized
+        {
+            self.verify_class(class)?;
+        }
+
+        let current_thread = self.current_thread_id();
+        {
+            let mut state = class.init_state.lock().unwrap();
+            // Other threads wait for the initializing one, which itself sees the class as
+            // initialized when it gets here again through a recursive request
+            if matches!(*state, InitState::InProgress(thread) if thread != current_thread) {
+                drop(state);
+                self.safe_region(|| {
+                    let mut state = class.init_state.lock().unwrap();
+                    while matches!(*state, InitState::InProgress(thread) if thread != current_thread)
+                    {
+                        state = class.init_done.wait(state).unwrap();
+                    }
+                });
+                // A class that was in progress can only be initialized or failed by now
+                state = class.init_state.lock().unwrap();
+            }
+
+            match *state {
+                InitState::Initialized | InitState::InProgress(_) => return Ok(()),
+                InitState::Failed => {
+                    return Err(self.new_exception(
+                        "java/lang/NoClassDefFoundError",
+                        &format!(
+                            "Could not initialize class {}",
+                            class.name.replace('/', ".")
+                        ),
+                    ))
+                }
+                InitState::Uninitialized => *state = InitState::InProgress(current_thread),
+            }
+        }
+
+        let result = self.run_static_initializer(class);
+
+        *class.init_state.lock().unwrap() = match result {
+            Ok(_) => InitState::Initialized,
+            Err(_) => InitState::Failed,
+        };
+        class.init_done.notify_all();
+
+        result
+    }
+
+    fn run_static_initializer(&self, class: &Arc<LoadedClass>) -> Result<(), JvmError> {
+        if let Some(super_class) = &class.super_class {
+            self.initialize_class(super_class)?;
+        }
+
+        let constant_pool = &class.java_class.constant_pool;
+        for field in &class.java_class.fields {
+            if field.access_flags & FieldAccessFlags::ACC_STATIC == 0 {
+                continue;
+            }
+            let Some(constant) = field.get_constant_value() else {
+                continue;
+            };
+
+            let value = match constant_pool.get_at(constant.constantvalue_index) {
+                Some(CpInfo::Integer(int)) => StackValue::Integer(int.bytes),
+                Some(CpInfo::Float(float)) => StackValue::Float(float.bytes),
+                Some(CpInfo::Long(long)) => StackValue::Long(long.bytes as i64),
+                Some(CpInfo::Double(double)) => StackValue::Double(double.bytes),
+                Some(CpInfo::String(string)) => StackValue::String(
+                    constant_pool
+                        .get_utf8_at(string.string_index)
+                        .map(|utf8| utf8.data.to_string())
+                        .unwrap_or_default(),
+                ),
+                _ => continue,
+            };
+
+            let name = constant_pool
+                .get_utf8_at(field.name_index)
+                .map(|utf8| utf8.data.to_string())
+                .unwrap_or_default();
+            class.statics.lock().unwrap().insert(name, value);
+        }
+
+        // Shims have a native binding instead
+        let Some(clinit) = self.find_declared_method(class, "<clinit>", "()V") else {
+            return Ok(());
+        };
+
+        // An exception that isn't an error is wrapped (JVMS 5.5 step 11)
+        let scope = self.handle_scope();
+        match self.invoke_method(clinit, vec![]) {
+            Err(JvmError::Exception(exception))
+                if !self.is_instance_of_name(exception, "java/lang/Error") =>
+            {
+                scope.add(exception);
+                let error = self.new_exception("java/lang/ExceptionInInitializerError", "");
+                if let JvmError::Exception(error) = error {
+                    self.set_field(error, "cause", StackValue::JavaObjectRef(exception));
+                }
+                Err(error)
+            }
+            result => result.map(|_| ()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LoaderId;
+    use crate::jvm::{test_class, test_class_bytes, JvmError, StackValue, JVM};
+
+    #[test]
+    fn classes_defined_by_a_loader_belong_to_it() {
+        let jvm = JVM::with_classes(["ClassLoaders", "ClassLoaders$Loader"].map(test_class));
+        let error = jvm
+            .attached(|| {
+                // The array is only referenced from here, the scope keeps it alive
+                let scope = jvm.handle_scope();
+                let array = jvm.new_byte_array(&test_class_bytes("ClassLoaders$Defined"))?;
+                scope.add(array);
+
+                let define = |times| {
+                    jvm.invoke_static(
+                        "ClassLoaders",
+                        "define",
+                        "([BI)Z",
+                        vec![StackValue::JavaObjectRef(array), StackValue::Integer(times)],
+                    )
+                };
+                // Every loader has its own namespace, so each one can define the class once
+                for _ in 0..2 {
+                    assert!(matches!(define(1), Ok(StackValue::Integer(1))));
+                }
+                define(2)
+            })
+            .unwrap_err();
+        assert!(error.starts_with("java.lang.LinkageError: "), "{error}");
+        assert!(jvm
+            .find_loaded_class(LoaderId::APP, "ClassLoaders$Defined")
+            .is_none());
+    }
+
+    #[test]
+    fn failed_initializers_leave_the_class_unusable() {
+        let jvm = JVM::with_classes(["ClassLoaders", "ClassLoaders$Boom"].map(test_class));
+
+        let run = || {
+            jvm.run_static("ClassLoaders$Boom", "run", "()V", vec![])
+                .unwrap_err()
+        };
+        assert_eq!(run(), "java.lang.ExceptionInInitializerError");
+        assert_eq!(
+            run(),
+            "java.lang.NoClassDefFoundError: Could not initialize class ClassLoaders$Boom"
+        );
+    }
+
+    #[test]
+    fn failed_definitions_can_be_retried() {
+        let jvm = JVM::with_classes(["ClassLoaders", "ClassLoaders$Child"].map(test_class));
+
+        // The class stays on the class path, so both attempts fail on its super class
+        for _ in 0..2 {
+            let error = jvm
+                .run_static("ClassLoaders$Child", "run", "()V", vec![])
+                .unwrap_err();
+            assert_eq!(error, "java.lang.NoClassDefFoundError: ClassLoaders$Parent");
+        }
+    }
+
+    #[test]
+    fn duplicate_definitions_throw() {
+        let jvm = JVM::with_classes([]);
+
+        let define = || {
+            let class = test_class("ClassLoaders$Defined");
+            jvm.define_class(LoaderId::APP, class, None)
+        };
+        assert!(define().is_ok());
+        let error = match define() {
+            Err(JvmError::Exception(exception)) => jvm.describe_exception(exception),
+            _ => panic!("the second definition succeeded"),
+        };
+        assert_eq!(
+            error,
+            "java.lang.LinkageError: loader 'app' attempted duplicate class definition for \
+             ClassLoaders$Defined."
+        );
+    }
+}
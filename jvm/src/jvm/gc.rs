@@ -0,0 +1,418 @@
+//! A stop-the-world mark-sweep garbage collector for the java heap.
+//!
+//! Threads stop at safepoints: between two instructions, while they are blocked and while they
+//! allocate. A collection waits until every other thread has stopped, marks the objects that can
+//! be reached from the roots and frees the rest. Objects never move, so references stay valid.
+//!
+//! The roots are the locals and operand stacks of every frame, the references an instruction
+//! popped and still uses, static fields, class mirrors, class loader and thread objects, errors
+//! and constants cached by the runtime constant pools, locked monitors and the native handles of
+//! rust code.
+//! Strings are values rather than heap objects, so there is no intern table to scan.
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fmt,
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
+
+use super::{
+    heap::{HeapObject, HeapStats, JavaObject, NativeData},
+    monitors::MonitorKey,
+    threads::JavaThread,
+    JavaObjectRef, JvmError, StackValue, JVM,
+};
+
+thread_local! {
+    /// The calling OS thread left the safe region, so it is counted in `SafepointState::running`
+    static RUNNING: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Debug, Default)]
+pub struct SafepointState {
+    /// Attached threads that aren't stopped at a safepoint
+    running: usize,
+    /// A collection or heap dump is in progress, threads that reach a safepoint wait for it to
+    /// finish
+    collecting: bool,
+}
+
+/// Why a collection ran, `-Xlog:gc` prints it the way HotSpot does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GcCause {
+    /// An allocation took the heap past its threshold
+    AllocationFailure,
+    /// `System.gc()` or `Runtime.gc()`
+    SystemGc,
+}
+
+impl fmt::Display for GcCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GcCause::AllocationFailure => write!(f, "Allocation Failure"),
+            GcCause::SystemGc => write!(f, "System.gc()"),
+        }
+    }
+}
+
+/// Keeps objects that only rust code refers to alive, like JNI local references do. The objects
+/// are roots until the scope is dropped
+pub struct HandleScope {
+    thread: Option<Arc<JavaThread>>,
+    base: usize,
+}
+
+impl HandleScope {
+    pub fn add(&self, object: JavaObjectRef) {
+        if let Some(thread) = &self.thread {
+            thread.handles.lock().unwrap().push(object);
+        }
+    }
+}
+
+impl Drop for HandleScope {
+    fn drop(&mut self) {
+        if let Some(thread) = &self.thread {
+            thread.handles.lock().unwrap().truncate(self.base);
+        }
+    }
+}
+
+impl JVM {
+    /// Sets `-Xmx`, the size in bytes the heap can't grow past
+    pub fn set_max_heap_size(&mut self, max_size: usize) {
+        let heap = self.heap.get_mut().unwrap();
+        heap.max_size = max_size;
+        heap.threshold = heap.threshold.min(max_size);
+    }
+
+    /// Prints every collection like `-Xlog:gc` does
+    pub fn set_gc_log(&mut self, gc_log: bool) {
+        self.gc_log = gc_log;
+    }
+
+    /// Counts the objects on the heap and their sizes by class
+    pub fn heap_stats(&self) -> HeapStats {
+        self.heap.lock().unwrap().stats()
+    }
+
+    /// Allocates an object, collecting garbage first when the heap has grown past its threshold
+    pub(super) fn allocate(&self, object: HeapObject) -> Result<JavaObjectRef, JvmError> {
+        self.reserve(object.size())?;
+        Ok(self.heap.lock().unwrap().allocate(object))
+    }
+
+    /// Makes room for `size` bytes, collecting garbage first when the heap has grown past its
+    /// threshold. Large arrays reserve their size before their elements are built, so a length
+    /// the heap can't hold throws `OutOfMemoryError` instead of aborting the process
+    pub(super) fn reserve(&self, size: usize) -> Result<(), JvmError> {
+        {
+            let heap = self.heap.lock().unwrap();
+            if heap.used().saturating_add(size) <= heap.threshold {
+                return Ok(());
+            }
+        }
+
+        self.collect_garbage(GcCause::AllocationFailure);
+
+        let mut heap = self.heap.lock().unwrap();
+        let needed = heap.used().saturating_add(size);
+        if needed <= heap.max_size {
+            // The allocation that follows shouldn't collect a second time
+            heap.threshold = heap.threshold.max(needed);
+            return Ok(());
+        }
+        drop(heap);
+        Err(self.out_of_memory())
+    }
+
+    /// The error is allocated past the maximum size, there wouldn't be room for it otherwise
+    pub(super) fn out_of_memory(&self) -> JvmError {
+        self.dump_heap_on_out_of_memory();
+
+        let class = match self.resolve_class(
+            super::class_loader::LoaderId::BOOTSTRAP,
+            "java/lang/OutOfMemoryError",
+        ) {
+            Ok(class) => class,
+            Err(err) => return err,
+        };
+
+        let exception = self
+            .heap
+            .lock()
+            .unwrap()
+            .allocate(HeapObject::Instance(JavaObject {
+                class,
+                fields: HashMap::from([(
+                    "detailMessage".to_string(),
+                    StackValue::String("Java heap space".to_string()),
+                )]),
+                native_data: NativeData::None,
+            }));
+        JvmError::Exception(exception)
+    }
+
+    pub(super) fn handle_scope(&self) -> HandleScope {
+        let thread = self.current_thread();
+        let base = thread
+            .as_ref()
+            .map(|thread| thread.handles.lock().unwrap().len())
+            .unwrap_or_default();
+        HandleScope { thread, base }
+    }
+
+    /// Stops every other thread at a safepoint, then frees the objects that can't be reached
+    pub(super) fn collect_garbage(&self, cause: GcCause) {
+        self.stop_the_world(|| {
+            let start = Instant::now();
+            let (collection, before, after, max_size) = self.mark_and_sweep();
+            if self.gc_log {
+                const KB: usize = 1024;
+                println!(
+                    "[{:.3}s][info][gc] GC({collection}) Pause Full ({cause}) {}K->{}K({}K) {:.3}ms",
+                    self.started.elapsed().as_secs_f64(),
+                    before / KB,
+                    after / KB,
+                    max_size / KB,
+                    start.elapsed().as_secs_f64() * 1000.0
+                );
+            }
+        });
+    }
+
+    /// Runs `f` once every other thread is stopped at a safepoint. Returns `None` without running
+    /// it if another thread stopped the world first, after waiting for that thread to finish
+    pub(super) fn stop_the_world<T>(&self, f: impl FnOnce() -> T) -> Option<T> {
+        // Rust code that isn't running on a java thread isn't counted as running, a thread that
+        // is attaching is counted before its `java/lang/Thread` object is allocated
+        let attached = RUNNING.with(Cell::get);
+
+        let mut state = self.safepoint.lock().unwrap();
+        if state.collecting {
+            drop(state);
+            if attached {
+                self.safe_region(|| ());
+            } else {
+                self.wait_for_safepoint_end();
+            }
+            return None;
+        }
+
+        state.collecting = true;
+        self.gc_requested.store(true, Ordering::SeqCst);
+        if attached {
+            state.running -= 1;
+        }
+        while state.running > 0 {
+            state = self.safepoint_changed.wait(state).unwrap();
+        }
+        drop(state);
+
+        let result = f();
+
+        let mut state = self.safepoint.lock().unwrap();
+        state.collecting = false;
+        self.gc_requested.store(false, Ordering::SeqCst);
+        if attached {
+            state.running += 1;
+        }
+        self.safepoint_changed.notify_all();
+        Some(result)
+    }
+
+    fn wait_for_safepoint_end(&self) {
+        let mut state = self.safepoint.lock().unwrap();
+        while state.collecting {
+            state = self.safepoint_changed.wait(state).unwrap();
+        }
+    }
+
+    /// Returns the number of the collection, and the heap occupancy before and after it and the
+    /// maximum size of the heap in bytes
+    fn mark_and_sweep(&self) -> (usize, usize, usize, usize) {
+        let mut pending = self.gc_roots();
+        let mut heap = self.heap.lock().unwrap();
+        let before = heap.used();
+
+        let mut marked = vec![false; heap.capacity()];
+        while let Some(object) = pending.pop() {
+            if marked[object.index] {
+                continue;
+            }
+            marked[object.index] = true;
+            pending.extend(heap.get(object).references());
+        }
+
+        heap.sweep(&marked);
+        (heap.collections - 1, before, heap.used(), heap.max_size)
+    }
+
+    fn gc_roots(&self) -> Vec<JavaObjectRef> {
+        let mut roots = vec![];
+        let reference = |value: &StackValue| match value {
+            StackValue::JavaObjectRef(object) => Some(*object),
+            _ => None,
+        };
+
+        for thread in self.live_threads() {
+            roots.push(thread.object);
+            roots.extend(thread.handles.lock().unwrap().iter());
+            for frame in thread.frames.lock().unwrap().iter() {
+                let values = frame.lock().unwrap();
+                roots.extend(
+                    values
+                        .locals
+                        .iter()
+                        .chain(&values.stack)
+                        .filter_map(reference),
+                );
+                roots.extend(&values.popped);
+            }
+        }
+
+        for loader in self.class_loaders.lock().unwrap().iter() {
+            roots.extend(loader.object);
+            for class in loader.classes.values() {
+                roots.extend(class.mirror());
+                roots.extend(class.runtime_constant_pool.cached_objects());
+                roots.extend(class.statics.lock().unwrap().values().filter_map(reference));
+            }
+        }
+
+        roots.extend(
+            self.locked_monitors()
+                .into_iter()
+                .filter_map(|key| match key {
+                    MonitorKey::Object(object) => Some(object),
+                    MonitorKey::String(_) => None,
+                }),
+        );
+
+        roots
+    }
+
+    /// Stops at a safepoint if a collection is waiting for this thread, called between
+    /// instructions
+    pub(super) fn safepoint_poll(&self) {
+        if self.gc_requested.load(Ordering::Relaxed) {
+            self.safe_region(|| ());
+        }
+    }
+
+    /// Runs `f` as if the thread was stopped at a safepoint, so collections can run meanwhile.
+    /// `f` may block, but it must not allocate or touch references that aren't rooted
+    pub(super) fn safe_region<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.enter_safe_region();
+        let result = f();
+        self.leave_safe_region();
+        result
+    }
+
+    /// Called when a thread terminates or blocks, collections don't wait for it anymore
+    pub(super) fn enter_safe_region(&self) {
+        let mut state = self.safepoint.lock().unwrap();
+        state.running -= 1;
+        RUNNING.with(|running| running.set(false));
+        self.safepoint_changed.notify_all();
+    }
+
+    /// Called when a thread attaches to the vm or unblocks, waits for a running collection first
+    pub(super) fn leave_safe_region(&self) {
+        let mut state = self.safepoint.lock().unwrap();
+        while state.collecting {
+            state = self.safepoint_changed.wait(state).unwrap();
+        }
+        state.running += 1;
+        RUNNING.with(|running| running.set(true));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GcCause;
+    use crate::jvm::{
+        heap::{Heap, HeapObject},
+        test_class, StackValue, JVM,
+    };
+
+    fn gc(max_heap_size: usize) -> std::sync::Arc<JVM> {
+        let mut jvm = JVM::with_classes(["Gc", "Gc$Node"].map(test_class));
+        std::sync::Arc::get_mut(&mut jvm)
+            .unwrap()
+            .set_max_heap_size(max_heap_size);
+        jvm
+    }
+
+    #[test]
+    fn arrays_past_the_max_heap_size_throw() {
+        let jvm = gc(16 * 1024 * 1024);
+        let longs = |n| {
+            jvm.run_static("Gc", "longs", "(I)I", vec![StackValue::Integer(n)])
+                .unwrap()
+        };
+        assert!(matches!(longs(200_000_000), StackValue::Integer(-1)));
+        // Every element is a whole `StackValue`, a million of them don't fit in 16MB
+        assert!(matches!(longs(1_000_000), StackValue::Integer(-1)));
+        assert!(matches!(longs(1_000), StackValue::Integer(1_000)));
+    }
+
+    #[test]
+    fn field_writes_count_towards_the_heap_size() {
+        let jvm = gc(1024 * 1024);
+        let fill = |n| jvm.run_static("Gc", "fill", "(I)Z", vec![StackValue::Integer(n)]);
+        // The nodes fit while their fields are unset, the next allocation doesn't once they are
+        assert!(matches!(fill(10_000), Ok(StackValue::Integer(1))));
+        assert!(matches!(fill(100), Ok(StackValue::Integer(0))));
+    }
+
+    #[test]
+    fn static_fields_keep_objects_alive() {
+        let jvm = gc(Heap::DEFAULT_MAX_SIZE);
+        jvm.run_static("Gc", "keep", "()V", vec![]).unwrap();
+        let used = jvm.heap.lock().unwrap().used();
+
+        jvm.attached(|| {
+            jvm.collect_garbage(GcCause::SystemGc);
+            Ok(())
+        })
+        .unwrap();
+        assert!(jvm.heap.lock().unwrap().used() < used);
+        let Some(StackValue::JavaObjectRef(array)) = jvm.static_value("Gc", "kept") else {
+            panic!("the array isn't in the static field");
+        };
+        assert!(matches!(
+            jvm.heap.lock().unwrap().get(array),
+            HeapObject::Array(array) if array.values.len() == 10
+        ));
+    }
+
+    #[test]
+    fn explicit_collections_free_unreachable_objects() {
+        let jvm = JVM::with_classes([]);
+        let stats = jvm
+            .attached(|| {
+                for _ in 0..10 {
+                    jvm.new_array("I", vec![StackValue::Integer(0); 100])?;
+                }
+                let before = jvm.heap_stats();
+                let arrays = &before.classes["[I"];
+                assert_eq!(arrays.instances, 10);
+                assert_eq!(arrays.bytes, 10 * HeapObject::array_size(100));
+                let bytes: usize = before.classes.values().map(|class| class.bytes).sum();
+                assert_eq!(bytes, before.used);
+
+                jvm.collect_garbage(GcCause::SystemGc);
+                let stats = jvm.heap_stats();
+                assert_eq!(stats.used, before.used - arrays.bytes);
+                Ok(stats)
+            })
+            .unwrap();
+        assert_eq!(stats.collections, 1);
+        assert!(!stats.classes.contains_key("[I"));
+        // Logged as `Pause Full (System.gc())`, like HotSpot
+        assert_eq!(GcCause::SystemGc.to_string(), "System.gc()");
+    }
+}
@@ -0,0 +1,267 @@
+use std::{collections::HashMap, sync::Arc};
+
+use jvm_parser::classfile::signature::TypeArgument;
+
+use super::{
+    class_loader::{LoadedClass, LoaderId},
+    method_handles::MethodHandle,
+    threads::JavaThread,
+    JavaObjectRef, StackValue,
+};
+
+#[derive(Debug)]
+pub enum HeapObject {
+    Instance(JavaObject),
+    Array(JavaArray),
+}
+
+#[derive(Debug)]
+pub struct JavaObject {
+    pub class: Arc<LoadedClass>,
+    /// Instance fields by name, fields that were never written are missing and read as their default value
+    pub fields: HashMap<String, StackValue>,
+    /// State the vm keeps for objects that have a native counterpart
+    pub native_data: NativeData,
+}
+
+#[derive(Debug, Default)]
+pub enum NativeData {
+    #[default]
+    None,
+    /// A `java/lang/Class` mirror of a loaded class
+    Class(Arc<LoadedClass>),
+    /// A `java/lang/ClassLoader` and the loader it represents
+    ClassLoader(LoaderId),
+    /// A `java/lang/Thread` that the vm created a thread for
+    Thread(Arc<JavaThread>),
+    /// A `java/lang/reflect/Field`, the declaring class and the index of the field in its class file
+    Field(Arc<LoadedClass>, usize),
+    /// A `java/lang/reflect/Method`, the declaring class and the index of the method in its class
+    /// file
+    Method(Arc<LoadedClass>, usize),
+    /// A `java/lang/reflect/Type` that isn't a `Class`, the type from a generic signature and the
+    /// loader that resolves its classes. Only wildcards use the bounds of the type argument
+    GenericType(LoaderId, TypeArgument),
+    /// A `java/lang/invoke/MethodType` and its method descriptor
+    MethodType(String),
+    /// A `java/lang/invoke/MethodHandle` and the field or method it refers to
+    MethodHandle(Arc<MethodHandle>),
+}
+
+#[derive(Debug)]
+pub struct JavaArray {
+    /// The field descriptor of the elements, e.g. `I` or `Ljava/lang/String;`
+    pub component_type: String,
+    pub values: Vec<StackValue>,
+}
+
+/// The bytes every object takes up besides its fields or elements
+const HEADER_SIZE: usize = 16;
+/// Every field and element is held as a `StackValue`
+const VALUE_SIZE: usize = std::mem::size_of::<StackValue>();
+
+fn value_size(value: &StackValue) -> usize {
+    match value {
+        StackValue::String(string) => VALUE_SIZE + string.len(),
+        _ => VALUE_SIZE,
+    }
+}
+
+impl HeapObject {
+    /// An estimate of the bytes the object takes up, a `StackValue` per field or element plus
+    /// the characters of strings
+    pub fn size(&self) -> usize {
+        HEADER_SIZE
+            + match self {
+                HeapObject::Instance(instance) => instance.fields.values().map(value_size).sum(),
+                HeapObject::Array(array) => array.values.iter().map(value_size).sum::<usize>(),
+            }
+    }
+
+    /// The size of an array of `length` elements that aren't strings, known before its elements
+    /// are built
+    pub fn array_size(length: usize) -> usize {
+        length
+            .saturating_mul(VALUE_SIZE)
+            .saturating_add(HEADER_SIZE)
+    }
+
+    /// The objects this one refers to
+    pub fn references(&self) -> impl Iterator<Item = JavaObjectRef> + '_ {
+        let values: Box<dyn Iterator<Item = &StackValue>> = match self {
+            HeapObject::Instance(instance) => Box::new(instance.fields.values()),
+            HeapObject::Array(array) => Box::new(array.values.iter()),
+        };
+
+        values.filter_map(|value| match value {
+            StackValue::JavaObjectRef(object) => Some(*object),
+            _ => None,
+        })
+    }
+}
+
+/// A class histogram of the heap, like `jmap -histo` prints
+#[derive(Debug, Clone, Default)]
+pub struct HeapStats {
+    /// The estimated size of the objects in bytes, the sum of the bytes of every class
+    pub used: usize,
+    pub max_size: usize,
+    /// The number of garbage collections so far
+    pub collections: usize,
+    /// Keyed by class name, arrays are named by their descriptor like `[I`
+    pub classes: HashMap<String, ClassStats>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClassStats {
+    pub instances: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug)]
+pub struct Heap {
+    /// The slots of collected objects are `None` until an allocation reuses them
+    objects: Vec<Option<HeapObject>>,
+    free_slots: Vec<usize>,
+    /// The estimated size of the objects in bytes, it follows every allocation and write
+    used: usize,
+    /// `-Xmx`, an allocation that doesn't fit after a collection throws `OutOfMemoryError`
+    pub max_size: usize,
+    /// An allocation that takes `used` past this collects garbage first
+    pub threshold: usize,
+    pub collections: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            objects: vec![],
+            free_slots: vec![],
+            used: 0,
+            max_size: Heap::DEFAULT_MAX_SIZE,
+            threshold: Heap::MIN_THRESHOLD,
+            collections: 0,
+        }
+    }
+}
+
+impl Heap {
+    pub const DEFAULT_MAX_SIZE: usize = 256 * 1024 * 1024;
+    pub const MIN_THRESHOLD: usize = 1024 * 1024;
+
+    pub fn allocate(&mut self, object: HeapObject) -> JavaObjectRef {
+        self.used += object.size();
+
+        let index = match self.free_slots.pop() {
+            Some(index) => {
+                self.objects[index] = Some(object);
+                index
+            }
+            None => {
+                self.objects.push(Some(object));
+                self.objects.len() - 1
+            }
+        };
+        JavaObjectRef { index }
+    }
+
+    pub fn get(&self, object_ref: JavaObjectRef) -> &HeapObject {
+        self.objects[object_ref.index]
+            .as_ref()
+            .expect("a reachable object was collected")
+    }
+
+    pub fn get_mut(&mut self, object_ref: JavaObjectRef) -> &mut HeapObject {
+        self.objects[object_ref.index]
+            .as_mut()
+            .expect("a reachable object was collected")
+    }
+
+    /// Writes a field of an instance. `used` follows the size of the value, so an object whose
+    /// fields are written after it was allocated still counts fully towards the next collection
+    pub fn set_field(&mut self, object_ref: JavaObjectRef, name: &str, value: StackValue) {
+        let HeapObject::Instance(instance) = self.get_mut(object_ref) else {
+            return;
+        };
+        let added = value_size(&value);
+        let removed = instance
+            .fields
+            .insert(name.to_string(), value)
+            .map_or(0, |old| value_size(&old));
+        self.used = (self.used + added).saturating_sub(removed);
+    }
+
+    /// Writes an element of an array, `used` follows the size of the value like for fields
+    pub fn set_element(&mut self, array_ref: JavaObjectRef, index: usize, value: StackValue) {
+        let HeapObject::Array(array) = self.get_mut(array_ref) else {
+            return;
+        };
+        let added = value_size(&value);
+        let removed = value_size(&std::mem::replace(&mut array.values[index], value));
+        self.used = (self.used + added).saturating_sub(removed);
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// The number of slots, live or not, references index into them
+    pub fn capacity(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// The objects that haven't been collected
+    pub fn objects(&self) -> impl Iterator<Item = (JavaObjectRef, &HeapObject)> {
+        self.objects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, object)| {
+                object
+                    .as_ref()
+                    .map(|object| (JavaObjectRef { index }, object))
+            })
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        let mut classes: HashMap<String, ClassStats> = HashMap::new();
+        for (_, object) in self.objects() {
+            let class_name = match object {
+                HeapObject::Instance(instance) => instance.class.name.clone(),
+                HeapObject::Array(array) => format!("[{}", array.component_type),
+            };
+            let stats = classes.entry(class_name).or_default();
+            stats.instances += 1;
+            stats.bytes += object.size();
+        }
+
+        HeapStats {
+            used: classes.values().map(|stats| stats.bytes).sum(),
+            max_size: self.max_size,
+            collections: self.collections,
+            classes,
+        }
+    }
+
+    /// Frees every object that isn't marked, returns the number of freed objects
+    pub fn sweep(&mut self, marked: &[bool]) -> usize {
+        let mut freed = 0;
+        self.used = 0;
+        self.collections += 1;
+
+        for (index, slot) in self.objects.iter_mut().enumerate() {
+            match slot {
+                Some(object) if marked[index] => self.used += object.size(),
+                Some(_) => {
+                    *slot = None;
+                    self.free_slots.push(index);
+                    freed += 1;
+                }
+                None => {}
+            }
+        }
+
+        self.threshold =
+            (self.used * 2).clamp(Heap::MIN_THRESHOLD.min(self.max_size), self.max_size);
+        freed
+    }
+}
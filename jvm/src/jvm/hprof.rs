@@ -0,0 +1,824 @@
+//! Heap dumps in the HPROF binary format of HotSpot, which tools like Eclipse MAT and VisualVM
+//! open.
+//!
+//! The heap is dumped while every other thread is stopped at a safepoint, like a collection.
+//! Class mirrors are dumped as the classes they mirror, the way HotSpot does. Strings are values
+//! rather than heap objects in this vm, so every distinct string the dump refers to becomes a
+//! `java/lang/String` with a `char[]` value, as if it was interned.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use jvm_parser::classfile::classfile::FieldAccessFlags;
+
+use super::{
+    class_loader::{LoadedClass, LoaderId},
+    heap::{Heap, HeapObject, NativeData},
+    monitors::MonitorKey,
+    threads::JavaThread,
+    JavaObjectRef, StackValue, JVM,
+};
+
+// Record tags
+const UTF8: u8 = 0x01;
+const LOAD_CLASS: u8 = 0x02;
+const STACK_TRACE: u8 = 0x05;
+const HEAP_DUMP_SEGMENT: u8 = 0x1c;
+const HEAP_DUMP_END: u8 = 0x2c;
+
+// Tags of the records in a heap dump segment
+const ROOT_UNKNOWN: u8 = 0xff;
+const ROOT_JNI_LOCAL: u8 = 0x02;
+const ROOT_JAVA_FRAME: u8 = 0x03;
+const ROOT_STICKY_CLASS: u8 = 0x05;
+const ROOT_MONITOR_USED: u8 = 0x07;
+const ROOT_THREAD_OBJECT: u8 = 0x08;
+const CLASS_DUMP: u8 = 0x20;
+const INSTANCE_DUMP: u8 = 0x21;
+const OBJECT_ARRAY_DUMP: u8 = 0x22;
+const PRIMITIVE_ARRAY_DUMP: u8 = 0x23;
+
+// The basic types of fields and array elements
+const OBJECT: u8 = 2;
+const BOOLEAN: u8 = 4;
+const CHAR: u8 = 5;
+const FLOAT: u8 = 6;
+const DOUBLE: u8 = 7;
+const BYTE: u8 = 8;
+const SHORT: u8 = 9;
+const INT: u8 = 10;
+const LONG: u8 = 11;
+
+/// Identifiers are as wide as pointers on a 64 bit vm
+const ID_SIZE: u32 = 8;
+
+/// A segment is written out once it grows past this, the length of a record has to fit in a u4
+const SEGMENT_SIZE: usize = 1 << 24;
+
+/// The serial number of the empty stack trace every object is allocated at
+const NO_STACK_TRACE: u32 = 1;
+
+fn basic_type(descriptor: &str) -> u8 {
+    match descriptor.as_bytes().first() {
+        Some(b'Z') => BOOLEAN,
+        Some(b'C') => CHAR,
+        Some(b'F') => FLOAT,
+        Some(b'D') => DOUBLE,
+        Some(b'B') => BYTE,
+        Some(b'S') => SHORT,
+        Some(b'I') => INT,
+        Some(b'J') => LONG,
+        _ => OBJECT,
+    }
+}
+
+/// The type of a field that isn't declared, guessed from its value
+fn basic_type_of(value: &StackValue) -> u8 {
+    match value {
+        StackValue::Integer(_) => INT,
+        StackValue::Long(_) => LONG,
+        StackValue::Float(_) => FLOAT,
+        StackValue::Double(_) => DOUBLE,
+        StackValue::Short(_) => SHORT,
+        StackValue::Byte(_) => BYTE,
+        _ => OBJECT,
+    }
+}
+
+fn basic_type_size(basic_type: u8) -> u32 {
+    match basic_type {
+        BOOLEAN | BYTE => 1,
+        CHAR | SHORT => 2,
+        FLOAT | INT => 4,
+        _ => 8,
+    }
+}
+
+struct DumpClass {
+    id: u64,
+    name: String,
+    super_class: Option<usize>,
+    loader_id: u64,
+    /// The declared instance fields, followed by the fields the vm set on instances of the class
+    /// without a declaration, like the ones of the shims
+    fields: Vec<(String, u8)>,
+    statics: Vec<(String, u8, StackValue)>,
+}
+
+/// Everything the dump refers to, gathered while the world is stopped
+struct HeapDump<'a> {
+    heap: &'a Heap,
+    classes: Vec<DumpClass>,
+    /// Indices into `classes` by the address of the loaded class
+    loaded: HashMap<*const LoadedClass, usize>,
+    array_classes: HashMap<String, usize>,
+    string_class: usize,
+    threads: Vec<Arc<JavaThread>>,
+    /// Objects that are roots for another reason than a frame, a thread or a class
+    other_roots: Vec<JavaObjectRef>,
+    monitors: Vec<MonitorKey>,
+    /// The ids of the string objects and their `char[]` values
+    strings: HashMap<String, (u64, u64)>,
+    names: HashMap<String, u64>,
+    next_id: u64,
+}
+
+impl<'a> HeapDump<'a> {
+    fn object_id(object: JavaObjectRef) -> u64 {
+        (object.index as u64 + 1) * ID_SIZE as u64
+    }
+
+    /// An id that can't be taken by a heap object
+    fn new_id(&mut self) -> u64 {
+        self.next_id += ID_SIZE as u64;
+        self.next_id
+    }
+
+    fn name_id(&mut self, name: &str) -> u64 {
+        if let Some(id) = self.names.get(name) {
+            return *id;
+        }
+        let id = self.new_id();
+        self.names.insert(name.to_string(), id);
+        id
+    }
+
+    fn intern(&mut self, value: &StackValue) {
+        if let StackValue::String(string) = value {
+            if !self.strings.contains_key(string) {
+                let ids = (self.new_id(), self.new_id());
+                self.strings.insert(string.clone(), ids);
+            }
+        }
+    }
+
+    fn value_id(&self, value: &StackValue) -> u64 {
+        match value {
+            StackValue::JavaObjectRef(object) => HeapDump::object_id(*object),
+            StackValue::String(string) => self.strings[string].0,
+            _ => 0,
+        }
+    }
+
+    fn add_class(&mut self, class: DumpClass) -> usize {
+        self.name_id(&class.name);
+        for (name, _) in &class.fields {
+            self.name_id(name);
+        }
+        for (name, _, value) in &class.statics {
+            self.name_id(name);
+            self.intern(value);
+        }
+        self.classes.push(class);
+        self.classes.len() - 1
+    }
+
+    fn add_loaded_classes(&mut self, jvm: &JVM) {
+        let loaders = jvm.class_loaders.lock().unwrap();
+        let mut classes: Vec<Arc<LoadedClass>> = vec![];
+        for loader in loaders.iter() {
+            for class in loader.classes.values() {
+                // Initiating loaders refer to the same class as its defining loader
+                if !classes.iter().any(|known| Arc::ptr_eq(known, class)) {
+                    classes.push(class.clone());
+                }
+            }
+        }
+
+        // Indexed up front, super classes can come after their subclasses
+        let base = self.classes.len();
+        let mut ids = vec![];
+        for (index, class) in classes.iter().enumerate() {
+            let id = match class.mirror() {
+                Some(mirror) => HeapDump::object_id(mirror),
+                None => self.new_id(),
+            };
+            ids.push(id);
+            self.loaded.insert(Arc::as_ptr(class), base + index);
+        }
+
+        for (class, id) in classes.iter().zip(ids) {
+            let constant_pool = &class.java_class.constant_pool;
+            let utf8 = |index| {
+                constant_pool
+                    .get_utf8_at(index)
+                    .map(|utf8| utf8.data.to_string())
+                    .unwrap_or_default()
+            };
+
+            let statics = class.statics.lock().unwrap();
+            let mut fields = vec![];
+            let mut static_fields = vec![];
+            for field in &class.java_class.fields {
+                let name = utf8(field.name_index);
+                let basic_type = basic_type(&utf8(field.descriptor_index));
+                if field.access_flags & FieldAccessFlags::ACC_STATIC == 0 {
+                    fields.push((name, basic_type));
+                } else {
+                    let value = statics.get(&name).cloned().unwrap_or_default();
+                    static_fields.push((name, basic_type, value));
+                }
+            }
+            // Statics the vm set without a declaration
+            for (name, value) in statics.iter() {
+                if !static_fields.iter().any(|(known, _, _)| known == name) {
+                    static_fields.push((name.clone(), basic_type_of(value), value.clone()));
+                }
+            }
+            drop(statics);
+
+            self.add_class(DumpClass {
+                id,
+                name: class.name.clone(),
+                super_class: class
+                    .super_class
+                    .as_ref()
+                    .and_then(|super_class| self.loaded.get(&Arc::as_ptr(super_class)).copied()),
+                loader_id: loaders[class.loader.0]
+                    .object
+                    .map(HeapDump::object_id)
+                    .unwrap_or_default(),
+                fields,
+                statics: static_fields,
+            });
+
+            self.other_roots
+                .extend(class.runtime_constant_pool.cached_objects());
+        }
+
+        self.other_roots
+            .extend(loaders.iter().filter_map(|loader| loader.object));
+    }
+
+    fn bootstrap_class(&self, jvm: &JVM, name: &str) -> Option<usize> {
+        let loaders = jvm.class_loaders.lock().unwrap();
+        let class = loaders[LoaderId::BOOTSTRAP.0].classes.get(name)?;
+        self.loaded.get(&Arc::as_ptr(class)).copied()
+    }
+
+    fn synthetic_class(&mut self, name: &str, fields: Vec<(String, u8)>) -> usize {
+        let id = self.new_id();
+        let object_class = self
+            .classes
+            .iter()
+            .position(|class| class.name == "java/lang/Object");
+        self.add_class(DumpClass {
+            id,
+            name: name.to_string(),
+            super_class: object_class,
+            loader_id: 0,
+            fields,
+            statics: vec![],
+        })
+    }
+
+    fn has_field(&self, class: usize, name: &str) -> bool {
+        let mut class = Some(class);
+        while let Some(index) = class {
+            if self.classes[index]
+                .fields
+                .iter()
+                .any(|(field, _)| field == name)
+            {
+                return true;
+            }
+            class = self.classes[index].super_class;
+        }
+        false
+    }
+
+    fn add_field(&mut self, class: usize, name: &str, basic_type: u8) {
+        if !self.has_field(class, name) {
+            self.name_id(name);
+            self.classes[class]
+                .fields
+                .push((name.to_string(), basic_type));
+        }
+    }
+
+    /// Finds the array classes and the undeclared fields the heap objects need, and the strings
+    /// they refer to
+    fn scan_heap(&mut self) {
+        for (_, object) in self.heap.objects() {
+            match object {
+                HeapObject::Instance(instance) => {
+                    let Some(&class) = self.loaded.get(&Arc::as_ptr(&instance.class)) else {
+                        continue;
+                    };
+                    for (name, value) in &instance.fields {
+                        self.add_field(class, name, basic_type_of(value));
+                        self.intern(value);
+                    }
+                }
+                HeapObject::Array(array) => {
+                    if basic_type(&array.component_type) == OBJECT {
+                        let name = format!("[{}", array.component_type);
+                        if !self.array_classes.contains_key(&name) {
+                            // Array classes only exist once something asked for them
+                            let class = match self.classes.iter().position(|c| c.name == name) {
+                                Some(class) => class,
+                                None => self.synthetic_class(&name, vec![]),
+                            };
+                            self.array_classes.insert(name, class);
+                        }
+                    }
+                    for value in &array.values {
+                        self.intern(value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn scan_roots(&mut self) {
+        for thread in self.threads.clone() {
+            for frame in thread.frames.lock().unwrap().iter() {
+                let values = frame.lock().unwrap();
+                for value in values.locals.iter().chain(&values.stack) {
+                    self.intern(value);
+                }
+            }
+        }
+        for key in self.monitors.clone() {
+            if let MonitorKey::String(string) = key {
+                self.intern(&StackValue::String(string));
+            }
+        }
+    }
+
+    /// The fields of an instance in the order its data is dumped, the class's own fields first
+    fn layout(&self, class: usize) -> Vec<(String, u8)> {
+        let mut layout = vec![];
+        let mut class = Some(class);
+        while let Some(index) = class {
+            layout.extend(self.classes[index].fields.iter().cloned());
+            class = self.classes[index].super_class;
+        }
+        layout
+    }
+}
+
+struct HprofWriter<W: Write> {
+    out: W,
+    segment: Vec<u8>,
+    written: u64,
+}
+
+impl<W: Write> HprofWriter<W> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.written += bytes.len() as u64;
+        self.out.write_all(bytes)
+    }
+
+    fn record(&mut self, tag: u8, body: &[u8]) -> io::Result<()> {
+        let mut header = vec![tag];
+        // Microseconds since the time in the file header
+        header.put_u4(0);
+        header.put_u4(body.len() as u32);
+        self.write(&header)?;
+        self.write(body)
+    }
+
+    /// Called after a record was added to the segment
+    fn end_sub_record(&mut self) -> io::Result<()> {
+        if self.segment.len() >= SEGMENT_SIZE {
+            self.flush_segment()?;
+        }
+        Ok(())
+    }
+
+    fn flush_segment(&mut self) -> io::Result<()> {
+        if !self.segment.is_empty() {
+            let segment = std::mem::take(&mut self.segment);
+            self.record(HEAP_DUMP_SEGMENT, &segment)?;
+        }
+        Ok(())
+    }
+}
+
+trait PutBytes {
+    fn put_u1(&mut self, value: u8);
+    fn put_u2(&mut self, value: u16);
+    fn put_u4(&mut self, value: u32);
+    fn put_u8(&mut self, value: u64);
+}
+
+impl PutBytes for Vec<u8> {
+    fn put_u1(&mut self, value: u8) {
+        self.push(value);
+    }
+
+    fn put_u2(&mut self, value: u16) {
+        self.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn put_u4(&mut self, value: u32) {
+        self.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn put_u8(&mut self, value: u64) {
+        self.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+impl HeapDump<'_> {
+    fn put_value(&self, out: &mut Vec<u8>, basic_type: u8, value: Option<&StackValue>) {
+        let int = match value {
+            Some(StackValue::Integer(int)) => *int as i64,
+            Some(StackValue::Long(long)) => *long,
+            Some(StackValue::Short(short)) => *short as i64,
+            Some(StackValue::Byte(byte)) => *byte as i64,
+            Some(StackValue::Float(float)) => *float as i64,
+            Some(StackValue::Double(double)) => *double as i64,
+            _ => 0,
+        };
+        let float = match value {
+            Some(StackValue::Float(float)) => *float as f64,
+            Some(StackValue::Double(double)) => *double,
+            _ => int as f64,
+        };
+
+        match basic_type {
+            OBJECT => out.put_u8(value.map(|value| self.value_id(value)).unwrap_or_default()),
+            BOOLEAN | BYTE => out.put_u1(int as u8),
+            CHAR | SHORT => out.put_u2(int as u16),
+            INT => out.put_u4(int as u32),
+            LONG => out.put_u8(int as u64),
+            FLOAT => out.put_u4((float as f32).to_bits()),
+            _ => out.put_u8(float.to_bits()),
+        }
+    }
+
+    fn write(&self, out: &mut HprofWriter<impl Write>) -> io::Result<()> {
+        let mut header = b"JAVA PROFILE 1.0.2\0".to_vec();
+        header.put_u4(ID_SIZE);
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_millis() as u64)
+            .unwrap_or_default();
+        header.put_u8(millis);
+        out.write(&header)?;
+
+        for (name, id) in &self.names {
+            let mut body = vec![];
+            body.put_u8(*id);
+            body.extend_from_slice(name.as_bytes());
+            out.record(UTF8, &body)?;
+        }
+
+        for (serial, class) in self.classes.iter().enumerate() {
+            let mut body = vec![];
+            body.put_u4(serial as u32 + 1);
+            body.put_u8(class.id);
+            body.put_u4(NO_STACK_TRACE);
+            body.put_u8(self.names[&class.name]);
+            out.record(LOAD_CLASS, &body)?;
+        }
+
+        // Frames aren't recorded, every thread has an empty stack trace
+        for serial in 0..=self.threads.len() as u32 {
+            let mut body = vec![];
+            body.put_u4(NO_STACK_TRACE + serial);
+            body.put_u4(serial);
+            body.put_u4(0);
+            out.record(STACK_TRACE, &body)?;
+        }
+
+        self.write_roots(out)?;
+        self.write_classes(out)?;
+        self.write_objects(out)?;
+        self.write_strings(out)?;
+
+        out.flush_segment()?;
+        out.record(HEAP_DUMP_END, &[])?;
+        out.out.flush()
+    }
+
+    fn write_roots(&self, out: &mut HprofWriter<impl Write>) -> io::Result<()> {
+        for (index, thread) in self.threads.iter().enumerate() {
+            let serial = index as u32 + 1;
+            let segment = &mut out.segment;
+            segment.put_u1(ROOT_THREAD_OBJECT);
+            segment.put_u8(HeapDump::object_id(thread.object));
+            segment.put_u4(serial);
+            segment.put_u4(NO_STACK_TRACE + serial);
+
+            for object in thread.handles.lock().unwrap().iter() {
+                segment.put_u1(ROOT_JNI_LOCAL);
+                segment.put_u8(HeapDump::object_id(*object));
+                segment.put_u4(serial);
+                segment.put_u4(u32::MAX);
+            }
+
+            let frames = thread.frames.lock().unwrap();
+            for (depth, frame) in frames.iter().rev().enumerate() {
+                let values = frame.lock().unwrap();
+                let popped = values
+                    .popped
+                    .iter()
+                    .map(|object| HeapDump::object_id(*object));
+                let ids = values
+                    .locals
+                    .iter()
+                    .chain(&values.stack)
+                    .map(|value| self.value_id(value))
+                    .chain(popped)
+                    .filter(|id| *id != 0);
+                for id in ids {
+                    segment.put_u1(ROOT_JAVA_FRAME);
+                    segment.put_u8(id);
+                    segment.put_u4(serial);
+                    segment.put_u4(depth as u32);
+                }
+            }
+            drop(frames);
+            out.end_sub_record()?;
+        }
+
+        // Classes are never unloaded
+        for class in self.loaded.values() {
+            out.segment.put_u1(ROOT_STICKY_CLASS);
+            out.segment.put_u8(self.classes[*class].id);
+        }
+        for object in &self.other_roots {
+            out.segment.put_u1(ROOT_UNKNOWN);
+            out.segment.put_u8(HeapDump::object_id(*object));
+        }
+        for key in &self.monitors {
+            let id = match key {
+                MonitorKey::Object(object) => HeapDump::object_id(*object),
+                MonitorKey::String(string) => self.strings[string].0,
+            };
+            out.segment.put_u1(ROOT_MONITOR_USED);
+            out.segment.put_u8(id);
+        }
+        out.end_sub_record()
+    }
+
+    fn write_classes(&self, out: &mut HprofWriter<impl Write>) -> io::Result<()> {
+        for (index, class) in self.classes.iter().enumerate() {
+            let instance_size: u32 = self
+                .layout(index)
+                .iter()
+                .map(|(_, basic_type)| basic_type_size(*basic_type))
+                .sum();
+
+            let segment = &mut out.segment;
+            segment.put_u1(CLASS_DUMP);
+            segment.put_u8(class.id);
+            segment.put_u4(NO_STACK_TRACE);
+            segment.put_u8(
+                class
+                    .super_class
+                    .map(|super_class| self.classes[super_class].id)
+                    .unwrap_or_default(),
+            );
+            segment.put_u8(class.loader_id);
+            // The signers, the protection domain and two reserved ids
+            for _ in 0..4 {
+                segment.put_u8(0);
+            }
+            segment.put_u4(instance_size);
+            // The constant pool
+            segment.put_u2(0);
+
+            segment.put_u2(class.statics.len() as u16);
+            for (name, basic_type, value) in &class.statics {
+                segment.put_u8(self.names[name]);
+                segment.put_u1(*basic_type);
+                self.put_value(segment, *basic_type, Some(value));
+            }
+
+            segment.put_u2(class.fields.len() as u16);
+            for (name, basic_type) in &class.fields {
+                segment.put_u8(self.names[name]);
+                segment.put_u1(*basic_type);
+            }
+            out.end_sub_record()?;
+        }
+        Ok(())
+    }
+
+    fn write_objects(&self, out: &mut HprofWriter<impl Write>) -> io::Result<()> {
+        let mut layouts: HashMap<usize, Vec<(String, u8)>> = HashMap::new();
+
+        for (object, heap_object) in self.heap.objects() {
+            let id = HeapDump::object_id(object);
+            match heap_object {
+                HeapObject::Instance(instance) => {
+                    // Mirrors are dumped as their classes
+                    if matches!(instance.native_data, NativeData::Class(_)) {
+                        continue;
+                    }
+                    let Some(&class) = self.loaded.get(&Arc::as_ptr(&instance.class)) else {
+                        continue;
+                    };
+                    let layout = layouts.entry(class).or_insert_with(|| self.layout(class));
+
+                    let mut data = vec![];
+                    for (name, basic_type) in layout.iter() {
+                        self.put_value(&mut data, *basic_type, instance.fields.get(name));
+                    }
+
+                    let segment = &mut out.segment;
+                    segment.put_u1(INSTANCE_DUMP);
+                    segment.put_u8(id);
+                    segment.put_u4(NO_STACK_TRACE);
+                    segment.put_u8(self.classes[class].id);
+                    segment.put_u4(data.len() as u32);
+                    segment.extend_from_slice(&data);
+                }
+                HeapObject::Array(array) => {
+                    let basic_type = basic_type(&array.component_type);
+                    let segment = &mut out.segment;
+                    if basic_type == OBJECT {
+                        let class = self.array_classes[&format!("[{}", array.component_type)];
+                        segment.put_u1(OBJECT_ARRAY_DUMP);
+                        segment.put_u8(id);
+                        segment.put_u4(NO_STACK_TRACE);
+                        segment.put_u4(array.values.len() as u32);
+                        segment.put_u8(self.classes[class].id);
+                    } else {
+                        segment.put_u1(PRIMITIVE_ARRAY_DUMP);
+                        segment.put_u8(id);
+                        segment.put_u4(NO_STACK_TRACE);
+                        segment.put_u4(array.values.len() as u32);
+                        segment.put_u1(basic_type);
+                    }
+                    for value in &array.values {
+                        self.put_value(segment, basic_type, Some(value));
+                    }
+                }
+            }
+            out.end_sub_record()?;
+        }
+        Ok(())
+    }
+
+    fn write_strings(&self, out: &mut HprofWriter<impl Write>) -> io::Result<()> {
+        let layout = self.layout(self.string_class);
+        for (string, (id, value_id)) in &self.strings {
+            let mut data = vec![];
+            for (name, basic_type) in &layout {
+                if name == "value" && *basic_type == OBJECT {
+                    data.put_u8(*value_id);
+                } else {
+                    self.put_value(&mut data, *basic_type, None);
+                }
+            }
+
+            let segment = &mut out.segment;
+            segment.put_u1(INSTANCE_DUMP);
+            segment.put_u8(*id);
+            segment.put_u4(NO_STACK_TRACE);
+            segment.put_u8(self.classes[self.string_class].id);
+            segment.put_u4(data.len() as u32);
+            segment.extend_from_slice(&data);
+
+            let chars: Vec<u16> = string.encode_utf16().collect();
+            segment.put_u1(PRIMITIVE_ARRAY_DUMP);
+            segment.put_u8(*value_id);
+            segment.put_u4(NO_STACK_TRACE);
+            segment.put_u4(chars.len() as u32);
+            segment.put_u1(CHAR);
+            for char in chars {
+                segment.put_u2(char);
+            }
+            out.end_sub_record()?;
+        }
+        Ok(())
+    }
+}
+
+impl JVM {
+    /// `-XX:+HeapDumpOnOutOfMemoryError`, the heap is written to `path` the first time it runs out
+    pub fn set_heap_dump_on_out_of_memory(&mut self, path: PathBuf) {
+        self.heap_dump_path = Some(path);
+    }
+
+    /// Writes the heap to an HPROF file, stopping the other threads meanwhile. Returns the size
+    /// of the file in bytes
+    pub fn dump_heap(&self, path: &Path) -> io::Result<u64> {
+        loop {
+            if let Some(result) = self.stop_the_world(|| self.write_heap_dump(path)) {
+                return result;
+            }
+        }
+    }
+
+    fn write_heap_dump(&self, path: &Path) -> io::Result<u64> {
+        let threads = self.live_threads();
+        let monitors = self.locked_monitors();
+        let heap = self.heap.lock().unwrap();
+
+        let mut dump = HeapDump {
+            heap: &heap,
+            classes: vec![],
+            loaded: HashMap::new(),
+            array_classes: HashMap::new(),
+            string_class: 0,
+            threads,
+            other_roots: vec![],
+            monitors,
+            strings: HashMap::new(),
+            names: HashMap::new(),
+            next_id: heap.capacity() as u64 * ID_SIZE as u64,
+        };
+        dump.add_loaded_classes(self);
+        dump.string_class = match dump.bootstrap_class(self, "java/lang/String") {
+            Some(class) => class,
+            None => dump.synthetic_class("java/lang/String", vec![]),
+        };
+        dump.add_field(dump.string_class, "value", OBJECT);
+        dump.scan_heap();
+        dump.scan_roots();
+
+        let mut out = HprofWriter {
+            out: BufWriter::new(File::create(path)?),
+            segment: vec![],
+            written: 0,
+        };
+        dump.write(&mut out)?;
+        Ok(out.written)
+    }
+
+    pub(super) fn dump_heap_on_out_of_memory(&self) {
+        let Some(path) = &self.heap_dump_path else {
+            return;
+        };
+        if self.heap_dumped.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        println!("java.lang.OutOfMemoryError: Java heap space");
+        println!("Dumping heap to {} ...", path.display());
+
+        let start = Instant::now();
+        match self.dump_heap(path) {
+            Ok(size) => println!(
+                "Heap dump file created [{size} bytes in {:.3} secs]",
+                start.elapsed().as_secs_f64()
+            ),
+            Err(err) => println!("Unable to create {}: {err}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{HEAP_DUMP_END, HEAP_DUMP_SEGMENT, ID_SIZE, LOAD_CLASS, UTF8};
+    use crate::jvm::{test_class, JVM};
+
+    #[test]
+    fn dumps_are_split_into_records() {
+        let jvm = JVM::with_classes(["Gc", "Gc$Node"].map(test_class));
+        jvm.run_static("Gc", "label", "()V", vec![]).unwrap();
+        let path = std::env::temp_dir().join(format!("heap-{}.hprof", std::process::id()));
+        let size = jvm.dump_heap(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(size, bytes.len() as u64);
+
+        let header = b"JAVA PROFILE 1.0.2\0";
+        assert!(bytes.starts_with(header));
+        let u4 = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+        assert_eq!(u4(header.len()), ID_SIZE);
+
+        // Every record is a tag, a time and the length of its body
+        let mut names = HashSet::new();
+        let (mut tags, mut segments) = (vec![], vec![]);
+        let mut at = header.len() + 4 + 8;
+        while at < bytes.len() {
+            let length = u4(at + 5) as usize;
+            let body = &bytes[at + 9..at + 9 + length];
+            match bytes[at] {
+                UTF8 => {
+                    names.insert(String::from_utf8(body[ID_SIZE as usize..].to_vec()).unwrap());
+                }
+                HEAP_DUMP_SEGMENT => segments.extend_from_slice(body),
+                _ => {}
+            }
+            tags.push(bytes[at]);
+            at += 9 + length;
+        }
+        assert_eq!(at, bytes.len());
+
+        assert_eq!(tags.last(), Some(&HEAP_DUMP_END));
+        assert!(tags.contains(&LOAD_CLASS));
+        assert!(names.contains("Gc") && names.contains("label"));
+        // The string is dumped as a java/lang/String with a char[] value
+        let chars = "kept in the heap"
+            .encode_utf16()
+            .flat_map(u16::to_be_bytes)
+            .collect::<Vec<_>>();
+        assert!(segments.windows(chars.len()).any(|window| window == chars));
+    }
+}
@@ -0,0 +1,1577 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use jvm_parser::classfile::{classfile::MethodAccessFlags, descriptor::parse_method_descriptor};
+
+use super::{
+    class_loader::{LoadedClass, LoaderId},
+    heap::{HeapObject, NativeData},
+    monitors::MonitorKey,
+    opcodes::{CmpConditions, OpCodes},
+    threads::JavaThread,
+    JavaObjectRef, JvmError, StackValue, JVM,
+};
+
+/// The decoded byte code of a method
+pub struct DecodedCode {
+    pub opcodes: Vec<(usize, OpCodes)>,
+    /// Maps a pc to its index in `opcodes`, used to find branch targets
+    pc_index: HashMap<usize, usize>,
+}
+
+impl DecodedCode {
+    pub fn new(opcodes: Vec<(usize, OpCodes)>) -> Self {
+        let pc_index = opcodes
+            .iter()
+            .enumerate()
+            .map(|(index, (pc, _))| (*pc, index))
+            .collect();
+
+        Self { opcodes, pc_index }
+    }
+
+    fn index_of(&self, pc: usize) -> Result<usize, JvmError> {
+        self.pc_index
+            .get(&pc)
+            .copied()
+            .ok_or_else(|| JvmError::Internal(format!("Branch to the invalid pc: {pc}")))
+    }
+}
+
+/// A method that has been looked up and can be invoked
+#[derive(Debug, Clone)]
+pub enum MethodTarget {
+    /// A method with byte code, and its index in the class' methods
+    Java(Arc<LoadedClass>, usize),
+    /// A rust binding, its key in the natives table and the descriptor it is invoked with
+    Native(String, String),
+}
+
+/// The locals and operand stack of a frame. They are registered with the thread running the
+/// frame, the garbage collector reads them as roots while the thread is at a safepoint
+#[derive(Debug, Default)]
+pub struct FrameValues {
+    /// The class of the method the frame runs, reflection checks access against it
+    pub class: Option<Arc<LoadedClass>>,
+    pub locals: Vec<StackValue>,
+    pub stack: Vec<StackValue>,
+    /// The references the executing instruction popped, they stay alive until it completes
+    pub popped: Vec<JavaObjectRef>,
+}
+
+struct Frame {
+    class: Arc<LoadedClass>,
+    method_index: usize,
+    code: Arc<DecodedCode>,
+    values: Arc<Mutex<FrameValues>>,
+    /// The thread the values are registered with
+    thread: Option<Arc<JavaThread>>,
+    /// The index in `code.opcodes` of the next opcode to execute
+    index: usize,
+    /// The pc of the opcode that is executing, exception handlers are looked up with it
+    pc: usize,
+    /// The monitor a synchronized method holds while it runs
+    monitor: Option<MonitorKey>,
+    /// The monitors `monitorenter` entered in this frame and `monitorexit` didn't exit yet
+    entered: Vec<MonitorKey>,
+}
+
+enum Action {
+    Next,
+    /// Branch by an offset relative to the pc of the current opcode
+    Jump(i32),
+    Invoke(MethodTarget, Vec<StackValue>),
+    Return(Option<StackValue>),
+}
+
+impl Frame {
+    fn new(
+        class: Arc<LoadedClass>,
+        method_index: usize,
+        args: Vec<StackValue>,
+        thread: Option<Arc<JavaThread>>,
+    ) -> Result<Self, JvmError> {
+        let code = class.decoded_code(method_index)?;
+        let max_locals = class.java_class.methods[method_index]
+            .get_code()
+            .map(|code| code.max_locals as usize)
+            .unwrap_or_default();
+
+        let mut locals = vec![StackValue::None; max_locals];
+        let mut slot = 0;
+        for arg in args {
+            let size = if arg.is_category_2() { 2 } else { 1 };
+            if slot + size > locals.len() {
+                locals.resize(slot + size, StackValue::None);
+            }
+            locals[slot] = arg;
+            slot += size;
+        }
+
+        let values = Arc::new(Mutex::new(FrameValues {
+            class: Some(class.clone()),
+            locals,
+            ..Default::default()
+        }));
+        if let Some(thread) = &thread {
+            thread.frames.lock().unwrap().push(values.clone());
+        }
+
+        Ok(Self {
+            class,
+            method_index,
+            code,
+            values,
+            thread,
+            index: 0,
+            pc: 0,
+            monitor: None,
+            entered: vec![],
+        })
+    }
+
+    fn values(&self) -> MutexGuard<'_, FrameValues> {
+        self.values.lock().unwrap()
+    }
+
+    fn push(&mut self, value: StackValue) -> Result<Action, JvmError> {
+        self.values().stack.push(value);
+        Ok(Action::Next)
+    }
+
+    fn extend(&mut self, values: impl IntoIterator<Item = StackValue>) {
+        self.values().stack.extend(values);
+    }
+
+    fn pop(&mut self) -> Result<StackValue, JvmError> {
+        let mut values = self.values();
+        let value = values.stack.pop().ok_or_else(|| {
+            JvmError::Internal(format!("Operand stack underflow in '{}'", self.class.name))
+        })?;
+        if let StackValue::JavaObjectRef(object) = value {
+            values.popped.push(object);
+        }
+        Ok(value)
+    }
+
+    fn pop_args(&mut self, count: usize) -> Result<Vec<StackValue>, JvmError> {
+        let mut values = self.values();
+        if values.stack.len() < count {
+            return Err(JvmError::Internal(format!(
+                "Operand stack underflow in '{}'",
+                self.class.name
+            )));
+        }
+
+        let split = values.stack.len() - count;
+        let args = values.stack.split_off(split);
+        values
+            .popped
+            .extend(args.iter().filter_map(|arg| match arg {
+                StackValue::JavaObjectRef(object) => Some(*object),
+                _ => None,
+            }));
+        Ok(args)
+    }
+
+    fn pop_int(&mut self) -> Result<i32, JvmError> {
+        match self.pop()? {
+            StackValue::Integer(value) => Ok(value),
+            StackValue::Short(value) => Ok(value as i32),
+            StackValue::Byte(value) => Ok(value as i8 as i32),
+            value => Err(unexpected_value("int", &value)),
+        }
+    }
+
+    fn pop_long(&mut self) -> Result<i64, JvmError> {
+        match self.pop()? {
+            StackValue::Long(value) => Ok(value),
+            value => Err(unexpected_value("long", &value)),
+        }
+    }
+
+    fn pop_float(&mut self) -> Result<f32, JvmError> {
+        match self.pop()? {
+            StackValue::Float(value) => Ok(value),
+            value => Err(unexpected_value("float", &value)),
+        }
+    }
+
+    fn pop_double(&mut self) -> Result<f64, JvmError> {
+        match self.pop()? {
+            StackValue::Double(value) => Ok(value),
+            value => Err(unexpected_value("double", &value)),
+        }
+    }
+
+    fn load(&mut self, index: usize) -> Result<Action, JvmError> {
+        let mut values = self.values();
+        let value =
+            values.locals.get(index).cloned().ok_or_else(|| {
+                JvmError::Internal(format!("Invalid local variable index: {index}"))
+            })?;
+        values.stack.push(value);
+        Ok(Action::Next)
+    }
+
+    fn store(&mut self, index: usize) -> Result<Action, JvmError> {
+        let value = self.pop()?;
+        let mut values = self.values();
+        let size = if value.is_category_2() { 2 } else { 1 };
+        if index + size > values.locals.len() {
+            return Err(JvmError::Internal(format!(
+                "Invalid local variable index: {index}"
+            )));
+        }
+        if size == 2 {
+            values.locals[index + 1] = StackValue::Invalid;
+        }
+        values.locals[index] = value;
+        Ok(Action::Next)
+    }
+
+    /// `iinc`, adds a constant to an int local variable
+    fn increment(&mut self, index: usize, constant: i32) -> Result<Action, JvmError> {
+        let mut values = self.values();
+        let Some(StackValue::Integer(value)) = values.locals.get(index) else {
+            return Err(unexpected_value(
+                "int",
+                values.locals.get(index).unwrap_or(&StackValue::None),
+            ));
+        };
+        values.locals[index] = StackValue::Integer(value.wrapping_add(constant));
+        Ok(Action::Next)
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        if let Some(thread) = &self.thread {
+            let mut frames = thread.frames.lock().unwrap();
+            if let Some(index) = frames
+                .iter()
+                .rposition(|values| Arc::ptr_eq(values, &self.values))
+            {
+                frames.remove(index);
+            }
+        }
+    }
+}
+
+fn unexpected_value(expected: &str, value: &StackValue) -> JvmError {
+    JvmError::Internal(format!(
+        "Expected a value of type {expected} on the operand stack, found: {value:?}"
+    ))
+}
+
+fn compare(condition: &CmpConditions, ordering: Ordering) -> bool {
+    match condition {
+        CmpConditions::Equal => ordering == Ordering::Equal,
+        CmpConditions::NotEqual => ordering != Ordering::Equal,
+        CmpConditions::LessThan => ordering == Ordering::Less,
+        CmpConditions::LessOrEqual => ordering != Ordering::Greater,
+        CmpConditions::GreaterThan => ordering == Ordering::Greater,
+        CmpConditions::GreaterOrEqual => ordering != Ordering::Less,
+    }
+}
+
+/// `fcmpl`/`dcmpl` push -1 for NaN, `fcmpg`/`dcmpg` push 1
+fn compare_floating(a: f64, b: f64, nan_result: i8) -> i32 {
+    match a.partial_cmp(&b) {
+        Some(Ordering::Less) => -1,
+        Some(Ordering::Equal) => 0,
+        Some(Ordering::Greater) => 1,
+        None => nan_result as i32,
+    }
+}
+
+fn same_reference(a: &StackValue, b: &StackValue) -> bool {
+    match (a, b) {
+        (StackValue::Null, StackValue::Null) => true,
+        (StackValue::JavaObjectRef(a), StackValue::JavaObjectRef(b)) => a == b,
+        (StackValue::String(a), StackValue::String(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// The value a field or array element has before it is written to
+pub fn default_value(descriptor: &str) -> StackValue {
+    match descriptor.chars().next() {
+        Some('J') => StackValue::Long(0),
+        Some('F') => StackValue::Float(0.0),
+        Some('D') => StackValue::Double(0.0),
+        Some('L') | Some('[') => StackValue::Null,
+        _ => StackValue::Integer(0),
+    }
+}
+
+impl JVM {
+    /// Invokes a method and runs it until it returns
+    pub(super) fn invoke_method(
+        &self,
+        target: MethodTarget,
+        args: Vec<StackValue>,
+    ) -> Result<StackValue, JvmError> {
+        match target {
+            MethodTarget::Native(key, descriptor) => self.invoke_native(&key, &descriptor, args),
+            MethodTarget::Java(class, method_index) => {
+                self.execute(self.new_frame(class, method_index, args)?)
+            }
+        }
+    }
+
+    /// Invokes an instance method on `object`, selected from its runtime class
+    pub(super) fn invoke_virtual(
+        &self,
+        object: JavaObjectRef,
+        name: &str,
+        descriptor: &str,
+        mut args: Vec<StackValue>,
+    ) -> Result<StackValue, JvmError> {
+        let class = self.runtime_class(&StackValue::JavaObjectRef(object))?;
+        let Some(target) = self.find_method(&class, name, descriptor) else {
+            return Err(self.new_exception(
+                "java/lang/AbstractMethodError",
+                &format!("{}.{name}{descriptor}", class.name.replace('/', ".")),
+            ));
+        };
+
+        args.insert(0, StackValue::JavaObjectRef(object));
+        self.invoke_method(target, args)
+    }
+
+    fn invoke_native(
+        &self,
+        key: &str,
+        descriptor: &str,
+        args: Vec<StackValue>,
+    ) -> Result<StackValue, JvmError> {
+        let Some(native_method) = self.native_methods.get(key) else {
+            return Err(self.new_exception("java/lang/UnsatisfiedLinkError", key));
+        };
+
+        let descriptor = parse_method_descriptor(descriptor)
+            .map_err(|err| self.new_exception("java/lang/ClassFormatError", &err.to_string()))?;
+        native_method(self, args, descriptor)
+    }
+
+    /// Looks up a method in a class, its super classes and then the default methods of its
+    /// interfaces. A native binding for a class is used over the byte code of that class
+    pub(super) fn find_method(
+        &self,
+        class: &Arc<LoadedClass>,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<MethodTarget> {
+        class
+            .ancestors()
+            .find_map(|current| self.find_declared_method(&current, name, descriptor))
+            .or_else(|| {
+                class.ancestors().find_map(|current| {
+                    current
+                        .interfaces
+                        .iter()
+                        .find_map(|interface| self.find_method(interface, name, descriptor))
+                })
+            })
+    }
+
+    pub(super) fn find_declared_method(
+        &self,
+        class: &Arc<LoadedClass>,
+        name: &str,
+        descriptor: &str,
+    ) -> Option<MethodTarget> {
+        let key = format!("{};{}", class.name, name);
+        if self.native_methods.contains_key(key.as_str()) {
+            return Some(MethodTarget::Native(key, descriptor.to_string()));
+        }
+
+        let method = class.java_class.get_method(name, descriptor)?;
+        if method.access_flags & MethodAccessFlags::ACC_ABSTRACT != 0 {
+            return None;
+        }
+        if method.access_flags & MethodAccessFlags::ACC_NATIVE != 0 {
+            // Not bound, invoking it throws UnsatisfiedLinkError
+            return Some(MethodTarget::Native(key, descriptor.to_string()));
+        }
+
+        let index = class
+            .java_class
+            .methods
+            .iter()
+            .position(|candidate| std::ptr::eq(candidate, method))?;
+        Some(MethodTarget::Java(class.clone(), index))
+    }
+
+    /// The class of a reference on the operand stack
+    pub(super) fn runtime_class(&self, value: &StackValue) -> Result<Arc<LoadedClass>, JvmError> {
+        match value {
+            StackValue::JavaObjectRef(object) => {
+                let class = match self.heap.lock().unwrap().get(*object) {
+                    HeapObject::Instance(instance) => Some(instance.class.clone()),
+                    HeapObject::Array(_) => None,
+                };
+                match class {
+                    Some(class) => Ok(class),
+                    None => self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/Object"),
+                }
+            }
+            StackValue::String(_) => self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/String"),
+            StackValue::Null => Err(self.new_exception("java/lang/NullPointerException", "")),
+            value => Err(unexpected_value("reference", value)),
+        }
+    }
+
+    /// The descriptor of the elements of an array on the operand stack, `None` for other values
+    pub(super) fn array_component_type(&self, value: &StackValue) -> Option<String> {
+        match value {
+            StackValue::JavaObjectRef(object) => match self.heap.lock().unwrap().get(*object) {
+                HeapObject::Array(array) => Some(array.component_type.clone()),
+                HeapObject::Instance(_) => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// `checkcast` and `instanceof` (JVMS 6.5), whether a reference that isn't `null` is an
+    /// instance of the class or array type at `cp_index` in the constant pool of `class`
+    fn is_instance_of(
+        &self,
+        class: &Arc<LoadedClass>,
+        value: &StackValue,
+        cp_index: u16,
+    ) -> Result<bool, JvmError> {
+        let target = self.class_name_at(class, cp_index)?;
+        let component_type = self.array_component_type(value);
+
+        if target.starts_with('[') {
+            return match component_type {
+                Some(component_type) => {
+                    self.is_assignable_type(class.loader, &format!("[{component_type}"), &target)
+                }
+                None => Ok(false),
+            };
+        }
+
+        let target = self.resolve_class_ref(class, cp_index)?;
+        match component_type {
+            Some(_) => Ok(is_array_supertype(&target.name)),
+            None => Ok(self.runtime_class(value)?.is_assignable_to(&target)),
+        }
+    }
+
+    /// Whether a value of type `from` is an instance of `to`, both class names or array
+    /// descriptors like `[[Ljava/lang/String;`. Arrays don't record the loader of their element
+    /// class, so class names are resolved by `loader`
+    pub(super) fn is_assignable_type(
+        &self,
+        loader: LoaderId,
+        from: &str,
+        to: &str,
+    ) -> Result<bool, JvmError> {
+        let element_class = |descriptor: &str| {
+            descriptor
+                .strip_prefix('L')
+                .and_then(|name| name.strip_suffix(';'))
+                .map(str::to_string)
+                .or_else(|| descriptor.starts_with('[').then(|| descriptor.to_string()))
+        };
+
+        match (from.strip_prefix('['), to.strip_prefix('[')) {
+            (Some(from), Some(to)) => match (element_class(from), element_class(to)) {
+                (Some(from), Some(to)) => self.is_assignable_type(loader, &from, &to),
+                // Arrays of primitives are only instances of arrays of the same primitive
+                _ => Ok(from == to),
+            },
+            (Some(_), None) => Ok(is_array_supertype(to)),
+            (None, Some(_)) => Ok(false),
+            (None, None) => {
+                let from = self.resolve_class(loader, from)?;
+                let to = self.resolve_class(loader, to)?;
+                Ok(from.is_assignable_to(&to))
+            }
+        }
+    }
+
+    /// A `ClassCastException` for a failed `checkcast`, with the message HotSpot gives it, e.g.
+    /// `class A cannot be cast to class B (A and B are in unnamed module of loader 'app')`
+    fn class_cast_exception(
+        &self,
+        class: &Arc<LoadedClass>,
+        value: &StackValue,
+        cp_index: u16,
+    ) -> JvmError {
+        let (from, from_loader) = match self.array_component_type(value) {
+            Some(component_type) => {
+                let descriptor = format!("[{component_type}");
+                let loader = self.element_loader(class.loader, &descriptor);
+                (descriptor, loader)
+            }
+            None => match self.runtime_class(value) {
+                Ok(from) => (from.name.clone(), from.loader),
+                Err(err) => return err,
+            },
+        };
+        let to = match self.class_name_at(class, cp_index) {
+            Ok(to) => to,
+            Err(err) => return err,
+        };
+        let to_loader = self.element_loader(class.loader, &to);
+
+        let from = from.replace('/', ".");
+        let to = to.replace('/', ".");
+        let location = |loader: LoaderId| match loader {
+            LoaderId::BOOTSTRAP => "module java.base of loader 'bootstrap'".to_string(),
+            loader => format!("unnamed module of loader {}", self.describe_loader(loader)),
+        };
+        let locations = if from_loader == to_loader {
+            format!("{from} and {to} are in {}", location(from_loader))
+        } else {
+            format!(
+                "{from} is in {}; {to} is in {}",
+                location(from_loader),
+                location(to_loader)
+            )
+        };
+
+        self.new_exception(
+            "java/lang/ClassCastException",
+            &format!("class {from} cannot be cast to class {to} ({locations})"),
+        )
+    }
+
+    /// The defining loader of a class, or of the element class of an array type, resolved by
+    /// `loader`. Arrays of primitives belong to the bootstrap loader
+    fn element_loader(&self, loader: LoaderId, name: &str) -> LoaderId {
+        let class_name = if name.starts_with('[') {
+            match name.trim_start_matches('[').strip_prefix('L') {
+                Some(class_name) => class_name.trim_end_matches(';'),
+                None => return LoaderId::BOOTSTRAP,
+            }
+        } else {
+            name
+        };
+        self.resolve_class(loader, class_name)
+            .map(|class| class.loader)
+            .unwrap_or(loader)
+    }
+
+    /// Runs frames until the first one returns, calls between java methods don't recurse
+    fn execute(&self, frame: Frame) -> Result<StackValue, JvmError> {
+        let mut frames = vec![frame];
+
+        loop {
+            let frame = frames.last_mut().unwrap();
+            let code = frame.code.clone();
+            let Some((pc, opcode)) = code.opcodes.get(frame.index) else {
+                let err = JvmError::Internal(format!(
+                    "Execution fell off the end of the byte code in '{}'",
+                    frame.class.name
+                ));
+                return Err(self.abort(&mut frames, err));
+            };
+            frame.pc = *pc;
+            frame.values().popped.clear();
+            self.safepoint_poll();
+
+            let result = match self.execute_opcode(frame, opcode) {
+                Ok(Action::Next) => {
+                    frame.index += 1;
+                    Ok(())
+                }
+                Ok(Action::Jump(offset)) => code
+                    .index_of((*pc as i64 + offset as i64) as usize)
+                    .map(|index| frame.index = index),
+                Ok(Action::Invoke(target, args)) => {
+                    frame.index += 1;
+                    match target {
+                        MethodTarget::Java(class, method_index) => self
+                            .new_frame(class, method_index, args)
+                            .map(|callee| frames.push(callee)),
+                        MethodTarget::Native(key, descriptor) => {
+                            self.invoke_native(&key, &descriptor, args).map(|value| {
+                                if !descriptor.ends_with(")V") {
+                                    frame.extend([value]);
+                                }
+                            })
+                        }
+                    }
+                }
+                Ok(Action::Return(value)) => match self.release_frame_monitors(frame) {
+                    Ok(()) => {
+                        frames.pop();
+                        match frames.last_mut() {
+                            Some(caller) => {
+                                caller.extend(value);
+                                Ok(())
+                            }
+                            None => return Ok(value.unwrap_or_default()),
+                        }
+                    }
+                    Err(err) => Err(err),
+                },
+                Err(err) => Err(err),
+            };
+
+            if let Err(err) = result {
+                self.unwind(&mut frames, err)?;
+            }
+        }
+    }
+
+    /// Creates the frame of a method call, entering the monitor of a synchronized method. Static
+    /// methods lock their class, instance methods the object they are invoked on
+    fn new_frame(
+        &self,
+        class: Arc<LoadedClass>,
+        method_index: usize,
+        args: Vec<StackValue>,
+    ) -> Result<Frame, JvmError> {
+        let access_flags = class.java_class.methods[method_index].access_flags;
+        // The arguments are roots once the frame exists, getting the mirror can allocate
+        let mut frame = Frame::new(class, method_index, args, self.current_thread())?;
+
+        let monitor = if access_flags & MethodAccessFlags::ACC_SYNCHRONIZED == 0 {
+            None
+        } else if access_flags & MethodAccessFlags::ACC_STATIC != 0 {
+            Some(MonitorKey::Object(self.class_mirror(&frame.class)?))
+        } else {
+            let this = frame.values().locals.first().cloned();
+            Some(self.monitor_key(&this.unwrap_or(StackValue::Null))?)
+        };
+        if let Some(monitor) = monitor {
+            self.enter_monitor(&monitor);
+            frame.monitor = Some(monitor);
+        }
+        Ok(frame)
+    }
+
+    /// Exits the monitors of a frame when it returns or is unwound. The ones `monitorenter`
+    /// entered are exited too, like HotSpot does for methods that don't exit them
+    fn release_frame_monitors(&self, frame: &mut Frame) -> Result<(), JvmError> {
+        for monitor in frame.entered.drain(..).rev() {
+            let _ = self.exit_monitor(&monitor);
+        }
+
+        match frame.monitor.take() {
+            Some(monitor) => self.exit_monitor(&monitor),
+            None => Ok(()),
+        }
+    }
+
+    /// Pops frames until one has a handler for the exception, the error is returned if none do
+    fn unwind(&self, frames: &mut Vec<Frame>, err: JvmError) -> Result<(), JvmError> {
+        let JvmError::Exception(exception) = err else {
+            return Err(self.abort(frames, err));
+        };
+        let handles = self.handle_scope();
+        handles.add(exception);
+
+        while let Some(frame) = frames.last_mut() {
+            let handler = self
+                .find_exception_handler(frame, exception)
+                .and_then(|handler_pc| handler_pc.map(|pc| frame.code.index_of(pc)).transpose());
+            match handler {
+                Ok(Some(index)) => {
+                    let mut values = frame.values();
+                    values.stack.clear();
+                    values.stack.push(StackValue::JavaObjectRef(exception));
+                    drop(values);
+                    frame.index = index;
+                    return Ok(());
+                }
+                Ok(None) => {}
+                Err(err) => return Err(self.abort(frames, err)),
+            }
+            // The exception being thrown wins over a monitor that was exited by hand already
+            let _ = self.release_frame_monitors(frame);
+            frames.pop();
+        }
+
+        Err(JvmError::Exception(exception))
+    }
+
+    /// Pops every frame after a failure java code can't catch. Their monitors are released, so
+    /// other threads don't wait for them forever
+    fn abort(&self, frames: &mut Vec<Frame>, err: JvmError) -> JvmError {
+        while let Some(mut frame) = frames.pop() {
+            let _ = self.release_frame_monitors(&mut frame);
+        }
+        err
+    }
+
+    fn find_exception_handler(
+        &self,
+        frame: &Frame,
+        exception: JavaObjectRef,
+    ) -> Result<Option<usize>, JvmError> {
+        let Some(code) = frame.class.java_class.methods[frame.method_index].get_code() else {
+            return Ok(None);
+        };
+
+        for entry in &code.exception_table {
+            if frame.pc < entry.start_pc as usize || frame.pc >= entry.end_pc as usize {
+                continue;
+            }
+
+            if entry.catch_type == 0 {
+                return Ok(Some(entry.handler_pc as usize));
+            }
+
+            let catch_class = self.resolve_class_ref(&frame.class, entry.catch_type)?;
+            let exception_class = self.runtime_class(&StackValue::JavaObjectRef(exception))?;
+
+            if exception_class.is_subclass_of(&catch_class) {
+                return Ok(Some(entry.handler_pc as usize));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn array_length(&self, array: &StackValue) -> Result<usize, JvmError> {
+        let StackValue::JavaObjectRef(array) = array else {
+            return match array {
+                StackValue::Null => Err(self.new_exception("java/lang/NullPointerException", "")),
+                value => Err(unexpected_value("array", value)),
+            };
+        };
+
+        match self.heap.lock().unwrap().get(*array) {
+            HeapObject::Array(array) => Ok(array.values.len()),
+            HeapObject::Instance(_) => Err(JvmError::Internal(
+                "Expected an array but found an object".to_string(),
+            )),
+        }
+    }
+
+    fn check_array_index(&self, array: &StackValue, index: i32) -> Result<JavaObjectRef, JvmError> {
+        let length = self.array_length(array)?;
+        if index < 0 || index as usize >= length {
+            return Err(self.new_exception(
+                "java/lang/ArrayIndexOutOfBoundsException",
+                &format!("Index {index} out of bounds for length {length}"),
+            ));
+        }
+
+        match array {
+            StackValue::JavaObjectRef(array) => Ok(*array),
+            value => Err(unexpected_value("array", value)),
+        }
+    }
+
+    fn array_load(&self, frame: &mut Frame) -> Result<Action, JvmError> {
+        let index = frame.pop_int()?;
+        let array = frame.pop()?;
+        let array = self.check_array_index(&array, index)?;
+
+        let value = match self.heap.lock().unwrap().get(array) {
+            HeapObject::Array(array) => array.values[index as usize].clone(),
+            HeapObject::Instance(_) => unreachable!("checked by check_array_index"),
+        };
+        frame.push(value)
+    }
+
+    fn array_store(&self, frame: &mut Frame, opcode: &OpCodes) -> Result<Action, JvmError> {
+        let value = frame.pop()?;
+        let index = frame.pop_int()?;
+        let array = frame.pop()?;
+        let array = self.check_array_index(&array, index)?;
+
+        let value = match (opcode, value) {
+            (OpCodes::bastore, StackValue::Integer(value)) => {
+                StackValue::Integer(value as i8 as i32)
+            }
+            (OpCodes::castore, StackValue::Integer(value)) => {
+                StackValue::Integer(value as u16 as i32)
+            }
+            (OpCodes::sastore, StackValue::Integer(value)) => {
+                StackValue::Integer(value as i16 as i32)
+            }
+            (OpCodes::aastore, value) if !matches!(value, StackValue::Null) => {
+                self.check_array_store(frame.class.loader, array, &value)?;
+                value
+            }
+            (_, value) => value,
+        };
+
+        self.heap
+            .lock()
+            .unwrap()
+            .set_element(array, index as usize, value);
+        Ok(Action::Next)
+    }
+
+    /// `aastore` throws `ArrayStoreException` unless the value is an instance of the component
+    /// type of the array
+    fn check_array_store(
+        &self,
+        loader: LoaderId,
+        array: JavaObjectRef,
+        value: &StackValue,
+    ) -> Result<(), JvmError> {
+        let Some(component_type) = self.array_component_type(&StackValue::JavaObjectRef(array))
+        else {
+            return Ok(());
+        };
+        let to = component_type
+            .strip_prefix('L')
+            .and_then(|class_name| class_name.strip_suffix(';'))
+            .unwrap_or(&component_type);
+        let from = match self.array_component_type(value) {
+            Some(value_component_type) => format!("[{value_component_type}"),
+            None => self.runtime_class(value)?.name.clone(),
+        };
+
+        match self.is_assignable_type(loader, &from, to)? {
+            true => Ok(()),
+            false => {
+                Err(self.new_exception("java/lang/ArrayStoreException", &from.replace('/', ".")))
+            }
+        }
+    }
+
+    /// Creates an array of `dimensions[0]` elements, each an array of the remaining dimensions.
+    /// Every dimension is checked before anything is allocated, like `multianewarray` does
+    fn new_multi_array(
+        &self,
+        descriptor: &str,
+        dimensions: &[i32],
+    ) -> Result<JavaObjectRef, JvmError> {
+        if let Some(length) = dimensions.iter().find(|length| **length < 0) {
+            return Err(
+                self.new_exception("java/lang/NegativeArraySizeException", &length.to_string())
+            );
+        }
+
+        let component_type = &descriptor[1..];
+        let length = dimensions[0] as usize;
+
+        // The size is known from the length, an array that can't fit throws before it's built
+        self.reserve(HeapObject::array_size(length))?;
+        let mut values = Vec::new();
+        values
+            .try_reserve_exact(length)
+            .map_err(|_| self.out_of_memory())?;
+
+        // The sub arrays only become reachable once the outer array exists
+        let handles = self.handle_scope();
+        if dimensions.len() > 1 {
+            for _ in 0..length {
+                let array = self.new_multi_array(component_type, &dimensions[1..])?;
+                handles.add(array);
+                values.push(StackValue::JavaObjectRef(array));
+            }
+        } else {
+            values.resize(length, default_value(component_type));
+        }
+
+        self.new_array(component_type, values)
+    }
+
+    fn execute_opcode(&self, frame: &mut Frame, opcode: &OpCodes) -> Result<Action, JvmError> {
+        match opcode {
+            OpCodes::nop => Ok(Action::Next),
+
+            OpCodes::aconst_null => frame.push(StackValue::Null),
+            OpCodes::iconst_(value) => frame.push(StackValue::Integer(*value)),
+            OpCodes::lconst_(value) => frame.push(StackValue::Long(*value as i64)),
+            OpCodes::fconst_(value) => frame.push(StackValue::Float(*value)),
+            OpCodes::dconst_(value) => frame.push(StackValue::Double(*value)),
+            OpCodes::bipush(byte) => frame.push(StackValue::Integer(*byte as i8 as i32)),
+            OpCodes::sipush(short) => frame.push(StackValue::Integer(*short as i16 as i32)),
+
+            OpCodes::ldc(cp_index) => {
+                frame.push(self.load_constant(&frame.class, *cp_index as u16)?)
+            }
+            OpCodes::ldc_w(cp_index) | OpCodes::ldc2_w(cp_index) => {
+                frame.push(self.load_constant(&frame.class, *cp_index)?)
+            }
+
+            OpCodes::iload_(index)
+            | OpCodes::lload_(index)
+            | OpCodes::fload_(index)
+            | OpCodes::dload_(index)
+            | OpCodes::aload_(index) => frame.load(*index as usize),
+
+            OpCodes::istore_(index)
+            | OpCodes::lstore_(index)
+            | OpCodes::fstore_(index)
+            | OpCodes::dstore_(index)
+            | OpCodes::astore_(index) => frame.store(*index as usize),
+
+            OpCodes::iinc(index, constant) => frame.increment(*index as usize, *constant as i32),
+
+            OpCodes::wide {
+                opcode,
+                index,
+                constbyte,
+            } => match (opcode.as_ref(), constbyte) {
+                (OpCodes::iinc(_, _), Some(constant)) => {
+                    frame.increment(*index as usize, *constant as i32)
+                }
+                (
+                    OpCodes::iload_(_)
+                    | OpCodes::lload_(_)
+                    | OpCodes::fload_(_)
+                    | OpCodes::dload_(_)
+                    | OpCodes::aload_(_),
+                    _,
+                ) => frame.load(*index as usize),
+                (
+                    OpCodes::istore_(_)
+                    | OpCodes::lstore_(_)
+                    | OpCodes::fstore_(_)
+                    | OpCodes::dstore_(_)
+                    | OpCodes::astore_(_),
+                    _,
+                ) => frame.store(*index as usize),
+                (opcode, _) => Err(JvmError::Internal(format!(
+                    "The widened opcode {opcode:?} is not implemented"
+                ))),
+            },
+
+            OpCodes::iaload
+            | OpCodes::laload
+            | OpCodes::faload
+            | OpCodes::daload
+            | OpCodes::aaload
+            | OpCodes::baload
+            | OpCodes::caload
+            | OpCodes::saload => self.array_load(frame),
+
+            OpCodes::iastore
+            | OpCodes::lastore
+            | OpCodes::fastore
+            | OpCodes::dastore
+            | OpCodes::aastore
+            | OpCodes::bastore
+            | OpCodes::castore
+            | OpCodes::sastore => self.array_store(frame, opcode),
+
+            OpCodes::arraylength => {
+                let array = frame.pop()?;
+                let length = self.array_length(&array)?;
+                frame.push(StackValue::Integer(length as i32))
+            }
+
+            OpCodes::newarray(atype) => {
+                let component_type = match atype {
+                    4 => "Z",
+                    5 => "C",
+                    6 => "F",
+                    7 => "D",
+                    8 => "B",
+                    9 => "S",
+                    10 => "I",
+                    11 => "J",
+                    atype => {
+                        return Err(JvmError::Internal(format!(
+                            "Invalid newarray type: {atype}"
+                        )))
+                    }
+                };
+                let length = frame.pop_int()?;
+                let array = self.new_multi_array(&format!("[{component_type}"), &[length])?;
+                frame.push(StackValue::JavaObjectRef(array))
+            }
+            OpCodes::anewarray(cp_index) => {
+                let class_name = self.class_name_at(&frame.class, *cp_index)?;
+                let component_type = if class_name.starts_with('[') {
+                    class_name
+                } else {
+                    format!("L{class_name};")
+                };
+                let length = frame.pop_int()?;
+                let array = self.new_multi_array(&format!("[{component_type}"), &[length])?;
+                frame.push(StackValue::JavaObjectRef(array))
+            }
+            OpCodes::multianewarray(cp_index, dimensions) => {
+                let descriptor = self.class_name_at(&frame.class, *cp_index)?;
+                let mut lengths = (0..*dimensions)
+                    .map(|_| frame.pop_int())
+                    .collect::<Result<Vec<_>, _>>()?;
+                lengths.reverse();
+                let array = self.new_multi_array(&descriptor, &lengths)?;
+                frame.push(StackValue::JavaObjectRef(array))
+            }
+
+            OpCodes::pop => frame.pop().map(|_| Action::Next),
+            OpCodes::pop2 => {
+                if !frame.pop()?.is_category_2() {
+                    frame.pop()?;
+                }
+                Ok(Action::Next)
+            }
+            OpCodes::dup => {
+                let value = frame.pop()?;
+                frame.extend([value.clone(), value]);
+                Ok(Action::Next)
+            }
+            OpCodes::dup_x1 => {
+                let value1 = frame.pop()?;
+                let value2 = frame.pop()?;
+                frame.extend([value1.clone(), value2, value1]);
+                Ok(Action::Next)
+            }
+            OpCodes::dup_x2 => {
+                let value1 = frame.pop()?;
+                let value2 = frame.pop()?;
+                if value2.is_category_2() {
+                    frame.extend([value1.clone(), value2, value1]);
+                } else {
+                    let value3 = frame.pop()?;
+                    frame.extend([value1.clone(), value3, value2, value1]);
+                }
+                Ok(Action::Next)
+            }
+            OpCodes::dup2 => {
+                let value1 = frame.pop()?;
+                if value1.is_category_2() {
+                    frame.extend([value1.clone(), value1]);
+                } else {
+                    let value2 = frame.pop()?;
+                    frame.extend([value2.clone(), value1.clone(), value2, value1]);
+                }
+                Ok(Action::Next)
+            }
+            OpCodes::dup2_x1 => {
+                let value1 = frame.pop()?;
+                if value1.is_category_2() {
+                    let value2 = frame.pop()?;
+                    frame.extend([value1.clone(), value2, value1]);
+                } else {
+                    let value2 = frame.pop()?;
+                    let value3 = frame.pop()?;
+                    frame.extend([value2.clone(), value1.clone(), value3, value2, value1]);
+                }
+                Ok(Action::Next)
+            }
+            OpCodes::dup2_x2 => {
+                let value1 = frame.pop()?;
+                let mut top = vec![value1.clone()];
+                if !value1.is_category_2() {
+                    top.insert(0, frame.pop()?);
+                }
+                let value = frame.pop()?;
+                let mut below = vec![value.clone()];
+                if !value.is_category_2() {
+                    below.insert(0, frame.pop()?);
+                }
+                frame.extend(top.iter().cloned());
+                frame.extend(below);
+                frame.extend(top);
+                Ok(Action::Next)
+            }
+            OpCodes::swap => {
+                let value1 = frame.pop()?;
+                let value2 = frame.pop()?;
+                frame.extend([value1, value2]);
+                Ok(Action::Next)
+            }
+
+            OpCodes::iadd
+            | OpCodes::isub
+            | OpCodes::imul
+            | OpCodes::idiv
+            | OpCodes::irem
+            | OpCodes::iand
+            | OpCodes::ior
+            | OpCodes::ixor
+            | OpCodes::ishl
+            | OpCodes::ishr
+            | OpCodes::iushr => {
+                let b = frame.pop_int()?;
+                let a = frame.pop_int()?;
+                if b == 0 && matches!(opcode, OpCodes::idiv | OpCodes::irem) {
+                    return Err(self.new_exception("java/lang/ArithmeticException", "/ by zero"));
+                }
+                let value = match opcode {
+                    OpCodes::iadd => a.wrapping_add(b),
+                    OpCodes::isub => a.wrapping_sub(b),
+                    OpCodes::imul => a.wrapping_mul(b),
+                    OpCodes::idiv => a.wrapping_div(b),
+                    OpCodes::irem => a.wrapping_rem(b),
+                    OpCodes::iand => a & b,
+                    OpCodes::ior => a | b,
+                    OpCodes::ixor => a ^ b,
+                    OpCodes::ishl => a.wrapping_shl(b as u32 & 0x1f),
+                    OpCodes::ishr => a.wrapping_shr(b as u32 & 0x1f),
+                    _ => ((a as u32) >> (b as u32 & 0x1f)) as i32,
+                };
+                frame.push(StackValue::Integer(value))
+            }
+            OpCodes::ineg => {
+                let value = frame.pop_int()?;
+                frame.push(StackValue::Integer(value.wrapping_neg()))
+            }
+
+            OpCodes::lshl | OpCodes::lshr | OpCodes::lushr => {
+                let shift = frame.pop_int()? as u32 & 0x3f;
+                let value = frame.pop_long()?;
+                let value = match opcode {
+                    OpCodes::lshl => value.wrapping_shl(shift),
+                    OpCodes::lshr => value.wrapping_shr(shift),
+                    _ => ((value as u64) >> shift) as i64,
+                };
+                frame.push(StackValue::Long(value))
+            }
+            OpCodes::ladd
+            | OpCodes::lsub
+            | OpCodes::lmul
+            | OpCodes::ldiv
+            | OpCodes::lrem
+            | OpCodes::land
+            | OpCodes::lor
+            | OpCodes::lxor => {
+                let b = frame.pop_long()?;
+                let a = frame.pop_long()?;
+                if b == 0 && matches!(opcode, OpCodes::ldiv | OpCodes::lrem) {
+                    return Err(self.new_exception("java/lang/ArithmeticException", "/ by zero"));
+                }
+                let value = match opcode {
+                    OpCodes::ladd => a.wrapping_add(b),
+                    OpCodes::lsub => a.wrapping_sub(b),
+                    OpCodes::lmul => a.wrapping_mul(b),
+                    OpCodes::ldiv => a.wrapping_div(b),
+                    OpCodes::lrem => a.wrapping_rem(b),
+                    OpCodes::land => a & b,
+                    OpCodes::lor => a | b,
+                    _ => a ^ b,
+                };
+                frame.push(StackValue::Long(value))
+            }
+            OpCodes::lneg => {
+                let value = frame.pop_long()?;
+                frame.push(StackValue::Long(value.wrapping_neg()))
+            }
+
+            OpCodes::fadd | OpCodes::fsub | OpCodes::fmul | OpCodes::fdiv | OpCodes::frem => {
+                let b = frame.pop_float()?;
+                let a = frame.pop_float()?;
+                let value = match opcode {
+                    OpCodes::fadd => a + b,
+                    OpCodes::fsub => a - b,
+                    OpCodes::fmul => a * b,
+                    OpCodes::fdiv => a / b,
+                    _ => a % b,
+                };
+                frame.push(StackValue::Float(value))
+            }
+            OpCodes::fneg => {
+                let value = frame.pop_float()?;
+                frame.push(StackValue::Float(-value))
+            }
+
+            OpCodes::dadd | OpCodes::dsub | OpCodes::dmul | OpCodes::ddiv | OpCodes::drem => {
+                let b = frame.pop_double()?;
+                let a = frame.pop_double()?;
+                let value = match opcode {
+                    OpCodes::dadd => a + b,
+                    OpCodes::dsub => a - b,
+                    OpCodes::dmul => a * b,
+                    OpCodes::ddiv => a / b,
+                    _ => a % b,
+                };
+                frame.push(StackValue::Double(value))
+            }
+            OpCodes::dneg => {
+                let value = frame.pop_double()?;
+                frame.push(StackValue::Double(-value))
+            }
+
+            OpCodes::i2l => {
+                let value = frame.pop_int()?;
+                frame.push(StackValue::Long(value as i64))
+            }
+            OpCodes::i2f => {
+                let value = frame.pop_int()?;
+                frame.push(StackValue::Float(value as f32))
+            }
+            OpCodes::i2d => {
+                let value = frame.pop_int()?;
+                frame.push(StackValue::Double(value as f64))
+            }
+            OpCodes::i2b => {
+                let value = frame.pop_int()?;
+                frame.push(StackValue::Integer(value as i8 as i32))
+            }
+            OpCodes::i2c => {
+                let value = frame.pop_int()?;
+                frame.push(StackValue::Integer(value as u16 as i32))
+            }
+            OpCodes::i2s => {
+                let value = frame.pop_int()?;
+                frame.push(StackValue::Integer(value as i16 as i32))
+            }
+            OpCodes::l2i => {
+                let value = frame.pop_long()?;
+                frame.push(StackValue::Integer(value as i32))
+            }
+            OpCodes::l2f => {
+                let value = frame.pop_long()?;
+                frame.push(StackValue::Float(value as f32))
+            }
+            OpCodes::l2d => {
+                let value = frame.pop_long()?;
+                frame.push(StackValue::Double(value as f64))
+            }
+            // Rust's float to int casts saturate and turn NaN into 0, just like java's
+            OpCodes::f2i => {
+                let value = frame.pop_float()?;
+                frame.push(StackValue::Integer(value as i32))
+            }
+            OpCodes::f2l => {
+                let value = frame.pop_float()?;
+                frame.push(StackValue::Long(value as i64))
+            }
+            OpCodes::f2d => {
+                let value = frame.pop_float()?;
+                frame.push(StackValue::Double(value as f64))
+            }
+            OpCodes::d2i => {
+                let value = frame.pop_double()?;
+                frame.push(StackValue::Integer(value as i32))
+            }
+            OpCodes::d2l => {
+                let value = frame.pop_double()?;
+                frame.push(StackValue::Long(value as i64))
+            }
+            OpCodes::d2f => {
+                let value = frame.pop_double()?;
+                frame.push(StackValue::Float(value as f32))
+            }
+
+            OpCodes::lcmp => {
+                let b = frame.pop_long()?;
+                let a = frame.pop_long()?;
+                frame.push(StackValue::Integer(a.cmp(&b) as i32))
+            }
+            OpCodes::fcmp(nan_result) => {
+                let b = frame.pop_float()?;
+                let a = frame.pop_float()?;
+                let value = compare_floating(a as f64, b as f64, *nan_result);
+                frame.push(StackValue::Integer(value))
+            }
+            OpCodes::dcmp(nan_result) => {
+                let b = frame.pop_double()?;
+                let a = frame.pop_double()?;
+                frame.push(StackValue::Integer(compare_floating(a, b, *nan_result)))
+            }
+
+            OpCodes::if_cond(condition, offset) => {
+                let value = frame.pop_int()?;
+                Ok(match compare(condition, value.cmp(&0)) {
+                    true => Action::Jump(*offset as i32),
+                    false => Action::Next,
+                })
+            }
+            OpCodes::if_icmp(condition, offset) => {
+                let b = frame.pop_int()?;
+                let a = frame.pop_int()?;
+                Ok(match compare(condition, a.cmp(&b)) {
+                    true => Action::Jump(*offset as i32),
+                    false => Action::Next,
+                })
+            }
+            OpCodes::if_acmp(condition, offset) => {
+                let b = frame.pop()?;
+                let a = frame.pop()?;
+                let ordering = match same_reference(&a, &b) {
+                    true => Ordering::Equal,
+                    false => Ordering::Less,
+                };
+                Ok(match compare(condition, ordering) {
+                    true => Action::Jump(*offset as i32),
+                    false => Action::Next,
+                })
+            }
+            OpCodes::if_null(offset) => Ok(match frame.pop()? {
+                StackValue::Null => Action::Jump(*offset as i32),
+                _ => Action::Next,
+            }),
+            OpCodes::if_notnull(offset) => Ok(match frame.pop()? {
+                StackValue::Null => Action::Next,
+                _ => Action::Jump(*offset as i32),
+            }),
+            OpCodes::goto(offset) => Ok(Action::Jump(*offset as i32)),
+            OpCodes::goto_w(offset) => Ok(Action::Jump(*offset)),
+
+            OpCodes::tableswitch(default, low, high, offsets) => {
+                let value = frame.pop_int()?;
+                Ok(Action::Jump(if value < *low || value > *high {
+                    *default
+                } else {
+                    offsets[(value - low) as usize]
+                }))
+            }
+            OpCodes::lookupswitch(default, pairs) => {
+                let value = frame.pop_int()?;
+                Ok(Action::Jump(
+                    pairs
+                        .iter()
+                        .find(|(key, _)| *key == value)
+                        .map(|(_, offset)| *offset)
+                        .unwrap_or(*default),
+                ))
+            }
+
+            OpCodes::ireturn
+            | OpCodes::lreturn
+            | OpCodes::freturn
+            | OpCodes::dreturn
+            | OpCodes::areturn => Ok(Action::Return(Some(frame.pop()?))),
+            OpCodes::Return => Ok(Action::Return(None)),
+
+            OpCodes::getstatic(cp_index) | OpCodes::putstatic(cp_index) => {
+                let field = self.resolve_field_ref(&frame.class, *cp_index)?;
+                if !field.is_static {
+                    return Err(self.new_exception(
+                        "java/lang/IncompatibleClassChangeError",
+                        &format!(
+                            "Expected static field {}.{}",
+                            field.class.name.replace('/', "."),
+                            field.name
+                        ),
+                    ));
+                }
+                self.initialize_class(&field.class)?;
+
+                if let OpCodes::putstatic(_) = opcode {
+                    let value = frame.pop()?;
+                    field
+                        .class
+                        .statics
+                        .lock()
+                        .unwrap()
+                        .insert(field.name.clone(), value);
+                    return Ok(Action::Next);
+                }
+
+                let value = field
+                    .class
+                    .statics
+                    .lock()
+                    .unwrap()
+                    .get(&field.name)
+                    .cloned()
+                    .unwrap_or_else(|| default_value(&field.descriptor));
+                frame.push(value)
+            }
+            OpCodes::getfield(cp_index) | OpCodes::putfield(cp_index) => {
+                let field = self.resolve_field_ref(&frame.class, *cp_index)?;
+                if field.is_static {
+                    return Err(self.new_exception(
+                        "java/lang/IncompatibleClassChangeError",
+                        &format!(
+                            "Expected non-static field {}.{}",
+                            field.class.name.replace('/', "."),
+                            field.name
+                        ),
+                    ));
+                }
+
+                let value = match opcode {
+                    OpCodes::putfield(_) => Some(frame.pop()?),
+                    _ => None,
+                };
+                let object = match frame.pop()? {
+                    StackValue::JavaObjectRef(object) => object,
+                    StackValue::Null => {
+                        let action = if value.is_some() { "assign" } else { "read" };
+                        return Err(self.new_exception(
+                            "java/lang/NullPointerException",
+                            &format!(
+                                "Cannot {action} field \"{}\" because value is null",
+                                field.name
+                            ),
+                        ));
+                    }
+                    value => return Err(unexpected_value("object", &value)),
+                };
+
+                match value {
+                    Some(value) => {
+                        self.set_field(object, &field.name, value);
+                        Ok(Action::Next)
+                    }
+                    None => {
+                        let value = self
+                            .get_field(object, &field.name)
+                            .unwrap_or_else(|| default_value(&field.descriptor));
+                        frame.push(value)
+                    }
+                }
+            }
+
+            OpCodes::new(cp_index) => {
+                let class = self.resolve_class_ref(&frame.class, *cp_index)?;
+                self.initialize_class(&class)?;
+                let object = self.new_object(&class, NativeData::None)?;
+                frame.push(StackValue::JavaObjectRef(object))
+            }
+
+            OpCodes::invokestatic(cp_index) | OpCodes::invokespecial(cp_index) => {
+                let method = self.resolve_method_ref(&frame.class, *cp_index)?;
+                let Some(target) = method.target.clone() else {
+                    return Err(self.new_exception(
+                        "java/lang/AbstractMethodError",
+                        &format!(
+                            "{}.{}{}",
+                            method.class.name.replace('/', "."),
+                            method.name,
+                            method.descriptor
+                        ),
+                    ));
+                };
+
+                if let OpCodes::invokestatic(_) = opcode {
+                    self.initialize_class(&method.class)?;
+                    let args = frame.pop_args(method.parameter_count)?;
+                    return Ok(Action::Invoke(target, args));
+                }
+
+                let args = frame.pop_args(method.parameter_count + 1)?;
+                if let StackValue::Null = args[0] {
+                    return Err(self.new_exception("java/lang/NullPointerException", ""));
+                }
+                Ok(Action::Invoke(target, args))
+            }
+            OpCodes::invokevirtual(cp_index) | OpCodes::invokeinterface(cp_index, _) => {
+                let method = self.resolve_method_ref(&frame.class, *cp_index)?;
+                let args = frame.pop_args(method.parameter_count + 1)?;
+                let class = self.runtime_class(&args[0])?;
+                let target = self.select_method(&method, &class)?;
+                Ok(Action::Invoke(target, args))
+            }
+
+            OpCodes::monitorenter => {
+                let key = self.monitor_key(&frame.pop()?)?;
+                self.enter_monitor(&key);
+                frame.entered.push(key);
+                Ok(Action::Next)
+            }
+            OpCodes::monitorexit => {
+                let key = self.monitor_key(&frame.pop()?)?;
+                self.exit_monitor(&key)?;
+                if let Some(index) = frame.entered.iter().rposition(|entered| *entered == key) {
+                    frame.entered.remove(index);
+                }
+                Ok(Action::Next)
+            }
+
+            OpCodes::checkcast(cp_index) => {
+                let value = frame.pop()?;
+                if !matches!(value, StackValue::Null)
+                    && !self.is_instance_of(&frame.class, &value, *cp_index)?
+                {
+                    return Err(self.class_cast_exception(&frame.class, &value, *cp_index));
+                }
+                frame.push(value)
+            }
+            OpCodes::instanceof(cp_index) => {
+                let value = frame.pop()?;
+                let is_instance = !matches!(value, StackValue::Null)
+                    && self.is_instance_of(&frame.class, &value, *cp_index)?;
+                frame.push(StackValue::Integer(is_instance as i32))
+            }
+
+            OpCodes::athrow => match frame.pop()? {
+                StackValue::JavaObjectRef(exception) => Err(JvmError::Exception(exception)),
+                StackValue::Null => Err(self.new_exception("java/lang/NullPointerException", "")),
+                value => Err(unexpected_value("throwable", &value)),
+            },
+
+            opcode => Err(JvmError::Internal(format!(
+                "The opcode {opcode:?} is not implemented"
+            ))),
+        }
+    }
+}
+
+/// The classes and interfaces every array is an instance of (JVMS 4.10.1.2)
+fn is_array_supertype(class_name: &str) -> bool {
+    matches!(
+        class_name,
+        "java/lang/Object" | "java/lang/Cloneable" | "java/io/Serializable"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jvm::{test_class, StackValue, JVM};
+
+    #[test]
+    fn negative_array_sizes_throw() {
+        let jvm = JVM::with_classes([test_class("Arrays")]);
+
+        let error = "java.lang.NegativeArraySizeException: -1";
+        let ints = jvm.run_static("Arrays", "ints", "(I)[I", vec![StackValue::Integer(-1)]);
+        assert_eq!(ints.unwrap_err(), error);
+        let strings = jvm.run_static(
+            "Arrays",
+            "strings",
+            "(I)[Ljava/lang/String;",
+            vec![StackValue::Integer(-1)],
+        );
+        assert_eq!(strings.unwrap_err(), error);
+        for (a, b) in [(0, -1), (2, -1)] {
+            let args = vec![StackValue::Integer(a), StackValue::Integer(b)];
+            let matrix = jvm.run_static("Arrays", "matrix", "(II)[[I", args);
+            assert_eq!(matrix.unwrap_err(), error);
+        }
+        assert!(jvm
+            .run_static(
+                "Arrays",
+                "matrix",
+                "(II)[[I",
+                vec![StackValue::Integer(2); 2]
+            )
+            .is_ok());
+
+        let caught = |n| {
+            jvm.run_static("Arrays", "caught", "(I)I", vec![StackValue::Integer(n)])
+                .unwrap()
+        };
+        assert!(matches!(caught(-5), StackValue::Integer(-1)));
+        assert!(matches!(caught(3), StackValue::Integer(3)));
+    }
+
+    #[test]
+    fn casts_follow_the_subtype_rules() {
+        let jvm = JVM::with_classes(
+            ["Casts", "Casts$Shape", "Casts$Square", "Casts$Other"].map(test_class),
+        );
+        let cast = |name| jvm.run_static("Casts", name, "()Ljava/lang/Object;", vec![]);
+
+        let instances = jvm.run_static("Casts", "instances", "()I", vec![]);
+        assert!(matches!(instances, Ok(StackValue::Integer(7))));
+        assert_eq!(
+            cast("square").unwrap_err(),
+            "java.lang.ClassCastException: class Casts$Square cannot be cast to class \
+             Casts$Other (Casts$Square and Casts$Other are in unnamed module of loader 'app')"
+        );
+        assert_eq!(
+            cast("ints").unwrap_err(),
+            "java.lang.ClassCastException: class [I cannot be cast to class \
+             [Ljava.lang.Object; ([I and [Ljava.lang.Object; are in module java.base of loader \
+             'bootstrap')"
+        );
+        assert!(matches!(cast("none"), Ok(StackValue::Null)));
+    }
+
+    #[test]
+    fn aastore_checks_the_component_type() {
+        let jvm = JVM::with_classes([test_class("Casts")]);
+
+        let ints = jvm.run_static("Casts", "storeInts", "()V", vec![]);
+        assert_eq!(ints.unwrap_err(), "java.lang.ArrayStoreException: [I");
+        assert!(jvm
+            .run_static("Casts", "storeStrings", "()V", vec![])
+            .is_ok());
+    }
+}
@@ -0,0 +1,400 @@
+//! The `java/lang/invoke` objects the constant pool can produce: method types, method handles and
+//! the lookups passed to bootstrap methods (JVMS 5.4.3.5, 5.4.3.6).
+
+use std::sync::Arc;
+
+use jvm_parser::classfile::{
+    classfile::MethodAccessFlags,
+    descriptor::{parse_method_descriptor, FieldDescriptor},
+};
+
+use super::{
+    class_loader::{LoadedClass, LoaderId},
+    heap::{HeapObject, NativeData},
+    interpreter::default_value,
+    runtime_constant_pool::{ResolvedField, ResolvedMethod},
+    JavaObjectRef, JvmError, StackValue, JVM,
+};
+
+/// The kind of a `CONSTANT_MethodHandle`, the instruction the handle behaves like (JVMS 5.4.3.5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    GetField = 1,
+    GetStatic,
+    PutField,
+    PutStatic,
+    InvokeVirtual,
+    InvokeStatic,
+    InvokeSpecial,
+    NewInvokeSpecial,
+    InvokeInterface,
+}
+
+impl ReferenceKind {
+    pub fn from_u8(kind: u8) -> Option<Self> {
+        Some(match kind {
+            1 => Self::GetField,
+            2 => Self::GetStatic,
+            3 => Self::PutField,
+            4 => Self::PutStatic,
+            5 => Self::InvokeVirtual,
+            6 => Self::InvokeStatic,
+            7 => Self::InvokeSpecial,
+            8 => Self::NewInvokeSpecial,
+            9 => Self::InvokeInterface,
+            _ => return None,
+        })
+    }
+
+    /// Whether the handle refers to a field rather than a method
+    pub fn is_field(self) -> bool {
+        matches!(
+            self,
+            Self::GetField | Self::GetStatic | Self::PutField | Self::PutStatic
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum HandleMember {
+    Field(Arc<ResolvedField>),
+    Method(Arc<ResolvedMethod>),
+}
+
+/// The native state of a `java/lang/invoke/MethodHandle`
+#[derive(Debug)]
+pub struct MethodHandle {
+    pub kind: ReferenceKind,
+    pub member: HandleMember,
+    /// The method descriptor of the handle's type, instance members take the receiver first
+    pub descriptor: String,
+}
+
+impl JVM {
+    /// Creates a `java/lang/invoke/MethodType`, the classes it names are resolved through `loader`
+    pub(super) fn new_method_type(
+        &self,
+        loader: LoaderId,
+        descriptor: &str,
+    ) -> Result<JavaObjectRef, JvmError> {
+        let method_descriptor = parse_method_descriptor(descriptor)
+            .map_err(|err| JvmError::Internal(err.to_string()))?;
+        for field_type in &method_descriptor.parameters {
+            self.class_of_descriptor(loader, &field_type.to_string())?;
+        }
+        if let Some(return_type) = &method_descriptor.return_type {
+            self.class_of_descriptor(loader, &return_type.to_string())?;
+        }
+
+        let class = self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/invoke/MethodType")?;
+        self.new_object(&class, NativeData::MethodType(descriptor.to_string()))
+    }
+
+    /// Creates a `java/lang/invoke/MethodHandle` for a resolved field or method, checking it
+    /// matches the kind of the handle
+    pub(super) fn new_method_handle(
+        &self,
+        kind: ReferenceKind,
+        member: HandleMember,
+    ) -> Result<JavaObjectRef, JvmError> {
+        let receiver = |class: &LoadedClass| match class.name.starts_with('[') {
+            true => class.name.clone(),
+            false => format!("L{};", class.name),
+        };
+
+        let descriptor = match &member {
+            HandleMember::Field(field) => {
+                let is_static = matches!(kind, ReferenceKind::GetStatic | ReferenceKind::PutStatic);
+                if field.is_static != is_static {
+                    return Err(self.new_exception(
+                        "java/lang/IncompatibleClassChangeError",
+                        &format!(
+                            "Expected {}static field {}.{}",
+                            if is_static { "" } else { "non-" },
+                            field.class.name.replace('/', "."),
+                            field.name
+                        ),
+                    ));
+                }
+
+                match kind {
+                    ReferenceKind::GetField => {
+                        format!("({}){}", receiver(&field.class), field.descriptor)
+                    }
+                    ReferenceKind::GetStatic => format!("(){}", field.descriptor),
+                    ReferenceKind::PutField => {
+                        format!("({}{})V", receiver(&field.class), field.descriptor)
+                    }
+                    _ => format!("({})V", field.descriptor),
+                }
+            }
+            HandleMember::Method(method) => {
+                let is_static = kind == ReferenceKind::InvokeStatic;
+                if self
+                    .is_static_method(method)
+                    .is_some_and(|declared| declared != is_static)
+                {
+                    return Err(self.new_exception(
+                        "java/lang/IncompatibleClassChangeError",
+                        &format!(
+                            "Expected {}static method {}.{}{}",
+                            if is_static { "" } else { "non-" },
+                            method.class.name.replace('/', "."),
+                            method.name,
+                            method.descriptor
+                        ),
+                    ));
+                }
+                if (kind == ReferenceKind::NewInvokeSpecial) != (method.name == "<init>") {
+                    return Err(self.new_exception(
+                        "java/lang/ClassFormatError",
+                        &format!(
+                            "Bad method handle kind {} for the method {}",
+                            kind as u8, method.name
+                        ),
+                    ));
+                }
+
+                let parameters = method
+                    .descriptor
+                    .strip_prefix('(')
+                    .and_then(|descriptor| descriptor.split_once(')'))
+                    .map(|(parameters, _)| parameters)
+                    .unwrap_or_default();
+                match kind {
+                    ReferenceKind::InvokeStatic => method.descriptor.clone(),
+                    ReferenceKind::NewInvokeSpecial => {
+                        format!("({parameters}){}", receiver(&method.class))
+                    }
+                    _ => format!("({}{}", receiver(&method.class), &method.descriptor[1..]),
+                }
+            }
+        };
+
+        let class = self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/invoke/MethodHandle")?;
+        let handle = MethodHandle {
+            kind,
+            member,
+            descriptor,
+        };
+        self.new_object(&class, NativeData::MethodHandle(Arc::new(handle)))
+    }
+
+    /// Whether a resolved method is static, `None` for natives the class file doesn't declare
+    fn is_static_method(&self, method: &ResolvedMethod) -> Option<bool> {
+        method.class.ancestors().find_map(|class| {
+            class
+                .java_class
+                .get_method(&method.name, &method.descriptor)
+                .map(|declared| declared.access_flags & MethodAccessFlags::ACC_STATIC != 0)
+        })
+    }
+
+    /// The native state of a `java/lang/invoke/MethodHandle` object
+    pub(super) fn method_handle_of(
+        &self,
+        value: &StackValue,
+    ) -> Result<Arc<MethodHandle>, JvmError> {
+        let object = match value {
+            StackValue::JavaObjectRef(object) => *object,
+            StackValue::Null => {
+                return Err(self.new_exception("java/lang/NullPointerException", ""));
+            }
+            value => {
+                return Err(JvmError::Internal(format!(
+                    "Expected a method handle, found: {value:?}"
+                )))
+            }
+        };
+
+        match self.heap.lock().unwrap().get(object) {
+            HeapObject::Instance(instance) => match &instance.native_data {
+                NativeData::MethodHandle(handle) => Ok(handle.clone()),
+                _ => Err(JvmError::Internal(
+                    "Expected a java/lang/invoke/MethodHandle object".to_string(),
+                )),
+            },
+            HeapObject::Array(_) => Err(JvmError::Internal(
+                "Expected a java/lang/invoke/MethodHandle object".to_string(),
+            )),
+        }
+    }
+
+    /// Invokes a method handle like the instruction its kind names would, `args` start with the
+    /// receiver for instance members
+    pub(super) fn invoke_method_handle(
+        &self,
+        handle: &MethodHandle,
+        mut args: Vec<StackValue>,
+    ) -> Result<StackValue, JvmError> {
+        let receiver = |args: &[StackValue]| match args.first() {
+            Some(StackValue::JavaObjectRef(object)) => Ok(*object),
+            Some(StackValue::Null) => Err(self.new_exception("java/lang/NullPointerException", "")),
+            value => Err(JvmError::Internal(format!(
+                "Expected an object as the receiver of a method handle, found: {value:?}"
+            ))),
+        };
+
+        match &handle.member {
+            HandleMember::Field(field) => match handle.kind {
+                ReferenceKind::GetField => Ok(self
+                    .get_field(receiver(&args)?, &field.name)
+                    .unwrap_or_else(|| default_value(&field.descriptor))),
+                ReferenceKind::PutField => {
+                    let object = receiver(&args)?;
+                    self.set_field(object, &field.name, args.swap_remove(1));
+                    Ok(StackValue::None)
+                }
+                ReferenceKind::GetStatic => {
+                    self.initialize_class(&field.class)?;
+                    Ok(field
+                        .class
+                        .statics
+                        .lock()
+                        .unwrap()
+                        .get(&field.name)
+                        .cloned()
+                        .unwrap_or_else(|| default_value(&field.descriptor)))
+                }
+                _ => {
+                    self.initialize_class(&field.class)?;
+                    let value = args.swap_remove(0);
+                    field
+                        .class
+                        .statics
+                        .lock()
+                        .unwrap()
+                        .insert(field.name.clone(), value);
+                    Ok(StackValue::None)
+                }
+            },
+            HandleMember::Method(method) => {
+                let direct_target = || {
+                    method.target.clone().ok_or_else(|| {
+                        self.new_exception(
+                            "java/lang/AbstractMethodError",
+                            &format!(
+                                "{}.{}{}",
+                                method.class.name.replace('/', "."),
+                                method.name,
+                                method.descriptor
+                            ),
+                        )
+                    })
+                };
+
+                match handle.kind {
+                    ReferenceKind::InvokeStatic => {
+                        self.initialize_class(&method.class)?;
+                        self.invoke_method(direct_target()?, args)
+                    }
+                    ReferenceKind::InvokeSpecial => {
+                        receiver(&args)?;
+                        self.invoke_method(direct_target()?, args)
+                    }
+                    ReferenceKind::NewInvokeSpecial => {
+                        self.initialize_class(&method.class)?;
+                        let scope = self.handle_scope();
+                        let object = self.new_object(&method.class, NativeData::None)?;
+                        scope.add(object);
+
+                        args.insert(0, StackValue::JavaObjectRef(object));
+                        self.invoke_method(direct_target()?, args)?;
+                        Ok(StackValue::JavaObjectRef(object))
+                    }
+                    _ => {
+                        receiver(&args)?;
+                        let class = self.runtime_class(&args[0])?;
+                        let target = self.select_method(method, &class)?;
+                        self.invoke_method(target, args)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Creates the `java/lang/invoke/MethodHandles$Lookup` bootstrap methods get, its lookup
+    /// class is the class whose constant pool is being resolved
+    fn new_lookup(&self, class: &Arc<LoadedClass>) -> Result<JavaObjectRef, JvmError> {
+        let mirror = self.class_mirror(class)?;
+        let lookup_class =
+            self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/invoke/MethodHandles$Lookup")?;
+        let lookup = self.new_object(&lookup_class, NativeData::None)?;
+        self.set_field(lookup, "lookupClass", StackValue::JavaObjectRef(mirror));
+        Ok(lookup)
+    }
+
+    /// Computes the value of a `CONSTANT_Dynamic` by invoking its bootstrap method with a lookup,
+    /// the constant's name and type, and the static arguments (JVMS 5.4.3.6)
+    pub(super) fn compute_dynamic_constant(
+        &self,
+        class: &Arc<LoadedClass>,
+        bootstrap_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> Result<StackValue, JvmError> {
+        let Some(bootstrap_method) = class
+            .java_class
+            .get_bootstrap_methods()
+            .and_then(|attribute| attribute.bootstrap_methods.get(bootstrap_index as usize))
+        else {
+            return Err(self.new_exception(
+                "java/lang/ClassFormatError",
+                &format!(
+                    "Missing bootstrap method {bootstrap_index} in class {}",
+                    class.name
+                ),
+            ));
+        };
+
+        let scope = self.handle_scope();
+        let handle_ref = bootstrap_method.bootstrap_method_ref;
+        let handle = self.resolve_method_handle_ref(class, handle_ref)?;
+        let handle = self.method_handle_of(&handle)?;
+
+        let lookup = self.new_lookup(class)?;
+        scope.add(lookup);
+        let constant_type = self.class_of_descriptor(class.loader, descriptor)?;
+        let mut args = vec![
+            StackValue::JavaObjectRef(lookup),
+            StackValue::String(name.to_string()),
+            StackValue::JavaObjectRef(self.class_mirror(&constant_type)?),
+        ];
+        for argument in &bootstrap_method.bootstrap_arguments {
+            let value = self.load_constant(class, *argument)?;
+            if let StackValue::JavaObjectRef(object) = value {
+                scope.add(object);
+            }
+            args.push(value);
+        }
+
+        // A variable arity bootstrap method gets the trailing arguments as an array
+        let parameters = parse_method_descriptor(&handle.descriptor)
+            .map(|descriptor| descriptor.parameters)
+            .unwrap_or_default();
+        if let Some(FieldDescriptor::Array(component_type)) =
+            parameters.last().filter(|_| parameters.len() != args.len())
+        {
+            let trailing = args.split_off((parameters.len() - 1).min(args.len()));
+            let array = self.new_array(&component_type.to_string(), trailing)?;
+            args.push(StackValue::JavaObjectRef(array));
+        }
+
+        match self.invoke_method_handle(&handle, args) {
+            Err(JvmError::Exception(exception))
+                if !self.is_instance_of_name(exception, "java/lang/Error") =>
+            {
+                scope.add(exception);
+                let error = self.new_exception(
+                    "java/lang/BootstrapMethodError",
+                    "bootstrap method initialization exception",
+                );
+                if let JvmError::Exception(error) = error {
+                    self.set_field(error, "cause", StackValue::JavaObjectRef(exception));
+                }
+                Err(error)
+            }
+            result => result,
+        }
+    }
+}
@@ -1,89 +1,153 @@
+#[cfg(test)]
+mod assembler;
+pub mod bootstrap;
+pub mod class_loader;
+pub mod gc;
+pub mod heap;
+pub mod hprof;
+pub mod interpreter;
+pub mod method_handles;
+pub mod monitors;
+pub mod natives;
 pub mod opcodes;
-
-use std::collections::HashMap;
+pub mod reflection;
+mod runtime_constant_pool;
+pub mod threads;
+mod verifier;
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc, Condvar, Mutex, OnceLock, Weak},
+    time::Instant,
+};
 
 use jvm_parser::{
-    classfile::{
-        attributes::{AttributeInfoData, CodeAttribute},
-        classfile::{MethodAccessFlags, MethodInfo},
-        constant_pool::CpInfo,
-        JavaClass,
-    },
+    classfile::{descriptor::MethodDescriptor, JavaClass},
     jar::JarFile,
 };
 
-use crate::{
-    jvm::opcodes::OpCodes,
-    utils::{parse_descriptor, Descriptor, DescriptorTypes},
+use self::{
+    class_loader::{ClassLoaderEntry, LoadedClass, LoaderId},
+    gc::SafepointState,
+    heap::{Heap, HeapObject, JavaArray, JavaObject, NativeData},
+    monitors::Monitors,
+    threads::Threads,
 };
 
-use self::opcodes::parse_opcodes;
-// use jvm_parser::ClassFile;
-
 #[allow(dead_code)]
 #[derive(Debug, Clone, Default)]
-enum StackValue {
+pub enum StackValue {
     Integer(i32),
+    Long(i64),
     Float(f32),
+    Double(f64),
     String(String),
     Short(i16),
     Byte(u8),
     JavaObjectRef(JavaObjectRef),
     JavaStaticClassRef(String),
+    Null,
     Invalid,
     #[default]
     None,
 }
 
-#[derive(Debug, Clone)]
+impl StackValue {
+    /// Longs and doubles take up two local variable slots
+    pub fn is_category_2(&self) -> bool {
+        matches!(self, StackValue::Long(_) | StackValue::Double(_))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct JavaObjectRef {
     index: usize,
 }
 
+#[derive(Debug, Clone)]
+pub enum JvmError {
+    /// A java exception being thrown up the frame stack
+    Exception(JavaObjectRef),
+    /// A failure in the vm itself, java code can't catch these
+    Internal(String),
+}
+
+/// A rust implementation of a java method, instance methods get `this` as the first argument
+pub type NativeMethod = Box<
+    dyn Fn(&JVM, Vec<StackValue>, MethodDescriptor) -> Result<StackValue, JvmError> + Send + Sync,
+>;
+
+/// The Java SE feature version the vm runs, the classes of multi-release jars are read for it
+pub const FEATURE_VERSION: u16 = 17;
+
 pub struct JVM {
-    classes: HashMap<String, JavaClass>,
+    class_loaders: Mutex<Vec<ClassLoaderEntry>>,
+    heap: Mutex<Heap>,
     main_method_class: Option<String>,
-    native_methods: HashMap<&'static str, Box<dyn Fn(Vec<StackValue>, Descriptor) -> StackValue>>,
+    /// Keyed by `class;method`. A binding takes precedence over the byte code of the class it is
+    /// registered for, so core classes work the same with and without an rt.jar
+    native_methods: HashMap<&'static str, NativeMethod>,
+    /// Verify classes not loaded by the bootstrap loader before they are initialized
+    verify: bool,
+    threads: Mutex<Threads>,
+    /// Notified whenever a thread starts, terminates or is interrupted
+    threads_changed: Condvar,
+    /// The vm itself, set by [`JVM::run`], so natives can hand it to the threads they start
+    handle: OnceLock<Weak<JVM>>,
+    monitors: Mutex<Monitors>,
+    /// Notified whenever a monitor is exited, notified or a waiting thread is interrupted
+    monitors_changed: Condvar,
+    safepoint: Mutex<SafepointState>,
+    /// Notified when a thread stops at or leaves a safepoint, and when a collection finishes
+    safepoint_changed: Condvar,
+    /// Set while a collection waits for the threads to reach a safepoint
+    gc_requested: AtomicBool,
+    gc_log: bool,
+    /// Log lines are stamped with the time since the vm was created
+    started: Instant,
+    /// Where the heap is dumped when it runs out, see [`JVM::set_heap_dump_on_out_of_memory`]
+    heap_dump_path: Option<PathBuf>,
+    heap_dumped: AtomicBool,
 }
 
 impl JVM {
     pub fn new() -> Self {
-        let mut natives: HashMap<
-            &'static str,
-            Box<dyn Fn(Vec<StackValue>, Descriptor) -> StackValue>,
-        > = HashMap::new();
-        natives.insert(
-            "com/ahse/jvm/Main;print",
-            Box::new(|args, descriptor| match descriptor {
-                Descriptor {
-                    return_value: DescriptorTypes::Void,
-                    parameters: params,
-                } if params.get(0)
-                    == Some(&DescriptorTypes::Class("java/lang/String".to_string())) =>
-                {
-                    let StackValue::String(v) = &args[0] else {
-                        panic!(
-                            "A string wans't passed as an argument to the print(String) function"
-                        );
-                    };
-                    println!("{v}");
-                    StackValue::None
-                }
-
-                _ => {
-                    todo!("Implement this");
-                }
-            }),
+        let mut natives: HashMap<&'static str, NativeMethod> = HashMap::new();
+        natives::register_natives(&mut natives);
+
+        let mut bootstrap_loader = ClassLoaderEntry::builtin(None);
+        bootstrap_loader.class_path.extend(
+            bootstrap::shim_classes()
+                .into_iter()
+                .map(|(name, java_class)| (name, Arc::new(java_class))),
         );
 
         Self {
-            classes: HashMap::new(),
+            class_loaders: Mutex::new(vec![
+                bootstrap_loader,
+                ClassLoaderEntry::builtin(Some(LoaderId::BOOTSTRAP)),
+            ]),
+            heap: Mutex::new(Heap::default()),
             main_method_class: None,
             native_methods: natives,
+            verify: false,
+            threads: Mutex::new(Threads::default()),
+            threads_changed: Condvar::new(),
+            handle: OnceLock::new(),
+            monitors: Mutex::new(Monitors::default()),
+            monitors_changed: Condvar::new(),
+            safepoint: Mutex::new(SafepointState::default()),
+            safepoint_changed: Condvar::new(),
+            gc_requested: AtomicBool::new(false),
+            gc_log: false,
+            started: Instant::now(),
+            heap_dump_path: None,
+            heap_dumped: AtomicBool::new(false),
         }
     }
 
-    pub fn add_class(&mut self, java_class: JavaClass) -> Result<(), String> {
+    fn class_name(java_class: &JavaClass) -> Result<String, String> {
         let Some(cp_class) = java_class.constant_pool.get_class_at(java_class.this_class) else {
             return Err(format!(
                 "No class constant pool entry at location {}, found: {:?}",
@@ -100,680 +164,291 @@ impl JVM {
             ));
         };
 
-        if java_class.access_flags & 0x0001 != 0 {
-            if let Some(method) = java_class.get_method_by_name(&"main".to_string()) {
-                if method.access_flags == (0x0001 | 0x0008) {
-                    self.main_method_class = Some(class_name.data.clone());
+        Ok(class_name.data.to_string())
+    }
+
+    /// Turns byte code verification of the classes outside of the core library on or off
+    pub fn set_verify(&mut self, verify: bool) {
+        self.verify = verify;
+    }
+
+    /// Adds a class to the class path of the app class loader
+    pub fn add_class(&mut self, java_class: JavaClass<'static>) -> Result<(), String> {
+        let class_name = JVM::class_name(&java_class)?;
+
+        if JVM::has_main_method(&java_class) {
+            self.main_method_class = Some(class_name.clone());
+        }
+        self.class_loaders.get_mut().unwrap()[LoaderId::APP.0]
+            .class_path
+            .insert(class_name, Arc::new(java_class));
+
+        Ok(())
+    }
+
+    /// Whether the class is public and has a `public static main`
+    fn has_main_method(java_class: &JavaClass) -> bool {
+        java_class.access_flags & 0x0001 != 0
+            && java_class
+                .get_method_by_name("main")
+                .is_some_and(|method| method.access_flags == (0x0001 | 0x0008))
+    }
+
+    /// Adds a jar to the app class loader, its classes are parsed when they are loaded and borrow
+    /// from it, so it stays open as long as the vm runs. The main class is the one named in the
+    /// manifest, or else a class with a main method. The entries of a multi-release jar should
+    /// be selected for [`FEATURE_VERSION`]
+    pub fn add_jar(&mut self, jar_file: &'static JarFile) -> Result<(), String> {
+        match &jar_file.manifest.main_class {
+            Some(main_class) => self.main_method_class = Some(main_class.replace('.', "/")),
+            None => {
+                // Only the declarations are parsed, the classes are parsed again when loaded
+                for class_name in jar_file.class_names() {
+                    let file_name = format!("{class_name}.class");
+                    let bytes = jar_file.read(&file_name).map_err(|err| err.to_string())?;
+                    let java_class = JavaClass::skim(&bytes).map_err(|err| {
+                        format!("Failed to add the class file: {file_name}\n{err}")
+                    })?;
+                    if JVM::has_main_method(&java_class) {
+                        self.main_method_class = Some(class_name.to_string());
+                    }
                 }
             }
         }
-        self.classes.insert(class_name.data.clone(), java_class);
+
+        self.class_loaders.get_mut().unwrap()[LoaderId::APP.0]
+            .jars
+            .push(jar_file);
 
         Ok(())
     }
 
-    pub fn add_jar(&mut self, jar_file: JarFile) -> Result<(), String> {
-        for (file_name, java_class) in jar_file.classes {
-            if let Err(error_msg) = self.add_class(java_class) {
-                return Err(format!(
-                    "Failed to add the class file: {file_name}\n{error_msg}"
-                ));
-            }
+    /// Adds a jar to the bootstrap class path, its classes replace the builtin shims
+    pub fn add_boot_jar(&mut self, jar_file: &'static JarFile) -> Result<(), String> {
+        let bootstrap_loader = &mut self.class_loaders.get_mut().unwrap()[LoaderId::BOOTSTRAP.0];
+        for class_name in jar_file.class_names() {
+            bootstrap_loader.class_path.remove(class_name);
         }
+        bootstrap_loader.jars.push(jar_file);
 
         Ok(())
     }
 
-    pub fn get_classes(&self) -> &HashMap<String, JavaClass> {
-        return &self.classes;
+    /// Runs the main method on the calling thread, then waits for the threads that aren't daemons
+    pub fn run(self: &Arc<Self>) -> Result<(), String> {
+        let Some(main_method_class_name) = &self.main_method_class else {
+            return Err("There is no main function to run".to_string());
+        };
+        let _ = self.handle.set(Arc::downgrade(self));
+
+        let result = self
+            .attach_main_thread()
+            .and_then(|_| self.resolve_class(LoaderId::APP, main_method_class_name))
+            .and_then(|main_class| {
+                self.initialize_class(&main_class)?;
+
+                let Some(target) = self.find_method(&main_class, "main", "([Ljava/lang/String;)V")
+                else {
+                    return Err(JvmError::Internal(format!(
+                        "Could not find main method in class: {main_method_class_name}"
+                    )));
+                };
+
+                let args = self.new_array("Ljava/lang/String;", vec![])?;
+                self.invoke_method(target, vec![StackValue::JavaObjectRef(args)])
+            });
+
+        // Described while the main thread still runs, a collection could free it afterwards
+        let result = match result {
+            Ok(_) => Ok(()),
+            Err(JvmError::Internal(message)) => Err(message),
+            Err(JvmError::Exception(exception)) => Err(format!(
+                "Exception in thread \"main\" {}",
+                self.describe_exception(exception)
+            )),
+        };
+
+        // Like `DestroyJavaVM`, an exception thrown out of main doesn't stop the other threads
+        self.detach_current_thread();
+        self.wait_for_non_daemon_threads();
+        result
     }
 
-    fn get_method_from_class(
+    pub(crate) fn handle(&self) -> Arc<JVM> {
+        self.handle
+            .get()
+            .and_then(Weak::upgrade)
+            .expect("the vm is running")
+    }
+
+    pub(crate) fn new_object(
         &self,
-        class_name: &str,
-        method_name: &str,
-    ) -> Option<(&JavaClass, &MethodInfo)> {
-        let Some(class) = &self.classes.get(class_name) else {
-            return None;
-        };
+        class: &Arc<LoadedClass>,
+        native_data: NativeData,
+    ) -> Result<JavaObjectRef, JvmError> {
+        self.allocate(HeapObject::Instance(JavaObject {
+            class: class.clone(),
+            fields: HashMap::new(),
+            native_data,
+        }))
+    }
 
-        let Some(method) = class.get_method_by_name(&method_name.to_string()) else {
-            return None;
-        };
+    pub(crate) fn new_array(
+        &self,
+        component_type: &str,
+        values: Vec<StackValue>,
+    ) -> Result<JavaObjectRef, JvmError> {
+        self.allocate(HeapObject::Array(JavaArray {
+            component_type: component_type.to_string(),
+            values,
+        }))
+    }
 
-        Some((class, method))
+    pub(crate) fn get_field(&self, object: JavaObjectRef, name: &str) -> Option<StackValue> {
+        match self.heap.lock().unwrap().get(object) {
+            HeapObject::Instance(instance) => instance.fields.get(name).cloned(),
+            HeapObject::Array(_) => None,
+        }
     }
 
-    pub fn run(&self) -> Result<(), String> {
-        let Some(main_method_class_name) = &self.main_method_class else {
-            return Err("There is no main function to run".to_string());
-        };
+    pub(crate) fn set_field(&self, object: JavaObjectRef, name: &str, value: StackValue) {
+        self.heap.lock().unwrap().set_field(object, name, value);
+    }
 
-        let Some((main_class, method)) = self.get_method_from_class(main_method_class_name, "main")
-        else {
-            return Err(format!(
-                "Could not find main method in class: {main_method_class_name}"
-            ));
+    pub(crate) fn is_instance_of_name(&self, object: JavaObjectRef, class_name: &str) -> bool {
+        self.runtime_class(&StackValue::JavaObjectRef(object))
+            .map(|class| class.ancestors().any(|class| class.name == class_name))
+            .unwrap_or(false)
+    }
+
+    /// Creates an exception the vm throws, like `NullPointerException`
+    pub(crate) fn new_exception(&self, class_name: &str, message: &str) -> JvmError {
+        let class = match self.load_class(LoaderId::BOOTSTRAP, class_name) {
+            Ok(Some(class)) => class,
+            _ => return JvmError::Internal(format!("{class_name}: {message}")),
         };
 
-        if method.access_flags != /* public static */ (0x0001 | 0x0008) {
-            return Err(format!(
-                "The main method in the class '{main_method_class_name}' is not public and static"
-            ));
+        let exception = match self.new_object(&class, NativeData::None) {
+            Ok(exception) => exception,
+            Err(err) => return err,
+        };
+        if !message.is_empty() {
+            self.set_field(
+                exception,
+                "detailMessage",
+                StackValue::String(message.to_string()),
+            );
         }
+        JvmError::Exception(exception)
+    }
 
-        let Some(method_byte_code) = method
-            .attributes
-            .iter()
-            .find(|attribute| match attribute.attribute {
-                AttributeInfoData::Code(_) => true,
-                _ => false,
-            })
-            .map(|attribute| match &attribute.attribute {
-                AttributeInfoData::Code(byte_code_data) => byte_code_data,
-                _ => unreachable!(),
-            })
-        else {
-            return Err(format!("The main method in the class '{main_method_class_name}' does not have runnable byte code"));
-        };
+    /// Formats an exception like `java.lang.RuntimeException: message`
+    pub(crate) fn describe_exception(&self, exception: JavaObjectRef) -> String {
+        let class_name = self
+            .runtime_class(&StackValue::JavaObjectRef(exception))
+            .map(|class| class.name.replace('/', "."))
+            .unwrap_or_default();
+
+        match self.get_field(exception, "detailMessage") {
+            Some(StackValue::String(message)) => format!("{class_name}: {message}"),
+            _ => class_name,
+        }
+    }
+}
 
-        // dbg!(method_byte_code);
+/// Runs the classes compiled from `test-data/src`, for the tests of the vm. They are compiled with
+/// `javac --release 8 -d test-data/classes test-data/src/*.java`
+#[cfg(test)]
+impl JVM {
+    /// A vm with the classes on the class path of the app loader
+    pub(crate) fn with_classes(classes: impl IntoIterator<Item = JavaClass<'static>>) -> Arc<JVM> {
+        let mut jvm = JVM::new();
+        for class in classes {
+            jvm.add_class(class).unwrap();
+        }
+        Arc::new(jvm)
+    }
 
-        self.execute_code(method, method_byte_code, main_class);
+    /// Runs a static method on the calling thread, which is attached to the vm for the call, then
+    /// waits for the threads that aren't daemons. An exception thrown out of the method is
+    /// described like `java.lang.ArithmeticException: / by zero`
+    pub(crate) fn run_static(
+        self: &Arc<Self>,
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+        args: Vec<StackValue>,
+    ) -> Result<StackValue, String> {
+        self.attached(|| self.invoke_static(class_name, name, descriptor, args))
+    }
 
-        Ok(())
+    /// Runs `f` on the calling thread while it is attached to the vm, so objects `f` creates can
+    /// be kept alive by a handle scope until it returns. Errors are described like `run_static`
+    pub(crate) fn attached<T>(
+        self: &Arc<Self>,
+        f: impl FnOnce() -> Result<T, JvmError>,
+    ) -> Result<T, String> {
+        let _ = self.handle.set(Arc::downgrade(self));
+        let result = self
+            .attach_main_thread()
+            .and_then(|_| f())
+            .map_err(|err| match err {
+                JvmError::Exception(exception) => self.describe_exception(exception),
+                JvmError::Internal(message) => message,
+            });
+
+        self.detach_current_thread();
+        self.wait_for_non_daemon_threads();
+        result
     }
 
-    pub fn execute_code(
+    /// Invokes a static method of a class of the app loader, the calling thread must be attached
+    pub(crate) fn invoke_static(
         &self,
-        _method: &MethodInfo,
-        code_data: &CodeAttribute,
-        java_class: &JavaClass,
-    ) {
-        let opcodes = parse_opcodes(&code_data.code).unwrap();
-        let mut stack: Vec<StackValue> = vec![];
-
-        for opcode in opcodes {
-            #[allow(unused_variables)]
-            match opcode {
-                OpCodes::dup => todo!(),
-                OpCodes::getstatic(cp_index) => todo!(),
-                OpCodes::ldc(cp_index) => {
-                    let Some(entry) = java_class.constant_pool.get_at(cp_index as u16) else {
-                        panic!("No entry at index: {cp_index} in constant_pool");
-                    };
-
-                    match entry {
-                        CpInfo::Integer(cp_int) => todo!("ldc integer"),
-                        CpInfo::Float(cp_f) => todo!("ldc float"),
-                        CpInfo::Class(cp_class) => todo!("ldc class"),
-                        CpInfo::String(cp_str) => stack.push(StackValue::String(
-                            java_class
-                                .constant_pool
-                                .get_utf8_at(cp_str.string_index)
-                                .unwrap()
-                                .data
-                                .clone(),
-                        )),
-                        CpInfo::MethodHandle(cp_method) => todo!("ldc method handle"),
-                        CpInfo::MethodType(cp_method_type) => todo!("ldc method type"),
-                        // TODO: CpInfo::Dynamic -- find out which this one is
-                        _ => panic!("Tried to load an unloadable constant value"),
-                    }
-                }
-                OpCodes::ldc2_w(cp_index) => todo!(),
-                OpCodes::ldc_w(cp_index) => todo!(),
-                OpCodes::invokevirtual(cp_index) => todo!(),
-                OpCodes::invokespecial(cp_index) => todo!(),
-                OpCodes::invokestatic(cp_index) => {
-                    let Some((_, cp_class, cp_name_n_type)) =
-                        java_class.constant_pool.get_refs_ext_at(cp_index as u16)
-                    else {
-                        panic!("Didn't find shit");
-                    };
-
-                    let Some(class_name) =
-                        java_class.constant_pool.get_utf8_at(cp_class.name_index)
-                    else {
-                        panic!(
-                            "Couldn't find class name in constant pool at index: {}",
-                            cp_class.name_index
-                        );
-                    };
-
-                    let Some(method_name) = java_class
-                        .constant_pool
-                        .get_utf8_at(cp_name_n_type.name_index)
-                    else {
-                        panic!(
-                            "Couldn't find method name in constant pool at index: {}",
-                            cp_name_n_type.name_index
-                        );
-                    };
-
-                    let Some((j_class, method)) =
-                        self.get_method_from_class(&class_name.data, &method_name.data)
-                    else {
-                        panic!(
-                            "Couldn't find class '{}' with method '{}' in class hash list",
-                            class_name.data, method_name.data
-                        );
-                    };
-
-                    if method.access_flags & MethodAccessFlags::ACC_NATIVE != 0 {
-                        let Some(native_method) = self
-                            .native_methods
-                            .get(format!("{};{}", class_name.data, method_name.data).as_str())
-                        else {
-                            panic!(
-                                "Native method isn't in library: {};{}",
-                                class_name.data, method_name.data
-                            );
-                        };
-
-                        let Some(descriptor) =
-                            j_class.constant_pool.get_utf8_at(method.descriptor_index)
-                        else {
-                            panic!("Failed to find descriptor");
-                        };
-
-                        let descriptor = parse_descriptor(&descriptor.data);
-
-                        let params = stack
-                            .drain(stack.len().saturating_sub(descriptor.parameters.len())..)
-                            .collect::<Vec<_>>();
-
-                        native_method(params, descriptor);
-                    }
+        class_name: &str,
+        name: &str,
+        descriptor: &str,
+        args: Vec<StackValue>,
+    ) -> Result<StackValue, JvmError> {
+        let class = self.resolve_class(LoaderId::APP, class_name)?;
+        self.initialize_class(&class)?;
+        let target = self.find_method(&class, name, descriptor).ok_or_else(|| {
+            JvmError::Internal(format!("No method {name}{descriptor} in {class_name}"))
+        })?;
+        self.invoke_method(target, args)
+    }
 
-                    // println!("{:#?}",);
-                }
-                OpCodes::bipush(byte) => todo!(),
-                OpCodes::sipush(short) => todo!(),
-                OpCodes::new(cp_index) => todo!(),
-                OpCodes::anewarray(cp_index) => todo!(),
-                OpCodes::istore_(local_index) => todo!(),
-                OpCodes::iconst_(local_index) => todo!(),
-                OpCodes::iload_(local_index) => todo!(),
-                OpCodes::fstore_(local_index) => todo!(),
-                OpCodes::fconst_(local_index) => todo!(),
-                OpCodes::fload_(local_index) => todo!(),
-                OpCodes::astore_(local_index) => todo!(),
-                OpCodes::aconst_null => todo!(),
-                OpCodes::aload_(local_index) => todo!(),
-
-                OpCodes::nop | OpCodes::Return => {}
-
-                _ => {}
-            }
-        }
+    /// The value of a static field of a class the app loader loaded
+    pub(crate) fn static_value(&self, class_name: &str, name: &str) -> Option<StackValue> {
+        let class = self.find_loaded_class(LoaderId::APP, class_name)?;
+        let value = class.statics.lock().unwrap().get(name).cloned();
+        value
+    }
 
-        // let mut reader = ByteReader::from_vec(&code_data.code);
-        // reader.set_endian(byte_reader::Endian::Big);
-
-        // let mut static_classes: HashMap<String, JavaClass> = HashMap::new();
-        // let mut java_objects: Vec<JavaClass> = vec![];
-        // let mut operand_stack: Vec<StackValue> = vec![];
-        // let mut frame = vec![StackValue::default(); code_data.max_locals as usize];
-
-        // #[allow(unused)]
-        // fn debug_memory(
-        //     operand_stack: &Vec<StackValue>,
-        //     frame: &Vec<StackValue>,
-        //     java_objects: &Vec<JavaClass>,
-        //     static_classes: &HashMap<String, JavaClass>,
-        // ) {
-        //     println!("==================\nCurrent JVM Memory:");
-        //     println!("Operand Stack: {:#?}\n", operand_stack);
-        //     println!("Current Frame: {:#?}\n", frame);
-        //     println!("Java Object Size: {}", java_objects.len());
-        //     println!("Java Static Classes: {:#?}", static_classes.keys());
-        // }
-
-        // #[allow(unused)]
-        // fn debug_bytecode(bytecode: &Vec<u8>) {
-        //     println!("===========================\nCurrent Remaining bytecode:");
-        //     println!("Byte Code Bytes: {:?}", &bytecode);
-        //     println!("With potential opcodes:");
-
-        //     let mut iter = bytecode.iter();
-
-        //     while let Some(v) = iter.next() {
-        //         let opcode = OpCodes::from(*v);
-        //         match opcode {
-        //             OpCodes::getstatic => {
-        //                 let index =
-        //                     u16::from_be_bytes([*iter.next().unwrap(), *iter.next().unwrap()]);
-        //                 println!("  {:?}({})", opcode, index);
-        //             }
-        //             OpCodes::ldc => {
-        //                 let index = u8::from_be_bytes([*iter.next().unwrap()]);
-        //                 println!("  {:?}({})", opcode, index);
-        //             }
-
-        //             OpCodes::invokevirtual => {
-        //                 let index =
-        //                     u16::from_be_bytes([*iter.next().unwrap(), *iter.next().unwrap()]);
-        //                 println!("  {:?}({})", opcode, index);
-        //             }
-
-        //             OpCodes::invokestatic => {
-        //                 let index =
-        //                     u16::from_be_bytes([*iter.next().unwrap(), *iter.next().unwrap()]);
-        //                 println!("  {:?}({})", opcode, index);
-        //             }
-
-        //             v => println!("  {:?}", v),
-        //         }
-        //     }
-        //     println!();
-        // }
-
-        // debug_bytecode(&reader.peak_rest().unwrap());
-        // while let Ok(opcode_byte) = reader.read::<u8>() {
-        //     let opcode_byte = opcode_byte;
-        //     let opcode = OpCodes::from(opcode_byte);
-        //     println!("[Executing Opcode : {opcode:?}]");
-
-        //     match opcode {
-        //         OpCodes::ldc => {
-        //             let index: u8 = reader.read().unwrap();
-
-        //             let stack_value = match java_class.constant_pool.get_at(index as u16).unwrap() {
-        //                 CpInfo::String(str) => {
-        //                     let text = java_class
-        //                         .constant_pool
-        //                         .get_utf8_at(str.string_index)
-        //                         .unwrap()
-        //                         .data
-        //                         .clone();
-
-        //                     StackValue::String(text)
-        //                 }
-
-        //                 CpInfo::Integer(int) => StackValue::Integer(int.bytes),
-        //                 CpInfo::Float(float) => StackValue::Float(float.bytes),
-
-        //                 unimplemented_type => {
-        //                     panic!("[OpCode : LDC] The value at index ( {} ) in the constant_pool, does not have an implementation on the operand stack. Constant pool value: {:#?}", index, unimplemented_type);
-        //                 }
-        //             };
-        //             operand_stack.push(stack_value);
-        //         }
-
-        //         OpCodes::invokestatic => {
-        //             let index: u16 = reader.read().unwrap();
-
-        //             let (_, class, name_type) =
-        //                 java_class.constant_pool.get_refs_ext_at(index).unwrap();
-        //             let class_name = java_class
-        //                 .constant_pool
-        //                 .get_utf8_at(class.name_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-
-        //             let function_name = java_class
-        //                 .constant_pool
-        //                 .get_utf8_at(name_type.name_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-        //             let descriptor = java_class
-        //                 .constant_pool
-        //                 .get_utf8_at(name_type.descriptor_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-
-        //             let descriptor = parse_descriptor(&descriptor);
-
-        //             let target_class = self.classes.get(&class_name).unwrap();
-
-        //             let method = target_class.get_method_by_name(&function_name).unwrap();
-
-        //             // match method.access_flags {}
-
-        //             if match_flag(
-        //                 method.access_flags,
-        //                 MethodAccessFlags::ACC_NATIVE | MethodAccessFlags::ACC_SYNCHRONIZED,
-        //             ) {
-        //                 todo!("Implement the seperate logic when executing native and synchronized: https://docs.oracle.com/javase/specs/jvms/se19/html/jvms-6.html#jvms-6.5.invokestatic")
-        //             } else if method.access_flags & MethodAccessFlags::ACC_NATIVE as u16 != 0 {
-        //                 // println!("[Invokenative]");
-
-        //                 self.native_methods
-        //                     .get(format!("{class_name};{function_name}").as_str())
-        //                     .unwrap()(
-        //                     vec![StackValue::String("Hello World, Hardcoded".to_string())],
-        //                     descriptor,
-        //                 );
-        //             } else {
-        //                 todo!("Implement logic for invoking static functions for a specified class")
-        //             }
-
-        //             // println!(
-        //             //     "[OpCodes : invokestatic] Invoking {:#?}",
-        //             //     (&class_name, &function_name, &descriptor)
-        //             // );
-        //         }
-
-        //         OpCodes::Return => { /* for now, just don't do shit */ }
-        //         OpCodes::nop => { /* this is meant to be empty */ }
-        //         unknown_opcode => {
-        //             panic!(
-        //                 "The OpCode ( {} : {:?} ) is not imeplemented",
-        //                 opcode_byte, unknown_opcode
-        //             )
-        //         }
-        //     }
-        // }
-
-        // while bytes.len() > 0 {
-        //     match opcode {
-        //         OpCodes::getstatic => {
-        //             let index = read_u2(&mut bytes);
-
-        //             let (_, class, name_type) = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_refs_ext_at(index)
-        //                 .unwrap();
-
-        //             let class_name = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(class.name_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-        //             let _name_type_name = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(name_type.name_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-        //             let _descriptor = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(name_type.descriptor_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-
-        //             dbgprint!(
-        //                 "[OpCodes : getstatic] Initialize new: {:#?}",
-        //                 (&class_name, &_name_type_name, &_descriptor)
-        //             );
-
-        //             if !static_classes.contains_key(&class_name) {
-        //                 let class =
-        //                     get_class_constructor(&class_name)(JavaClassInitContext::empty());
-        //                 static_classes.insert(String::from(&class_name), class);
-        //             }
-
-        //             if let Some(_) = static_classes.get(&class_name) {
-        //                 operand_stack
-        //                     .push(StackValue::JavaStaticClassRef(String::from(&class_name)));
-        //             }
-        //         }
-
-        //         OpCodes::ldc => {
-        //             let index = read_u1(&mut bytes);
-        //             let value = match &self.class_file.constant_pool.get_at(index as u16) {
-        //                 CpInfo::String(str) => {
-        //                     let text = self
-        //                         .class_file
-        //                         .constant_pool
-        //                         .get_utf8_at(str.string_index)
-        //                         .unwrap()
-        //                         .data
-        //                         .clone();
-
-        //                     StackValue::String(text)
-        //                 }
-
-        //                 CpInfo::Integer(int) => StackValue::Integer(int.bytes),
-        //                 CpInfo::Float(float) => StackValue::Float(float.bytes),
-
-        //                 unimplemented_type => {
-        //                     panic!("[OpCode : LDC] The value at index ( {} ) in the constant_pool, does not have an implementation on the operand stack. Constant pool value: {:#?}", index, unimplemented_type);
-        //                 }
-        //             };
-
-        //             dbgprint!("[OpCode : LDC] Adding value to operand_stack: {:?}", value);
-        //             operand_stack.push(value);
-        //         }
-
-        //         OpCodes::invokevirtual => {
-        //             let index = read_u2(&mut bytes);
-        //             let (_, class, name_type) = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_refs_ext_at(index)
-        //                 .unwrap();
-
-        //             let class_name = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(class.name_index)
-        //                 .unwrap();
-        //             let name_type_name = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(name_type.name_index)
-        //                 .unwrap();
-        //             let descriptor = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(name_type.descriptor_index)
-        //                 .unwrap();
-
-        //             dbgprint!(
-        //                 "[OpCode : invokevirtual] Invoking: {:#?}",
-        //                 (&class_name.data, &name_type_name.data, &descriptor.data)
-        //             );
-
-        //             dbgprint!("Operand Stack: {:#?}", operand_stack);
-
-        //             todo!("OpCode invokevirtual")
-        //         }
-
-        //         OpCodes::invokespecial => {
-        //             let index = read_u2(&mut bytes);
-
-        //             let (_refs, class, name_type) = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_refs_ext_at(index)
-        //                 .unwrap();
-
-        //             let class_name = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(class.name_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-
-        //             let name_type_name = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(name_type.name_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-        //             let descriptor = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(name_type.descriptor_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-
-        //             let descriptor = parse_descriptor(&descriptor);
-
-        //             dbgprint!(
-        //                 "[OpCodes : invokespecial] Invoking {:#?}",
-        //                 (&class_name, &name_type_name, &descriptor)
-        //             );
-
-        //             if name_type_name == "<init>" {
-        //                 let java_class =
-        //                     get_class_constructor(&class_name)(JavaClassInitContext::empty());
-        //             } else {
-        //             }
-
-        //             todo!("OpCode invokespecial")
-        //         }
-
-        //         OpCodes::invokestatic => {
-        //             let index = read_u2(&mut bytes);
-
-        //             let (_, class, name_type) = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_refs_ext_at(index)
-        //                 .unwrap();
-        //             let class_name = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(class.name_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-
-        //             let name_type_name = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(name_type.name_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-        //             let descriptor = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(name_type.descriptor_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-
-        //             let descriptor = parse_descriptor(&descriptor);
-
-        //             dbgprint!(
-        //                 "[OpCodes : invokestatic] Invoking {:#?}",
-        //                 (&class_name, &name_type_name, &descriptor)
-        //             );
-        //         }
-
-        //         OpCodes::bipush => {
-        //             let byte = read_u1(&mut bytes);
-        //             operand_stack.push(StackValue::Integer(i32::from_be_bytes([0, 0, 0, byte])));
-        //         }
-
-        //         OpCodes::sipush => {
-        //             let bytes = read_u2(&mut bytes);
-        //             operand_stack.push(StackValue::Short(i16::from_be_bytes(bytes.to_be_bytes())));
-        //         }
-
-        //         OpCodes::new => {
-        //             let index = read_u2(&mut bytes);
-
-        //             let class = self.class_file.constant_pool.get_class_at(index).unwrap();
-        //             let class_name = self
-        //                 .class_file
-        //                 .constant_pool
-        //                 .get_utf8_at(class.name_index)
-        //                 .unwrap()
-        //                 .data
-        //                 .clone();
-
-        //             dbgprint!(
-        //                 "[OpCodes : new] Initialize new class: {:#?}",
-        //                 (&class, &class_name)
-        //             );
-
-        //             let java_class =
-        //                 get_class_constructor(&class_name)(JavaClassInitContext::empty());
-        //             java_objects.push(java_class);
-
-        //             operand_stack.push(StackValue::JavaObjectRef(JavaObjectRef {
-        //                 index: java_objects.len() - 1,
-        //             }));
-        //         }
-
-        //         OpCodes::dup => {
-        //             let last_element = operand_stack.last().unwrap().clone();
-        //             dbgprint!("[OpCodes : dup] Duplicate {:#?}", last_element);
-        //             operand_stack.push(last_element);
-        //         }
-
-        //         OpCodes::istore_(n) => {
-        //             if let Some(int) = operand_stack.pop() {
-        //                 if let StackValue::Integer(int) = int {
-        //                     frame[n] = StackValue::Integer(int);
-        //                 } else {
-        //                     panic!("[OpCode : istore_{}] The stack value that was poped from the operand stack w", n);
-        //                 }
-        //             } else {
-        //                 panic!("[OpCode : istore_{}] Something wen't wrong. the operand stack seems to be empty when attempted to pop from", n);
-        //             }
-        //         }
-
-        //         OpCodes::iload_(n) => {
-        //             if let StackValue::Integer(int) = frame[n] {
-        //                 operand_stack.push(StackValue::Integer(int));
-        //                 frame[n] = StackValue::None;
-        //             }
-        //         }
-
-        //         OpCodes::iconst_(n) => {
-        //             operand_stack.push(StackValue::Integer(n));
-        //         }
-
-        //         OpCodes::fstore_(n) => {
-        //             if let Some(float) = operand_stack.pop() {
-        //                 if let StackValue::Float(float) = float {
-        //                     frame[n] = StackValue::Float(float);
-        //                 } else {
-        //                     panic!("The value gathered from the stack is not a float")
-        //                 }
-        //             } else {
-        //                 panic!("Oops seems like the stack is empty. this wasn't supposed to happen")
-        //             }
-        //         }
-
-        //         OpCodes::fload_(n) => {
-        //             if let StackValue::Float(float) = frame[n] {
-        //                 operand_stack.push(StackValue::Float(float));
-        //             } else {
-        //                 panic!("The frame value at index ( {} ) is either empty or doesn't contain float value", n);
-        //             }
-        //         }
-
-        //         OpCodes::fconst_(float) => {
-        //             operand_stack.push(StackValue::Float(float));
-        //         }
-
-        //         // Return or void or do nothing
-        //         OpCodes::Return | OpCodes::nop => {}
-
-        //         // Handle the opcodes that ins't implemented
-        //         OpCodes::OpCodeError(_) => {
-        //             panic!(
-        //                 "The OpCode ( {} ), is not implemented or doesn't exist",
-        //                 opcode_byte
-        //             );
-        //         }
-        //         #[allow(unreachable_patterns)]
-        //         unknown_opcode => {
-        //             panic!(
-        //                 "The OpCode ( {} : {:?} ) is not imeplemented",
-        //                 opcode_byte, unknown_opcode
-        //             )
-        //         }
-        //     }
-        // }
+    /// A `byte[]` with the contents of `bytes`
+    pub(crate) fn new_byte_array(&self, bytes: &[u8]) -> Result<JavaObjectRef, JvmError> {
+        self.new_array(
+            "B",
+            bytes
+                .iter()
+                .map(|byte| StackValue::Integer(*byte as i8 as i32))
+                .collect(),
+        )
     }
 }
+
+/// The bytes of a class compiled from `test-data/src`, e.g. `ClassLoaders$Loader`
+#[cfg(test)]
+pub(crate) fn test_class_bytes(name: &str) -> Vec<u8> {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("test-data/classes")
+        .join(format!("{name}.class"));
+    std::fs::read(path).unwrap()
+}
+
+#[cfg(test)]
+pub(crate) fn test_class(name: &str) -> JavaClass<'static> {
+    JavaClass::from_bytes(&test_class_bytes(name))
+        .unwrap()
+        .into_owned()
+}
@@ -0,0 +1,337 @@
+//! Object monitors (JVMS 2.11.10, 17.1). Any object can be locked by `monitorenter`, a
+//! synchronized method or `Object.wait`, a monitor only exists while it is owned or waited on.
+
+use std::{
+    collections::HashMap,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use super::{JavaObjectRef, JvmError, StackValue, JVM};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MonitorKey {
+    Object(JavaObjectRef),
+    /// Strings aren't heap objects, equal strings share a monitor like interned ones would
+    String(String),
+}
+
+#[derive(Debug, Default)]
+struct Monitor {
+    /// The id of the thread that owns the monitor
+    owner: Option<u64>,
+    /// How many times the owner entered the monitor without exiting it
+    entries: usize,
+    /// Threads in `Object.wait` that haven't been notified yet, in the order they started waiting
+    wait_set: Vec<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct Monitors {
+    monitors: HashMap<MonitorKey, Monitor>,
+}
+
+impl JVM {
+    pub(super) fn monitor_key(&self, value: &StackValue) -> Result<MonitorKey, JvmError> {
+        match value {
+            StackValue::JavaObjectRef(object) => Ok(MonitorKey::Object(*object)),
+            StackValue::String(string) => Ok(MonitorKey::String(string.clone())),
+            StackValue::Null => Err(self.new_exception("java/lang/NullPointerException", "")),
+            value => Err(JvmError::Internal(format!(
+                "Expected a reference to lock, found: {value:?}"
+            ))),
+        }
+    }
+
+    /// Blocks until the current thread owns the monitor, entering it again if it already does
+    pub(super) fn enter_monitor(&self, key: &MonitorKey) {
+        if !self.try_enter_monitor(key) {
+            self.safe_region(|| {
+                let mut monitors = self.monitors.lock().unwrap();
+                while !Self::enter_if_free(&mut monitors, key, self.current_thread_id()) {
+                    monitors = self.monitors_changed.wait(monitors).unwrap();
+                }
+            });
+        }
+    }
+
+    fn try_enter_monitor(&self, key: &MonitorKey) -> bool {
+        let mut monitors = self.monitors.lock().unwrap();
+        Self::enter_if_free(&mut monitors, key, self.current_thread_id())
+    }
+
+    fn enter_if_free(monitors: &mut Monitors, key: &MonitorKey, current: u64) -> bool {
+        let monitor = monitors.monitors.entry(key.clone()).or_default();
+        match monitor.owner {
+            None => {
+                monitor.owner = Some(current);
+                monitor.entries = 1;
+                true
+            }
+            Some(owner) if owner == current => {
+                monitor.entries += 1;
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    pub(super) fn exit_monitor(&self, key: &MonitorKey) -> Result<(), JvmError> {
+        let current = self.current_thread_id();
+        let mut monitors = self.monitors.lock().unwrap();
+
+        let Some(monitor) = monitors
+            .monitors
+            .get_mut(key)
+            .filter(|monitor| monitor.owner == Some(current))
+        else {
+            drop(monitors);
+            return Err(self.not_monitor_owner());
+        };
+
+        monitor.entries -= 1;
+        if monitor.entries == 0 {
+            monitor.owner = None;
+            if monitor.wait_set.is_empty() {
+                monitors.monitors.remove(key);
+            }
+            self.monitors_changed.notify_all();
+        }
+        Ok(())
+    }
+
+    pub(super) fn holds_monitor(&self, key: &MonitorKey) -> bool {
+        let current = self.current_thread_id();
+        self.monitors
+            .lock()
+            .unwrap()
+            .monitors
+            .get(key)
+            .is_some_and(|monitor| monitor.owner == Some(current))
+    }
+
+    /// `Object.wait`, releases the monitor until the thread is notified, interrupted or the
+    /// timeout passes, `millis` of 0 waits forever. The monitor is owned again when it returns
+    pub(super) fn wait_on_monitor(&self, key: &MonitorKey, millis: i64) -> Result<(), JvmError> {
+        if millis < 0 {
+            return Err(self.new_exception(
+                "java/lang/IllegalArgumentException",
+                "timeout value is negative",
+            ));
+        }
+
+        let thread = self.current_thread();
+        let current = self.current_thread_id();
+        let take_interrupt = || {
+            thread
+                .as_ref()
+                .is_some_and(|thread| thread.interrupted.swap(false, Ordering::SeqCst))
+        };
+
+        let mut monitors = self.monitors.lock().unwrap();
+        let Some(monitor) = monitors
+            .monitors
+            .get_mut(key)
+            .filter(|monitor| monitor.owner == Some(current))
+        else {
+            drop(monitors);
+            return Err(self.not_monitor_owner());
+        };
+        if take_interrupt() {
+            drop(monitors);
+            return Err(self.new_exception("java/lang/InterruptedException", ""));
+        }
+
+        let entries = monitor.entries;
+        monitor.owner = None;
+        monitor.entries = 0;
+        monitor.wait_set.push(current);
+        self.monitors_changed.notify_all();
+        drop(monitors);
+
+        let interrupted =
+            self.safe_region(|| self.wait_for_notification(key, millis, entries, take_interrupt));
+        if interrupted {
+            return Err(self.new_exception("java/lang/InterruptedException", ""));
+        }
+        Ok(())
+    }
+
+    /// The blocking part of `wait_on_monitor`, returns true if the thread was interrupted
+    fn wait_for_notification(
+        &self,
+        key: &MonitorKey,
+        millis: i64,
+        entries: usize,
+        take_interrupt: impl Fn() -> bool,
+    ) -> bool {
+        let current = self.current_thread_id();
+        let mut monitors = self.monitors.lock().unwrap();
+
+        let deadline = (millis > 0).then(|| Instant::now() + Duration::from_millis(millis as u64));
+        let mut interrupted = false;
+        loop {
+            let notified = monitors
+                .monitors
+                .get(key)
+                .is_none_or(|monitor| !monitor.wait_set.contains(&current));
+            if notified {
+                break;
+            }
+            if take_interrupt() {
+                interrupted = true;
+                break;
+            }
+
+            monitors = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    self.monitors_changed
+                        .wait_timeout(monitors, deadline - now)
+                        .unwrap()
+                        .0
+                }
+                None => self.monitors_changed.wait(monitors).unwrap(),
+            };
+        }
+
+        // Competes with the other threads for the monitor like `monitorenter` does
+        loop {
+            let monitor = monitors.monitors.entry(key.clone()).or_default();
+            monitor.wait_set.retain(|waiting| *waiting != current);
+            if monitor.owner.is_none() {
+                monitor.owner = Some(current);
+                monitor.entries = entries;
+                break;
+            }
+            monitors = self.monitors_changed.wait(monitors).unwrap();
+        }
+
+        interrupted
+    }
+
+    /// `Object.notify` and `Object.notifyAll`, wakes up one or all threads waiting on the monitor
+    pub(super) fn notify_monitor(&self, key: &MonitorKey, all: bool) -> Result<(), JvmError> {
+        let current = self.current_thread_id();
+        let mut monitors = self.monitors.lock().unwrap();
+
+        let Some(monitor) = monitors
+            .monitors
+            .get_mut(key)
+            .filter(|monitor| monitor.owner == Some(current))
+        else {
+            drop(monitors);
+            return Err(self.not_monitor_owner());
+        };
+
+        if all {
+            monitor.wait_set.clear();
+        } else if !monitor.wait_set.is_empty() {
+            monitor.wait_set.remove(0);
+        }
+        self.monitors_changed.notify_all();
+        Ok(())
+    }
+
+    /// The keys of the monitors that are owned or waited on
+    pub(super) fn locked_monitors(&self) -> Vec<MonitorKey> {
+        self.monitors
+            .lock()
+            .unwrap()
+            .monitors
+            .keys()
+            .cloned()
+            .collect()
+    }
+
+    /// Wakes up the threads waiting on a monitor so they see that they were interrupted
+    pub(super) fn wake_monitor_waiters(&self) {
+        let _monitors = self.monitors.lock().unwrap();
+        self.monitors_changed.notify_all();
+    }
+
+    fn not_monitor_owner(&self) -> JvmError {
+        self.new_exception(
+            "java/lang/IllegalMonitorStateException",
+            "current thread is not owner",
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jvm_parser::classfile::{
+        attributes::{AttributeInfoData, CodeAttribute},
+        JavaClass,
+    };
+
+    use crate::jvm::{test_class, StackValue, JVM};
+
+    fn code<'a>(class: &'a mut JavaClass<'static>, name: &str) -> &'a mut CodeAttribute<'static> {
+        let method = class
+            .methods
+            .iter_mut()
+            .find(|method| {
+                class
+                    .constant_pool
+                    .get_utf8_at(method.name_index)
+                    .unwrap()
+                    .data
+                    == name
+            })
+            .unwrap();
+        method
+            .attributes
+            .iter_mut()
+            .find_map(|attribute| match &mut attribute.attribute {
+                AttributeInfoData::Code(code) => Some(code),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn synchronized_methods_exclude_each_other() {
+        let jvm = JVM::with_classes([test_class("Monitors"), test_class("Monitors$Counter")]);
+        let result = jvm.run_static("Monitors", "race", "()I", vec![]);
+        assert!(matches!(result, Ok(StackValue::Integer(200))));
+    }
+
+    #[test]
+    fn monitors_are_reentrant_and_owned() {
+        let mut exit = test_class("Monitors");
+        // The notify of `unowned` becomes monitorexit, nop, nop
+        code(&mut exit, "unowned").code.to_mut()[7..10].copy_from_slice(&[0xc3, 0x00, 0x00]);
+
+        let jvm = JVM::with_classes([test_class("Monitors")]);
+        let result = jvm.run_static("Monitors", "reenter", "()I", vec![]);
+        assert!(matches!(result, Ok(StackValue::Integer(2))));
+        for jvm in [jvm, JVM::with_classes([exit])] {
+            assert_eq!(
+                jvm.run_static("Monitors", "unowned", "()V", vec![])
+                    .unwrap_err(),
+                "java.lang.IllegalMonitorStateException: current thread is not owner"
+            );
+        }
+    }
+
+    #[test]
+    fn failures_of_the_vm_release_monitors() {
+        let mut class = test_class("Monitors");
+        // Thread.yield() in the synchronized block becomes a lock of an int
+        let code = code(&mut class, "locked").code.to_mut();
+        let call = code.iter().position(|op| *op == 0xb8).unwrap();
+        code[call..call + 3].copy_from_slice(&[0x03, 0xc2, 0x00]);
+
+        let jvm = JVM::with_classes([class]);
+        assert_eq!(
+            jvm.run_static("Monitors", "locked", "()V", vec![])
+                .unwrap_err(),
+            "Expected a reference to lock, found: Integer(0)"
+        );
+        assert!(jvm.monitors.lock().unwrap().monitors.is_empty());
+    }
+}
@@ -0,0 +1,1079 @@
+use std::{
+    collections::HashMap,
+    sync::{atomic::Ordering, Arc},
+};
+
+use jvm_parser::classfile::{
+    classfile::ClassAccessFlags,
+    descriptor::{parse_method_descriptor, FieldDescriptor, MethodDescriptor},
+    signature::{JavaTypeSignature, ReferenceTypeSignature, TypeArgument},
+    JavaClass,
+};
+
+use super::{
+    class_loader::LoaderId,
+    gc::GcCause,
+    heap::{HeapObject, NativeData},
+    interpreter::default_value,
+    method_handles::HandleMember,
+    reflection::type_name,
+    threads::{JavaThread, ThreadState},
+    JavaObjectRef, JvmError, NativeMethod, StackValue, JVM,
+};
+
+pub fn register_natives(natives: &mut HashMap<&'static str, NativeMethod>) {
+    natives.insert(
+        "com/ahse/jvm/Main;print",
+        Box::new(|_, args, descriptor| match descriptor {
+            MethodDescriptor {
+                return_type: None,
+                parameters: params,
+            } if params.first()
+                == Some(&FieldDescriptor::Object("java/lang/String".to_string())) =>
+            {
+                let StackValue::String(v) = &args[0] else {
+                    panic!("A string wans't passed as an argument to the print(String) function");
+                };
+                println!("{v}");
+                Ok(StackValue::None)
+            }
+
+            _ => {
+                todo!("Implement this");
+            }
+        }),
+    );
+
+    natives.insert(
+        "java/lang/Object;<init>",
+        Box::new(|_, _, _| Ok(StackValue::None)),
+    );
+    natives.insert(
+        "java/lang/Object;registerNatives",
+        Box::new(|_, _, _| Ok(StackValue::None)),
+    );
+
+    natives.insert(
+        "java/lang/Object;wait",
+        Box::new(|jvm, args, _| {
+            // wait(), wait(long) and wait(long, int), the nanoseconds round up to a millisecond
+            let millis = match (args.get(1), args.get(2)) {
+                (Some(StackValue::Long(millis)), Some(StackValue::Integer(nanos))) => {
+                    if !(0..=999_999).contains(nanos) {
+                        return Err(jvm.new_exception(
+                            "java/lang/IllegalArgumentException",
+                            "nanosecond timeout value out of range",
+                        ));
+                    }
+                    millis.saturating_add((*nanos > 0) as i64)
+                }
+                (Some(StackValue::Long(millis)), _) => *millis,
+                _ => 0,
+            };
+            jvm.wait_on_monitor(&jvm.monitor_key(&args[0])?, millis)?;
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Object;notify",
+        Box::new(|jvm, args, _| {
+            jvm.notify_monitor(&jvm.monitor_key(&args[0])?, false)?;
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Object;notifyAll",
+        Box::new(|jvm, args, _| {
+            jvm.notify_monitor(&jvm.monitor_key(&args[0])?, true)?;
+            Ok(StackValue::None)
+        }),
+    );
+
+    natives.insert(
+        "java/lang/Throwable;<init>",
+        Box::new(|jvm, args, _| {
+            let this = this_object(&args)?;
+            for arg in &args[1..] {
+                match arg {
+                    StackValue::String(_) => jvm.set_field(this, "detailMessage", arg.clone()),
+                    StackValue::JavaObjectRef(_) => jvm.set_field(this, "cause", arg.clone()),
+                    _ => {}
+                }
+            }
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Throwable;getMessage",
+        Box::new(|jvm, args, _| {
+            Ok(jvm
+                .get_field(this_object(&args)?, "detailMessage")
+                .unwrap_or(StackValue::Null))
+        }),
+    );
+
+    natives.insert(
+        "java/lang/Throwable;getCause",
+        Box::new(|jvm, args, _| {
+            Ok(jvm
+                .get_field(this_object(&args)?, "cause")
+                .unwrap_or(StackValue::Null))
+        }),
+    );
+
+    natives.insert(
+        "java/lang/ClassLoader;<init>",
+        Box::new(|jvm, args, _| {
+            let this = this_object(&args)?;
+            // ClassLoader(), ClassLoader(ClassLoader parent) and ClassLoader(String name, ClassLoader parent)
+            let parent = match args.last() {
+                Some(StackValue::Null) => LoaderId::BOOTSTRAP,
+                Some(StackValue::JavaObjectRef(parent)) if args.len() > 1 => {
+                    jvm.loader_of_object(*parent).ok_or_else(|| {
+                        jvm.new_exception("java/lang/IllegalArgumentException", "Invalid parent")
+                    })?
+                }
+                _ => LoaderId::APP,
+            };
+
+            let loader = jvm.create_class_loader(Some(parent), this);
+            if let HeapObject::Instance(instance) = jvm.heap.lock().unwrap().get_mut(this) {
+                instance.native_data = NativeData::ClassLoader(loader);
+            }
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/ClassLoader;getSystemClassLoader",
+        Box::new(|jvm, _, _| jvm.class_loader_object(LoaderId::APP)),
+    );
+    natives.insert(
+        "java/lang/ClassLoader;getParent",
+        Box::new(|jvm, args, _| {
+            let loader = this_loader(jvm, &args)?;
+            match jvm.parent_loader(loader) {
+                Some(parent) => jvm.class_loader_object(parent),
+                None => Ok(StackValue::Null),
+            }
+        }),
+    );
+    natives.insert(
+        "java/lang/ClassLoader;loadClass",
+        Box::new(|jvm, args, descriptor| {
+            let this = this_object(&args)?;
+            let name = string_arg(jvm, &args[1])?;
+
+            if descriptor.parameters.len() == 1 {
+                // loadClass(String) goes through loadClass(String, boolean), which is the one
+                // loaders usually override
+                return jvm.invoke_virtual(
+                    this,
+                    "loadClass",
+                    "(Ljava/lang/String;Z)Ljava/lang/Class;",
+                    vec![args[1].clone(), StackValue::Integer(0)],
+                );
+            }
+
+            let loader = this_loader(jvm, &args)?;
+            let internal_name = name.replace('.', "/");
+
+            if loader == LoaderId::BOOTSTRAP || loader == LoaderId::APP {
+                return match jvm.load_class(loader, &internal_name)? {
+                    Some(class) => jvm.class_mirror(&class).map(StackValue::JavaObjectRef),
+                    None => Err(jvm.new_exception("java/lang/ClassNotFoundException", &name)),
+                };
+            }
+
+            if let Some(class) = jvm.find_loaded_class(loader, &internal_name) {
+                return jvm.class_mirror(&class).map(StackValue::JavaObjectRef);
+            }
+
+            if let Some(parent) = jvm.parent_loader(loader) {
+                if let Some(class) = jvm.load_class(parent, &internal_name)? {
+                    return jvm.class_mirror(&class).map(StackValue::JavaObjectRef);
+                }
+            }
+
+            jvm.invoke_virtual(
+                this,
+                "findClass",
+                "(Ljava/lang/String;)Ljava/lang/Class;",
+                vec![args[1].clone()],
+            )
+        }),
+    );
+    natives.insert(
+        "java/lang/ClassLoader;findClass",
+        Box::new(|jvm, args, _| {
+            let name = string_arg(jvm, &args[1])?;
+            Err(jvm.new_exception("java/lang/ClassNotFoundException", &name))
+        }),
+    );
+    natives.insert(
+        "java/lang/ClassLoader;findLoadedClass",
+        Box::new(|jvm, args, _| {
+            let loader = this_loader(jvm, &args)?;
+            let name = string_arg(jvm, &args[1])?.replace('.', "/");
+            match jvm.find_loaded_class(loader, &name) {
+                Some(class) => jvm.class_mirror(&class).map(StackValue::JavaObjectRef),
+                None => Ok(StackValue::Null),
+            }
+        }),
+    );
+    natives.insert(
+        "java/lang/ClassLoader;defineClass",
+        Box::new(|jvm, args, descriptor| {
+            let loader = this_loader(jvm, &args)?;
+
+            // defineClass(String name, byte[] b, int off, int len, ...) or the deprecated
+            // defineClass(byte[] b, int off, int len)
+            let (name, rest) = match descriptor.parameters.first() {
+                Some(FieldDescriptor::Object(class)) if class == "java/lang/String" => {
+                    let name = match &args[1] {
+                        StackValue::Null => None,
+                        value => Some(string_arg(jvm, value)?.replace('.', "/")),
+                    };
+                    (name, &args[2..])
+                }
+                _ => (None, &args[1..]),
+            };
+
+            let bytes = match (rest.first(), rest.get(1), rest.get(2)) {
+                (
+                    Some(StackValue::JavaObjectRef(array)),
+                    Some(StackValue::Integer(offset)),
+                    Some(StackValue::Integer(length)),
+                ) => byte_array_range(jvm, *array, *offset, *length)?,
+                (Some(StackValue::Null), _, _) => {
+                    return Err(jvm.new_exception("java/lang/NullPointerException", ""))
+                }
+                _ => {
+                    return Err(JvmError::Internal(
+                        "defineClass is only supported for byte arrays".to_string(),
+                    ))
+                }
+            };
+
+            let java_class = JavaClass::from_bytes(&bytes)
+                .map_err(|err| jvm.new_exception("java/lang/ClassFormatError", &err.to_string()))?
+                .into_owned();
+
+            let class = jvm.define_class(loader, java_class, name.as_deref())?;
+            jvm.class_mirror(&class).map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/ClassLoader;resolveClass",
+        Box::new(|_, _, _| Ok(StackValue::None)),
+    );
+
+    natives.insert(
+        "java/lang/Class;getClassLoader",
+        Box::new(|jvm, args, _| {
+            let class = this_class(jvm, &args)?;
+            jvm.class_loader_object(class.loader)
+        }),
+    );
+    natives.insert(
+        "java/lang/Class;forName",
+        Box::new(|jvm, args, _| {
+            let name = string_arg(jvm, &args[0])?;
+            // forName(String) uses the loader of the caller, which is the app loader for
+            // everything but the core classes
+            let (initialize, loader) = match (args.get(1), args.get(2)) {
+                (Some(StackValue::Integer(initialize)), Some(StackValue::Null)) => {
+                    (*initialize != 0, LoaderId::BOOTSTRAP)
+                }
+                (
+                    Some(StackValue::Integer(initialize)),
+                    Some(StackValue::JavaObjectRef(loader)),
+                ) => (
+                    *initialize != 0,
+                    jvm.loader_of_object(*loader).unwrap_or(LoaderId::APP),
+                ),
+                _ => (true, LoaderId::APP),
+            };
+
+            let Some(class) = jvm.load_class(loader, &name.replace('.', "/"))? else {
+                return Err(jvm.new_exception("java/lang/ClassNotFoundException", &name));
+            };
+            if initialize {
+                jvm.initialize_class(&class)?;
+            }
+            jvm.class_mirror(&class).map(StackValue::JavaObjectRef)
+        }),
+    );
+
+    natives.insert(
+        "java/lang/Runtime;gc",
+        Box::new(|jvm, _, _| {
+            jvm.collect_garbage(GcCause::SystemGc);
+            Ok(StackValue::None)
+        }),
+    );
+
+    natives.insert(
+        "java/lang/Object;getClass",
+        Box::new(|jvm, args, _| {
+            let class = jvm.class_of_value(&args[0])?;
+            jvm.class_mirror(&class).map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/Class;getName",
+        Box::new(|jvm, args, _| {
+            let class = this_class(jvm, &args)?;
+            Ok(StackValue::String(class.name.replace('/', ".")))
+        }),
+    );
+    natives.insert(
+        "java/lang/Class;getTypeName",
+        Box::new(|jvm, args, _| {
+            let class = this_class(jvm, &args)?;
+            Ok(StackValue::String(type_name(&class.name)))
+        }),
+    );
+    natives.insert(
+        "java/lang/Class;getSuperclass",
+        Box::new(|jvm, args, _| {
+            let class = this_class(jvm, &args)?;
+            let is_interface = class.java_class.access_flags & ClassAccessFlags::ACC_INTERFACE != 0;
+            match &class.super_class {
+                Some(super_class) if !is_interface => {
+                    jvm.class_mirror(super_class).map(StackValue::JavaObjectRef)
+                }
+                _ => Ok(StackValue::Null),
+            }
+        }),
+    );
+    natives.insert(
+        "java/lang/Class;isInstance",
+        Box::new(|jvm, args, _| {
+            let class = this_class(jvm, &args)?;
+            let is_instance = match &args[1] {
+                StackValue::Null => false,
+                value => jvm.is_instance(value, &class)?,
+            };
+            Ok(StackValue::Integer(is_instance as i32))
+        }),
+    );
+    natives.insert(
+        "java/lang/Class;getDeclaredFields",
+        Box::new(|jvm, args, _| {
+            let class = this_class(jvm, &args)?;
+            jvm.declared_fields(&class).map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/Class;getDeclaredMethods",
+        Box::new(|jvm, args, _| {
+            let class = this_class(jvm, &args)?;
+            jvm.declared_methods(&class).map(StackValue::JavaObjectRef)
+        }),
+    );
+
+    natives.insert(
+        "java/lang/reflect/AccessibleObject;setAccessible",
+        Box::new(|jvm, args, _| {
+            jvm.set_field(this_object(&args)?, "override", args[1].clone());
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/AccessibleObject;isAccessible",
+        Box::new(|jvm, args, _| {
+            Ok(jvm
+                .get_field(this_object(&args)?, "override")
+                .unwrap_or(StackValue::Integer(0)))
+        }),
+    );
+    // Field and Method share these, the object knows which member it reflects
+    for (get_name, get_modifiers, get_declaring_class) in [
+        (
+            "java/lang/reflect/Field;getName",
+            "java/lang/reflect/Field;getModifiers",
+            "java/lang/reflect/Field;getDeclaringClass",
+        ),
+        (
+            "java/lang/reflect/Method;getName",
+            "java/lang/reflect/Method;getModifiers",
+            "java/lang/reflect/Method;getDeclaringClass",
+        ),
+    ] {
+        natives.insert(
+            get_name,
+            Box::new(|jvm, args, _| {
+                let member = jvm.reflected_member(this_object(&args)?)?;
+                Ok(StackValue::String(member.name))
+            }),
+        );
+        natives.insert(
+            get_modifiers,
+            Box::new(|jvm, args, _| {
+                let member = jvm.reflected_member(this_object(&args)?)?;
+                Ok(StackValue::Integer(member.modifiers()))
+            }),
+        );
+        natives.insert(
+            get_declaring_class,
+            Box::new(|jvm, args, _| {
+                let member = jvm.reflected_member(this_object(&args)?)?;
+                jvm.class_mirror(&member.class)
+                    .map(StackValue::JavaObjectRef)
+            }),
+        );
+    }
+    natives.insert(
+        "java/lang/reflect/Field;getType",
+        Box::new(|jvm, args, _| {
+            let field = jvm.reflected_member(this_object(&args)?)?;
+            let class = jvm.class_of_descriptor(field.class.loader, &field.descriptor)?;
+            jvm.class_mirror(&class).map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/Field;getGenericType",
+        Box::new(|jvm, args, _| {
+            let field = jvm.reflected_member(this_object(&args)?)?;
+            jvm.field_generic_type(&field)
+                .map(StackValue::JavaObjectRef)
+        }),
+    );
+    // The types that generic signatures stand for besides classes print as in java source
+    for key in [
+        "java/lang/reflect/Type;getTypeName",
+        "java/lang/reflect/TypeVariable;getName",
+        "sun/reflect/generics/reflectiveObjects/ParameterizedTypeImpl;toString",
+        "sun/reflect/generics/reflectiveObjects/TypeVariableImpl;toString",
+        "sun/reflect/generics/reflectiveObjects/WildcardTypeImpl;toString",
+        "sun/reflect/generics/reflectiveObjects/GenericArrayTypeImpl;toString",
+    ] {
+        natives.insert(
+            key,
+            Box::new(|jvm, args, _| {
+                let (_, generic_type) = jvm.reflected_generic_type(this_object(&args)?)?;
+                Ok(StackValue::String(generic_type.to_string()))
+            }),
+        );
+    }
+    natives.insert(
+        "java/lang/reflect/ParameterizedType;getRawType",
+        Box::new(|jvm, args, _| {
+            let (loader, generic_type) = jvm.reflected_generic_type(this_object(&args)?)?;
+            let TypeArgument::Exact(ReferenceTypeSignature::Class(class_type)) = generic_type
+            else {
+                return Err(JvmError::Internal(
+                    "Expected a java/lang/reflect/ParameterizedType object".to_string(),
+                ));
+            };
+            let class = jvm.resolve_class(loader, &class_type.binary_name())?;
+            jvm.class_mirror(&class).map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/ParameterizedType;getActualTypeArguments",
+        Box::new(|jvm, args, _| {
+            let (loader, generic_type) = jvm.reflected_generic_type(this_object(&args)?)?;
+            let TypeArgument::Exact(ReferenceTypeSignature::Class(mut class_type)) = generic_type
+            else {
+                return Err(JvmError::Internal(
+                    "Expected a java/lang/reflect/ParameterizedType object".to_string(),
+                ));
+            };
+            let type_arguments = class_type
+                .classes
+                .pop()
+                .map(|class| class.type_arguments)
+                .unwrap_or_default();
+            jvm.generic_types(loader, type_arguments)
+                .map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/ParameterizedType;getOwnerType",
+        Box::new(|jvm, args, _| {
+            let (loader, generic_type) = jvm.reflected_generic_type(this_object(&args)?)?;
+            let TypeArgument::Exact(ReferenceTypeSignature::Class(mut class_type)) = generic_type
+            else {
+                return Err(JvmError::Internal(
+                    "Expected a java/lang/reflect/ParameterizedType object".to_string(),
+                ));
+            };
+            // Only the owners the signature spells out, like `Outer<T>` of `Outer<T>.Inner<U>`
+            class_type.classes.pop();
+            if class_type.classes.is_empty() {
+                return Ok(StackValue::Null);
+            }
+            let owner = TypeArgument::Exact(ReferenceTypeSignature::Class(class_type));
+            jvm.generic_type(loader, owner)
+                .map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/WildcardType;getUpperBounds",
+        Box::new(|jvm, args, _| {
+            let (loader, generic_type) = jvm.reflected_generic_type(this_object(&args)?)?;
+            match generic_type {
+                TypeArgument::Extends(bound) => {
+                    jvm.generic_types(loader, vec![TypeArgument::Exact(bound)])
+                }
+                _ => {
+                    let object = jvm.resolve_class(LoaderId::BOOTSTRAP, "java/lang/Object")?;
+                    let mirror = StackValue::JavaObjectRef(jvm.class_mirror(&object)?);
+                    jvm.new_array("Ljava/lang/reflect/Type;", vec![mirror])
+                }
+            }
+            .map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/WildcardType;getLowerBounds",
+        Box::new(|jvm, args, _| {
+            let (loader, generic_type) = jvm.reflected_generic_type(this_object(&args)?)?;
+            let bounds = match generic_type {
+                TypeArgument::Super(bound) => vec![TypeArgument::Exact(bound)],
+                _ => vec![],
+            };
+            jvm.generic_types(loader, bounds)
+                .map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/GenericArrayType;getGenericComponentType",
+        Box::new(|jvm, args, _| {
+            let (loader, generic_type) = jvm.reflected_generic_type(this_object(&args)?)?;
+            let TypeArgument::Exact(ReferenceTypeSignature::Array(component)) = generic_type else {
+                return Err(JvmError::Internal(
+                    "Expected a java/lang/reflect/GenericArrayType object".to_string(),
+                ));
+            };
+            let component = match *component {
+                JavaTypeSignature::Base(base_type) => {
+                    let class =
+                        jvm.class_of_descriptor(loader, &base_type.descriptor().to_string())?;
+                    jvm.class_mirror(&class)?
+                }
+                JavaTypeSignature::Reference(component) => {
+                    jvm.generic_type(loader, TypeArgument::Exact(component))?
+                }
+            };
+            Ok(StackValue::JavaObjectRef(component))
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/Field;get",
+        Box::new(|jvm, args, _| {
+            let this = this_object(&args)?;
+            let field = jvm.reflected_member(this)?;
+            let accessible = matches!(
+                jvm.get_field(this, "override"),
+                Some(StackValue::Integer(1))
+            );
+            jvm.reflected_field_value(&field, &args[1], accessible)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/Field;set",
+        Box::new(|jvm, args, _| {
+            let this = this_object(&args)?;
+            let field = jvm.reflected_member(this)?;
+            let accessible = matches!(
+                jvm.get_field(this, "override"),
+                Some(StackValue::Integer(1))
+            );
+            jvm.set_reflected_field_value(&field, &args[1], args[2].clone(), accessible)?;
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/Method;getReturnType",
+        Box::new(|jvm, args, _| {
+            let method = jvm.reflected_member(this_object(&args)?)?;
+            let return_type = parse_method_descriptor(&method.descriptor)
+                .ok()
+                .and_then(|descriptor| descriptor.return_type)
+                .map(|return_type| return_type.to_string())
+                .unwrap_or_else(|| "V".to_string());
+            let class = jvm.class_of_descriptor(method.class.loader, &return_type)?;
+            jvm.class_mirror(&class).map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/Method;getParameterTypes",
+        Box::new(|jvm, args, _| {
+            let method = jvm.reflected_member(this_object(&args)?)?;
+            let parameters = parse_method_descriptor(&method.descriptor)
+                .map(|descriptor| descriptor.parameters)
+                .unwrap_or_default();
+
+            let mut mirrors = vec![];
+            for parameter in parameters {
+                let class = jvm.class_of_descriptor(method.class.loader, &parameter.to_string())?;
+                mirrors.push(StackValue::JavaObjectRef(jvm.class_mirror(&class)?));
+            }
+            jvm.new_array("Ljava/lang/Class;", mirrors)
+                .map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/Method;getParameterCount",
+        Box::new(|jvm, args, _| {
+            let method = jvm.reflected_member(this_object(&args)?)?;
+            let parameter_count = parse_method_descriptor(&method.descriptor)
+                .map(|descriptor| descriptor.parameters.len())
+                .unwrap_or_default();
+            Ok(StackValue::Integer(parameter_count as i32))
+        }),
+    );
+    natives.insert(
+        "java/lang/reflect/Method;invoke",
+        Box::new(|jvm, args, _| {
+            let method = jvm.reflected_member(this_object(&args)?)?;
+            jvm.invoke_reflected_method(&method, &args[1], &args[2])
+        }),
+    );
+
+    for (key, class_name, primitive) in [
+        ("java/lang/Boolean;<clinit>", "java/lang/Boolean", "boolean"),
+        ("java/lang/Byte;<clinit>", "java/lang/Byte", "byte"),
+        (
+            "java/lang/Character;<clinit>",
+            "java/lang/Character",
+            "char",
+        ),
+        ("java/lang/Short;<clinit>", "java/lang/Short", "short"),
+        ("java/lang/Integer;<clinit>", "java/lang/Integer", "int"),
+        ("java/lang/Long;<clinit>", "java/lang/Long", "long"),
+        ("java/lang/Float;<clinit>", "java/lang/Float", "float"),
+        ("java/lang/Double;<clinit>", "java/lang/Double", "double"),
+    ] {
+        natives.insert(
+            key,
+            Box::new(move |jvm, _, _| {
+                let class = jvm.resolve_class(LoaderId::BOOTSTRAP, class_name)?;
+                let mirror = jvm.class_mirror(&jvm.primitive_class(primitive)?)?;
+                class
+                    .statics
+                    .lock()
+                    .unwrap()
+                    .insert("TYPE".to_string(), StackValue::JavaObjectRef(mirror));
+                Ok(StackValue::None)
+            }),
+        );
+    }
+    for (key, descriptor) in [
+        ("java/lang/Boolean;valueOf", "Z"),
+        ("java/lang/Byte;valueOf", "B"),
+        ("java/lang/Character;valueOf", "C"),
+        ("java/lang/Short;valueOf", "S"),
+        ("java/lang/Integer;valueOf", "I"),
+        ("java/lang/Long;valueOf", "J"),
+        ("java/lang/Float;valueOf", "F"),
+        ("java/lang/Double;valueOf", "D"),
+    ] {
+        natives.insert(
+            key,
+            Box::new(move |jvm, args, _| jvm.box_value(descriptor, args[0].clone())),
+        );
+    }
+    for (key, descriptor) in [
+        ("java/lang/Boolean;booleanValue", "Z"),
+        ("java/lang/Character;charValue", "C"),
+        ("java/lang/Number;byteValue", "B"),
+        ("java/lang/Number;shortValue", "S"),
+        ("java/lang/Number;intValue", "I"),
+        ("java/lang/Number;longValue", "J"),
+        ("java/lang/Number;floatValue", "F"),
+        ("java/lang/Number;doubleValue", "D"),
+    ] {
+        natives.insert(
+            key,
+            Box::new(move |jvm, args, _| {
+                let value = jvm
+                    .get_field(this_object(&args)?, "value")
+                    .unwrap_or_else(|| default_value(descriptor));
+                Ok(convert_primitive(descriptor, value))
+            }),
+        );
+    }
+
+    natives.insert(
+        "java/lang/invoke/MethodHandle;invokeExact",
+        Box::new(|jvm, mut args, descriptor| {
+            let handle = jvm.method_handle_of(&args[0])?;
+            if handle.descriptor != descriptor.to_string() {
+                return Err(jvm.new_exception(
+                    "java/lang/invoke/WrongMethodTypeException",
+                    &format!(
+                        "expected {} but found a call site of another type",
+                        handle.descriptor
+                    ),
+                ));
+            }
+            args.remove(0);
+            jvm.invoke_method_handle(&handle, args)
+        }),
+    );
+    natives.insert(
+        "java/lang/invoke/MethodHandle;invoke",
+        Box::new(|jvm, mut args, descriptor| {
+            let handle = jvm.method_handle_of(&args[0])?;
+            // Without boxing the arguments can only be passed as they are
+            let parameter_count = parse_method_descriptor(&handle.descriptor)
+                .map(|handle_descriptor| handle_descriptor.parameters.len())
+                .unwrap_or_default();
+            if parameter_count != descriptor.parameters.len() {
+                return Err(jvm.new_exception(
+                    "java/lang/invoke/WrongMethodTypeException",
+                    &format!(
+                        "cannot convert MethodHandle{} to a call with {} arguments",
+                        handle.descriptor,
+                        descriptor.parameters.len()
+                    ),
+                ));
+            }
+            args.remove(0);
+            jvm.invoke_method_handle(&handle, args)
+        }),
+    );
+    natives.insert(
+        "java/lang/invoke/MethodHandle;type",
+        Box::new(|jvm, args, _| {
+            let handle = jvm.method_handle_of(&args[0])?;
+            let loader = match &handle.member {
+                HandleMember::Field(field) => field.class.loader,
+                HandleMember::Method(method) => method.class.loader,
+            };
+            jvm.new_method_type(loader, &handle.descriptor)
+                .map(StackValue::JavaObjectRef)
+        }),
+    );
+    natives.insert(
+        "java/lang/invoke/MethodType;toMethodDescriptorString",
+        Box::new(|jvm, args, _| Ok(StackValue::String(this_method_type(jvm, &args)?))),
+    );
+    natives.insert(
+        "java/lang/invoke/MethodType;parameterCount",
+        Box::new(|jvm, args, _| {
+            let descriptor = this_method_type(jvm, &args)?;
+            let parameter_count = parse_method_descriptor(&descriptor)
+                .map(|descriptor| descriptor.parameters.len())
+                .unwrap_or_default();
+            Ok(StackValue::Integer(parameter_count as i32))
+        }),
+    );
+    natives.insert(
+        "java/lang/invoke/MethodHandles$Lookup;lookupClass",
+        Box::new(|jvm, args, _| {
+            Ok(jvm
+                .get_field(this_object(&args)?, "lookupClass")
+                .unwrap_or(StackValue::Null))
+        }),
+    );
+
+    natives.insert(
+        "java/lang/Thread;<init>",
+        Box::new(|jvm, args, descriptor| {
+            let this = this_object(&args)?;
+            // Thread(), Thread(Runnable), Thread(String), Thread(Runnable, String) and the ones
+            // that also take a ThreadGroup, which is ignored
+            let mut name = None;
+            for (parameter, arg) in descriptor.parameters.iter().zip(&args[1..]) {
+                match parameter {
+                    FieldDescriptor::Object(class) if class == "java/lang/String" => {
+                        name = Some(string_arg(jvm, arg)?)
+                    }
+                    FieldDescriptor::Object(class) if class == "java/lang/Runnable" => {
+                        jvm.set_field(this, "target", arg.clone())
+                    }
+                    _ => {}
+                }
+            }
+
+            let name = name.unwrap_or_else(|| jvm.next_thread_name());
+            jvm.set_field(this, "name", StackValue::String(name));
+            let priority = jvm
+                .current_thread()
+                .and_then(|current| jvm.get_field(current.object, "priority"))
+                .unwrap_or(StackValue::Integer(5));
+            jvm.set_field(this, "priority", priority);
+            jvm.create_thread(this);
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;run",
+        Box::new(
+            |jvm, args, _| match jvm.get_field(this_object(&args)?, "target") {
+                Some(StackValue::JavaObjectRef(target)) => {
+                    jvm.invoke_virtual(target, "run", "()V", vec![])
+                }
+                _ => Ok(StackValue::None),
+            },
+        ),
+    );
+    natives.insert(
+        "java/lang/Thread;start",
+        Box::new(|jvm, args, _| {
+            jvm.start_thread(this_thread(jvm, &args)?)?;
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;join",
+        Box::new(|jvm, args, _| {
+            let millis = match args.get(1) {
+                Some(StackValue::Long(millis)) => *millis,
+                _ => 0,
+            };
+            let thread = this_thread(jvm, &args)?;
+            jvm.join_thread(&thread, millis)?;
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;sleep",
+        Box::new(|jvm, args, _| {
+            let Some(StackValue::Long(millis)) = args.first() else {
+                return Err(JvmError::Internal(format!(
+                    "Expected the milliseconds to sleep, found: {:?}",
+                    args.first()
+                )));
+            };
+            jvm.sleep(*millis)?;
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;yield",
+        Box::new(|_, _, _| {
+            std::thread::yield_now();
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;currentThread",
+        Box::new(|jvm, _, _| match jvm.current_thread() {
+            Some(thread) => Ok(StackValue::JavaObjectRef(thread.object)),
+            None => Err(JvmError::Internal(
+                "The current OS thread isn't attached to the vm".to_string(),
+            )),
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;interrupt",
+        Box::new(|jvm, args, _| {
+            let thread = this_thread(jvm, &args)?;
+            jvm.interrupt_thread(&thread);
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;isInterrupted",
+        Box::new(|jvm, args, _| {
+            let thread = this_thread(jvm, &args)?;
+            Ok(StackValue::Integer(
+                thread.interrupted.load(Ordering::SeqCst) as i32,
+            ))
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;interrupted",
+        Box::new(|jvm, _, _| {
+            let interrupted = jvm
+                .current_thread()
+                .is_some_and(|thread| thread.interrupted.swap(false, Ordering::SeqCst));
+            Ok(StackValue::Integer(interrupted as i32))
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;holdsLock",
+        Box::new(|jvm, args, _| {
+            let key = jvm.monitor_key(&args[0])?;
+            Ok(StackValue::Integer(jvm.holds_monitor(&key) as i32))
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;isAlive",
+        Box::new(|jvm, args, _| {
+            let thread = this_thread(jvm, &args)?;
+            Ok(StackValue::Integer(
+                (thread.state() == ThreadState::Runnable) as i32,
+            ))
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;setDaemon",
+        Box::new(|jvm, args, _| {
+            let thread = this_thread(jvm, &args)?;
+            if thread.state() != ThreadState::New {
+                return Err(jvm.new_exception("java/lang/IllegalThreadStateException", ""));
+            }
+            let daemon = matches!(args.get(1), Some(StackValue::Integer(daemon)) if *daemon != 0);
+            thread.daemon.store(daemon, Ordering::SeqCst);
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;isDaemon",
+        Box::new(|jvm, args, _| {
+            let thread = this_thread(jvm, &args)?;
+            Ok(StackValue::Integer(
+                thread.daemon.load(Ordering::SeqCst) as i32
+            ))
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;getId",
+        Box::new(|jvm, args, _| Ok(StackValue::Long(this_thread(jvm, &args)?.id as i64))),
+    );
+    natives.insert(
+        "java/lang/Thread;getName",
+        Box::new(|jvm, args, _| {
+            let thread = this_thread(jvm, &args)?;
+            Ok(StackValue::String(jvm.thread_name(&thread)))
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;setName",
+        Box::new(|jvm, args, _| {
+            let name = string_arg(jvm, &args[1])?;
+            jvm.set_field(this_object(&args)?, "name", StackValue::String(name));
+            Ok(StackValue::None)
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;getPriority",
+        Box::new(|jvm, args, _| {
+            Ok(jvm
+                .get_field(this_object(&args)?, "priority")
+                .unwrap_or(StackValue::Integer(5)))
+        }),
+    );
+    natives.insert(
+        "java/lang/Thread;setPriority",
+        Box::new(|jvm, args, _| {
+            let this = this_object(&args)?;
+            match args.get(1) {
+                Some(StackValue::Integer(priority @ 1..=10)) => {
+                    jvm.set_field(this, "priority", StackValue::Integer(*priority));
+                    Ok(StackValue::None)
+                }
+                _ => Err(jvm.new_exception("java/lang/IllegalArgumentException", "")),
+            }
+        }),
+    );
+}
+
+fn this_object(args: &[StackValue]) -> Result<JavaObjectRef, JvmError> {
+    match args.first() {
+        Some(StackValue::JavaObjectRef(object)) => Ok(*object),
+        value => Err(JvmError::Internal(format!(
+            "Expected an object as `this`, found: {value:?}"
+        ))),
+    }
+}
+
+fn this_thread(jvm: &JVM, args: &[StackValue]) -> Result<Arc<JavaThread>, JvmError> {
+    jvm.thread_of_object(this_object(args)?).ok_or_else(|| {
+        JvmError::Internal("The constructor of the thread wasn't called".to_string())
+    })
+}
+
+fn this_loader(jvm: &JVM, args: &[StackValue]) -> Result<LoaderId, JvmError> {
+    jvm.loader_of_object(this_object(args)?).ok_or_else(|| {
+        jvm.new_exception(
+            "java/lang/SecurityException",
+            "The class loader was not initialized",
+        )
+    })
+}
+
+fn this_class(
+    jvm: &JVM,
+    args: &[StackValue],
+) -> Result<Arc<super::class_loader::LoadedClass>, JvmError> {
+    jvm.class_from_mirror(this_object(args)?)
+        .ok_or_else(|| JvmError::Internal("Expected a java/lang/Class object".to_string()))
+}
+
+/// Converts a primitive to the type of `descriptor` like the `i2b`, `l2i` or `f2d` instructions
+fn convert_primitive(descriptor: &str, value: StackValue) -> StackValue {
+    let (int, long, float, double) = match value {
+        StackValue::Integer(value) => (value, value as i64, value as f32, value as f64),
+        StackValue::Long(value) => (value as i32, value, value as f32, value as f64),
+        StackValue::Float(value) => (value as i32, value as i64, value, value as f64),
+        StackValue::Double(value) => (value as i32, value as i64, value as f32, value),
+        value => return value,
+    };
+
+    match descriptor {
+        "B" => StackValue::Integer(int as i8 as i32),
+        "S" => StackValue::Integer(int as i16 as i32),
+        "C" => StackValue::Integer(int as u16 as i32),
+        "J" => StackValue::Long(long),
+        "F" => StackValue::Float(float),
+        "D" => StackValue::Double(double),
+        _ => StackValue::Integer(int),
+    }
+}
+
+/// The method descriptor of a `java/lang/invoke/MethodType`
+fn this_method_type(jvm: &JVM, args: &[StackValue]) -> Result<String, JvmError> {
+    match jvm.heap.lock().unwrap().get(this_object(args)?) {
+        HeapObject::Instance(instance) => match &instance.native_data {
+            NativeData::MethodType(descriptor) => Ok(descriptor.clone()),
+            _ => Err(JvmError::Internal(
+                "Expected a java/lang/invoke/MethodType object".to_string(),
+            )),
+        },
+        HeapObject::Array(_) => Err(JvmError::Internal(
+            "Expected a java/lang/invoke/MethodType object".to_string(),
+        )),
+    }
+}
+
+fn string_arg(jvm: &JVM, value: &StackValue) -> Result<String, JvmError> {
+    match value {
+        StackValue::String(string) => Ok(string.clone()),
+        StackValue::Null => Err(jvm.new_exception("java/lang/NullPointerException", "")),
+        value => Err(JvmError::Internal(format!(
+            "Expected a string argument, found: {value:?}"
+        ))),
+    }
+}
+
+fn byte_array_range(
+    jvm: &JVM,
+    array: JavaObjectRef,
+    offset: i32,
+    length: i32,
+) -> Result<Vec<u8>, JvmError> {
+    let heap = jvm.heap.lock().unwrap();
+    let HeapObject::Array(array) = heap.get(array) else {
+        return Err(JvmError::Internal("Expected a byte array".to_string()));
+    };
+
+    let Some(values) = usize::try_from(offset)
+        .ok()
+        .zip(usize::try_from(length).ok())
+        .and_then(|(offset, length)| array.values.get(offset..offset.checked_add(length)?))
+    else {
+        let array_length = array.values.len();
+        drop(heap);
+        return Err(jvm.new_exception(
+            "java/lang/IndexOutOfBoundsException",
+            &format!(
+                "Range [{offset}, {offset} + {length}) out of bounds for length {array_length}"
+            ),
+        ));
+    };
+
+    Ok(values
+        .iter()
+        .map(|value| match value {
+            StackValue::Integer(byte) => *byte as u8,
+            _ => 0,
+        })
+        .collect())
+}
@@ -106,11 +106,11 @@ pub enum OpCodes {
 
     putstatic(u16),
 
-    if_icmp(CmpConditions, u16),
-    if_cond(CmpConditions, u16),
-    if_acmp(CmpConditions, u16),
-    if_null(u16),
-    if_notnull(u16),
+    if_icmp(CmpConditions, i16),
+    if_cond(CmpConditions, i16),
+    if_acmp(CmpConditions, i16),
+    if_null(i16),
+    if_notnull(i16),
     lcmp,
 
     isub,
@@ -126,6 +126,7 @@ pub enum OpCodes {
     iinc(u8, i8),
     irem,
     ineg,
+    swap,
 
     lsub,
     ladd,
@@ -145,6 +146,7 @@ pub enum OpCodes {
     fmul,
     fdiv,
     fneg,
+    frem,
     fcmp(i8),
 
     dsub,
@@ -155,15 +157,18 @@ pub enum OpCodes {
     drem,
     dcmp(i8),
 
-    goto(u16),
+    goto(i16),
+    goto_w(i32),
 
     monitorenter,
     monitorexit,
 
     instanceof(u16),
 
-    tableswitch(i32, i32, i32),
-    lookupswitch(u32, u32),
+    /// default, low, high and the jump offsets for every value in `low..=high`
+    tableswitch(i32, i32, i32, Vec<i32>),
+    /// default and the `(match, offset)` pairs
+    lookupswitch(i32, Vec<(i32, i32)>),
 
     wide {
         opcode: Box<OpCodes>,
@@ -172,6 +177,7 @@ pub enum OpCodes {
     },
 
     jsr(i16),
+    jsr_w(i32),
     ret(u8),
 
     athrow,
@@ -184,8 +190,9 @@ pub enum OpCodes {
     Return,
 }
 
-pub fn parse_opcodes(opcode_bytes: &Vec<u8>) -> std::io::Result<Vec<OpCodes>> {
-    let mut reader = ByteReader::from_vec(opcode_bytes);
+/// Decodes a method's byte code, pairing every opcode with the pc (byte offset) it starts at
+pub fn parse_opcodes(opcode_bytes: &[u8]) -> std::io::Result<Vec<(usize, OpCodes)>> {
+    let mut reader = ByteReader::from_slice(opcode_bytes);
     reader.set_endian(byte_reader::Endian::Big);
     let mut opcodes = vec![];
 
@@ -395,6 +402,7 @@ pub fn parse_opcodes(opcode_bytes: &Vec<u8>) -> std::io::Result<Vec<OpCodes>> {
             0x84 => OpCodes::iinc(reader.read()?, reader.read()?),
             0x70 => OpCodes::irem,
             0x74 => OpCodes::ineg,
+            0x5f => OpCodes::swap,
 
             0x65 => OpCodes::lsub,
             0x61 => OpCodes::ladd,
@@ -414,6 +422,7 @@ pub fn parse_opcodes(opcode_bytes: &Vec<u8>) -> std::io::Result<Vec<OpCodes>> {
             0x6a => OpCodes::fmul,
             0x6e => OpCodes::fdiv,
             0x76 => OpCodes::fneg,
+            0x72 => OpCodes::frem,
             0x96 => OpCodes::fcmp(1),
             0x95 => OpCodes::fcmp(-1),
 
@@ -427,6 +436,7 @@ pub fn parse_opcodes(opcode_bytes: &Vec<u8>) -> std::io::Result<Vec<OpCodes>> {
             0x97 => OpCodes::dcmp(-1),
 
             0xa7 => OpCodes::goto(reader.read()?),
+            0xc8 => OpCodes::goto_w(reader.read()?),
 
             0xc2 => OpCodes::monitorenter,
             0xc3 => OpCodes::monitorexit,
@@ -450,10 +460,11 @@ pub fn parse_opcodes(opcode_bytes: &Vec<u8>) -> std::io::Result<Vec<OpCodes>> {
                 let low: i32 = reader.read()?;
                 let high: i32 = reader.read()?;
 
-                let jump = (4 * (high - low + 1)) as usize;
-                reader.jump(jump);
+                let offsets = (low..=high)
+                    .map(|_| reader.read())
+                    .collect::<std::io::Result<Vec<i32>>>()?;
 
-                OpCodes::tableswitch(default_byte, low, high)
+                OpCodes::tableswitch(default_byte, low, high, offsets)
             }
 
             0xab => {
@@ -461,11 +472,13 @@ pub fn parse_opcodes(opcode_bytes: &Vec<u8>) -> std::io::Result<Vec<OpCodes>> {
                 reader.jump(jump_by);
 
                 let default_byte = reader.read()?;
-                let count = reader.read()?;
+                let count: i32 = reader.read()?;
 
-                reader.jump((8 * count) as usize);
+                let pairs = (0..count)
+                    .map(|_| Ok((reader.read()?, reader.read()?)))
+                    .collect::<std::io::Result<Vec<(i32, i32)>>>()?;
 
-                OpCodes::lookupswitch(default_byte, count)
+                OpCodes::lookupswitch(default_byte, pairs)
             }
 
             0xC4 => {
@@ -513,7 +526,7 @@ pub fn parse_opcodes(opcode_bytes: &Vec<u8>) -> std::io::Result<Vec<OpCodes>> {
                         constbyte: None,
                     },
                     0x37 => OpCodes::wide {
-                        opcode: Box::new(OpCodes::istore_(0)),
+                        opcode: Box::new(OpCodes::lstore_(0)),
                         index: reader.read()?,
                         constbyte: None,
                     },
@@ -545,6 +558,7 @@ pub fn parse_opcodes(opcode_bytes: &Vec<u8>) -> std::io::Result<Vec<OpCodes>> {
             }
 
             0xa8 => OpCodes::jsr(reader.read()?),
+            0xc9 => OpCodes::jsr_w(reader.read()?),
             0xa9 => OpCodes::ret(reader.read()?),
 
             unknown_opcode => {
@@ -556,7 +570,7 @@ pub fn parse_opcodes(opcode_bytes: &Vec<u8>) -> std::io::Result<Vec<OpCodes>> {
         };
 
         // println!("      #{pc} | OpCode: {:?}", opcode);
-        opcodes.push(opcode);
+        opcodes.push((pc, opcode));
     }
 
     Ok(opcodes)
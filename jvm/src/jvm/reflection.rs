@@ -0,0 +1,735 @@
+//! Reflection on loaded classes through their `java/lang/Class` mirrors.
+//!
+//! `java/lang/reflect/Field` and `Method` objects refer to a field or method of the class file by
+//! its index, everything they report comes from its `FieldInfo` or `MethodInfo`.
+
+use std::sync::Arc;
+
+use jvm_parser::classfile::{
+    classfile::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+    descriptor::parse_method_descriptor,
+    signature::{parse_field_signature, JavaTypeSignature, ReferenceTypeSignature, TypeArgument},
+};
+
+use super::{
+    class_loader::{LoadedClass, LoaderId},
+    heap::{HeapObject, NativeData},
+    interpreter::default_value,
+    JavaObjectRef, JvmError, StackValue, JVM,
+};
+
+/// The wrapper class of every primitive type, by the descriptor of the type
+const BOX_CLASSES: &[(&str, &str)] = &[
+    ("Z", "java/lang/Boolean"),
+    ("B", "java/lang/Byte"),
+    ("C", "java/lang/Character"),
+    ("S", "java/lang/Short"),
+    ("I", "java/lang/Integer"),
+    ("J", "java/lang/Long"),
+    ("F", "java/lang/Float"),
+    ("D", "java/lang/Double"),
+];
+
+/// The modifiers `Field.getModifiers` keeps, `java.lang.reflect.Modifier.fieldModifiers()`
+const FIELD_MODIFIERS: u16 = 0x00DF;
+/// The modifiers `Method.getModifiers` keeps, `java.lang.reflect.Modifier.methodModifiers()`
+const METHOD_MODIFIERS: u16 = 0x0D3F;
+
+/// The names `java.lang.reflect.Modifier.toString` gives the modifiers of a field, in its order
+const FIELD_MODIFIER_NAMES: &[(u16, &str)] = &[
+    (FieldAccessFlags::ACC_PUBLIC, "public"),
+    (FieldAccessFlags::ACC_PROTECTED, "protected"),
+    (FieldAccessFlags::ACC_PRIVATE, "private"),
+    (FieldAccessFlags::ACC_STATIC, "static"),
+    (FieldAccessFlags::ACC_FINAL, "final"),
+    (FieldAccessFlags::ACC_TRANSIENT, "transient"),
+    (FieldAccessFlags::ACC_VOLATILE, "volatile"),
+];
+
+/// A field or method of a class file that a reflection object refers to
+pub struct Member {
+    /// The declaring class
+    pub class: Arc<LoadedClass>,
+    pub name: String,
+    pub descriptor: String,
+    pub access_flags: u16,
+    pub is_field: bool,
+    /// The generic signature from the `Signature` attribute, if it has one
+    pub signature: Option<String>,
+}
+
+impl Member {
+    pub fn is_static(&self) -> bool {
+        // Fields and methods share the value of ACC_STATIC
+        self.access_flags & FieldAccessFlags::ACC_STATIC != 0
+    }
+
+    pub fn modifiers(&self) -> i32 {
+        let mask = if self.is_field {
+            FIELD_MODIFIERS
+        } else {
+            METHOD_MODIFIERS
+        };
+        (self.access_flags & mask) as i32
+    }
+
+    /// Describes a field like the messages of `IllegalArgumentException`, e.g.
+    /// `static final int com.example.Foo.count`
+    fn describe(&self) -> String {
+        let mut description = String::new();
+        if self.is_static() {
+            description.push_str("static ");
+        }
+        if self.access_flags & FieldAccessFlags::ACC_FINAL != 0 {
+            description.push_str("final ");
+        }
+        format!(
+            "{description}{} field {}.{}",
+            type_name(&self.descriptor),
+            self.class.name.replace('/', "."),
+            self.name
+        )
+    }
+}
+
+impl JVM {
+    /// The class `Object.getClass` returns. Arrays don't record the loader of their element
+    /// class, so their class is resolved by the app loader
+    pub(super) fn class_of_value(&self, value: &StackValue) -> Result<Arc<LoadedClass>, JvmError> {
+        match self.array_component_type(value) {
+            Some(component_type) => {
+                self.resolve_class(LoaderId::APP, &format!("[{component_type}"))
+            }
+            None => self.runtime_class(value),
+        }
+    }
+
+    /// `Class.isInstance`, whether a reference that isn't `null` is an instance of `class`
+    pub(super) fn is_instance(
+        &self,
+        value: &StackValue,
+        class: &Arc<LoadedClass>,
+    ) -> Result<bool, JvmError> {
+        match self.array_component_type(value) {
+            Some(component_type) => {
+                self.is_assignable_type(class.loader, &format!("[{component_type}"), &class.name)
+            }
+            None => Ok(self.runtime_class(value)?.is_assignable_to(class)),
+        }
+    }
+
+    /// A `java/lang/reflect/Field` for every field the class file of `class` declares
+    pub(super) fn declared_fields(
+        &self,
+        class: &Arc<LoadedClass>,
+    ) -> Result<JavaObjectRef, JvmError> {
+        let field_class = self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/reflect/Field")?;
+        let scope = self.handle_scope();
+
+        let mut fields = vec![];
+        for index in 0..class.java_class.fields.len() {
+            let field = self.new_object(&field_class, NativeData::Field(class.clone(), index))?;
+            scope.add(field);
+            fields.push(StackValue::JavaObjectRef(field));
+        }
+        self.new_array("Ljava/lang/reflect/Field;", fields)
+    }
+
+    /// A `java/lang/reflect/Method` for every method the class file of `class` declares, without
+    /// the constructors and the static initializer
+    pub(super) fn declared_methods(
+        &self,
+        class: &Arc<LoadedClass>,
+    ) -> Result<JavaObjectRef, JvmError> {
+        let method_class = self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/reflect/Method")?;
+        let scope = self.handle_scope();
+        let constant_pool = &class.java_class.constant_pool;
+
+        let mut methods = vec![];
+        for (index, method) in class.java_class.methods.iter().enumerate() {
+            let name = constant_pool.get_utf8_at(method.name_index);
+            if name.is_some_and(|name| name.data.starts_with('<')) {
+                continue;
+            }
+
+            let method =
+                self.new_object(&method_class, NativeData::Method(class.clone(), index))?;
+            scope.add(method);
+            methods.push(StackValue::JavaObjectRef(method));
+        }
+        self.new_array("Ljava/lang/reflect/Method;", methods)
+    }
+
+    /// The field or method a `java/lang/reflect/Field` or `Method` object refers to
+    pub(super) fn reflected_member(&self, object: JavaObjectRef) -> Result<Member, JvmError> {
+        let native_data = match self.heap.lock().unwrap().get(object) {
+            HeapObject::Instance(instance) => match &instance.native_data {
+                NativeData::Field(class, index) => Some((class.clone(), *index, true)),
+                NativeData::Method(class, index) => Some((class.clone(), *index, false)),
+                _ => None,
+            },
+            HeapObject::Array(_) => None,
+        };
+        let Some((class, index, is_field)) = native_data else {
+            return Err(JvmError::Internal(
+                "Expected a java/lang/reflect/Field or Method object".to_string(),
+            ));
+        };
+
+        let java_class = &class.java_class;
+        let constant_pool = &java_class.constant_pool;
+        let (access_flags, name_index, descriptor_index, signature) = if is_field {
+            let field = &java_class.fields[index];
+            (
+                field.access_flags,
+                field.name_index,
+                field.descriptor_index,
+                field.get_signature(constant_pool).map(str::to_string),
+            )
+        } else {
+            let method = &java_class.methods[index];
+            (
+                method.access_flags,
+                method.name_index,
+                method.descriptor_index,
+                method.get_signature(constant_pool).map(str::to_string),
+            )
+        };
+        let utf8 = |index: u16| {
+            constant_pool
+                .get_utf8_at(index)
+                .map(|utf8| utf8.data.to_string())
+                .unwrap_or_default()
+        };
+
+        Ok(Member {
+            name: utf8(name_index),
+            descriptor: utf8(descriptor_index),
+            class,
+            access_flags,
+            is_field,
+            signature,
+        })
+    }
+
+    /// `Field.getGenericType`. Like the JDK, a type without type arguments or type variables is
+    /// reported as its `java/lang/Class`, so is a field without a valid generic signature
+    pub(super) fn field_generic_type(&self, field: &Member) -> Result<JavaObjectRef, JvmError> {
+        let signature = field
+            .signature
+            .as_deref()
+            .and_then(|signature| parse_field_signature(signature).ok());
+        match signature {
+            Some(signature) => {
+                self.generic_type(field.class.loader, TypeArgument::Exact(signature))
+            }
+            None => {
+                let class = self.class_of_descriptor(field.class.loader, &field.descriptor)?;
+                self.class_mirror(&class)
+            }
+        }
+    }
+
+    /// The `java/lang/reflect/Type` of a type in a generic signature, wildcards are only valid
+    /// as type arguments. Classes are resolved by `loader`
+    pub(super) fn generic_type(
+        &self,
+        loader: LoaderId,
+        type_argument: TypeArgument,
+    ) -> Result<JavaObjectRef, JvmError> {
+        let implementation = match &type_argument {
+            TypeArgument::Exact(signature) if is_plain_class(signature) => {
+                let descriptor = signature.erased_descriptor().unwrap_or_default();
+                let class = self.class_of_descriptor(loader, &descriptor)?;
+                return self.class_mirror(&class);
+            }
+            TypeArgument::Exact(ReferenceTypeSignature::Class(_)) => "ParameterizedTypeImpl",
+            TypeArgument::Exact(ReferenceTypeSignature::TypeVariable(_)) => "TypeVariableImpl",
+            TypeArgument::Exact(ReferenceTypeSignature::Array(_)) => "GenericArrayTypeImpl",
+            _ => "WildcardTypeImpl",
+        };
+
+        let class = self.resolve_class(
+            LoaderId::BOOTSTRAP,
+            &format!("sun/reflect/generics/reflectiveObjects/{implementation}"),
+        )?;
+        self.new_object(&class, NativeData::GenericType(loader, type_argument))
+    }
+
+    /// A `java/lang/reflect/Type[]` of the types in a generic signature
+    pub(super) fn generic_types(
+        &self,
+        loader: LoaderId,
+        type_arguments: Vec<TypeArgument>,
+    ) -> Result<JavaObjectRef, JvmError> {
+        let scope = self.handle_scope();
+
+        let mut types = vec![];
+        for type_argument in type_arguments {
+            let generic_type = self.generic_type(loader, type_argument)?;
+            scope.add(generic_type);
+            types.push(StackValue::JavaObjectRef(generic_type));
+        }
+        self.new_array("Ljava/lang/reflect/Type;", types)
+    }
+
+    /// The type a `ParameterizedType`, `TypeVariable`, `WildcardType` or `GenericArrayType`
+    /// object stands for, and the loader its classes are resolved by
+    pub(super) fn reflected_generic_type(
+        &self,
+        object: JavaObjectRef,
+    ) -> Result<(LoaderId, TypeArgument), JvmError> {
+        match self.heap.lock().unwrap().get(object) {
+            HeapObject::Instance(instance) => match &instance.native_data {
+                NativeData::GenericType(loader, type_argument) => {
+                    Ok((*loader, type_argument.clone()))
+                }
+                _ => Err(JvmError::Internal(
+                    "Expected a java/lang/reflect/Type object".to_string(),
+                )),
+            },
+            HeapObject::Array(_) => Err(JvmError::Internal(
+                "Expected a java/lang/reflect/Type object".to_string(),
+            )),
+        }
+    }
+
+    /// `Field.get`, primitive values are boxed
+    pub(super) fn reflected_field_value(
+        &self,
+        field: &Member,
+        object: &StackValue,
+        accessible: bool,
+    ) -> Result<StackValue, JvmError> {
+        self.check_field_access(field, accessible)?;
+        let value = if field.is_static() {
+            self.initialize_class(&field.class)?;
+            field
+                .class
+                .statics
+                .lock()
+                .unwrap()
+                .get(&field.name)
+                .cloned()
+        } else {
+            let object = self.field_receiver(field, object)?;
+            self.get_field(object, &field.name)
+        };
+
+        let value = value.unwrap_or_else(|| default_value(&field.descriptor));
+        self.box_value(&field.descriptor, value)
+    }
+
+    /// `Field.set`, `value` is unboxed for primitive fields. Final fields can only be set when
+    /// they are instance fields made accessible with `setAccessible`
+    pub(super) fn set_reflected_field_value(
+        &self,
+        field: &Member,
+        object: &StackValue,
+        value: StackValue,
+        accessible: bool,
+    ) -> Result<(), JvmError> {
+        let value_name = match &value {
+            StackValue::Null => "null value".to_string(),
+            value => self.class_of_value(value)?.name.replace('/', "."),
+        };
+        let cannot_set = |class_name: &str| {
+            self.new_exception(
+                class_name,
+                &format!("Can not set {} to {value_name}", field.describe()),
+            )
+        };
+
+        self.check_field_access(field, accessible)?;
+        let is_final = field.access_flags & FieldAccessFlags::ACC_FINAL != 0;
+        if is_final && (field.is_static() || !accessible) {
+            return Err(cannot_set("java/lang/IllegalAccessException"));
+        }
+        let Some(value) = self.convert_argument(field.class.loader, &field.descriptor, value)?
+        else {
+            return Err(cannot_set("java/lang/IllegalArgumentException"));
+        };
+
+        if field.is_static() {
+            self.initialize_class(&field.class)?;
+            field
+                .class
+                .statics
+                .lock()
+                .unwrap()
+                .insert(field.name.clone(), value);
+        } else {
+            let object = self.field_receiver(field, object)?;
+            self.set_field(object, &field.name, value);
+        }
+        Ok(())
+    }
+
+    /// Throws `IllegalAccessException` when the caller of `Field.get` or `set` can't access the
+    /// field (JLS 6.6), unless it was made accessible with `setAccessible`
+    fn check_field_access(&self, field: &Member, accessible: bool) -> Result<(), JvmError> {
+        let Some(caller) = self.caller_class().filter(|_| !accessible) else {
+            return Ok(());
+        };
+        // Private members are accessible to the whole nest of their class
+        if self.are_nestmates(&caller, &field.class) {
+            return Ok(());
+        }
+
+        let same_package =
+            caller.loader == field.class.loader && caller.package() == field.class.package();
+        let class_is_public =
+            field.class.java_class.access_flags & ClassAccessFlags::ACC_PUBLIC != 0;
+        let field_is_accessible = if field.access_flags & FieldAccessFlags::ACC_PUBLIC != 0 {
+            true
+        } else if field.access_flags & FieldAccessFlags::ACC_PRIVATE != 0 {
+            false
+        } else if field.access_flags & FieldAccessFlags::ACC_PROTECTED != 0 {
+            same_package || caller.is_subclass_of(&field.class)
+        } else {
+            same_package
+        };
+        if (class_is_public || same_package) && field_is_accessible {
+            return Ok(());
+        }
+
+        let modifiers = FIELD_MODIFIER_NAMES
+            .iter()
+            .filter(|(flag, _)| field.access_flags & flag != 0)
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Err(self.new_exception(
+            "java/lang/IllegalAccessException",
+            &format!(
+                "class {} cannot access a member of class {} with modifiers \"{modifiers}\"",
+                caller.name.replace('/', "."),
+                field.class.name.replace('/', ".")
+            ),
+        ))
+    }
+
+    /// The object an instance field is accessed on, which has to be an instance of the class
+    /// declaring the field
+    fn field_receiver(
+        &self,
+        field: &Member,
+        object: &StackValue,
+    ) -> Result<JavaObjectRef, JvmError> {
+        match object {
+            StackValue::Null => Err(self.new_exception("java/lang/NullPointerException", "")),
+            StackValue::JavaObjectRef(reference) if self.is_instance(object, &field.class)? => {
+                Ok(*reference)
+            }
+            object => {
+                let object_name = self.class_of_value(object)?.name.replace('/', ".");
+                Err(self.new_exception(
+                    "java/lang/IllegalArgumentException",
+                    &format!("Can not set {} to {object_name}", field.describe()),
+                ))
+            }
+        }
+    }
+
+    /// `Method.invoke`, the arguments are unboxed and the result boxed. An exception thrown by
+    /// the method is wrapped in an `InvocationTargetException`
+    pub(super) fn invoke_reflected_method(
+        &self,
+        method: &Member,
+        object: &StackValue,
+        arguments: &StackValue,
+    ) -> Result<StackValue, JvmError> {
+        let descriptor = parse_method_descriptor(&method.descriptor)
+            .map_err(|err| JvmError::Internal(err.to_string()))?;
+
+        let mut args = vec![];
+        let receiver_class = if method.is_static() {
+            self.initialize_class(&method.class)?;
+            None
+        } else {
+            match object {
+                StackValue::Null => {
+                    return Err(self.new_exception("java/lang/NullPointerException", ""));
+                }
+                object if self.is_instance(object, &method.class)? => {
+                    args.push(object.clone());
+                    Some(self.class_of_value(object)?)
+                }
+                _ => {
+                    return Err(self.new_exception(
+                        "java/lang/IllegalArgumentException",
+                        "object is not an instance of declaring class",
+                    ));
+                }
+            }
+        };
+
+        let values = match arguments {
+            StackValue::JavaObjectRef(array) => match self.heap.lock().unwrap().get(*array) {
+                HeapObject::Array(array) => array.values.clone(),
+                HeapObject::Instance(_) => vec![],
+            },
+            _ => vec![],
+        };
+        if values.len() != descriptor.parameters.len() {
+            return Err(self.new_exception(
+                "java/lang/IllegalArgumentException",
+                "wrong number of arguments",
+            ));
+        }
+        for (parameter, value) in descriptor.parameters.iter().zip(values) {
+            match self.convert_argument(method.class.loader, &parameter.to_string(), value)? {
+                Some(value) => args.push(value),
+                None => {
+                    return Err(self.new_exception(
+                        "java/lang/IllegalArgumentException",
+                        "argument type mismatch",
+                    ));
+                }
+            }
+        }
+
+        // Private methods and constructors aren't selected by the receiver
+        let is_private = method.access_flags & MethodAccessFlags::ACC_PRIVATE != 0;
+        let target = match receiver_class.filter(|_| !is_private) {
+            Some(receiver_class) => {
+                self.find_method(&receiver_class, &method.name, &method.descriptor)
+            }
+            None => self.find_method(&method.class, &method.name, &method.descriptor),
+        };
+        let Some(target) = target else {
+            return Err(self.new_exception(
+                "java/lang/AbstractMethodError",
+                &format!(
+                    "{}.{}{}",
+                    method.class.name.replace('/', "."),
+                    method.name,
+                    method.descriptor
+                ),
+            ));
+        };
+
+        match self.invoke_method(target, args) {
+            Ok(value) => match &descriptor.return_type {
+                Some(return_type) => self.box_value(&return_type.to_string(), value),
+                None => Ok(StackValue::Null),
+            },
+            Err(JvmError::Exception(exception)) => {
+                let scope = self.handle_scope();
+                scope.add(exception);
+                let error = self.new_exception("java/lang/reflect/InvocationTargetException", "");
+                if let JvmError::Exception(error) = error {
+                    self.set_field(error, "target", StackValue::JavaObjectRef(exception));
+                    self.set_field(error, "cause", StackValue::JavaObjectRef(exception));
+                }
+                Err(error)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Checks a value passed reflectively has the type of `descriptor`, unboxing and widening
+    /// primitives (JLS 5.1.2). `None` if it doesn't
+    fn convert_argument(
+        &self,
+        loader: LoaderId,
+        descriptor: &str,
+        value: StackValue,
+    ) -> Result<Option<StackValue>, JvmError> {
+        if !matches!(descriptor.chars().next(), Some('L') | Some('[')) {
+            return Ok(self.unbox_value(descriptor, &value));
+        }
+        if let StackValue::Null = value {
+            return Ok(Some(value));
+        }
+
+        let class = self.class_of_descriptor(loader, descriptor)?;
+        Ok(self.is_instance(&value, &class)?.then_some(value))
+    }
+
+    /// Wraps a primitive in its wrapper class, like `Integer.valueOf`. References are returned
+    /// as they are
+    pub(super) fn box_value(
+        &self,
+        descriptor: &str,
+        value: StackValue,
+    ) -> Result<StackValue, JvmError> {
+        let Some((_, class_name)) = BOX_CLASSES
+            .iter()
+            .find(|(primitive, _)| *primitive == descriptor)
+        else {
+            return Ok(value);
+        };
+
+        let class = self.resolve_class(LoaderId::BOOTSTRAP, class_name)?;
+        let boxed = self.new_object(&class, NativeData::None)?;
+        self.set_field(boxed, "value", value);
+        Ok(StackValue::JavaObjectRef(boxed))
+    }
+
+    /// The primitive of type `descriptor` a wrapper object holds, widened if the wrapper is of a
+    /// smaller type. `None` for `null`, other objects and narrowing
+    pub(super) fn unbox_value(&self, descriptor: &str, value: &StackValue) -> Option<StackValue> {
+        let StackValue::JavaObjectRef(object) = value else {
+            return None;
+        };
+        let class = self.runtime_class(value).ok()?;
+        let (boxed, _) = BOX_CLASSES
+            .iter()
+            .find(|(_, class_name)| class.name == *class_name)?;
+        let value = self
+            .get_field(*object, "value")
+            .unwrap_or_else(|| default_value(boxed));
+
+        let widens = match (*boxed, descriptor) {
+            (from, to) if from == to => true,
+            ("B", "S") => true,
+            ("B" | "S" | "C", "I") => true,
+            ("B" | "S" | "C" | "I", "J") => true,
+            ("B" | "S" | "C" | "I" | "J", "F") => true,
+            (from, "D") => from != "Z",
+            _ => false,
+        };
+        if !widens {
+            return None;
+        }
+
+        Some(match (descriptor, value) {
+            ("J", StackValue::Integer(value)) => StackValue::Long(value as i64),
+            ("F", StackValue::Integer(value)) => StackValue::Float(value as f32),
+            ("F", StackValue::Long(value)) => StackValue::Float(value as f32),
+            ("D", StackValue::Integer(value)) => StackValue::Double(value as f64),
+            ("D", StackValue::Long(value)) => StackValue::Double(value as f64),
+            ("D", StackValue::Float(value)) => StackValue::Double(value as f64),
+            (_, value) => value,
+        })
+    }
+}
+
+/// Whether a generic type names a class without type arguments, like `Ljava/lang/String;` or
+/// `[[I`, which reflection reports as a `java/lang/Class`
+fn is_plain_class(signature: &ReferenceTypeSignature) -> bool {
+    match signature {
+        ReferenceTypeSignature::Class(class) => class
+            .classes
+            .iter()
+            .all(|class| class.type_arguments.is_empty()),
+        ReferenceTypeSignature::TypeVariable(_) => false,
+        ReferenceTypeSignature::Array(component) => match component.as_ref() {
+            JavaTypeSignature::Base(_) => true,
+            JavaTypeSignature::Reference(component) => is_plain_class(component),
+        },
+    }
+}
+
+/// The name of a type in java source, e.g. `int` or `java.lang.String[]`
+pub fn type_name(descriptor: &str) -> String {
+    if let Some(component_type) = descriptor.strip_prefix('[') {
+        return format!("{}[]", type_name(component_type));
+    }
+
+    match descriptor {
+        "B" => "byte".to_string(),
+        "C" => "char".to_string(),
+        "D" => "double".to_string(),
+        "F" => "float".to_string(),
+        "I" => "int".to_string(),
+        "J" => "long".to_string(),
+        "S" => "short".to_string(),
+        "Z" => "boolean".to_string(),
+        "V" => "void".to_string(),
+        _ => descriptor
+            .strip_prefix('L')
+            .and_then(|class_name| class_name.strip_suffix(';'))
+            .unwrap_or(descriptor)
+            .replace('/', "."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::jvm::{test_class, StackValue, JVM};
+
+    fn reflection() -> Arc<JVM> {
+        JVM::with_classes(["Reflection", "Reflected", "Pair"].map(test_class))
+    }
+
+    #[test]
+    fn mirrors_describe_their_classes() {
+        let jvm = reflection();
+        assert!(matches!(
+            jvm.run_static("Reflection", "superName", "()Ljava/lang/String;", vec![]),
+            Ok(StackValue::String(name)) if name == "java.lang.Object"
+        ));
+        assert!(matches!(
+            jvm.run_static("Reflection", "instances", "()I", vec![]),
+            Ok(StackValue::Integer(2))
+        ));
+    }
+
+    #[test]
+    fn fields_and_methods_are_used_reflectively() {
+        let jvm = reflection();
+        let Ok(StackValue::JavaObjectRef(result)) =
+            jvm.run_static("Reflection", "reflect", "()Ljava/lang/Object;", vec![])
+        else {
+            panic!("the method didn't return an object");
+        };
+        // The int the method returned is boxed
+        assert!(jvm.is_instance_of_name(result, "java/lang/Integer"));
+        assert!(matches!(
+            jvm.get_field(result, "value"),
+            Some(StackValue::Integer(10))
+        ));
+    }
+
+    #[test]
+    fn private_fields_need_set_accessible() {
+        let jvm = reflection();
+        let secret = |accessible| {
+            let args = vec![StackValue::Integer(accessible)];
+            jvm.run_static("Reflection", "secret", "(Z)Ljava/lang/Object;", args)
+        };
+
+        assert_eq!(
+            secret(0).unwrap_err(),
+            "java.lang.IllegalAccessException: class Reflection cannot access a member of class \
+             Reflected with modifiers \"private\""
+        );
+        let Ok(StackValue::JavaObjectRef(value)) = secret(1) else {
+            panic!("the field wasn't read");
+        };
+        assert!(matches!(
+            jvm.get_field(value, "value"),
+            Some(StackValue::Integer(7))
+        ));
+    }
+
+    #[test]
+    fn generic_types_print_like_the_jdk() {
+        let jvm = reflection();
+        let generic_type = |field, argument| {
+            let args = vec![StackValue::Integer(field), StackValue::Integer(argument)];
+            match jvm.run_static("Reflection", "genericType", "(II)Ljava/lang/String;", args) {
+                Ok(StackValue::String(name)) => name,
+                result => panic!("{result:?}"),
+            }
+        };
+
+        // `Pair<String, ? extends Number> pair`
+        assert_eq!(
+            generic_type(2, -1),
+            "Pair<java.lang.String, ? extends java.lang.Number>"
+        );
+        assert_eq!(generic_type(2, 0), "java.lang.String");
+        assert_eq!(generic_type(2, 1), "? extends java.lang.Number");
+        // `Pair<String, Integer>[] pairs` and `int x`
+        assert_eq!(
+            generic_type(3, -1),
+            "Pair<java.lang.String, java.lang.Integer>[]"
+        );
+        assert_eq!(generic_type(0, -1), "int");
+    }
+}
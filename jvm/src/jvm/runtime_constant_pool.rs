@@ -0,0 +1,793 @@
+//! The runtime constant pool of a loaded class (JVMS 5.1).
+//!
+//! Symbolic references to classes, fields and methods are resolved the first time an instruction
+//! uses them and the result is reused by every later execution (JVMS 5.4.3).
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use jvm_parser::classfile::{
+    classfile::{ClassAccessFlags, FieldAccessFlags, MethodAccessFlags},
+    constant_pool::CpInfo,
+    descriptor::parse_method_descriptor,
+};
+
+use super::{
+    class_loader::{LoadedClass, LoaderId},
+    interpreter::MethodTarget,
+    method_handles::{HandleMember, ReferenceKind},
+    JavaObjectRef, JvmError, StackValue, JVM,
+};
+
+pub struct RuntimeConstantPool {
+    /// Indexed like the constant pool of the class file, entries are set once resolved
+    entries: Vec<OnceLock<ResolvedEntry>>,
+}
+
+impl RuntimeConstantPool {
+    pub fn new(size: usize) -> Self {
+        Self {
+            entries: (0..size).map(|_| OnceLock::new()).collect(),
+        }
+    }
+
+    /// The errors cached for entries that failed to resolve, and the objects that resolved
+    /// constants evaluated to
+    pub fn cached_objects(&self) -> impl Iterator<Item = JavaObjectRef> + '_ {
+        self.entries.iter().filter_map(|entry| match entry.get() {
+            Some(ResolvedEntry::Failed(object))
+            | Some(ResolvedEntry::Constant(StackValue::JavaObjectRef(object))) => Some(*object),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ResolvedEntry {
+    Class(Arc<LoadedClass>),
+    Field(Arc<ResolvedField>),
+    Method(Arc<ResolvedMethod>),
+    /// A method type, a method handle or a dynamically-computed constant
+    Constant(StackValue),
+    /// Resolution threw a `LinkageError`, later attempts throw the same error
+    Failed(JavaObjectRef),
+}
+
+#[derive(Debug)]
+pub struct ResolvedField {
+    /// The class that declares the field, which can be a super class of the referenced one
+    pub class: Arc<LoadedClass>,
+    pub name: String,
+    pub descriptor: String,
+    pub is_static: bool,
+}
+
+#[derive(Debug)]
+pub struct ResolvedMethod {
+    /// The class named by the reference
+    pub class: Arc<LoadedClass>,
+    pub name: String,
+    pub descriptor: String,
+    pub parameter_count: usize,
+    /// Private methods aren't selected by the receiver, every call runs `target` (JVMS 5.4.6)
+    pub is_private: bool,
+    /// The method `invokestatic` and `invokespecial` call, `None` if it is abstract
+    pub target: Option<MethodTarget>,
+    /// The method `invokevirtual`/`invokeinterface` last selected, and the receiver class it was
+    /// selected for
+    selected: Mutex<Option<(Arc<LoadedClass>, MethodTarget)>>,
+}
+
+impl JVM {
+    fn resolve_entry(
+        &self,
+        class: &LoadedClass,
+        index: u16,
+        resolve: impl FnOnce() -> Result<ResolvedEntry, JvmError>,
+    ) -> Result<ResolvedEntry, JvmError> {
+        let Some(slot) = (index as usize)
+            .checked_sub(1)
+            .and_then(|index| class.runtime_constant_pool.entries.get(index))
+        else {
+            return Err(JvmError::Internal(format!(
+                "Invalid constant pool index {index} in '{}'",
+                class.name
+            )));
+        };
+
+        let entry = match slot.get() {
+            Some(entry) => entry.clone(),
+            None => {
+                let entry = match resolve() {
+                    Ok(entry) => entry,
+                    Err(JvmError::Exception(exception))
+                        if self.is_instance_of_name(exception, "java/lang/LinkageError") =>
+                    {
+                        ResolvedEntry::Failed(exception)
+                    }
+                    Err(err) => return Err(err),
+                };
+                // Another thread may have resolved it first, its result wins
+                slot.get_or_init(|| entry).clone()
+            }
+        };
+
+        match entry {
+            ResolvedEntry::Failed(exception) => Err(JvmError::Exception(exception)),
+            entry => Ok(entry),
+        }
+    }
+
+    /// Resolves a `CONSTANT_Class` entry of `class` (JVMS 5.4.3.1)
+    pub(super) fn resolve_class_ref(
+        &self,
+        class: &Arc<LoadedClass>,
+        index: u16,
+    ) -> Result<Arc<LoadedClass>, JvmError> {
+        let entry = self.resolve_entry(class, index, || {
+            let name = self.class_name_at(class, index)?;
+            self.resolve_class(class.loader, &name)
+                .map(ResolvedEntry::Class)
+        })?;
+
+        match entry {
+            ResolvedEntry::Class(resolved) => Ok(resolved),
+            entry => Err(unexpected_entry("class", index, &entry)),
+        }
+    }
+
+    /// Resolves a `CONSTANT_Fieldref` entry of `class` (JVMS 5.4.3.2)
+    pub(super) fn resolve_field_ref(
+        &self,
+        class: &Arc<LoadedClass>,
+        index: u16,
+    ) -> Result<Arc<ResolvedField>, JvmError> {
+        let entry = self.resolve_entry(class, index, || {
+            let (class_name, name, descriptor) = self.member_ref_at(class, index)?;
+            let referenced = self.resolve_class_ref(class, self.ref_class_index(class, index)?)?;
+
+            let Some(declaring) = self.find_field_class(&referenced, &name) else {
+                return Err(self.new_exception(
+                    "java/lang/NoSuchFieldError",
+                    &format!("{}.{name}", class_name.replace('/', ".")),
+                ));
+            };
+            let access_flags = declaring
+                .java_class
+                .get_field(&name)
+                .map_or(0, |field| field.access_flags);
+            if access_flags & FieldAccessFlags::ACC_PRIVATE != 0 {
+                self.check_private_access(class, &declaring, &format!("field {name}"))?;
+            }
+            let is_static = access_flags & FieldAccessFlags::ACC_STATIC != 0;
+
+            Ok(ResolvedEntry::Field(Arc::new(ResolvedField {
+                class: declaring,
+                name,
+                descriptor,
+                is_static,
+            })))
+        })?;
+
+        match entry {
+            ResolvedEntry::Field(field) => Ok(field),
+            entry => Err(unexpected_entry("field", index, &entry)),
+        }
+    }
+
+    /// Resolves a `CONSTANT_Methodref` or `CONSTANT_InterfaceMethodref` entry of `class`
+    /// (JVMS 5.4.3.3, 5.4.3.4)
+    pub(super) fn resolve_method_ref(
+        &self,
+        class: &Arc<LoadedClass>,
+        index: u16,
+    ) -> Result<Arc<ResolvedMethod>, JvmError> {
+        let entry = self.resolve_entry(class, index, || {
+            let (class_name, name, descriptor) = self.member_ref_at(class, index)?;
+            // Arrays only have the methods of Object, `int[].clone()` is one example
+            let referenced = if class_name.starts_with('[') {
+                self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/Object")?
+            } else {
+                self.resolve_class_ref(class, self.ref_class_index(class, index)?)?
+            };
+
+            let is_interface =
+                referenced.java_class.access_flags & ClassAccessFlags::ACC_INTERFACE != 0;
+            let interface_ref = class
+                .java_class
+                .constant_pool
+                .get_refs_at(index)
+                .map(|refs| refs.tag)
+                == Some("CONSTANT_InterfaceMethodref");
+            if is_interface != interface_ref {
+                return Err(self.new_exception(
+                    "java/lang/IncompatibleClassChangeError",
+                    &format!(
+                        "Found {} {}, but {} was expected",
+                        if is_interface { "interface" } else { "class" },
+                        class_name.replace('/', "."),
+                        if is_interface { "class" } else { "interface" },
+                    ),
+                ));
+            }
+
+            if !self.has_method(&referenced, &name, &descriptor) {
+                return Err(self.new_exception(
+                    "java/lang/NoSuchMethodError",
+                    &format!("{}.{name}{descriptor}", class_name.replace('/', ".")),
+                ));
+            }
+
+            let declared = referenced.ancestors().find_map(|current| {
+                let method = current.java_class.get_method(&name, &descriptor)?;
+                Some((current.clone(), method.access_flags))
+            });
+            let is_private = match declared {
+                Some((declaring, access_flags))
+                    if access_flags & MethodAccessFlags::ACC_PRIVATE != 0 =>
+                {
+                    self.check_private_access(
+                        class,
+                        &declaring,
+                        &format!("method {name}{descriptor}"),
+                    )?;
+                    true
+                }
+                _ => false,
+            };
+
+            Ok(ResolvedEntry::Method(Arc::new(ResolvedMethod {
+                target: self.find_method(&referenced, &name, &descriptor),
+                is_private,
+                class: referenced,
+                parameter_count: parse_method_descriptor(&descriptor)
+                    .map_err(|err| {
+                        self.new_exception("java/lang/ClassFormatError", &err.to_string())
+                    })?
+                    .parameters
+                    .len(),
+                name,
+                descriptor,
+                selected: Mutex::new(None),
+            })))
+        })?;
+
+        match entry {
+            ResolvedEntry::Method(method) => Ok(method),
+            entry => Err(unexpected_entry("method", index, &entry)),
+        }
+    }
+
+    /// The value `ldc`, `ldc_w` or `ldc2_w` pushes for a constant pool entry of `class`, symbolic
+    /// references are resolved first (JVMS 5.1, 5.4.3)
+    pub(super) fn load_constant(
+        &self,
+        class: &Arc<LoadedClass>,
+        index: u16,
+    ) -> Result<StackValue, JvmError> {
+        let constant_pool = &class.java_class.constant_pool;
+        let entry = (index != 0).then(|| constant_pool.get_at(index)).flatten();
+
+        match entry {
+            Some(CpInfo::Integer(int)) => Ok(StackValue::Integer(int.bytes)),
+            Some(CpInfo::Float(float)) => Ok(StackValue::Float(float.bytes)),
+            Some(CpInfo::Long(long)) => Ok(StackValue::Long(long.bytes as i64)),
+            Some(CpInfo::Double(double)) => Ok(StackValue::Double(double.bytes)),
+            Some(CpInfo::String(string)) => match constant_pool.get_utf8_at(string.string_index) {
+                Some(utf8) => Ok(StackValue::String(utf8.data.to_string())),
+                None => Err(JvmError::Internal(format!(
+                    "No Utf8 constant pool entry at index {} in '{}'",
+                    string.string_index, class.name
+                ))),
+            },
+            Some(CpInfo::Class(_)) => {
+                let resolved = self.resolve_class_ref(class, index)?;
+                self.class_mirror(&resolved).map(StackValue::JavaObjectRef)
+            }
+            Some(CpInfo::MethodType(_)) => self.resolve_method_type_ref(class, index),
+            Some(CpInfo::MethodHandle(_)) => self.resolve_method_handle_ref(class, index),
+            Some(CpInfo::InvokeDynamic(dynamic)) if dynamic.tag == "CONSTANT_Dynamic" => {
+                self.resolve_dynamic_ref(class, index)
+            }
+            entry => Err(JvmError::Internal(format!(
+                "The constant pool entry at index {index} in '{}' isn't loadable, found: {entry:?}",
+                class.name
+            ))),
+        }
+    }
+
+    /// Resolves a `CONSTANT_MethodType` entry of `class` to a `java/lang/invoke/MethodType`
+    /// (JVMS 5.4.3.5)
+    fn resolve_method_type_ref(
+        &self,
+        class: &Arc<LoadedClass>,
+        index: u16,
+    ) -> Result<StackValue, JvmError> {
+        let entry = self.resolve_entry(class, index, || {
+            let constant_pool = &class.java_class.constant_pool;
+            let descriptor = match constant_pool.get_at(index) {
+                Some(CpInfo::MethodType(method_type)) => {
+                    constant_pool.get_utf8_at(method_type.descriptor_index)
+                }
+                _ => None,
+            };
+            let Some(descriptor) = descriptor else {
+                return Err(JvmError::Internal(format!(
+                    "No method type at constant pool index {index} in '{}'",
+                    class.name
+                )));
+            };
+
+            self.new_method_type(class.loader, &descriptor.data)
+                .map(|method_type| ResolvedEntry::Constant(StackValue::JavaObjectRef(method_type)))
+        })?;
+
+        match entry {
+            ResolvedEntry::Constant(method_type) => Ok(method_type),
+            entry => Err(unexpected_entry("method type", index, &entry)),
+        }
+    }
+
+    /// Resolves a `CONSTANT_MethodHandle` entry of `class` to a `java/lang/invoke/MethodHandle`,
+    /// resolving the field or method it refers to (JVMS 5.4.3.5)
+    pub(super) fn resolve_method_handle_ref(
+        &self,
+        class: &Arc<LoadedClass>,
+        index: u16,
+    ) -> Result<StackValue, JvmError> {
+        let entry = self.resolve_entry(class, index, || {
+            let Some(CpInfo::MethodHandle(handle)) = class.java_class.constant_pool.get_at(index)
+            else {
+                return Err(JvmError::Internal(format!(
+                    "No method handle at constant pool index {index} in '{}'",
+                    class.name
+                )));
+            };
+            let Some(kind) = ReferenceKind::from_u8(handle.reference_kind) else {
+                return Err(JvmError::Internal(format!(
+                    "Invalid reference kind {} of the method handle at constant pool index \
+                     {index} in '{}'",
+                    handle.reference_kind, class.name
+                )));
+            };
+
+            let member = if kind.is_field() {
+                HandleMember::Field(self.resolve_field_ref(class, handle.reference_index)?)
+            } else {
+                HandleMember::Method(self.resolve_method_ref(class, handle.reference_index)?)
+            };
+            self.new_method_handle(kind, member)
+                .map(|handle| ResolvedEntry::Constant(StackValue::JavaObjectRef(handle)))
+        })?;
+
+        match entry {
+            ResolvedEntry::Constant(handle) => Ok(handle),
+            entry => Err(unexpected_entry("method handle", index, &entry)),
+        }
+    }
+
+    /// Resolves a `CONSTANT_Dynamic` entry of `class` by invoking its bootstrap method
+    /// (JVMS 5.4.3.6)
+    fn resolve_dynamic_ref(
+        &self,
+        class: &Arc<LoadedClass>,
+        index: u16,
+    ) -> Result<StackValue, JvmError> {
+        let entry = self.resolve_entry(class, index, || {
+            let constant_pool = &class.java_class.constant_pool;
+            let dynamic = match constant_pool.get_at(index) {
+                Some(CpInfo::InvokeDynamic(dynamic)) => constant_pool
+                    .get_name_type_at(dynamic.name_and_type_index)
+                    .and_then(|name_and_type| {
+                        Some((
+                            dynamic.bootstrap_method_attr_index,
+                            constant_pool.get_utf8_at(name_and_type.name_index)?,
+                            constant_pool.get_utf8_at(name_and_type.descriptor_index)?,
+                        ))
+                    }),
+                _ => None,
+            };
+            let Some((bootstrap_index, name, descriptor)) = dynamic else {
+                return Err(JvmError::Internal(format!(
+                    "No dynamic constant at constant pool index {index} in '{}'",
+                    class.name
+                )));
+            };
+
+            self.compute_dynamic_constant(class, bootstrap_index, &name.data, &descriptor.data)
+                .map(ResolvedEntry::Constant)
+        })?;
+
+        match entry {
+            ResolvedEntry::Constant(constant) => Ok(constant),
+            entry => Err(unexpected_entry("dynamic constant", index, &entry)),
+        }
+    }
+
+    /// Selects the method a virtual call to `method` runs for a receiver of class `receiver`
+    /// (JVMS 5.4.6)
+    pub(super) fn select_method(
+        &self,
+        method: &ResolvedMethod,
+        receiver: &Arc<LoadedClass>,
+    ) -> Result<MethodTarget, JvmError> {
+        if method.is_private {
+            if let Some(target) = &method.target {
+                return Ok(target.clone());
+            }
+        }
+
+        if let Some((class, target)) = &*method.selected.lock().unwrap() {
+            if Arc::ptr_eq(class, receiver) {
+                return Ok(target.clone());
+            }
+        }
+
+        let Some(target) = self.find_method(receiver, &method.name, &method.descriptor) else {
+            return Err(self.new_exception(
+                "java/lang/AbstractMethodError",
+                &format!(
+                    "{}.{}{}",
+                    receiver.name.replace('/', "."),
+                    method.name,
+                    method.descriptor
+                ),
+            ));
+        };
+
+        *method.selected.lock().unwrap() = Some((receiver.clone(), target.clone()));
+        Ok(target)
+    }
+
+    /// A private member of `declaring` is only accessible to the classes of its nest (JVMS 5.4.4)
+    fn check_private_access(
+        &self,
+        accessor: &Arc<LoadedClass>,
+        declaring: &Arc<LoadedClass>,
+        member: &str,
+    ) -> Result<(), JvmError> {
+        if self.are_nestmates(accessor, declaring) {
+            return Ok(());
+        }
+
+        Err(self.new_exception(
+            "java/lang/IllegalAccessError",
+            &format!(
+                "class {} tried to access private {member} of class {}",
+                accessor.name.replace('/', "."),
+                declaring.name.replace('/', ".")
+            ),
+        ))
+    }
+
+    pub(super) fn class_name_at(
+        &self,
+        class: &LoadedClass,
+        index: u16,
+    ) -> Result<String, JvmError> {
+        class
+            .java_class
+            .constant_pool
+            .get_class_name_at(index)
+            .map(|name| name.to_string())
+            .ok_or_else(|| {
+                JvmError::Internal(format!(
+                    "No class constant pool entry at index {index} in '{}'",
+                    class.name
+                ))
+            })
+    }
+
+    fn ref_class_index(&self, class: &LoadedClass, index: u16) -> Result<u16, JvmError> {
+        class
+            .java_class
+            .constant_pool
+            .get_refs_at(index)
+            .map(|refs| refs.class_index)
+            .ok_or_else(|| {
+                JvmError::Internal(format!(
+                    "No member reference at constant pool index {index} in '{}'",
+                    class.name
+                ))
+            })
+    }
+
+    /// Returns the class, name and descriptor of a Fieldref/Methodref/InterfaceMethodref
+    fn member_ref_at(
+        &self,
+        class: &LoadedClass,
+        index: u16,
+    ) -> Result<(String, String, String), JvmError> {
+        let constant_pool = &class.java_class.constant_pool;
+        let member =
+            constant_pool
+                .get_refs_ext_at(index)
+                .and_then(|(_, cp_class, name_and_type)| {
+                    Some((
+                        constant_pool
+                            .get_utf8_at(cp_class.name_index)?
+                            .data
+                            .to_string(),
+                        constant_pool
+                            .get_utf8_at(name_and_type.name_index)?
+                            .data
+                            .to_string(),
+                        constant_pool
+                            .get_utf8_at(name_and_type.descriptor_index)?
+                            .data
+                            .to_string(),
+                    ))
+                });
+
+        member.ok_or_else(|| {
+            JvmError::Internal(format!(
+                "No member reference at constant pool index {index} in '{}'",
+                class.name
+            ))
+        })
+    }
+
+    /// The class that declares a field, searching the class, its super interfaces and then its
+    /// super class
+    fn find_field_class(&self, class: &Arc<LoadedClass>, name: &str) -> Option<Arc<LoadedClass>> {
+        class.ancestors().find_map(|current| {
+            if current.java_class.get_field(name).is_some() {
+                return Some(current);
+            }
+            current
+                .interfaces
+                .iter()
+                .find_map(|interface| self.find_field_class(interface, name))
+        })
+    }
+
+    /// Whether the class or one of its super types declares the method, abstract ones included
+    fn has_method(&self, class: &Arc<LoadedClass>, name: &str, descriptor: &str) -> bool {
+        class.ancestors().any(|current| {
+            current.java_class.get_method(name, descriptor).is_some()
+                || self
+                    .native_methods
+                    .contains_key(format!("{};{name}", current.name).as_str())
+                || current
+                    .interfaces
+                    .iter()
+                    .any(|interface| self.has_method(interface, name, descriptor))
+        })
+    }
+}
+
+fn unexpected_entry(expected: &str, index: u16, entry: &ResolvedEntry) -> JvmError {
+    JvmError::Internal(format!(
+        "Expected a {expected} reference at constant pool index {index}, found: {entry:?}"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use jvm_parser::classfile::{
+        attributes::{
+            AttributeInfo, AttributeInfoData, BootstrapMethod, BootstrapMethodsAttribute,
+        },
+        constant_pool::{
+            CpInfo, CpInfoInteger, CpInfoInvokeDynamic, CpInfoMethodHandle, CpInfoMethodType,
+            CpInfoUtf8,
+        },
+        JavaClass,
+    };
+
+    use crate::jvm::{test_class, StackValue, JVM};
+
+    /// The index of the first constant that matches
+    fn constant(class: &JavaClass, matches: impl Fn(&CpInfo) -> bool) -> u16 {
+        let entries = &class.constant_pool.pool_entries;
+        entries.iter().position(matches).unwrap() as u16 + 1
+    }
+
+    fn add_constant(class: &mut JavaClass<'static>, constant: CpInfo<'static>) -> u16 {
+        class.constant_pool.pool_entries.push(constant);
+        class.constant_pool.pool_entries.len() as u16
+    }
+
+    /// Replaces the byte code of a method, the constants javac doesn't `ldc` are loaded this way
+    fn set_code(class: &mut JavaClass<'static>, name: &str, code: Vec<u8>) {
+        let name_index = constant(
+            class,
+            |entry| matches!(entry, CpInfo::Utf8(utf8) if utf8.data == name),
+        );
+        let method = class
+            .methods
+            .iter_mut()
+            .find(|method| method.name_index == name_index)
+            .unwrap();
+        for attribute in &mut method.attributes {
+            if let AttributeInfoData::Code(attribute) = &mut attribute.attribute {
+                attribute.code = code.clone().into();
+            }
+        }
+    }
+
+    #[test]
+    fn unresolvable_references_throw_linkage_errors() {
+        let mut target = test_class("Linkage$Target");
+        let removed = |index| {
+            let name = target.constant_pool.get_utf8_at(index);
+            matches!(
+                name.map(|utf8| utf8.data.as_ref()),
+                Some("missing" | "absent")
+            )
+        };
+        target.methods.retain(|method| !removed(method.name_index));
+        target.fields.retain(|field| !removed(field.name_index));
+
+        let jvm = JVM::with_classes([test_class("Linkage"), target]);
+        let call = |name| jvm.run_static("Linkage", name, "()I", vec![]);
+
+        // Resolved entries are reused, and so are the errors of entries that failed to resolve
+        for _ in 0..2 {
+            assert!(matches!(call("twice"), Ok(StackValue::Integer(14))));
+            assert_eq!(
+                call("missing").unwrap_err(),
+                "java.lang.NoSuchMethodError: Linkage$Target.missing()I"
+            );
+            assert_eq!(
+                call("absent").unwrap_err(),
+                "java.lang.NoSuchFieldError: Linkage$Target.absent"
+            );
+            assert_eq!(
+                call("gone").unwrap_err(),
+                "java.lang.NoClassDefFoundError: Linkage$Gone"
+            );
+        }
+    }
+
+    #[test]
+    fn every_loadable_constant_kind_is_loaded() {
+        let mut class = test_class("Constants");
+        let utf8 = |class: &JavaClass, data: &str| {
+            constant(
+                class,
+                |entry| matches!(entry, CpInfo::Utf8(utf8) if utf8.data == data),
+            )
+        };
+        let name_and_type = |class: &JavaClass, name: &str| {
+            let name = utf8(class, name);
+            constant(
+                class,
+                |entry| matches!(entry, CpInfo::NameAndType(name_and_type) if name_and_type.name_index == name),
+            )
+        };
+        // static int answer(MethodHandles.Lookup lookup, String name, Class<?> type, int base)
+        let answer_type = name_and_type(&class, "answer");
+        let answer = constant(
+            &class,
+            |entry| matches!(entry, CpInfo::Refs(refs) if refs.name_and_type_index == answer_type),
+        );
+        let string = constant(&class, |entry| matches!(entry, CpInfo::String(_)));
+        let widen = utf8(&class, "(I)J");
+        let dynamic_type = name_and_type(&class, "ANSWER");
+
+        let method_type = add_constant(
+            &mut class,
+            CpInfo::MethodType(CpInfoMethodType {
+                tag: "CONSTANT_MethodType",
+                descriptor_index: widen,
+            }),
+        );
+        let answer_handle = add_constant(
+            &mut class,
+            CpInfo::MethodHandle(CpInfoMethodHandle {
+                tag: "CONSTANT_MethodHandle",
+                reference_kind: 6,
+                reference_index: answer,
+            }),
+        );
+        let base = add_constant(
+            &mut class,
+            CpInfo::Integer(CpInfoInteger {
+                tag: "CONSTANT_Integer",
+                bytes: 41,
+            }),
+        );
+        // The value of `ANSWER` is `answer(lookup, "ANSWER", int.class, 41)`
+        let dynamic = add_constant(
+            &mut class,
+            CpInfo::InvokeDynamic(CpInfoInvokeDynamic {
+                tag: "CONSTANT_Dynamic",
+                bootstrap_method_attr_index: 0,
+                name_and_type_index: dynamic_type,
+            }),
+        );
+        let attribute_name_index = add_constant(
+            &mut class,
+            CpInfo::Utf8(CpInfoUtf8 {
+                tag: "CONSTANT_Utf8",
+                data: "BootstrapMethods".into(),
+                raw: None,
+            }),
+        );
+        class.attributes.push(AttributeInfo {
+            attribute_name_index,
+            attribute: AttributeInfoData::BootstrapMethods(BootstrapMethodsAttribute {
+                attribute_name_index,
+                bootstrap_methods: vec![BootstrapMethod {
+                    bootstrap_method_ref: answer_handle,
+                    bootstrap_arguments: vec![base],
+                }],
+            }),
+        });
+
+        let [ldc, ldc_w, ireturn, areturn] = [0x12, 0x13, 0xac, 0xb0];
+        let [high, low] = string.to_be_bytes();
+        set_code(&mut class, "wide", vec![ldc_w, high, low, areturn]);
+        set_code(
+            &mut class,
+            "methodType",
+            vec![ldc, method_type as u8, areturn],
+        );
+        set_code(
+            &mut class,
+            "methodHandle",
+            vec![ldc, answer_handle as u8, areturn],
+        );
+        set_code(&mut class, "dynamic", vec![ldc, dynamic as u8, ireturn]);
+
+        let jvm = JVM::with_classes([class]);
+        let load = |name, descriptor| jvm.run_static("Constants", name, descriptor, vec![]);
+        let is_instance = |value, class_name| match value {
+            Ok(StackValue::JavaObjectRef(object)) => jvm.is_instance_of_name(object, class_name),
+            _ => false,
+        };
+
+        assert!(matches!(
+            load("integer", "()I"),
+            Ok(StackValue::Integer(100_000))
+        ));
+        assert!(matches!(load("decimal", "()F"), Ok(StackValue::Float(1.5))));
+        let object = "()Ljava/lang/Object;";
+        assert!(matches!(load("wide", object), Ok(StackValue::String(s)) if s == "wide"));
+        assert!(is_instance(load("mirror", object), "java/lang/Class"));
+        assert!(is_instance(
+            load("methodType", object),
+            "java/lang/invoke/MethodType"
+        ));
+        assert!(is_instance(
+            load("methodHandle", object),
+            "java/lang/invoke/MethodHandle"
+        ));
+        assert!(matches!(
+            load("dynamic", "()I"),
+            Ok(StackValue::Integer(42))
+        ));
+    }
+
+    #[test]
+    fn private_members_are_only_accessible_to_nestmates() {
+        let peek = |host: JavaClass<'static>, member: JavaClass<'static>| {
+            let jvm = JVM::with_classes([host, member]);
+            jvm.run_static("Nests$Member", "peek", "()I", vec![])
+        };
+        let without = |mut class: JavaClass<'static>,
+                       nest_attribute: fn(&AttributeInfoData) -> bool| {
+            class
+                .attributes
+                .retain(|attribute| !nest_attribute(&attribute.attribute));
+            class
+        };
+        let error = "java.lang.IllegalAccessError: class Nests$Member tried to access private \
+                     method secret()I of class Nests";
+
+        assert!(matches!(
+            peek(test_class("Nests"), test_class("Nests$Member")),
+            Ok(StackValue::Integer(42))
+        ));
+        // Outside of the nest
+        let outsider = without(test_class("Nests$Member"), |attribute| {
+            matches!(attribute, AttributeInfoData::NestHost(_))
+        });
+        assert_eq!(peek(test_class("Nests"), outsider).unwrap_err(), error);
+        // Claims to be in the nest, but the host doesn't list it
+        let host = without(test_class("Nests"), |attribute| {
+            matches!(attribute, AttributeInfoData::NestMembers(_))
+        });
+        assert_eq!(peek(host, test_class("Nests$Member")).unwrap_err(), error);
+    }
+}
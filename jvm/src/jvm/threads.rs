@@ -0,0 +1,353 @@
+//! Java threads. Every `java/lang/Thread` that is started runs on its own OS thread, with its
+//! own frame stack, and shares the heap and the loaded classes with the others.
+//!
+//! Thread state changes happen under the lock of the thread table and wake up everything that
+//! waits on `threads_changed`, which is how `join`, `sleep` and interrupts are implemented.
+
+use std::{
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use super::{
+    class_loader::{LoadedClass, LoaderId},
+    heap::{HeapObject, NativeData},
+    interpreter::FrameValues,
+    JavaObjectRef, JvmError, StackValue, JVM,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    New,
+    Runnable,
+    Terminated,
+}
+
+#[derive(Debug)]
+pub struct JavaThread {
+    pub id: u64,
+    /// The `java/lang/Thread` object of the thread
+    pub object: JavaObjectRef,
+    /// The vm exits once the last thread that isn't a daemon terminates
+    pub daemon: AtomicBool,
+    pub interrupted: AtomicBool,
+    pub state: Mutex<ThreadState>,
+    /// The values of the frames on the thread's stack, from the bottom up
+    pub frames: Mutex<Vec<Arc<Mutex<FrameValues>>>>,
+    /// Objects rust code running on the thread keeps alive, see [`super::gc::HandleScope`]
+    pub handles: Mutex<Vec<JavaObjectRef>>,
+}
+
+impl JavaThread {
+    pub fn state(&self) -> ThreadState {
+        *self.state.lock().unwrap()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Threads {
+    /// Threads that have been started and haven't terminated yet
+    live: Vec<Arc<JavaThread>>,
+    next_id: u64,
+    /// The number in the name of the next unnamed thread, `Thread-0`, `Thread-1`, ...
+    next_unnamed: u64,
+}
+
+thread_local! {
+    static CURRENT_THREAD: RefCell<Option<Arc<JavaThread>>> = const { RefCell::new(None) };
+}
+
+impl JVM {
+    /// The java thread running on the calling OS thread
+    pub(super) fn current_thread(&self) -> Option<Arc<JavaThread>> {
+        CURRENT_THREAD.with(|current| current.borrow().clone())
+    }
+
+    /// The class of the innermost java method on the current thread, which is the caller of a
+    /// native. `None` when rust code calls
+    pub(super) fn caller_class(&self) -> Option<Arc<LoadedClass>> {
+        let thread = self.current_thread()?;
+        let frames = thread.frames.lock().unwrap();
+        let values = frames.last()?.lock().unwrap();
+        values.class.clone()
+    }
+
+    pub(super) fn current_thread_id(&self) -> u64 {
+        self.current_thread()
+            .map(|thread| thread.id)
+            .unwrap_or_default()
+    }
+
+    /// Creates the vm side of a `java/lang/Thread` object, the thread isn't started yet
+    pub(super) fn create_thread(&self, object: JavaObjectRef) -> Arc<JavaThread> {
+        // New threads are daemons if the thread creating them is one
+        let daemon = self
+            .current_thread()
+            .is_some_and(|current| current.daemon.load(Ordering::SeqCst));
+
+        let thread = {
+            let mut threads = self.threads.lock().unwrap();
+            threads.next_id += 1;
+            Arc::new(JavaThread {
+                id: threads.next_id,
+                object,
+                daemon: AtomicBool::new(daemon),
+                interrupted: AtomicBool::new(false),
+                state: Mutex::new(ThreadState::New),
+                frames: Mutex::new(vec![]),
+                handles: Mutex::new(vec![]),
+            })
+        };
+
+        if let HeapObject::Instance(instance) = self.heap.lock().unwrap().get_mut(object) {
+            instance.native_data = NativeData::Thread(thread.clone());
+        }
+        thread
+    }
+
+    /// The name a thread gets if its constructor isn't given one
+    pub(super) fn next_thread_name(&self) -> String {
+        let mut threads = self.threads.lock().unwrap();
+        let name = format!("Thread-{}", threads.next_unnamed);
+        threads.next_unnamed += 1;
+        name
+    }
+
+    pub(super) fn thread_of_object(&self, object: JavaObjectRef) -> Option<Arc<JavaThread>> {
+        match self.heap.lock().unwrap().get(object) {
+            HeapObject::Instance(instance) => match &instance.native_data {
+                NativeData::Thread(thread) => Some(thread.clone()),
+                _ => None,
+            },
+            HeapObject::Array(_) => None,
+        }
+    }
+
+    pub(super) fn thread_name(&self, thread: &JavaThread) -> String {
+        match self.get_field(thread.object, "name") {
+            Some(StackValue::String(name)) => name,
+            _ => format!("Thread-{}", thread.id),
+        }
+    }
+
+    /// The threads that have been started and haven't terminated yet
+    pub(super) fn live_threads(&self) -> Vec<Arc<JavaThread>> {
+        self.threads.lock().unwrap().live.clone()
+    }
+
+    /// Creates the `java/lang/Thread` object for the OS thread the vm was started on
+    pub(super) fn attach_main_thread(&self) -> Result<Arc<JavaThread>, JvmError> {
+        self.leave_safe_region();
+
+        let thread_class = self.resolve_class(LoaderId::BOOTSTRAP, "java/lang/Thread")?;
+        let object = self.new_object(&thread_class, NativeData::None)?;
+        self.set_field(object, "name", StackValue::String("main".to_string()));
+        self.set_field(object, "priority", StackValue::Integer(5));
+
+        let thread = self.create_thread(object);
+        *thread.state.lock().unwrap() = ThreadState::Runnable;
+        self.threads.lock().unwrap().live.push(thread.clone());
+        CURRENT_THREAD.with(|current| *current.borrow_mut() = Some(thread.clone()));
+
+        Ok(thread)
+    }
+
+    /// Starts running `run()` of the thread on a new OS thread
+    pub(super) fn start_thread(&self, thread: Arc<JavaThread>) -> Result<(), JvmError> {
+        {
+            let mut threads = self.threads.lock().unwrap();
+            let mut state = thread.state.lock().unwrap();
+            if *state != ThreadState::New {
+                drop(state);
+                drop(threads);
+                return Err(self.new_exception("java/lang/IllegalThreadStateException", ""));
+            }
+            *state = ThreadState::Runnable;
+            // Registered before the OS thread exists, so the vm can't exit in between
+            threads.live.push(thread.clone());
+            self.threads_changed.notify_all();
+        }
+
+        let jvm = self.handle();
+        let name = self.thread_name(&thread);
+        let spawned = std::thread::Builder::new().name(name).spawn({
+            let thread = thread.clone();
+            move || {
+                // A panic is a bug in the vm, it takes the process down like one in main does
+                // instead of leaving the threads that wait for this one hanging
+                if panic::catch_unwind(AssertUnwindSafe(|| jvm.run_thread(thread))).is_err() {
+                    std::process::exit(101);
+                }
+            }
+        });
+
+        if let Err(err) = spawned {
+            self.terminate_thread(&thread);
+            return Err(JvmError::Internal(format!(
+                "Unable to create a native thread: {err}"
+            )));
+        }
+        Ok(())
+    }
+
+    fn run_thread(&self, thread: Arc<JavaThread>) {
+        CURRENT_THREAD.with(|current| *current.borrow_mut() = Some(thread.clone()));
+        self.leave_safe_region();
+
+        match self.invoke_virtual(thread.object, "run", "()V", vec![]) {
+            Ok(_) => {}
+            Err(JvmError::Exception(exception)) => eprintln!(
+                "Exception in thread \"{}\" {}",
+                self.thread_name(&thread),
+                self.describe_exception(exception)
+            ),
+            Err(JvmError::Internal(message)) => eprintln!("{message}"),
+        }
+
+        self.detach_current_thread();
+    }
+
+    /// Ends the java thread of the calling OS thread, like `DetachCurrentThread`. It won't run
+    /// java code anymore, so collections don't wait for it and joining it returns
+    pub(super) fn detach_current_thread(&self) {
+        let Some(thread) = CURRENT_THREAD.with(|current| current.borrow_mut().take()) else {
+            return;
+        };
+        self.enter_safe_region();
+        self.terminate_thread(&thread);
+    }
+
+    fn terminate_thread(&self, thread: &Arc<JavaThread>) {
+        let mut threads = self.threads.lock().unwrap();
+        *thread.state.lock().unwrap() = ThreadState::Terminated;
+        threads.live.retain(|live| !Arc::ptr_eq(live, thread));
+        self.threads_changed.notify_all();
+    }
+
+    /// Waits for a thread to terminate, `millis` of 0 waits forever
+    pub(super) fn join_thread(&self, thread: &JavaThread, millis: i64) -> Result<(), JvmError> {
+        if millis < 0 {
+            return Err(self.new_exception(
+                "java/lang/IllegalArgumentException",
+                "timeout value is negative",
+            ));
+        }
+
+        let deadline = (millis > 0).then(|| Instant::now() + Duration::from_millis(millis as u64));
+        if self.wait_for_thread_event(deadline, || thread.state() != ThreadState::Runnable) {
+            return Err(self.new_exception("java/lang/InterruptedException", ""));
+        }
+        Ok(())
+    }
+
+    pub(super) fn sleep(&self, millis: i64) -> Result<(), JvmError> {
+        if millis < 0 {
+            return Err(self.new_exception(
+                "java/lang/IllegalArgumentException",
+                "timeout value is negative",
+            ));
+        }
+
+        let deadline = Instant::now() + Duration::from_millis(millis as u64);
+        if self.wait_for_thread_event(Some(deadline), || false) {
+            return Err(self.new_exception("java/lang/InterruptedException", "sleep interrupted"));
+        }
+        Ok(())
+    }
+
+    /// Blocks until `done` returns true or the deadline passes. Returns true if the current
+    /// thread was interrupted while waiting, which clears its interrupt status
+    fn wait_for_thread_event(&self, deadline: Option<Instant>, done: impl Fn() -> bool) -> bool {
+        let current = self.current_thread();
+
+        self.safe_region(|| {
+            let mut threads = self.threads.lock().unwrap();
+            loop {
+                if done() {
+                    return false;
+                }
+                if let Some(current) = &current {
+                    if current.interrupted.swap(false, Ordering::SeqCst) {
+                        return true;
+                    }
+                }
+
+                threads = match deadline {
+                    Some(deadline) => {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            return false;
+                        }
+                        self.threads_changed
+                            .wait_timeout(threads, deadline - now)
+                            .unwrap()
+                            .0
+                    }
+                    None => self.threads_changed.wait(threads).unwrap(),
+                };
+            }
+        })
+    }
+
+    pub(super) fn interrupt_thread(&self, thread: &JavaThread) {
+        {
+            let _threads = self.threads.lock().unwrap();
+            thread.interrupted.store(true, Ordering::SeqCst);
+            self.threads_changed.notify_all();
+        }
+        self.wake_monitor_waiters();
+    }
+
+    /// Blocks until every thread that isn't a daemon terminated
+    pub(super) fn wait_for_non_daemon_threads(&self) {
+        let mut threads = self.threads.lock().unwrap();
+        while threads
+            .live
+            .iter()
+            .any(|thread| !thread.daemon.load(Ordering::SeqCst))
+        {
+            threads = self.threads_changed.wait(threads).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jvm::{test_class, StackValue, JVM};
+
+    fn threads() -> std::sync::Arc<JVM> {
+        JVM::with_classes(["Threads", "Threads$Worker"].map(test_class))
+    }
+
+    #[test]
+    fn joining_waits_for_the_thread() {
+        let jvm = threads();
+        let result = jvm.run_static("Threads", "start", "(Z)I", vec![StackValue::Integer(1)]);
+        assert!(matches!(result, Ok(StackValue::Integer(42))));
+    }
+
+    #[test]
+    fn the_vm_waits_for_threads_that_are_not_daemons() {
+        let jvm = threads();
+        let result = jvm.run_static("Threads", "start", "(Z)I", vec![StackValue::Integer(0)]);
+        // The worker is still sleeping when the method returns
+        assert!(matches!(result, Ok(StackValue::Integer(0))));
+        assert!(matches!(
+            jvm.static_value("Threads$Worker", "result"),
+            Some(StackValue::Integer(42))
+        ));
+    }
+
+    #[test]
+    fn interrupted_threads_stop_sleeping() {
+        let jvm = threads();
+        // Throwing InterruptedException clears the interrupt status
+        let result = jvm.run_static("Threads", "sleep", "()I", vec![]);
+        assert!(matches!(result, Ok(StackValue::Integer(1))));
+    }
+}
@@ -0,0 +1,1694 @@
+//! Verification by type checking (JVMS 4.10.1).
+//!
+//! Every method is checked in a single pass over its instructions, using the frames of its
+//! `StackMapTable` at branch targets and exception handlers instead of inferring them. Class
+//! files older than version 50 have no stack maps and are not verified.
+
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use jvm_parser::classfile::{
+    attributes::{CodeAttribute, StackMapFrame, VerificationTypeInfo},
+    classfile::{ClassAccessFlags, MethodAccessFlags, MethodInfo},
+    constant_pool::CpInfo,
+    descriptor::{parse_method_descriptor, BaseType, FieldDescriptor, MethodDescriptor},
+};
+
+use super::{
+    class_loader::LoadedClass,
+    opcodes::{parse_opcodes, CmpConditions, OpCodes},
+    JvmError, JVM,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum VerificationType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    /// Created by the `new` instruction at this offset and not initialized yet
+    Uninitialized(u16),
+    /// A class name or an array descriptor
+    Reference(String),
+}
+
+use VerificationType as VT;
+
+impl VerificationType {
+    pub(super) fn from_descriptor(descriptor: &str) -> Option<Self> {
+        match descriptor.as_bytes().first()? {
+            b'B' | b'C' | b'I' | b'S' | b'Z' => Some(VT::Integer),
+            b'F' => Some(VT::Float),
+            b'J' => Some(VT::Long),
+            b'D' => Some(VT::Double),
+            b'L' => Some(VT::Reference(
+                descriptor.strip_prefix('L')?.strip_suffix(';')?.to_string(),
+            )),
+            b'[' => Some(VT::Reference(descriptor.to_string())),
+            _ => None,
+        }
+    }
+
+    pub(super) fn from_field_descriptor(field: &FieldDescriptor) -> Self {
+        match field {
+            FieldDescriptor::Base(BaseType::Float) => VT::Float,
+            FieldDescriptor::Base(BaseType::Long) => VT::Long,
+            FieldDescriptor::Base(BaseType::Double) => VT::Double,
+            FieldDescriptor::Base(_) => VT::Integer,
+            FieldDescriptor::Object(class_name) => VT::Reference(class_name.clone()),
+            FieldDescriptor::Array(_) => VT::Reference(field.to_string()),
+        }
+    }
+
+    pub(super) fn is_category_2(&self) -> bool {
+        matches!(self, VT::Long | VT::Double)
+    }
+
+    fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            VT::Null | VT::UninitializedThis | VT::Uninitialized(_) | VT::Reference(_)
+        )
+    }
+
+    /// The element type of an array type, `None` if it isn't one
+    pub(super) fn component_type(&self) -> Option<VerificationType> {
+        match self {
+            VT::Reference(name) => VT::from_descriptor(name.strip_prefix('[')?),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for VerificationType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VT::Top => write!(f, "top"),
+            VT::Integer => write!(f, "integer"),
+            VT::Float => write!(f, "float"),
+            VT::Long => write!(f, "long"),
+            VT::Double => write!(f, "double"),
+            VT::Null => write!(f, "null"),
+            VT::UninitializedThis => write!(f, "uninitializedThis"),
+            VT::Uninitialized(offset) => write!(f, "uninitialized({offset})"),
+            VT::Reference(name) => write!(f, "'{name}'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Frame {
+    /// One entry per local variable, longs and doubles are followed by a `Top`
+    pub(super) locals: Vec<VerificationType>,
+    /// One entry per value, longs and doubles count twice towards `max_stack`
+    pub(super) stack: Vec<VerificationType>,
+}
+
+impl Frame {
+    /// Set while `this` is uninitialized in a constructor
+    fn flag_this_uninit(&self) -> bool {
+        self.locals.contains(&VT::UninitializedThis)
+    }
+
+    pub(super) fn stack_size(&self) -> usize {
+        self.stack
+            .iter()
+            .map(|value| if value.is_category_2() { 2 } else { 1 })
+            .sum()
+    }
+
+    fn describe(&self, pc: usize) -> String {
+        // Written like `{ integer, 'java/lang/String' }`, or `{ }` when empty
+        let list = |types: &[VerificationType]| {
+            let types = types
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            match types.is_empty() {
+                true => "{ }".to_string(),
+                false => format!("{{ {} }}", types.join(", ")),
+            }
+        };
+        let flags = if self.flag_this_uninit() {
+            "flagThisUninit "
+        } else {
+            ""
+        };
+
+        format!(
+            "    bci: @{pc}\n    flags: {{ {flags}}}\n    locals: {}\n    stack: {}",
+            list(&self.locals),
+            list(&self.stack)
+        )
+    }
+}
+
+/// Why verification failed, the headline is the message HotSpot uses for the same failure
+struct Failure {
+    headline: &'static str,
+    reason: String,
+}
+
+type Check<T = ()> = Result<T, Failure>;
+
+fn fail<T>(headline: &'static str, reason: String) -> Check<T> {
+    Err(Failure { headline, reason })
+}
+
+/// How control continues after an instruction
+struct Flow {
+    falls_through: bool,
+    /// Absolute pcs the instruction can branch to
+    targets: Vec<usize>,
+}
+
+impl Flow {
+    fn next() -> Self {
+        Self {
+            falls_through: true,
+            targets: vec![],
+        }
+    }
+
+    fn end() -> Self {
+        Self {
+            falls_through: false,
+            targets: vec![],
+        }
+    }
+}
+
+struct MethodVerifier<'a> {
+    jvm: &'a JVM,
+    class: &'a Arc<LoadedClass>,
+    name: &'a str,
+    descriptor: &'a str,
+    code: &'a CodeAttribute<'a>,
+    return_type: Option<VerificationType>,
+}
+
+impl JVM {
+    /// Verifies every method of a class, throws `VerifyError` for the first one that fails
+    pub(super) fn verify_class(&self, class: &Arc<LoadedClass>) -> Result<(), JvmError> {
+        if class.java_class.major_version < 50 {
+            return Ok(());
+        }
+
+        let constant_pool = &class.java_class.constant_pool;
+        for method in &class.java_class.methods {
+            let Some(code) = method.get_code() else {
+                continue;
+            };
+            let utf8 = |index| {
+                constant_pool
+                    .get_utf8_at(index)
+                    .map(|utf8| utf8.data.as_ref())
+                    .unwrap_or_default()
+            };
+            let (name, descriptor) = (utf8(method.name_index), utf8(method.descriptor_index));
+
+            let verifier = MethodVerifier {
+                jvm: self,
+                class,
+                name,
+                descriptor,
+                code,
+                return_type: descriptor
+                    .rsplit_once(')')
+                    .and_then(|(_, return_type)| VT::from_descriptor(return_type)),
+            };
+            verifier
+                .verify(method)
+                .map_err(|message| self.new_exception("java/lang/VerifyError", &message))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MethodVerifier<'_> {
+    fn verify(&self, method: &MethodInfo) -> Result<(), String> {
+        let opcodes = parse_opcodes(&self.code.code)
+            .map_err(|err| self.message(0, None, None, "Bad instruction", &format!("{err}")))?;
+
+        let initial = self
+            .initial_frame(method)
+            .map_err(|failure| self.message(0, None, None, failure.headline, &failure.reason))?;
+        let frames = self
+            .stack_map_frames(&initial)
+            .map_err(|failure| self.message(0, None, None, failure.headline, &failure.reason))?;
+
+        if let Some(pc) = frames
+            .keys()
+            .find(|pc| !opcodes.iter().any(|(opcode_pc, _)| opcode_pc == *pc))
+        {
+            return Err(self.message(
+                *pc,
+                None,
+                None,
+                "Illegal stack map frame",
+                &format!("Stack map frame at bci {pc} is not on an instruction boundary"),
+            ));
+        }
+
+        let mut current = Some(initial);
+        for (pc, opcode) in &opcodes {
+            let pc = *pc;
+            let mut frame = match (current.take(), frames.get(&pc)) {
+                (Some(frame), Some(map_frame)) => {
+                    self.check_assignable_frame(
+                        &frame,
+                        map_frame,
+                        "Instruction type does not match stack map",
+                    )
+                    .map_err(|failure| {
+                        self.message(
+                            pc,
+                            Some(opcode),
+                            Some(&frame),
+                            failure.headline,
+                            &failure.reason,
+                        )
+                    })?;
+                    map_frame.clone()
+                }
+                (None, Some(map_frame)) => map_frame.clone(),
+                (Some(frame), None) => frame,
+                (None, None) => {
+                    return Err(self.message(
+                        pc,
+                        Some(opcode),
+                        None,
+                        "Expecting a stackmap frame at branch target",
+                        "Expected stackmap frame at this location.",
+                    ))
+                }
+            };
+
+            let incoming = frame.clone();
+            let result = self
+                .check_handlers(pc, &incoming, &frames)
+                .and_then(|_| self.execute(pc, opcode, &mut frame))
+                .and_then(|flow| {
+                    if is_local_store(opcode) {
+                        self.check_handlers(pc, &frame, &frames)?;
+                    }
+                    for target in &flow.targets {
+                        let Some(target_frame) = frames.get(target) else {
+                            return fail(
+                                "Expecting a stackmap frame at branch target",
+                                format!("Expected stackmap frame at branch target {target}"),
+                            );
+                        };
+                        self.check_assignable_frame(
+                            &frame,
+                            target_frame,
+                            "Inconsistent stackmap frames at branch target",
+                        )?;
+                    }
+                    Ok(flow)
+                });
+
+            match result {
+                Ok(flow) => current = flow.falls_through.then_some(frame),
+                Err(failure) => {
+                    return Err(self.message(
+                        pc,
+                        Some(opcode),
+                        Some(&incoming),
+                        failure.headline,
+                        &failure.reason,
+                    ))
+                }
+            }
+        }
+
+        if current.is_some() {
+            let pc = opcodes.last().map(|(pc, _)| *pc).unwrap_or_default();
+            return Err(self.message(
+                pc,
+                None,
+                None,
+                "Falling off the end of the code",
+                "Control flow falls through code end",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Formats a failure like HotSpot does
+    fn message(
+        &self,
+        pc: usize,
+        opcode: Option<&OpCodes>,
+        frame: Option<&Frame>,
+        headline: &str,
+        reason: &str,
+    ) -> String {
+        let opcode = opcode
+            .map(|opcode| format!(": {}", mnemonic(opcode)))
+            .unwrap_or_default();
+        let mut message = format!(
+            "{headline}\nException Details:\n  Location:\n    {}.{}{} @{pc}{opcode}\n  Reason:\n    {reason}",
+            self.class.name, self.name, self.descriptor
+        );
+        if let Some(frame) = frame {
+            message.push_str("\n  Current Frame:\n");
+            message.push_str(&frame.describe(pc));
+        }
+        message
+    }
+
+    fn initial_frame(&self, method: &MethodInfo) -> Check<Frame> {
+        let mut locals = vec![];
+        if method.access_flags & MethodAccessFlags::ACC_STATIC == 0 {
+            if self.name == "<init>" && self.class.name != "java/lang/Object" {
+                locals.push(VT::UninitializedThis);
+            } else {
+                locals.push(VT::Reference(self.class.name.clone()));
+            }
+        }
+
+        let descriptor = self.method_descriptor(self.descriptor)?;
+        locals.extend(descriptor.parameters.iter().map(VT::from_field_descriptor));
+
+        self.expand_locals(&locals).map(|locals| Frame {
+            locals,
+            stack: vec![],
+        })
+    }
+
+    /// Turns locals as written in a stack map, where longs and doubles are a single entry, into
+    /// a full frame of `max_locals` entries
+    fn expand_locals(&self, locals: &[VerificationType]) -> Check<Vec<VerificationType>> {
+        let mut expanded = vec![];
+        for local in locals {
+            expanded.push(local.clone());
+            if local.is_category_2() {
+                expanded.push(VT::Top);
+            }
+        }
+
+        let max_locals = self.code.max_locals as usize;
+        if expanded.len() > max_locals {
+            return fail(
+                "Illegal local variable number",
+                format!(
+                    "The frame has {} locals, but max_locals is {max_locals}",
+                    expanded.len()
+                ),
+            );
+        }
+        expanded.resize(max_locals, VT::Top);
+        Ok(expanded)
+    }
+
+    /// The frames of the `StackMapTable`, keyed by the pc they apply to
+    fn stack_map_frames(&self, initial: &Frame) -> Check<BTreeMap<usize, Frame>> {
+        let mut frames = BTreeMap::new();
+        let Some(stack_map) = self.code.get_stack_map_table() else {
+            return Ok(frames);
+        };
+
+        // The locals of the previous frame the way the stack map writes them
+        let mut locals = compact_locals(&initial.locals);
+        let mut pc: Option<usize> = None;
+
+        for entry in &stack_map.entries {
+            let offset = entry.offset_delta() as usize;
+            let current_pc = pc.map(|pc| pc + offset + 1).unwrap_or(offset);
+            pc = Some(current_pc);
+
+            let stack = match entry {
+                StackMapFrame::None => continue,
+                StackMapFrame::SameFrame { .. } | StackMapFrame::SameFrameExtended { .. } => vec![],
+                StackMapFrame::SameLocalsStackItemFrame { stack, .. }
+                | StackMapFrame::SameLocalsStackItemFrameExtended { stack, .. } => {
+                    vec![self.verification_type(stack)?]
+                }
+                StackMapFrame::ChopFrame { chopped, .. } => {
+                    let Some(length) = locals.len().checked_sub(*chopped as usize) else {
+                        return fail(
+                            "Illegal stack map frame",
+                            format!(
+                                "Chop frame at bci {current_pc} removes more locals than there are"
+                            ),
+                        );
+                    };
+                    locals.truncate(length);
+                    vec![]
+                }
+                StackMapFrame::AppendFrame {
+                    locals: appended, ..
+                } => {
+                    for local in appended {
+                        locals.push(self.verification_type(local)?);
+                    }
+                    vec![]
+                }
+                StackMapFrame::FullFrame {
+                    locals: full_locals,
+                    stack,
+                    ..
+                } => {
+                    locals = full_locals
+                        .iter()
+                        .map(|local| self.verification_type(local))
+                        .collect::<Check<_>>()?;
+                    stack
+                        .iter()
+                        .map(|value| self.verification_type(value))
+                        .collect::<Check<_>>()?
+                }
+            };
+
+            let frame = Frame {
+                locals: self.expand_locals(&locals)?,
+                stack,
+            };
+            if frame.stack_size() > self.code.max_stack as usize {
+                return fail(
+                    "Illegal stack map frame",
+                    format!("The frame at bci {current_pc} has more stack items than max_stack"),
+                );
+            }
+            frames.insert(current_pc, frame);
+        }
+
+        Ok(frames)
+    }
+
+    fn verification_type(&self, info: &VerificationTypeInfo) -> Check<VerificationType> {
+        Ok(match info {
+            VerificationTypeInfo::Top => VT::Top,
+            VerificationTypeInfo::Integer => VT::Integer,
+            VerificationTypeInfo::Float => VT::Float,
+            VerificationTypeInfo::Double => VT::Double,
+            VerificationTypeInfo::Long => VT::Long,
+            VerificationTypeInfo::Null => VT::Null,
+            VerificationTypeInfo::UninitializedThis => VT::UninitializedThis,
+            VerificationTypeInfo::Object { cpool_index } => {
+                VT::Reference(self.class_at(*cpool_index)?)
+            }
+            VerificationTypeInfo::Uninitialized { offset } => VT::Uninitialized(*offset),
+        })
+    }
+
+    /// Checks that the frame at a handler accepts the locals of every instruction it covers
+    fn check_handlers(&self, pc: usize, frame: &Frame, frames: &BTreeMap<usize, Frame>) -> Check {
+        for entry in &self.code.exception_table {
+            if pc < entry.start_pc as usize || pc >= entry.end_pc as usize {
+                continue;
+            }
+
+            let handler_pc = entry.handler_pc as usize;
+            let Some(handler_frame) = frames.get(&handler_pc) else {
+                return fail(
+                    "Expecting a stackmap frame at branch target",
+                    format!("Expected stackmap frame at exception handler {handler_pc}"),
+                );
+            };
+
+            let catch_type = match entry.catch_type {
+                0 => "java/lang/Throwable".to_string(),
+                index => self.class_at(index)?,
+            };
+            let exception_frame = Frame {
+                locals: frame.locals.clone(),
+                stack: vec![VT::Reference(catch_type)],
+            };
+            self.check_assignable_frame(
+                &exception_frame,
+                handler_frame,
+                "Stack map does not match the one at exception handler",
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn check_assignable_frame(&self, from: &Frame, to: &Frame, headline: &'static str) -> Check {
+        for (index, (from_local, to_local)) in from.locals.iter().zip(&to.locals).enumerate() {
+            if !self.is_assignable(from_local, to_local) {
+                return fail(
+                    headline,
+                    format!(
+                        "Type {from_local} (current frame, locals[{index}]) is not assignable to {to_local} (stack map, locals[{index}])"
+                    ),
+                );
+            }
+        }
+
+        if from.stack.len() != to.stack.len() {
+            return fail(
+                headline,
+                format!(
+                    "Current frame's stack size doesn't match stackmap. ({} != {})",
+                    from.stack.len(),
+                    to.stack.len()
+                ),
+            );
+        }
+        for (index, (from_value, to_value)) in from.stack.iter().zip(&to.stack).enumerate() {
+            if !self.is_assignable(from_value, to_value) {
+                return fail(
+                    headline,
+                    format!(
+                        "Type {from_value} (current frame, stack[{index}]) is not assignable to {to_value} (stack map, stack[{index}])"
+                    ),
+                );
+            }
+        }
+
+        if from.flag_this_uninit() && !to.flag_this_uninit() {
+            return fail(
+                headline,
+                "Current frame's flags are not assignable to stack map frame's.".to_string(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The subtyping rules of the type checker (JVMS 4.10.1.2)
+    fn is_assignable(&self, from: &VerificationType, to: &VerificationType) -> bool {
+        if from == to || *to == VT::Top {
+            return true;
+        }
+
+        match (from, to) {
+            (VT::Null, VT::Reference(_)) => true,
+            (VT::Reference(from), VT::Reference(to)) => self.is_reference_assignable(from, to),
+            _ => false,
+        }
+    }
+
+    fn is_reference_assignable(&self, from: &str, to: &str) -> bool {
+        if from == to || to == "java/lang/Object" {
+            return true;
+        }
+
+        match (from.strip_prefix('['), to.strip_prefix('[')) {
+            (Some(from), Some(to)) => match (VT::from_descriptor(from), VT::from_descriptor(to)) {
+                (Some(VT::Reference(from)), Some(VT::Reference(to))) => {
+                    self.is_reference_assignable(&from, &to)
+                }
+                (from, to) => from == to,
+            },
+            (Some(_), None) => matches!(to, "java/lang/Cloneable" | "java/io/Serializable"),
+            (None, Some(_)) => false,
+            (None, None) => {
+                let load = |name| self.jvm.load_class(self.class.loader, name).ok().flatten();
+                let Some(to_class) = load(to) else {
+                    return false;
+                };
+                // Interfaces are treated like Object, invokeinterface checks the receiver instead
+                if to_class.java_class.access_flags & ClassAccessFlags::ACC_INTERFACE != 0 {
+                    return true;
+                }
+                load(from).is_some_and(|from_class| from_class.is_subclass_of(&to_class))
+            }
+        }
+    }
+
+    fn pop(&self, frame: &mut Frame, expected: &VerificationType) -> Check<VerificationType> {
+        let Some(value) = frame.stack.pop() else {
+            return fail(
+                "Operand stack underflow",
+                "Attempt to pop empty stack.".to_string(),
+            );
+        };
+        if !self.is_assignable(&value, expected) {
+            return fail(
+                "Bad type on operand stack",
+                format!(
+                    "Type {value} (current frame, stack[{}]) is not assignable to {expected}",
+                    frame.stack.len()
+                ),
+            );
+        }
+        Ok(value)
+    }
+
+    fn pop_reference(&self, frame: &mut Frame) -> Check<VerificationType> {
+        let Some(value) = frame.stack.pop() else {
+            return fail(
+                "Operand stack underflow",
+                "Attempt to pop empty stack.".to_string(),
+            );
+        };
+        if !value.is_reference() {
+            return fail(
+                "Bad type on operand stack",
+                format!(
+                    "Type {value} (current frame, stack[{}]) is not assignable to reference type",
+                    frame.stack.len()
+                ),
+            );
+        }
+        Ok(value)
+    }
+
+    /// Pops a value of the given category, `dup`, `pop` and `swap` don't care about the type
+    fn pop_category(&self, frame: &mut Frame, category_2: bool) -> Check<VerificationType> {
+        let Some(value) = frame.stack.pop() else {
+            return fail(
+                "Operand stack underflow",
+                "Attempt to pop empty stack.".to_string(),
+            );
+        };
+        if value.is_category_2() != category_2 {
+            return fail(
+                "Bad type on operand stack",
+                format!(
+                    "Type {value} (current frame, stack[{}]) is not a category {} type",
+                    frame.stack.len(),
+                    if category_2 { 2 } else { 1 }
+                ),
+            );
+        }
+        Ok(value)
+    }
+
+    fn push(&self, frame: &mut Frame, value: VerificationType) -> Check {
+        frame.stack.push(value);
+        if frame.stack_size() > self.code.max_stack as usize {
+            return fail(
+                "Operand stack overflow",
+                "Exceeded max stack size.".to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    fn load(&self, frame: &mut Frame, index: usize, expected: &VerificationType) -> Check {
+        let Some(value) = frame.locals.get(index).cloned() else {
+            return fail(
+                "Illegal local variable number",
+                format!("Local index {index} is invalid"),
+            );
+        };
+
+        let matches = match expected {
+            VT::Reference(_) => value.is_reference(),
+            expected => value == *expected,
+        };
+        let second_half =
+            !expected.is_category_2() || frame.locals.get(index + 1) == Some(&VT::Top);
+        if !matches || !second_half {
+            return fail(
+                "Bad local variable type",
+                format!(
+                    "Type {value} (current frame, locals[{index}]) is not assignable to {}",
+                    match expected {
+                        VT::Reference(_) => "reference type".to_string(),
+                        expected => expected.to_string(),
+                    }
+                ),
+            );
+        }
+
+        self.push(frame, value)
+    }
+
+    fn store(&self, frame: &mut Frame, index: usize, expected: &VerificationType) -> Check {
+        let value = match expected {
+            VT::Reference(_) => self.pop_reference(frame)?,
+            expected => self.pop(frame, expected)?,
+        };
+
+        let size = if value.is_category_2() { 2 } else { 1 };
+        if index + size > frame.locals.len() {
+            return fail(
+                "Illegal local variable number",
+                format!("Local index {index} is invalid"),
+            );
+        }
+
+        // Overwriting the second half of a long or double invalidates it
+        if index > 0 && frame.locals[index - 1].is_category_2() {
+            frame.locals[index - 1] = VT::Top;
+        }
+        if size == 2 {
+            frame.locals[index + 1] = VT::Top;
+        }
+        frame.locals[index] = value;
+        Ok(())
+    }
+
+    fn binary(&self, frame: &mut Frame, operand: VerificationType) -> Check {
+        self.pop(frame, &operand)?;
+        self.pop(frame, &operand)?;
+        self.push(frame, operand)
+    }
+
+    fn unary(&self, frame: &mut Frame, from: VerificationType, to: VerificationType) -> Check {
+        self.pop(frame, &from)?;
+        self.push(frame, to)
+    }
+
+    fn array_load(&self, frame: &mut Frame, components: &[&str]) -> Check {
+        self.pop(frame, &VT::Integer)?;
+        let array = self.pop_reference(frame)?;
+        let value = match &array {
+            VT::Null => match components {
+                ["L"] => VT::Null,
+                components => VT::from_descriptor(components[0]).unwrap_or(VT::Top),
+            },
+            VT::Reference(name) if is_array_of(name, components) => {
+                array.component_type().unwrap_or(VT::Top)
+            }
+            _ => {
+                return fail(
+                    "Bad type on operand stack",
+                    format!(
+                        "Type {array} (current frame, stack[{}]) is not an array of {}",
+                        frame.stack.len(),
+                        components.join(" or ")
+                    ),
+                )
+            }
+        };
+        self.push(frame, value)
+    }
+
+    fn array_store(&self, frame: &mut Frame, components: &[&str]) -> Check {
+        let value = match components {
+            ["L"] => self.pop_reference(frame)?,
+            components => {
+                let expected = VT::from_descriptor(components[0]).unwrap_or(VT::Top);
+                self.pop(frame, &expected)?
+            }
+        };
+        self.pop(frame, &VT::Integer)?;
+        let array = self.pop_reference(frame)?;
+        match &array {
+            VT::Null => Ok(()),
+            VT::Reference(name) if is_array_of(name, components) => Ok(()),
+            _ => fail(
+                "Bad type on operand stack",
+                format!(
+                    "Type {array} (current frame, stack[{}]) is not an array of {} to store {value} in",
+                    frame.stack.len(),
+                    components.join(" or ")
+                ),
+            ),
+        }
+    }
+
+    fn branch(&self, pc: usize, offset: i32) -> Check<usize> {
+        let target = pc as i64 + offset as i64;
+        if target < 0 || target >= self.code.code.len() as i64 {
+            return fail(
+                "Illegal target of jump or branch",
+                format!("Branch target {target} is outside of the code"),
+            );
+        }
+        Ok(target as usize)
+    }
+
+    fn execute(&self, pc: usize, opcode: &OpCodes, frame: &mut Frame) -> Check<Flow> {
+        match opcode {
+            OpCodes::nop => {}
+
+            OpCodes::aconst_null => self.push(frame, VT::Null)?,
+            OpCodes::iconst_(_) | OpCodes::bipush(_) | OpCodes::sipush(_) => {
+                self.push(frame, VT::Integer)?
+            }
+            OpCodes::fconst_(_) => self.push(frame, VT::Float)?,
+            OpCodes::lconst_(_) => self.push(frame, VT::Long)?,
+            OpCodes::dconst_(_) => self.push(frame, VT::Double)?,
+            OpCodes::ldc(index) => {
+                let value = self.loadable_constant(*index as u16, false)?;
+                self.push(frame, value)?
+            }
+            OpCodes::ldc_w(index) => {
+                let value = self.loadable_constant(*index, false)?;
+                self.push(frame, value)?
+            }
+            OpCodes::ldc2_w(index) => {
+                let value = self.loadable_constant(*index, true)?;
+                self.push(frame, value)?
+            }
+
+            OpCodes::iload_(index) => self.load(frame, *index as usize, &VT::Integer)?,
+            OpCodes::lload_(index) => self.load(frame, *index as usize, &VT::Long)?,
+            OpCodes::fload_(index) => self.load(frame, *index as usize, &VT::Float)?,
+            OpCodes::dload_(index) => self.load(frame, *index as usize, &VT::Double)?,
+            OpCodes::aload_(index) => {
+                self.load(frame, *index as usize, &VT::Reference(String::new()))?
+            }
+            OpCodes::istore_(index) => self.store(frame, *index as usize, &VT::Integer)?,
+            OpCodes::lstore_(index) => self.store(frame, *index as usize, &VT::Long)?,
+            OpCodes::fstore_(index) => self.store(frame, *index as usize, &VT::Float)?,
+            OpCodes::dstore_(index) => self.store(frame, *index as usize, &VT::Double)?,
+            OpCodes::astore_(index) => {
+                self.store(frame, *index as usize, &VT::Reference(String::new()))?
+            }
+            OpCodes::iinc(index, _) => self.iinc(frame, *index as usize)?,
+            OpCodes::wide { opcode, index, .. } => {
+                let index = *index as usize;
+                match opcode.as_ref() {
+                    OpCodes::iload_(_) => self.load(frame, index, &VT::Integer)?,
+                    OpCodes::lload_(_) => self.load(frame, index, &VT::Long)?,
+                    OpCodes::fload_(_) => self.load(frame, index, &VT::Float)?,
+                    OpCodes::dload_(_) => self.load(frame, index, &VT::Double)?,
+                    OpCodes::aload_(_) => self.load(frame, index, &VT::Reference(String::new()))?,
+                    OpCodes::istore_(_) => self.store(frame, index, &VT::Integer)?,
+                    OpCodes::lstore_(_) => self.store(frame, index, &VT::Long)?,
+                    OpCodes::fstore_(_) => self.store(frame, index, &VT::Float)?,
+                    OpCodes::dstore_(_) => self.store(frame, index, &VT::Double)?,
+                    OpCodes::astore_(_) => {
+                        self.store(frame, index, &VT::Reference(String::new()))?
+                    }
+                    OpCodes::iinc(_, _) => self.iinc(frame, index)?,
+                    opcode => {
+                        return fail(
+                            "Bad instruction",
+                            format!("The instruction {} can't be widened", mnemonic(opcode)),
+                        )
+                    }
+                }
+            }
+
+            OpCodes::iaload => self.array_load(frame, &["I"])?,
+            OpCodes::laload => self.array_load(frame, &["J"])?,
+            OpCodes::faload => self.array_load(frame, &["F"])?,
+            OpCodes::daload => self.array_load(frame, &["D"])?,
+            OpCodes::aaload => self.array_load(frame, &["L"])?,
+            OpCodes::baload => self.array_load(frame, &["B", "Z"])?,
+            OpCodes::caload => self.array_load(frame, &["C"])?,
+            OpCodes::saload => self.array_load(frame, &["S"])?,
+            OpCodes::iastore => self.array_store(frame, &["I"])?,
+            OpCodes::lastore => self.array_store(frame, &["J"])?,
+            OpCodes::fastore => self.array_store(frame, &["F"])?,
+            OpCodes::dastore => self.array_store(frame, &["D"])?,
+            OpCodes::aastore => self.array_store(frame, &["L"])?,
+            OpCodes::bastore => self.array_store(frame, &["B", "Z"])?,
+            OpCodes::castore => self.array_store(frame, &["C"])?,
+            OpCodes::sastore => self.array_store(frame, &["S"])?,
+            OpCodes::arraylength => {
+                let array = self.pop_reference(frame)?;
+                if !matches!(&array, VT::Null) && array.component_type().is_none() {
+                    return fail(
+                        "Bad type on operand stack",
+                        format!(
+                            "Type {array} (current frame, stack[{}]) is not an array",
+                            frame.stack.len()
+                        ),
+                    );
+                }
+                self.push(frame, VT::Integer)?
+            }
+
+            OpCodes::pop => {
+                self.pop_category(frame, false)?;
+            }
+            OpCodes::pop2 => {
+                if !self.peek_category_2(frame)? {
+                    self.pop_category(frame, false)?;
+                    self.pop_category(frame, false)?;
+                } else {
+                    self.pop_category(frame, true)?;
+                }
+            }
+            OpCodes::dup => {
+                let value = self.pop_category(frame, false)?;
+                self.push(frame, value.clone())?;
+                self.push(frame, value)?
+            }
+            OpCodes::dup_x1 => {
+                let value1 = self.pop_category(frame, false)?;
+                let value2 = self.pop_category(frame, false)?;
+                self.push_all(frame, [value1.clone(), value2, value1])?
+            }
+            OpCodes::dup_x2 => {
+                let value1 = self.pop_category(frame, false)?;
+                if self.peek_category_2(frame)? {
+                    let value2 = self.pop_category(frame, true)?;
+                    self.push_all(frame, [value1.clone(), value2, value1])?
+                } else {
+                    let value2 = self.pop_category(frame, false)?;
+                    let value3 = self.pop_category(frame, false)?;
+                    self.push_all(frame, [value1.clone(), value3, value2, value1])?
+                }
+            }
+            OpCodes::dup2 => {
+                if self.peek_category_2(frame)? {
+                    let value = self.pop_category(frame, true)?;
+                    self.push_all(frame, [value.clone(), value])?
+                } else {
+                    let value1 = self.pop_category(frame, false)?;
+                    let value2 = self.pop_category(frame, false)?;
+                    self.push_all(frame, [value2.clone(), value1.clone(), value2, value1])?
+                }
+            }
+            OpCodes::dup2_x1 => {
+                if self.peek_category_2(frame)? {
+                    let value1 = self.pop_category(frame, true)?;
+                    let value2 = self.pop_category(frame, false)?;
+                    self.push_all(frame, [value1.clone(), value2, value1])?
+                } else {
+                    let value1 = self.pop_category(frame, false)?;
+                    let value2 = self.pop_category(frame, false)?;
+                    let value3 = self.pop_category(frame, false)?;
+                    self.push_all(
+                        frame,
+                        [value2.clone(), value1.clone(), value3, value2, value1],
+                    )?
+                }
+            }
+            OpCodes::dup2_x2 => {
+                // Pop two stack words at a time, whatever the categories are
+                let upper = self.pop_words(frame)?;
+                let lower = self.pop_words(frame)?;
+                let values = upper
+                    .iter()
+                    .chain(&lower)
+                    .chain(&upper)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                self.push_all(frame, values)?
+            }
+            OpCodes::swap => {
+                let value1 = self.pop_category(frame, false)?;
+                let value2 = self.pop_category(frame, false)?;
+                self.push_all(frame, [value1, value2])?
+            }
+
+            OpCodes::iadd
+            | OpCodes::isub
+            | OpCodes::imul
+            | OpCodes::idiv
+            | OpCodes::irem
+            | OpCodes::iand
+            | OpCodes::ior
+            | OpCodes::ixor
+            | OpCodes::ishl
+            | OpCodes::ishr
+            | OpCodes::iushr => self.binary(frame, VT::Integer)?,
+            OpCodes::ladd
+            | OpCodes::lsub
+            | OpCodes::lmul
+            | OpCodes::ldiv
+            | OpCodes::lrem
+            | OpCodes::land
+            | OpCodes::lor
+            | OpCodes::lxor => self.binary(frame, VT::Long)?,
+            OpCodes::lshl | OpCodes::lshr | OpCodes::lushr => {
+                self.pop(frame, &VT::Integer)?;
+                self.unary(frame, VT::Long, VT::Long)?
+            }
+            OpCodes::fadd | OpCodes::fsub | OpCodes::fmul | OpCodes::fdiv | OpCodes::frem => {
+                self.binary(frame, VT::Float)?
+            }
+            OpCodes::dadd | OpCodes::dsub | OpCodes::dmul | OpCodes::ddiv | OpCodes::drem => {
+                self.binary(frame, VT::Double)?
+            }
+            OpCodes::ineg => self.unary(frame, VT::Integer, VT::Integer)?,
+            OpCodes::lneg => self.unary(frame, VT::Long, VT::Long)?,
+            OpCodes::fneg => self.unary(frame, VT::Float, VT::Float)?,
+            OpCodes::dneg => self.unary(frame, VT::Double, VT::Double)?,
+
+            OpCodes::i2l => self.unary(frame, VT::Integer, VT::Long)?,
+            OpCodes::i2f => self.unary(frame, VT::Integer, VT::Float)?,
+            OpCodes::i2d => self.unary(frame, VT::Integer, VT::Double)?,
+            OpCodes::i2b | OpCodes::i2c | OpCodes::i2s => {
+                self.unary(frame, VT::Integer, VT::Integer)?
+            }
+            OpCodes::l2i => self.unary(frame, VT::Long, VT::Integer)?,
+            OpCodes::l2f => self.unary(frame, VT::Long, VT::Float)?,
+            OpCodes::l2d => self.unary(frame, VT::Long, VT::Double)?,
+            OpCodes::f2i => self.unary(frame, VT::Float, VT::Integer)?,
+            OpCodes::f2l => self.unary(frame, VT::Float, VT::Long)?,
+            OpCodes::f2d => self.unary(frame, VT::Float, VT::Double)?,
+            OpCodes::d2i => self.unary(frame, VT::Double, VT::Integer)?,
+            OpCodes::d2l => self.unary(frame, VT::Double, VT::Long)?,
+            OpCodes::d2f => self.unary(frame, VT::Double, VT::Float)?,
+
+            OpCodes::lcmp => {
+                self.pop(frame, &VT::Long)?;
+                self.unary(frame, VT::Long, VT::Integer)?
+            }
+            OpCodes::fcmp(_) => {
+                self.pop(frame, &VT::Float)?;
+                self.unary(frame, VT::Float, VT::Integer)?
+            }
+            OpCodes::dcmp(_) => {
+                self.pop(frame, &VT::Double)?;
+                self.unary(frame, VT::Double, VT::Integer)?
+            }
+
+            OpCodes::if_cond(_, offset) => {
+                self.pop(frame, &VT::Integer)?;
+                return self.conditional_branch(pc, *offset);
+            }
+            OpCodes::if_icmp(_, offset) => {
+                self.pop(frame, &VT::Integer)?;
+                self.pop(frame, &VT::Integer)?;
+                return self.conditional_branch(pc, *offset);
+            }
+            OpCodes::if_acmp(_, offset) => {
+                self.pop_reference(frame)?;
+                self.pop_reference(frame)?;
+                return self.conditional_branch(pc, *offset);
+            }
+            OpCodes::if_null(offset) | OpCodes::if_notnull(offset) => {
+                self.pop_reference(frame)?;
+                return self.conditional_branch(pc, *offset);
+            }
+            OpCodes::goto(offset) => {
+                return Ok(Flow {
+                    falls_through: false,
+                    targets: vec![self.branch(pc, *offset as i32)?],
+                })
+            }
+            OpCodes::goto_w(offset) => {
+                return Ok(Flow {
+                    falls_through: false,
+                    targets: vec![self.branch(pc, *offset)?],
+                })
+            }
+            OpCodes::tableswitch(default, _, _, offsets) => {
+                self.pop(frame, &VT::Integer)?;
+                let targets = std::iter::once(default)
+                    .chain(offsets)
+                    .map(|offset| self.branch(pc, *offset))
+                    .collect::<Check<_>>()?;
+                return Ok(Flow {
+                    falls_through: false,
+                    targets,
+                });
+            }
+            OpCodes::lookupswitch(default, pairs) => {
+                self.pop(frame, &VT::Integer)?;
+                let targets = std::iter::once(default)
+                    .chain(pairs.iter().map(|(_, offset)| offset))
+                    .map(|offset| self.branch(pc, *offset))
+                    .collect::<Check<_>>()?;
+                return Ok(Flow {
+                    falls_through: false,
+                    targets,
+                });
+            }
+            OpCodes::jsr(_) | OpCodes::jsr_w(_) | OpCodes::ret(_) => {
+                return fail(
+                    "Bad instruction",
+                    format!(
+                        "{} is not allowed in class files of version 50 or later",
+                        mnemonic(opcode)
+                    ),
+                )
+            }
+
+            OpCodes::ireturn => return self.return_value(frame, &VT::Integer),
+            OpCodes::lreturn => return self.return_value(frame, &VT::Long),
+            OpCodes::freturn => return self.return_value(frame, &VT::Float),
+            OpCodes::dreturn => return self.return_value(frame, &VT::Double),
+            OpCodes::areturn => return self.return_value(frame, &VT::Reference(String::new())),
+            OpCodes::Return => {
+                if self.return_type.is_some() {
+                    return fail(
+                        "Method expects a return value",
+                        "Error exists in the bytecode".to_string(),
+                    );
+                }
+                if self.name == "<init>" && frame.flag_this_uninit() {
+                    return fail(
+                        "Constructor must call super() or this() before return",
+                        "Error exists in the bytecode".to_string(),
+                    );
+                }
+                return Ok(Flow::end());
+            }
+            OpCodes::athrow => {
+                self.pop(frame, &VT::Reference("java/lang/Throwable".to_string()))?;
+                return Ok(Flow::end());
+            }
+
+            OpCodes::getstatic(index) => {
+                let (_, _, descriptor) = self.member_at(*index)?;
+                self.push(frame, self.field_type(&descriptor)?)?
+            }
+            OpCodes::putstatic(index) => {
+                let (_, _, descriptor) = self.member_at(*index)?;
+                self.pop(frame, &self.field_type(&descriptor)?)?;
+            }
+            OpCodes::getfield(index) => {
+                let (class_name, _, descriptor) = self.member_at(*index)?;
+                self.pop(frame, &VT::Reference(class_name))?;
+                self.push(frame, self.field_type(&descriptor)?)?
+            }
+            OpCodes::putfield(index) => {
+                let (class_name, _, descriptor) = self.member_at(*index)?;
+                self.pop(frame, &self.field_type(&descriptor)?)?;
+                // Constructors can set the fields of their own class before calling super()
+                let receiver = frame.stack.last();
+                if receiver == Some(&VT::UninitializedThis) && class_name == self.class.name {
+                    frame.stack.pop();
+                } else {
+                    self.pop(frame, &VT::Reference(class_name))?;
+                }
+            }
+
+            OpCodes::invokestatic(index)
+            | OpCodes::invokespecial(index)
+            | OpCodes::invokevirtual(index)
+            | OpCodes::invokeinterface(index, _) => self.invoke(opcode, *index, frame)?,
+            OpCodes::invokedynamic(index) => {
+                let descriptor = self.dynamic_descriptor(*index)?;
+                self.invoke_descriptor(frame, &descriptor)?
+            }
+
+            OpCodes::new(index) => {
+                let class_name = self.class_at(*index)?;
+                if class_name.starts_with('[') {
+                    return fail(
+                        "Illegal new instruction",
+                        format!("Can't create an array with new: {class_name}"),
+                    );
+                }
+                self.push(frame, VT::Uninitialized(pc as u16))?
+            }
+            OpCodes::newarray(atype) => {
+                let component = match atype {
+                    4 => "Z",
+                    5 => "C",
+                    6 => "F",
+                    7 => "D",
+                    8 => "B",
+                    9 => "S",
+                    10 => "I",
+                    11 => "J",
+                    atype => {
+                        return fail(
+                            "Illegal newarray instruction",
+                            format!("Invalid array type: {atype}"),
+                        )
+                    }
+                };
+                self.unary(frame, VT::Integer, VT::Reference(format!("[{component}")))?
+            }
+            OpCodes::anewarray(index) => {
+                let class_name = self.class_at(*index)?;
+                let array = if class_name.starts_with('[') {
+                    format!("[{class_name}")
+                } else {
+                    format!("[L{class_name};")
+                };
+                self.unary(frame, VT::Integer, VT::Reference(array))?
+            }
+            OpCodes::multianewarray(index, dimensions) => {
+                let class_name = self.class_at(*index)?;
+                let array_dimensions = class_name.chars().take_while(|char| *char == '[').count();
+                if *dimensions == 0 || (*dimensions as usize) > array_dimensions {
+                    return fail(
+                        "Illegal dimension in multianewarray instruction",
+                        format!("{dimensions} dimensions for the type {class_name}"),
+                    );
+                }
+                for _ in 0..*dimensions {
+                    self.pop(frame, &VT::Integer)?;
+                }
+                self.push(frame, VT::Reference(class_name))?
+            }
+            OpCodes::checkcast(index) => {
+                let class_name = self.class_at(*index)?;
+                self.pop_reference(frame)?;
+                self.push(frame, VT::Reference(class_name))?
+            }
+            OpCodes::instanceof(index) => {
+                self.class_at(*index)?;
+                self.pop_reference(frame)?;
+                self.push(frame, VT::Integer)?
+            }
+            OpCodes::monitorenter | OpCodes::monitorexit => {
+                self.pop_reference(frame)?;
+            }
+        }
+
+        Ok(Flow::next())
+    }
+
+    fn iinc(&self, frame: &mut Frame, index: usize) -> Check {
+        match frame.locals.get(index) {
+            Some(VT::Integer) => Ok(()),
+            Some(value) => fail(
+                "Bad local variable type",
+                format!(
+                    "Type {value} (current frame, locals[{index}]) is not assignable to integer"
+                ),
+            ),
+            None => fail(
+                "Illegal local variable number",
+                format!("Local index {index} is invalid"),
+            ),
+        }
+    }
+
+    fn peek_category_2(&self, frame: &Frame) -> Check<bool> {
+        match frame.stack.last() {
+            Some(value) => Ok(value.is_category_2()),
+            None => fail(
+                "Operand stack underflow",
+                "Attempt to pop empty stack.".to_string(),
+            ),
+        }
+    }
+
+    /// Pops two words, which is either a single long or double or two other values
+    fn pop_words(&self, frame: &mut Frame) -> Check<Vec<VerificationType>> {
+        if self.peek_category_2(frame)? {
+            return Ok(vec![self.pop_category(frame, true)?]);
+        }
+        let value1 = self.pop_category(frame, false)?;
+        let value2 = self.pop_category(frame, false)?;
+        Ok(vec![value2, value1])
+    }
+
+    /// Pushes values in order, the last one ends up on top
+    fn push_all(
+        &self,
+        frame: &mut Frame,
+        values: impl IntoIterator<Item = VerificationType>,
+    ) -> Check {
+        values
+            .into_iter()
+            .try_for_each(|value| self.push(frame, value))
+    }
+
+    fn conditional_branch(&self, pc: usize, offset: i16) -> Check<Flow> {
+        Ok(Flow {
+            falls_through: true,
+            targets: vec![self.branch(pc, offset as i32)?],
+        })
+    }
+
+    fn return_value(&self, frame: &mut Frame, kind: &VerificationType) -> Check<Flow> {
+        let Some(return_type) = &self.return_type else {
+            return fail(
+                "Method does not expect a return value",
+                "Error exists in the bytecode".to_string(),
+            );
+        };
+
+        let matches = match kind {
+            VT::Reference(_) => matches!(return_type, VT::Reference(_)),
+            kind => return_type == kind,
+        };
+        if !matches {
+            return fail(
+                "Wrong return type in function",
+                format!("Type {kind} is not assignable to {return_type}"),
+            );
+        }
+
+        self.pop(frame, return_type)?;
+        Ok(Flow::end())
+    }
+
+    fn invoke(&self, opcode: &OpCodes, index: u16, frame: &mut Frame) -> Check {
+        let (class_name, name, descriptor) = self.member_at(index)?;
+        let is_init = name == "<init>";
+        if name == "<clinit>" || (is_init && !matches!(opcode, OpCodes::invokespecial(_))) {
+            return fail(
+                "Illegal call to internal method",
+                format!(
+                    "Method {class_name}.{name}{descriptor} can't be called by {}",
+                    mnemonic(opcode)
+                ),
+            );
+        }
+
+        let method_descriptor = self.method_descriptor(&descriptor)?;
+        for parameter in method_descriptor.parameters.iter().rev() {
+            self.pop(frame, &VT::from_field_descriptor(parameter))?;
+        }
+
+        match opcode {
+            OpCodes::invokestatic(_) => {}
+            OpCodes::invokespecial(_) if is_init => {
+                let receiver = self.pop_reference(frame)?;
+                let initialized = match &receiver {
+                    VT::UninitializedThis => VT::Reference(self.class.name.clone()),
+                    VT::Uninitialized(new_pc) => {
+                        let new_class = self.new_class_at(*new_pc as usize)?;
+                        if new_class != class_name {
+                            return fail(
+                                "Bad type on operand stack",
+                                format!("Call to wrong <init> method, {class_name} for an object of {new_class}"),
+                            );
+                        }
+                        VT::Reference(new_class)
+                    }
+                    value => {
+                        return fail(
+                            "Bad type on operand stack",
+                            format!(
+                                "Type {value} (current frame, stack[{}]) is not assignable to uninitialized",
+                                frame.stack.len()
+                            ),
+                        )
+                    }
+                };
+
+                for slot in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+                    if *slot == receiver {
+                        *slot = initialized.clone();
+                    }
+                }
+            }
+            _ => {
+                self.pop(frame, &VT::Reference(class_name))?;
+            }
+        }
+
+        if let Some(return_type) = &method_descriptor.return_type {
+            self.push(frame, VT::from_field_descriptor(return_type))?;
+        }
+        Ok(())
+    }
+
+    fn invoke_descriptor(&self, frame: &mut Frame, descriptor: &str) -> Check {
+        let descriptor = self.method_descriptor(descriptor)?;
+        for parameter in descriptor.parameters.iter().rev() {
+            self.pop(frame, &VT::from_field_descriptor(parameter))?;
+        }
+        if let Some(return_type) = &descriptor.return_type {
+            self.push(frame, VT::from_field_descriptor(return_type))?;
+        }
+        Ok(())
+    }
+
+    fn method_descriptor(&self, descriptor: &str) -> Check<MethodDescriptor> {
+        parse_method_descriptor(descriptor)
+            .or_else(|err| fail("Illegal method descriptor", err.to_string()))
+    }
+
+    fn field_type(&self, descriptor: &str) -> Check<VerificationType> {
+        match VT::from_descriptor(descriptor) {
+            Some(value) => Ok(value),
+            None => fail(
+                "Illegal field descriptor",
+                format!("Invalid field descriptor: {descriptor}"),
+            ),
+        }
+    }
+
+    /// The class of the `new` instruction at `pc`
+    fn new_class_at(&self, pc: usize) -> Check<String> {
+        let opcodes = parse_opcodes(&self.code.code).unwrap_or_default();
+        match opcodes.iter().find(|(opcode_pc, _)| *opcode_pc == pc) {
+            Some((_, OpCodes::new(index))) => self.class_at(*index),
+            _ => fail(
+                "Bad type on operand stack",
+                format!("There is no new instruction at bci {pc}"),
+            ),
+        }
+    }
+
+    fn class_at(&self, index: u16) -> Check<String> {
+        let constant_pool = &self.class.java_class.constant_pool;
+        match constant_pool
+            .pool_entries
+            .get((index as usize).wrapping_sub(1))
+        {
+            Some(CpInfo::Class(_)) => Ok(constant_pool
+                .get_class_name_at(index)
+                .unwrap_or_default()
+                .to_string()),
+            _ => fail(
+                "Illegal constant pool index",
+                format!("Expecting a class at constant pool index {index}"),
+            ),
+        }
+    }
+
+    fn member_at(&self, index: u16) -> Check<(String, String, String)> {
+        let constant_pool = &self.class.java_class.constant_pool;
+        let member = match constant_pool
+            .pool_entries
+            .get((index as usize).wrapping_sub(1))
+        {
+            Some(CpInfo::Refs(_)) => {
+                constant_pool
+                    .get_refs_ext_at(index)
+                    .and_then(|(_, class, name_and_type)| {
+                        Some((
+                            constant_pool
+                                .get_utf8_at(class.name_index)?
+                                .data
+                                .to_string(),
+                            constant_pool
+                                .get_utf8_at(name_and_type.name_index)?
+                                .data
+                                .to_string(),
+                            constant_pool
+                                .get_utf8_at(name_and_type.descriptor_index)?
+                                .data
+                                .to_string(),
+                        ))
+                    })
+            }
+            _ => None,
+        };
+
+        match member {
+            Some(member) => Ok(member),
+            None => fail(
+                "Illegal constant pool index",
+                format!("Expecting a member reference at constant pool index {index}"),
+            ),
+        }
+    }
+
+    fn dynamic_descriptor(&self, index: u16) -> Check<String> {
+        let constant_pool = &self.class.java_class.constant_pool;
+        let descriptor = match constant_pool
+            .pool_entries
+            .get((index as usize).wrapping_sub(1))
+        {
+            Some(CpInfo::InvokeDynamic(dynamic)) => constant_pool
+                .get_name_type_at(dynamic.name_and_type_index)
+                .and_then(|name_and_type| constant_pool.get_utf8_at(name_and_type.descriptor_index))
+                .map(|utf8| utf8.data.to_string()),
+            _ => None,
+        };
+
+        match descriptor {
+            Some(descriptor) => Ok(descriptor),
+            None => fail(
+                "Illegal constant pool index",
+                format!("Expecting an invokedynamic entry at constant pool index {index}"),
+            ),
+        }
+    }
+
+    fn loadable_constant(&self, index: u16, category_2: bool) -> Check<VerificationType> {
+        let constant_pool = &self.class.java_class.constant_pool;
+        let value = match constant_pool
+            .pool_entries
+            .get((index as usize).wrapping_sub(1))
+        {
+            Some(CpInfo::Integer(_)) => VT::Integer,
+            Some(CpInfo::Float(_)) => VT::Float,
+            Some(CpInfo::Long(_)) => VT::Long,
+            Some(CpInfo::Double(_)) => VT::Double,
+            Some(CpInfo::String(_)) => VT::Reference("java/lang/String".to_string()),
+            Some(CpInfo::Class(_)) => VT::Reference("java/lang/Class".to_string()),
+            Some(CpInfo::MethodType(_)) => VT::Reference("java/lang/invoke/MethodType".to_string()),
+            Some(CpInfo::MethodHandle(_)) => {
+                VT::Reference("java/lang/invoke/MethodHandle".to_string())
+            }
+            Some(CpInfo::InvokeDynamic(dynamic)) if dynamic.tag == "CONSTANT_Dynamic" => {
+                let descriptor = constant_pool
+                    .get_name_type_at(dynamic.name_and_type_index)
+                    .and_then(|name_and_type| {
+                        constant_pool.get_utf8_at(name_and_type.descriptor_index)
+                    })
+                    .map(|utf8| utf8.data.to_string())
+                    .unwrap_or_default();
+                self.field_type(&descriptor)?
+            }
+            entry => {
+                return fail(
+                    "Illegal type in constant pool",
+                    format!("Constant pool index {index} is not loadable: {entry:?}"),
+                )
+            }
+        };
+
+        if value.is_category_2() != category_2 {
+            return fail(
+                "Illegal type in constant pool",
+                format!(
+                    "{} can't load the constant at index {index}",
+                    if category_2 { "ldc2_w" } else { "ldc" }
+                ),
+            );
+        }
+        Ok(value)
+    }
+}
+
+/// Collapses a full list of locals into the stack map form, where longs and doubles are a single
+/// entry and trailing `Top`s are left out
+pub(super) fn compact_locals(locals: &[VerificationType]) -> Vec<VerificationType> {
+    let mut compact = vec![];
+    let mut index = 0;
+    while index < locals.len() {
+        compact.push(locals[index].clone());
+        index += if locals[index].is_category_2() { 2 } else { 1 };
+    }
+    while compact.last() == Some(&VT::Top) {
+        compact.pop();
+    }
+    compact
+}
+
+/// Whether `array` is an array descriptor with one of the component types, `L` stands for any
+/// reference component
+fn is_array_of(array: &str, components: &[&str]) -> bool {
+    let Some(component) = array.strip_prefix('[') else {
+        return false;
+    };
+    components.iter().any(|expected| match *expected {
+        "L" => component.starts_with('L') || component.starts_with('['),
+        expected => component == expected,
+    })
+}
+
+pub(super) fn is_local_store(opcode: &OpCodes) -> bool {
+    match opcode {
+        OpCodes::istore_(_)
+        | OpCodes::lstore_(_)
+        | OpCodes::fstore_(_)
+        | OpCodes::dstore_(_)
+        | OpCodes::astore_(_)
+        | OpCodes::iinc(_, _) => true,
+        OpCodes::wide { opcode, .. } => is_local_store(opcode),
+        _ => false,
+    }
+}
+
+/// The name of an instruction as javap prints it
+fn mnemonic(opcode: &OpCodes) -> String {
+    let condition = |condition: &CmpConditions| match condition {
+        CmpConditions::Equal => "eq",
+        CmpConditions::NotEqual => "ne",
+        CmpConditions::LessThan => "lt",
+        CmpConditions::LessOrEqual => "le",
+        CmpConditions::GreaterThan => "gt",
+        CmpConditions::GreaterOrEqual => "ge",
+    };
+
+    match opcode {
+        OpCodes::if_icmp(cmp, _) => format!("if_icmp{}", condition(cmp)),
+        OpCodes::if_acmp(cmp, _) => format!("if_acmp{}", condition(cmp)),
+        OpCodes::if_cond(cmp, _) => format!("if{}", condition(cmp)),
+        OpCodes::if_null(_) => "ifnull".to_string(),
+        OpCodes::if_notnull(_) => "ifnonnull".to_string(),
+        OpCodes::fcmp(nan) => if *nan < 0 { "fcmpl" } else { "fcmpg" }.to_string(),
+        OpCodes::dcmp(nan) => if *nan < 0 { "dcmpl" } else { "dcmpg" }.to_string(),
+        OpCodes::Return => "return".to_string(),
+        opcode => {
+            let debug = format!("{opcode:?}");
+            let name = debug.split(['(', ' ', '{']).next().unwrap_or_default();
+            name.trim_end_matches('_').to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jvm_parser::classfile::{
+        attributes::{AttributeInfoData, CodeAttribute},
+        JavaClass,
+    };
+
+    use crate::jvm::{test_class, StackValue, JVM};
+
+    /// The code of `static int run()`
+    fn run_code<'a>(class: &'a mut JavaClass<'static>) -> &'a mut CodeAttribute<'static> {
+        let run = class
+            .methods
+            .iter_mut()
+            .find(|method| {
+                class
+                    .constant_pool
+                    .get_utf8_at(method.name_index)
+                    .unwrap()
+                    .data
+                    == "run"
+            })
+            .unwrap();
+        run.attributes
+            .iter_mut()
+            .find_map(|attribute| match &mut attribute.attribute {
+                AttributeInfoData::Code(code) => Some(code),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn classes() -> [JavaClass<'static>; 3] {
+        let mut wrong_type = test_class("Verification$WrongType");
+        // iconst_1 becomes aconst_null
+        run_code(&mut wrong_type).code.to_mut()[0] = 0x01;
+        let mut no_frame = test_class("Verification$NoFrame");
+        run_code(&mut no_frame).attribute_info.retain(|attribute| {
+            !matches!(attribute.attribute, AttributeInfoData::StackMapTable(_))
+        });
+
+        [test_class("Verification$Good"), wrong_type, no_frame]
+    }
+
+    #[test]
+    fn malformed_methods_are_rejected() {
+        // Without verification every method runs
+        let jvm = JVM::with_classes(classes());
+        let run = |class_name| jvm.run_static(class_name, "run", "()I", vec![]);
+        assert!(matches!(
+            run("Verification$Good"),
+            Ok(StackValue::Integer(1))
+        ));
+        assert!(matches!(
+            run("Verification$WrongType"),
+            Ok(StackValue::Null)
+        ));
+        assert!(matches!(
+            run("Verification$NoFrame"),
+            Ok(StackValue::Integer(2))
+        ));
+
+        let mut jvm = JVM::with_classes(classes());
+        std::sync::Arc::get_mut(&mut jvm).unwrap().set_verify(true);
+        let run = |class_name| jvm.run_static(class_name, "run", "()I", vec![]);
+        assert!(matches!(
+            run("Verification$Good"),
+            Ok(StackValue::Integer(1))
+        ));
+        assert_eq!(
+            run("Verification$WrongType").unwrap_err(),
+            "java.lang.VerifyError: Bad type on operand stack\n\
+             Exception Details:\n  \
+               Location:\n    \
+                 Verification$WrongType.run()I @1: ireturn\n  \
+               Reason:\n    \
+                 Type null (current frame, stack[0]) is not assignable to integer\n  \
+               Current Frame:\n    \
+                 bci: @1\n    \
+                 flags: { }\n    \
+                 locals: { }\n    \
+                 stack: { null }"
+        );
+        let error = run("Verification$NoFrame").unwrap_err();
+        assert!(
+            error.starts_with("java.lang.VerifyError: Expecting a stackmap frame at branch target"),
+            "{error}"
+        );
+    }
+}
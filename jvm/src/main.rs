@@ -1,10 +1,12 @@
+mod javap;
 mod jvm;
 mod utils;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use javap::JavapOptions;
 use jvm::JVM;
 use jvm_parser::{classfile::JavaClass, jar::JarFile};
-use std::path::PathBuf;
+use std::{path::PathBuf, sync::Arc};
 
 #[macro_export]
 #[cfg(not(feature = "debug"))]
@@ -16,6 +18,9 @@ macro_rules! dbgprint {
 #[derive(Parser, Debug)]
 #[command(author,version,about,long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// The path to the .jar or .class file to be executed
     #[arg(short, long)]
     path: Option<PathBuf>,
@@ -23,10 +28,165 @@ struct Args {
     /// Toggles the debug prints
     #[arg(short, long)]
     debug: bool,
+
+    /// Verifies the byte code of the program's classes before they are run
+    #[arg(long)]
+    verify: bool,
+
+    /// The maximum heap size, e.g. 64m or 1g, also accepted as -Xmx64m
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    xmx: Option<usize>,
+
+    /// Logs the given tags, `gc` prints every garbage collection, also accepted as -Xlog:gc
+    #[arg(long, value_name = "TAGS", value_parser = ["gc"])]
+    xlog: Option<String>,
+
+    /// Prints a histogram of the objects on the heap by class when the program exits
+    #[arg(long)]
+    heap_stats: bool,
+
+    /// Dumps the heap to an HPROF file the first time it runs out, also accepted as
+    /// -XX:+HeapDumpOnOutOfMemoryError
+    #[arg(long)]
+    heap_dump_on_out_of_memory_error: bool,
+
+    /// The file or directory heap dumps are written to, also accepted as -XX:HeapDumpPath=PATH
+    #[arg(long, value_name = "PATH")]
+    heap_dump_path: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Prints the declarations of a .class file or of every class in a .jar, like the JDK's javap
+    Javap {
+        /// The .class or .jar file to disassemble
+        path: PathBuf,
+
+        /// Prints the byte code of every method
+        #[arg(short = 'c')]
+        code: bool,
+
+        /// Prints the constant pool, flags and attributes as well as the byte code
+        #[arg(short = 'v', long)]
+        verbose: bool,
+
+        /// Prints private members too
+        #[arg(short = 'p', long)]
+        private: bool,
+
+        /// The version the classes of a multi-release jar are read for, the root ones if not given
+        #[arg(long, value_name = "VERSION")]
+        multi_release: Option<u16>,
+    },
+}
+
+/// Turns HotSpot style options into their long form, `-Xmx64m` into `--xmx=64m`, `-Xlog:gc`
+/// into `--xlog=gc` and `-XX:HeapDumpPath=dumps` into `--heap-dump-path=dumps`
+fn hotspot_options(args: impl Iterator<Item = String>) -> Vec<String> {
+    args.map(|arg| {
+        if let Some(size) = arg.strip_prefix("-Xmx") {
+            format!("--xmx={size}")
+        } else if let Some(tags) = arg.strip_prefix("-Xlog:") {
+            format!("--xlog={tags}")
+        } else if arg == "-XX:+HeapDumpOnOutOfMemoryError" {
+            "--heap-dump-on-out-of-memory-error".to_string()
+        } else if let Some(path) = arg.strip_prefix("-XX:HeapDumpPath=") {
+            format!("--heap-dump-path={path}")
+        } else {
+            arg
+        }
+    })
+    .collect()
+}
+
+/// The file a heap dump is written to, `java_pid<pid>.hprof` if no path or a directory is given
+fn heap_dump_file(path: Option<PathBuf>) -> PathBuf {
+    let file_name = format!("java_pid{}.hprof", std::process::id());
+    match path {
+        Some(path) if path.is_dir() => path.join(file_name),
+        Some(path) => path,
+        None => PathBuf::from(file_name),
+    }
+}
+
+/// Opens a jar of the class path for the version the vm runs, it is never closed as its classes
+/// borrow from it while the vm runs
+fn open_jar(path: &PathBuf) -> Result<&'static JarFile, String> {
+    let mut jar_file = JarFile::from_file(path).map_err(|err| err.to_string())?;
+    jar_file.set_version(jvm::FEATURE_VERSION);
+    Ok(Box::leak(Box::new(jar_file)))
+}
+
+/// Prints the heap like `jmap -histo`, the classes that take up the most bytes first
+fn print_heap_stats(jvm: &JVM) {
+    let stats = jvm.heap_stats();
+    let mut classes: Vec<_> = stats.classes.iter().collect();
+    classes.sort_by(|(a_name, a), (b_name, b)| b.bytes.cmp(&a.bytes).then(a_name.cmp(b_name)));
+
+    println!(" num     #instances         #bytes  class name");
+    println!("----------------------------------------------");
+    for (num, (class_name, class)) in classes.iter().enumerate() {
+        println!(
+            "{:4}: {:14} {:14}  {}",
+            num + 1,
+            class.instances,
+            class.bytes,
+            class_name.replace('/', ".")
+        );
+    }
+    println!(
+        "Total {:14} {:14}",
+        classes
+            .iter()
+            .map(|(_, class)| class.instances)
+            .sum::<usize>(),
+        stats.used
+    );
+    println!(
+        "{} garbage collections, {} of {} bytes used",
+        stats.collections, stats.used, stats.max_size
+    );
+}
+
+/// Parses a size in bytes with an optional `k`, `m` or `g` suffix
+fn parse_size(size: &str) -> Result<usize, String> {
+    let (digits, unit) = match size.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&size[..index], 1024),
+        Some((index, 'm' | 'M')) => (&size[..index], 1024 * 1024),
+        Some((index, 'g' | 'G')) => (&size[..index], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|value| value.checked_mul(unit))
+        .ok_or_else(|| format!("Invalid heap size: {size}"))
 }
 
 fn main() {
-    let args = Args::parse();
+    let args = Args::parse_from(hotspot_options(std::env::args()));
+
+    if let Some(Command::Javap {
+        path,
+        code,
+        verbose,
+        private,
+        multi_release,
+    }) = args.command
+    {
+        let options = JavapOptions {
+            code,
+            verbose,
+            private,
+            multi_release,
+        };
+        if let Err(message) = javap::javap(&path, options) {
+            eprintln!("Failed to load {}: {message}", path.display());
+            std::process::exit(1);
+        }
+        return;
+    }
 
     let file = args
         .path
@@ -39,17 +199,43 @@ fn main() {
         .unwrap();
 
     let mut jvm = JVM::new();
+    jvm.set_verify(args.verify);
+    if let Some(max_heap_size) = args.xmx {
+        jvm.set_max_heap_size(max_heap_size);
+    }
+    jvm.set_gc_log(args.xlog.as_deref() == Some("gc"));
+    if args.heap_dump_on_out_of_memory_error {
+        jvm.set_heap_dump_on_out_of_memory(heap_dump_file(args.heap_dump_path));
+    }
 
-    jvm.add_jar(JarFile::from_file(&PathBuf::from("./rt.jar")).unwrap())
-        .unwrap();
+    let rt_jar = PathBuf::from("./rt.jar");
+    if rt_jar.exists() {
+        jvm.add_boot_jar(open_jar(&rt_jar).unwrap()).unwrap();
+    }
 
     let file_ext = file.extension().unwrap();
 
-    if file_ext == "class" {
-        jvm.add_class(JavaClass::from_file(&file).unwrap()).unwrap();
+    let loaded = if file_ext == "class" {
+        JavaClass::from_file(&file)
+            .map_err(|err| err.to_string())
+            .and_then(|java_class| jvm.add_class(java_class))
     } else if file_ext == "jar" || file_ext == "zip" {
-        jvm.add_jar(JarFile::from_file(&file).unwrap()).unwrap();
+        open_jar(&file).and_then(|jar_file| jvm.add_jar(jar_file))
+    } else {
+        Ok(())
+    };
+    if let Err(message) = loaded {
+        eprintln!("Failed to load {}: {message}", file.display());
+        std::process::exit(1);
     }
 
-    jvm.run().unwrap();
+    let jvm = Arc::new(jvm);
+    let result = jvm.run();
+    if args.heap_stats {
+        print_heap_stats(&jvm);
+    }
+    if let Err(message) = result {
+        eprintln!("{message}");
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,21 @@
+public class Arrays {
+    public static int[] ints(int n) {
+        return new int[n];
+    }
+
+    public static String[] strings(int n) {
+        return new String[n];
+    }
+
+    public static int[][] matrix(int a, int b) {
+        return new int[a][b];
+    }
+
+    public static int caught(int n) {
+        try {
+            return new int[n].length;
+        } catch (NegativeArraySizeException e) {
+            return -1;
+        }
+    }
+}
@@ -0,0 +1,50 @@
+public class Casts {
+    interface Shape {
+    }
+
+    static class Square implements Shape {
+    }
+
+    static class Other {
+    }
+
+    public static int instances() {
+        Object square = new Square();
+        Object squares = new Square[1][];
+        Object ints = new int[1];
+        Object none = null;
+        return (square instanceof Shape ? 1 : 0)
+                + (squares instanceof Object[] ? 2 : 0)
+                + (squares instanceof Shape[][] ? 4 : 0)
+                + (ints instanceof Object[] ? 8 : 0)
+                + (none instanceof Object ? 16 : 0);
+    }
+
+    public static Object square() {
+        Object square = new Square();
+        return (Other) square;
+    }
+
+    public static Object ints() {
+        Object ints = new int[1];
+        return (Object[]) ints;
+    }
+
+    public static Object none() {
+        Object none = null;
+        return (Other) none;
+    }
+
+    static void store(Object[] array, Object value) {
+        array[0] = value;
+    }
+
+    public static void storeInts() {
+        store(new String[1], new int[1]);
+    }
+
+    public static void storeStrings() {
+        store(new Object[1], "hello");
+        store(new String[1], "hello");
+    }
+}
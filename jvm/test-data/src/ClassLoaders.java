@@ -0,0 +1,36 @@
+public class ClassLoaders {
+    static class Loader extends ClassLoader {
+        Class<?> define(byte[] b) {
+            return defineClass(null, b, 0, b.length);
+        }
+    }
+
+    // Not on the class path, the tests define it from its bytes
+    static class Defined {
+    }
+
+    static class Boom {
+        static int zero = 1 / 0;
+
+        static void run() {
+        }
+    }
+
+    // Not on the class path, so Child can't be defined
+    static class Parent {
+    }
+
+    static class Child extends Parent {
+        static void run() {
+        }
+    }
+
+    public static boolean define(byte[] b, int times) {
+        Loader loader = new Loader();
+        Class<?> c = null;
+        for (; times > 0; times--) {
+            c = loader.define(b);
+        }
+        return c.getClassLoader() == loader;
+    }
+}
@@ -0,0 +1,46 @@
+import java.lang.invoke.MethodHandles;
+
+/** The tests load the constants javac doesn't emit `ldc` for by patching the methods */
+public class Constants {
+    static int ANSWER;
+
+    static int answer(MethodHandles.Lookup lookup, String name, Class<?> type, int base) {
+        return base + 1;
+    }
+
+    static int answer() {
+        return answer(null, null, null, ANSWER);
+    }
+
+    static long widen(int value) {
+        return value;
+    }
+
+    public static int integer() {
+        return 100_000;
+    }
+
+    public static float decimal() {
+        return 1.5f;
+    }
+
+    public static Object wide() {
+        return "wide";
+    }
+
+    public static Object mirror() {
+        return Constants.class;
+    }
+
+    public static Object methodType() {
+        return null;
+    }
+
+    public static Object methodHandle() {
+        return null;
+    }
+
+    public static int dynamic() {
+        return 0;
+    }
+}
@@ -0,0 +1,48 @@
+public class Gc {
+    static int[] kept;
+    static String label;
+
+    static class Node {
+        int a, b, c, d, e, f, g, h;
+    }
+
+    public static int longs(int n) {
+        try {
+            return new long[n].length;
+        } catch (OutOfMemoryError e) {
+            return -1;
+        }
+    }
+
+    public static void keep() {
+        kept = new int[10];
+        int[] dropped = new int[10];
+    }
+
+    public static void label() {
+        label = "kept in the heap";
+    }
+
+    public static boolean fill(int n) {
+        Node[] nodes = new Node[n];
+        for (int i = 0; i < n; i++) {
+            nodes[i] = new Node();
+        }
+        for (Node node : nodes) {
+            node.a = 1;
+            node.b = 1;
+            node.c = 1;
+            node.d = 1;
+            node.e = 1;
+            node.f = 1;
+            node.g = 1;
+            node.h = 1;
+        }
+        try {
+            new Object();
+            return false;
+        } catch (OutOfMemoryError e) {
+            return true;
+        }
+    }
+}
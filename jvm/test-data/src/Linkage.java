@@ -0,0 +1,38 @@
+public class Linkage {
+    // The tests remove missing and absent, as if Target had changed since Linkage was compiled
+    static class Target {
+        static int value = 7;
+        static int absent;
+
+        static int twice() {
+            return value * 2;
+        }
+
+        static int missing() {
+            return 0;
+        }
+    }
+
+    // Not on the class path
+    static class Gone {
+        static int run() {
+            return 0;
+        }
+    }
+
+    public static int twice() {
+        return Target.twice();
+    }
+
+    public static int missing() {
+        return Target.missing();
+    }
+
+    public static int absent() {
+        return Target.absent;
+    }
+
+    public static int gone() {
+        return Gone.run();
+    }
+}
@@ -0,0 +1,49 @@
+public class Monitors {
+    static class Counter extends Thread {
+        static int count;
+
+        static synchronized void add() {
+            int c = count;
+            Thread.yield();
+            count = c + 1;
+        }
+
+        public void run() {
+            for (int i = 0; i < 100; i++) {
+                add();
+            }
+        }
+    }
+
+    public static int race() throws InterruptedException {
+        Counter a = new Counter();
+        Counter b = new Counter();
+        a.start();
+        b.start();
+        a.join();
+        b.join();
+        return Counter.count;
+    }
+
+    public static int reenter() {
+        Object o = new Object();
+        int held;
+        synchronized (o) {
+            synchronized (o) {
+            }
+            held = Thread.holdsLock(o) ? 1 : 0;
+        }
+        return held * 2 + (Thread.holdsLock(o) ? 1 : 0);
+    }
+
+    public static void unowned() {
+        new Object().notify();
+    }
+
+    public static synchronized void locked() {
+        Object o = new Object();
+        synchronized (o) {
+            Thread.yield();
+        }
+    }
+}
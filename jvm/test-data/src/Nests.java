@@ -0,0 +1,11 @@
+public class Nests {
+    private static int secret() {
+        return 42;
+    }
+
+    public static class Member {
+        public static int peek() {
+            return secret();
+        }
+    }
+}
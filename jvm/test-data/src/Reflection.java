@@ -0,0 +1,55 @@
+import java.lang.reflect.Field;
+import java.lang.reflect.Method;
+import java.lang.reflect.ParameterizedType;
+import java.lang.reflect.Type;
+
+public class Reflection {
+    public static String superName() {
+        return new Reflected().getClass().getSuperclass().getName();
+    }
+
+    public static int instances() {
+        return (Reflected.class.isInstance(new Reflected()) ? 2 : 0)
+                + (Reflected.class.isInstance("text") ? 1 : 0);
+    }
+
+    public static Object reflect() throws Exception {
+        Reflected p = new Reflected();
+        p.x = 5;
+        Field x = Reflected.class.getDeclaredFields()[0];
+        Reflected q = new Reflected();
+        x.set(q, x.get(p));
+        Method plus = Reflected.class.getDeclaredMethods()[0];
+        return plus.invoke(q, x.get(q));
+    }
+
+    public static Object secret(boolean accessible) throws Exception {
+        Field secret = Reflected.class.getDeclaredFields()[1];
+        secret.setAccessible(accessible);
+        Reflected r = new Reflected();
+        secret.set(r, 7);
+        return secret.get(r);
+    }
+
+    public static String genericType(int field, int argument) throws Exception {
+        Type type = Reflected.class.getDeclaredFields()[field].getGenericType();
+        if (argument >= 0) {
+            type = ((ParameterizedType) type).getActualTypeArguments()[argument];
+        }
+        return type.getTypeName();
+    }
+}
+
+class Pair<A, B> {
+}
+
+class Reflected {
+    public int x;
+    private int secret;
+    public Pair<String, ? extends Number> pair;
+    public Pair<String, Integer>[] pairs;
+
+    public int plus(int d) {
+        return x + d;
+    }
+}
@@ -0,0 +1,33 @@
+public class Threads {
+    static class Worker extends Thread {
+        static int result;
+
+        public void run() {
+            try {
+                sleep(20);
+            } catch (InterruptedException e) {
+                return;
+            }
+            result = 42;
+        }
+    }
+
+    public static int start(boolean join) throws InterruptedException {
+        Worker worker = new Worker();
+        worker.start();
+        if (join) {
+            worker.join();
+        }
+        return Worker.result;
+    }
+
+    public static int sleep() {
+        Thread.currentThread().interrupt();
+        try {
+            Thread.sleep(60_000);
+        } catch (InterruptedException e) {
+            return Thread.interrupted() ? 2 : 1;
+        }
+        return 0;
+    }
+}
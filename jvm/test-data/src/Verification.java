@@ -0,0 +1,25 @@
+public class Verification {
+    static class Good {
+        static int run() {
+            return 1;
+        }
+    }
+
+    // The tests load aconst_null instead of the 1
+    static class WrongType {
+        static int run() {
+            return 1;
+        }
+    }
+
+    // The tests remove the stack map frames
+    static class NoFrame {
+        static int run() {
+            int zero = 0;
+            if (zero == 0) {
+                return 2;
+            }
+            return 1;
+        }
+    }
+}